@@ -0,0 +1,378 @@
+//! `smsql`: inspect and query sol-micro-sql graphs from the command line.
+//!
+//! Reads fetch the graph account and run the query locally with the
+//! off-chain engine, so they cost nothing. Writes are printed as unsigned
+//! instructions, to be signed and sent with a wallet.
+
+mod rpc;
+
+use anchor_lang::prelude::{AnchorDeserialize, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use base64::Engine as _;
+use rpc::Rpc;
+use serde_json::json;
+use sol_micro_sql::cypher::parse;
+use sol_micro_sql::export;
+use sol_micro_sql::graph::{GraphSizing, GraphStore};
+use sol_micro_sql::lexer::compile_to_opcodes;
+use sol_micro_sql::lint;
+use sol_micro_sql::vm::VmResult;
+use sol_micro_sql_client::{decode_result, initialize_graph, Event, GraphClient};
+use sol_micro_sql_engine::Engine;
+use sol_micro_sql_lang::format_query;
+use std::io::BufRead;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+const USAGE: &str = "usage: smsql <command> [options]
+
+commands:
+  query <graph> <query> [--url URL] [--partition N]
+      fetch <graph> and run <query> against it locally
+  export <graph> [--url URL] [--format json|graphml|dot] [--label LABEL]
+      print <graph> as JSON (the default), GraphML or Graphviz DOT;
+      --label limits DOT output to nodes with that label
+  compile <query> [--json true]
+      print the opcodes <query> compiles to, optionally as JSON
+  format <query>
+      print <query> in canonical form
+  lint <graph> <query> [--url URL]
+      estimate <query>'s compute units on <graph> and flag likely problems
+  init-graph <name> <authority> [--payer KEY] [--max-nodes N] [--max-edges N]
+      print the instruction creating graph <name>, sized for at most N
+      nodes and edges (the program's caps by default)
+  execute <graph> <authority> <query>
+      print the instruction running <query> on-chain
+  decode-result <base64>
+      decode a query's transaction return data
+  decode-logs
+      decode return data and events from transaction logs on stdin
+
+URL is a cluster (devnet, testnet, mainnet, localnet) or an RPC endpoint;
+it defaults to localnet.";
+
+/// Positional arguments and `--flag value` options.
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(flag) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{} needs a value", flag))?;
+                    options.push((flag.to_string(), value));
+                }
+                None => positional.push(arg),
+            }
+        }
+        Ok(Args {
+            positional,
+            options,
+        })
+    }
+
+    fn arg(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing <{}>\n\n{}", name, USAGE))
+    }
+
+    fn option(&self, flag: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(f, _)| f == flag)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn pubkey(value: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(value).map_err(|_| format!("invalid public key: {}", value))
+}
+
+fn main() -> ExitCode {
+    match Args::parse(std::env::args().skip(1)).and_then(|args| run(&args)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    match args.arg(0, "command")? {
+        "query" => {
+            let graph = args.arg(1, "graph")?;
+            let query = args.arg(2, "query")?;
+            let partition = args
+                .option("partition")
+                .map(|p| p.parse().map_err(|_| format!("invalid partition: {}", p)))
+                .transpose()?;
+            let rpc = Rpc::new(args.option("url").unwrap_or("localnet"));
+
+            let data = rpc.account_data(graph)?;
+            let mut engine = Engine::from_account_data(&data)
+                .map_err(|e| format!("{} is not a readable graph: {:?}", graph, e))?
+                .at_slot(rpc.slot()?);
+            let result = engine
+                .query(query, partition)
+                .map_err(|e| format!("query failed: {:?}", e))?;
+            print_result(&result, Some(engine.graph()));
+        }
+        "export" => {
+            let graph = args.arg(1, "graph")?;
+            let rpc = Rpc::new(args.option("url").unwrap_or("localnet"));
+            let engine = Engine::from_account_data(&rpc.account_data(graph)?)
+                .map_err(|e| format!("{} is not a readable graph: {:?}", graph, e))?;
+            match args.option("format").unwrap_or("json") {
+                "json" => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&export::to_json(engine.graph())).unwrap()
+                ),
+                "graphml" => print!("{}", export::to_graphml(engine.graph())),
+                "dot" => print!("{}", export::to_dot(engine.graph(), args.option("label"))),
+                other => return Err(format!("unknown format: {}", other)),
+            }
+        }
+        "compile" => {
+            let query = parse(args.arg(1, "query")?).map_err(|e| format!("{:?}", e))?;
+            let ops = compile_to_opcodes(query);
+            if args.option("json") == Some("true") {
+                println!("{}", serde_json::to_string_pretty(&ops).unwrap());
+            } else {
+                for op in ops {
+                    println!("{:?}", op);
+                }
+            }
+        }
+        "format" => {
+            let query = format_query(args.arg(1, "query")?).map_err(|e| format!("{:?}", e))?;
+            println!("{}", query);
+        }
+        "lint" => {
+            let graph = args.arg(1, "graph")?;
+            let query = args.arg(2, "query")?;
+            let rpc = Rpc::new(args.option("url").unwrap_or("localnet"));
+            let engine = Engine::from_account_data(&rpc.account_data(graph)?)
+                .map_err(|e| format!("{} is not a readable graph: {:?}", graph, e))?;
+            let ops = compile_to_opcodes(parse(query).map_err(|e| format!("{:?}", e))?);
+            println!(
+                "~{} compute units",
+                lint::compute_units(&ops, engine.graph(), Some(query))
+            );
+            for warning in lint::lint(&ops, engine.graph(), Some(query)) {
+                println!("warning: {}", warning);
+            }
+        }
+        "init-graph" => {
+            let name = args.arg(1, "name")?;
+            let authority = pubkey(args.arg(2, "authority")?)?;
+            let payer = args.option("payer").map(pubkey).transpose()?;
+            let mut sizing = GraphSizing::default();
+            for (flag, max) in [
+                ("max-nodes", &mut sizing.max_nodes),
+                ("max-edges", &mut sizing.max_edges),
+            ] {
+                if let Some(value) = args.option(flag) {
+                    *max = value
+                        .parse()
+                        .map_err(|_| format!("invalid --{}: {}", flag, value))?;
+                }
+            }
+            print_instruction(&initialize_graph(
+                name,
+                sizing,
+                authority,
+                payer.unwrap_or(authority),
+            ));
+        }
+        "execute" => {
+            let client = GraphClient::new(
+                pubkey(args.arg(1, "graph")?)?,
+                pubkey(args.arg(2, "authority")?)?,
+            );
+            let query = args.arg(3, "query")?;
+            parse(query).map_err(|e| format!("{:?}", e))?;
+            print_instruction(&client.execute_query_text(query, None, None));
+        }
+        "decode-result" => {
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(args.arg(1, "base64")?)
+                .map_err(|e| e.to_string())?;
+            print_result(&decode_result(&data).map_err(|e| e.to_string())?, None);
+        }
+        "decode-logs" => {
+            for line in std::io::stdin().lock().lines() {
+                if let Some(decoded) = decode_log(&line.map_err(|e| e.to_string())?) {
+                    println!("{}", decoded);
+                }
+            }
+        }
+        other => return Err(format!("unknown command: {}\n\n{}", other, USAGE)),
+    }
+    Ok(())
+}
+
+fn print_result(result: &VmResult, graph: Option<&GraphStore>) {
+    match result {
+        VmResult::Nodes(ids) => {
+            for id in ids {
+                match graph.and_then(|g| g.get_node_by_id(*id)) {
+                    Some(node) => println!("{}\t{}\t{} bytes", id, node.label, node.data.len()),
+                    None => println!("{}", id),
+                }
+            }
+            println!("({} nodes)", ids.len());
+        }
+        VmResult::Values(values) => {
+            for (id, value) in values {
+                println!("{}\t{}", id, value.as_deref().unwrap_or("null"));
+            }
+            println!("({} nodes)", values.len());
+        }
+        VmResult::Records(nodes) => {
+            for node in nodes {
+                println!("{}\t{}\t{} bytes", node.id, node.label, node.data.len());
+            }
+            println!("({} nodes)", nodes.len());
+        }
+        VmResult::Pairs(pairs) => {
+            for (a, b) in pairs {
+                println!("{}\t{}", a, b);
+            }
+            println!("({} pairs)", pairs.len());
+        }
+        VmResult::Delegates(delegates) => {
+            for (id, delegate) in delegates {
+                match delegate {
+                    Some(delegate) => println!("{}\t{}", id, delegate),
+                    None => println!("{}\t(unresolved)", id),
+                }
+            }
+            println!("({} nodes)", delegates.len());
+        }
+        VmResult::Weights(weights) => {
+            for (id, weight) in weights {
+                println!("{}\t{}", id, weight);
+            }
+            println!("({} nodes)", weights.len());
+        }
+        VmResult::Cycle(ids) if ids.is_empty() => println!("(no cycle)"),
+        VmResult::Cycle(ids) => {
+            for id in ids {
+                print!("{} -> ", id);
+            }
+            println!("{}", ids[0]);
+        }
+        VmResult::Scalar(value) => println!("{}", value),
+        VmResult::None => println!("(no result)"),
+    }
+}
+
+fn print_instruction(ix: &Instruction) {
+    let accounts: Vec<_> = ix
+        .accounts
+        .iter()
+        .map(|meta| {
+            json!({
+                "pubkey": meta.pubkey.to_string(),
+                "isSigner": meta.is_signer,
+                "isWritable": meta.is_writable,
+            })
+        })
+        .collect();
+    let ix = json!({
+        "programId": ix.program_id.to_string(),
+        "accounts": accounts,
+        "data": base64::engine::general_purpose::STANDARD.encode(&ix.data),
+    });
+    println!("{}", serde_json::to_string_pretty(&ix).unwrap());
+}
+
+/// Describes a `Program return:` or `Program data:` log line.
+fn decode_log(line: &str) -> Option<String> {
+    let line = line.trim().trim_start_matches("Program log: ");
+    if let Some(rest) = line.strip_prefix("Program return: ") {
+        let (program, encoded) = rest.split_once(' ')?;
+        if program != sol_micro_sql::ID.to_string() {
+            return None;
+        }
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .ok()?;
+        return Some(match VmResult::try_from_slice(&data) {
+            Ok(result) => format!("return: {:?}", result),
+            Err(_) => format!("return: {} bytes", data.len()),
+        });
+    }
+
+    let encoded = line.strip_prefix("Program data: ")?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()?;
+    Some(match Event::decode(&data) {
+        Some(event) => format!("event: {} ({} bytes)", event.name(), data.len() - 8),
+        None => format!("event: unknown ({} bytes)", data.len()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::parse(
+            [
+                "query",
+                "Graph1",
+                "MATCH (n) RETURN n LIMIT 1",
+                "--url",
+                "devnet",
+            ]
+            .into_iter()
+            .map(String::from),
+        )
+        .unwrap();
+        assert_eq!(args.arg(1, "graph").unwrap(), "Graph1");
+        assert_eq!(args.option("url"), Some("devnet"));
+        assert!(args.arg(3, "extra").is_err());
+        assert!(Args::parse(["--url"].into_iter().map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_decode_log() {
+        let event = sol_micro_sql::NodeDeleted { node_id: 7 };
+        let line = format!(
+            "Program data: {}",
+            base64::engine::general_purpose::STANDARD.encode(event.data())
+        );
+        assert_eq!(
+            decode_log(&line).unwrap(),
+            format!(
+                "event: NodeDeleted ({} bytes)",
+                sol_micro_sql::graph::NODE_ID_LEN
+            )
+        );
+
+        let mut data = Vec::new();
+        anchor_lang::AnchorSerialize::serialize(&VmResult::Scalar(3), &mut data).unwrap();
+        let line = format!(
+            "Program return: {} {}",
+            sol_micro_sql::ID,
+            base64::engine::general_purpose::STANDARD.encode(data)
+        );
+        assert_eq!(decode_log(&line).unwrap(), "return: Scalar(3)");
+        assert!(decode_log("Program log: Instruction: ExecuteQuery").is_none());
+    }
+}
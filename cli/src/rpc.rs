@@ -0,0 +1,65 @@
+use base64::Engine as _;
+use serde_json::{json, Value};
+use std::process::Command;
+
+/// RPC endpoint for a cluster name, or `url` itself.
+pub fn cluster_url(url: &str) -> &str {
+    match url {
+        "devnet" => "https://api.devnet.solana.com",
+        "testnet" => "https://api.testnet.solana.com",
+        "mainnet" | "mainnet-beta" => "https://api.mainnet-beta.solana.com",
+        "localnet" | "localhost" => "http://127.0.0.1:8899",
+        other => other,
+    }
+}
+
+/// Minimal JSON-RPC client; requests go through `curl` so the CLI carries
+/// no HTTP or TLS stack of its own.
+pub struct Rpc {
+    url: String,
+}
+
+impl Rpc {
+    pub fn new(url: &str) -> Self {
+        Rpc {
+            url: cluster_url(url).to_string(),
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let output = Command::new("curl")
+            .args(["-sS", "-X", "POST", "-H", "Content-Type: application/json"])
+            .arg("-d")
+            .arg(body.to_string())
+            .arg(&self.url)
+            .output()
+            .map_err(|e| format!("could not run curl: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+
+        let mut response: Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("invalid RPC response: {}", e))?;
+        if let Some(error) = response.get("error") {
+            return Err(format!("RPC error: {}", error));
+        }
+        Ok(response["result"].take())
+    }
+
+    pub fn slot(&self) -> Result<u64, String> {
+        self.call("getSlot", json!([]))?
+            .as_u64()
+            .ok_or_else(|| "invalid slot".to_string())
+    }
+
+    pub fn account_data(&self, address: &str) -> Result<Vec<u8>, String> {
+        let result = self.call("getAccountInfo", json!([address, { "encoding": "base64" }]))?;
+        let encoded = result["value"]["data"][0]
+            .as_str()
+            .ok_or_else(|| format!("account {} not found", address))?;
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("invalid account data: {}", e))
+    }
+}
@@ -0,0 +1,301 @@
+//! Typed program events from transaction logs, for indexers.
+//!
+//! The program emits events with `emit!`, which logs them as base64
+//! `Program data:` lines. [`decode_logs`] turns a transaction's log messages
+//! into [`Event`]s, and [`EventStream`] does the same for a `logsSubscribe`
+//! websocket subscription. The stream takes the raw text messages so any
+//! websocket client can feed it:
+//!
+//! ```ignore
+//! socket.send(logs_subscribe_request(1, "confirmed"))?;
+//! for log_event in EventStream::new(socket.into_iter()) {
+//!     println!("{} {:?}", log_event.signature, log_event.event);
+//! }
+//! ```
+
+use base64::Engine as _;
+use serde_json::{json, Value};
+use sol_micro_sql_types::events::*;
+use std::collections::VecDeque;
+
+macro_rules! events {
+    ($($event:ident),* $(,)?) => {
+        /// Any event the program emits.
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum Event {
+            $($event($event),)*
+        }
+
+        impl Event {
+            /// Decodes a `Program data:` entry, or `None` if it is not one of
+            /// the program's events.
+            pub fn decode(data: &[u8]) -> Option<Self> {
+                $(
+                    if let Some(event) = $event::decode(data) {
+                        return Some(Event::$event(event));
+                    }
+                )*
+                None
+            }
+
+            /// The event's struct name.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Event::$event(_) => $event::NAME,)*
+                }
+            }
+        }
+    };
+}
+
+events!(
+    NodeAdded,
+    EdgeAdded,
+    NodeMatched,
+    NodeDeleted,
+    NodeRestored,
+    NodeProtectionChanged,
+    LabelDeleted,
+    NodeRemoved,
+    CompressedNodeAppended,
+    CompressedNodeUpdated,
+    MintBound,
+    AccountAttributeSet,
+    AttributeUpdated,
+    OracleChanged,
+    AcyclicLabelsChanged,
+    NodeDataUpdated,
+    RoleGranted,
+    RoleRevoked,
+    MultisigChanged,
+    GraphFrozenChanged,
+    GraphConfigChanged,
+    TokenGateChanged,
+    WriteFeeChanged,
+    GraphResized,
+    GraphMigrated,
+    NodeReclaimed,
+    EdgeReclaimed,
+    GraphCompacted,
+    IndexRebuilt,
+    GraphCloned,
+    AuthorityTransferProposed,
+    AuthorityTransferred,
+    GraphMetadataChanged,
+    QueryReceiptIssued,
+    WriteCommitted,
+    WriteRevealed,
+    ProposalStaged,
+    ProposalCommitted,
+    NodeAclChanged,
+    RateLimitChanged,
+    SessionCreated,
+    SessionRevoked,
+    GraphClosed,
+);
+
+/// Events the program emitted in one transaction's log messages, in order.
+/// `Program data:` lines logged by other programs, including ones the
+/// program calls into, are skipped.
+pub fn decode_logs<S: AsRef<str>>(logs: &[S]) -> Vec<Event> {
+    let program_id = sol_micro_sql::ID.to_string();
+    let mut invoked = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        let line = line.as_ref();
+        if let Some(encoded) = line.strip_prefix("Program data: ") {
+            if invoked.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+            let event = base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .ok()
+                .and_then(|data| Event::decode(&data));
+            events.extend(event);
+        } else if let Some(rest) = line.strip_prefix("Program ") {
+            let mut words = rest.split(' ');
+            let program = words.next().unwrap_or_default();
+            match words.next() {
+                Some("invoke") => invoked.push(program),
+                Some("success") | Some("failed:") => {
+                    invoked.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    events
+}
+
+/// The `logsSubscribe` request for transactions that mention the program.
+pub fn logs_subscribe_request(id: u64, commitment: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "logsSubscribe",
+        "params": [
+            { "mentions": [sol_micro_sql::ID.to_string()] },
+            { "commitment": commitment },
+        ],
+    })
+    .to_string()
+}
+
+/// An event together with the transaction that emitted it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    pub signature: String,
+    pub slot: u64,
+    pub event: Event,
+}
+
+/// Events in one `logsNotification` message. Failed transactions are
+/// skipped, since their writes were rolled back.
+pub fn decode_notification(message: &str) -> Vec<LogEvent> {
+    let Ok(message) = serde_json::from_str::<Value>(message) else {
+        return Vec::new();
+    };
+    if message["method"] != "logsNotification" {
+        return Vec::new();
+    }
+    let result = &message["params"]["result"];
+    let value = &result["value"];
+    if !value["err"].is_null() {
+        return Vec::new();
+    }
+    let (Some(signature), Some(logs)) = (value["signature"].as_str(), value["logs"].as_array())
+    else {
+        return Vec::new();
+    };
+    let logs: Vec<&str> = logs.iter().filter_map(Value::as_str).collect();
+    decode_logs(&logs)
+        .into_iter()
+        .map(|event| LogEvent {
+            signature: signature.to_string(),
+            slot: result["context"]["slot"].as_u64().unwrap_or_default(),
+            event,
+        })
+        .collect()
+}
+
+/// Events from a `logsSubscribe` subscription, given the websocket's text
+/// messages. Messages that are not notifications, like the subscription
+/// confirmation, are ignored.
+pub struct EventStream<I> {
+    messages: I,
+    pending: VecDeque<LogEvent>,
+}
+
+impl<I: Iterator<Item = String>> EventStream<I> {
+    pub fn new(messages: I) -> Self {
+        EventStream {
+            messages,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for EventStream<I> {
+    type Item = LogEvent;
+
+    fn next(&mut self) -> Option<LogEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            let message = self.messages.next()?;
+            self.pending.extend(decode_notification(&message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_line(event: impl anchor_lang::Event) -> String {
+        format!(
+            "Program data: {}",
+            base64::engine::general_purpose::STANDARD.encode(event.data())
+        )
+    }
+
+    #[test]
+    fn test_decode_logs() {
+        let other = "11111111111111111111111111111111";
+        let logs = vec![
+            format!("Program {} invoke [1]", sol_micro_sql::ID),
+            "Program log: Instruction: ExecuteQuery".to_string(),
+            data_line(sol_micro_sql::NodeAdded {
+                node_id: 4,
+                node_count: 1,
+            }),
+            format!("Program {} invoke [2]", other),
+            data_line(sol_micro_sql::NodeDeleted { node_id: 9 }),
+            format!("Program {} success", other),
+            data_line(sol_micro_sql::EdgeAdded {
+                from: 4,
+                to: 5,
+                edge_count: 1,
+            }),
+            format!(
+                "Program {} consumed 5000 of 200000 compute units",
+                sol_micro_sql::ID
+            ),
+            format!("Program {} success", sol_micro_sql::ID),
+        ];
+        let events = decode_logs(&logs);
+        assert_eq!(
+            events,
+            vec![
+                Event::NodeAdded(NodeAdded {
+                    node_id: 4,
+                    node_count: 1
+                }),
+                Event::EdgeAdded(EdgeAdded {
+                    from: 4,
+                    to: 5,
+                    edge_count: 1
+                }),
+            ]
+        );
+        assert_eq!(events[1].name(), "EdgeAdded");
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let notification = |err: Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "logsNotification",
+                "params": { "result": {
+                    "context": { "slot": 42 },
+                    "value": {
+                        "signature": "sig",
+                        "err": err,
+                        "logs": [
+                            format!("Program {} invoke [1]", sol_micro_sql::ID),
+                            data_line(sol_micro_sql::NodeDeleted { node_id: 9 }),
+                            format!("Program {} success", sol_micro_sql::ID),
+                        ],
+                    },
+                }, "subscription": 1 },
+            })
+            .to_string()
+        };
+        let messages = vec![
+            json!({ "jsonrpc": "2.0", "result": 1, "id": 1 }).to_string(),
+            notification(json!({ "InstructionError": [0, "Custom"] })),
+            notification(Value::Null),
+        ];
+        let events: Vec<_> = EventStream::new(messages.into_iter()).collect();
+        assert_eq!(
+            events,
+            vec![LogEvent {
+                signature: "sig".to_string(),
+                slot: 42,
+                event: Event::NodeDeleted(NodeDeleted { node_id: 9 }),
+            }]
+        );
+    }
+}
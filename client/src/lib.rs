@@ -0,0 +1,327 @@
+//! Client for the sol-micro-sql program: a typed query builder and
+//! instruction constructors, so callers need not hand-write Cypher or
+//! account lists, and typed events decoded from transaction logs.
+
+pub mod events;
+pub mod query;
+
+pub use events::{decode_logs, Event, EventStream, LogEvent};
+pub use query::{BuiltQuery, CreateEdge, CreateNode, MatchQuery, Query};
+pub use sol_micro_sql_macros::{cypher, cypher_ops};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{system_program, InstructionData};
+use sol_micro_sql::audit::AUDIT_LOG_SEED;
+use sol_micro_sql::cursor::CURSOR_SEED;
+use sol_micro_sql::cypher::CypherQuery;
+use sol_micro_sql::graph::{GraphSizing, GRAPH_STORE_SEED, USER_GRAPH_SEED};
+use sol_micro_sql::journal::JOURNAL_SEED;
+use sol_micro_sql::rate_limit::WRITER_RATE_SEED;
+use sol_micro_sql::vm::{Opcode, VmResult};
+use sol_micro_sql::{accounts, instruction};
+
+/// Address of the graph `name` created by `creator`.
+pub fn graph_store_address(name: &str, creator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[GRAPH_STORE_SEED, name.as_bytes(), creator.as_ref()],
+        &sol_micro_sql::ID,
+    )
+    .0
+}
+
+/// Address of `user`'s personal graph.
+pub fn user_graph_address(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[USER_GRAPH_SEED, user.as_ref()], &sol_micro_sql::ID).0
+}
+
+/// `initialize_graph` creating the graph `name` owned by `authority`,
+/// sized for and limited to `sizing`.
+pub fn initialize_graph(
+    name: &str,
+    sizing: GraphSizing,
+    authority: Pubkey,
+    payer: Pubkey,
+) -> Instruction {
+    let accounts = accounts::InitializeGraph {
+        graph_store: graph_store_address(name, &authority),
+        authority,
+        payer,
+        system_program: system_program::ID,
+    };
+    Instruction {
+        program_id: sol_micro_sql::ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::InitializeGraph {
+            name: name.to_string(),
+            sizing,
+        }
+        .data(),
+    }
+}
+
+/// Decodes the `VmResult` a query leaves in the transaction return data.
+pub fn decode_result(return_data: &[u8]) -> std::io::Result<VmResult> {
+    VmResult::try_from_slice(return_data)
+}
+
+/// Builds instructions against one graph on behalf of `authority`.
+#[derive(Debug, Clone)]
+pub struct GraphClient {
+    pub graph_store: Pubkey,
+    pub authority: Pubkey,
+    writer_rate: bool,
+    audit_log: bool,
+    journal: bool,
+}
+
+impl GraphClient {
+    pub fn new(graph_store: Pubkey, authority: Pubkey) -> Self {
+        GraphClient {
+            graph_store,
+            authority,
+            writer_rate: false,
+            audit_log: false,
+            journal: false,
+        }
+    }
+
+    /// Passes the authority's write counter, for rate-limited graphs.
+    pub fn with_writer_rate(mut self) -> Self {
+        self.writer_rate = true;
+        self
+    }
+
+    /// Passes the graph's audit log, once it is enabled.
+    pub fn with_audit_log(mut self) -> Self {
+        self.audit_log = true;
+        self
+    }
+
+    /// Passes the graph's journal, once the graph is journaled.
+    pub fn with_journal(mut self) -> Self {
+        self.journal = true;
+        self
+    }
+
+    /// `execute_query` with the Cypher text of `query`.
+    pub fn execute_query(
+        &self,
+        query: &dyn BuiltQuery,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Instruction {
+        self.execute_query_text(&query.to_string(), expected_version, partition)
+    }
+
+    /// `execute_query` with a hand-written query.
+    pub fn execute_query_text(
+        &self,
+        query: &str,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Instruction {
+        self.instruction(
+            instruction::ExecuteQuery {
+                query: query.to_string(),
+                expected_version,
+                partition,
+            }
+            .data(),
+        )
+    }
+
+    /// `execute_parsed_query` with a query parsed off-chain, e.g. from
+    /// `BuiltQuery::to_cypher`.
+    pub fn execute_parsed_query(
+        &self,
+        query: CypherQuery,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Instruction {
+        self.instruction(
+            instruction::ExecuteParsedQuery {
+                query,
+                expected_version,
+                partition,
+            }
+            .data(),
+        )
+    }
+
+    /// `execute_read_query`, which rejects writes and skips node data when
+    /// loading the graph.
+    pub fn execute_read_query(&self, query: &str, partition: Option<u32>) -> Instruction {
+        let accounts = accounts::ReadQuery {
+            graph_store: self.graph_store,
+            authority: self.authority,
+        };
+        Instruction {
+            program_id: sol_micro_sql::ID,
+            accounts: accounts.to_account_metas(None),
+            data: instruction::ExecuteReadQuery {
+                query: query.to_string(),
+                partition,
+            }
+            .data(),
+        }
+    }
+
+    /// `execute_opcodes` with precompiled opcodes, e.g. from
+    /// `BuiltQuery::to_opcodes`.
+    pub fn execute_opcodes(&self, ops: Vec<Opcode>, expected_version: Option<u64>) -> Instruction {
+        self.instruction(
+            instruction::ExecuteOpcodes {
+                ops,
+                expected_version,
+            }
+            .data(),
+        )
+    }
+
+    /// `execute_opcodes` with opcodes compiled at build time by
+    /// `cypher_ops!`, which are already Borsh-encoded.
+    pub fn execute_compiled(&self, ops: &[u8], expected_version: Option<u64>) -> Instruction {
+        let mut data = instruction::ExecuteOpcodes::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(ops);
+        expected_version
+            .serialize(&mut data)
+            .expect("writing to a Vec cannot fail");
+        self.instruction(data)
+    }
+
+    /// `open_query_cursor`, opening the authority's cursor on this graph
+    /// for a read query too large for one transaction.
+    pub fn open_query_cursor(&self, query: &str, partition: Option<u32>) -> Instruction {
+        let accounts = accounts::OpenQueryCursor {
+            graph_store: self.graph_store,
+            cursor: self.pda(CURSOR_SEED, &[self.authority.as_ref()]),
+            authority: self.authority,
+            system_program: system_program::ID,
+        };
+        Instruction {
+            program_id: sol_micro_sql::ID,
+            accounts: accounts.to_account_metas(None),
+            data: instruction::OpenQueryCursor {
+                query: query.to_string(),
+                partition,
+            }
+            .data(),
+        }
+    }
+
+    /// `resume_query`, running the authority's cursor for about `visits`
+    /// node and edge visits. Send it until the result is `Some`.
+    pub fn resume_query(&self, visits: u32) -> Instruction {
+        let accounts = accounts::ResumeQuery {
+            graph_store: self.graph_store,
+            cursor: self.pda(CURSOR_SEED, &[self.authority.as_ref()]),
+            authority: self.authority,
+            system_program: system_program::ID,
+        };
+        Instruction {
+            program_id: sol_micro_sql::ID,
+            accounts: accounts.to_account_metas(None),
+            data: instruction::ResumeQuery { visits }.data(),
+        }
+    }
+
+    /// `close_query_cursor`, abandoning the authority's cursor.
+    pub fn close_query_cursor(&self) -> Instruction {
+        let accounts = accounts::CloseQueryCursor {
+            cursor: self.pda(CURSOR_SEED, &[self.authority.as_ref()]),
+            authority: self.authority,
+        };
+        Instruction {
+            program_id: sol_micro_sql::ID,
+            accounts: accounts.to_account_metas(None),
+            data: instruction::CloseQueryCursor {}.data(),
+        }
+    }
+
+    fn pda(&self, seed: &[u8], extra: &[&[u8]]) -> Pubkey {
+        let mut seeds = vec![seed, self.graph_store.as_ref()];
+        seeds.extend_from_slice(extra);
+        Pubkey::find_program_address(&seeds, &sol_micro_sql::ID).0
+    }
+
+    fn instruction(&self, data: Vec<u8>) -> Instruction {
+        let accounts = accounts::ExecuteQuery {
+            graph_store: self.graph_store,
+            authority: self.authority,
+            treasury: None,
+            gate_token_account: None,
+            payer: None,
+            commitment: None,
+            session: None,
+            writer_rate: self
+                .writer_rate
+                .then(|| self.pda(WRITER_RATE_SEED, &[self.authority.as_ref()])),
+            audit_log: self.audit_log.then(|| self.pda(AUDIT_LOG_SEED, &[])),
+            journal: self.journal.then(|| self.pda(JOURNAL_SEED, &[])),
+            system_program: system_program::ID,
+        };
+        Instruction {
+            program_id: sol_micro_sql::ID,
+            accounts: accounts.to_account_metas(None),
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sol_micro_sql::cypher::parse;
+    use sol_micro_sql::lexer::compile_to_opcodes;
+
+    #[test]
+    fn test_execute_query_instruction() {
+        let authority = Pubkey::new_unique();
+        let graph_store = graph_store_address("default", &authority);
+        let client = GraphClient::new(graph_store, authority).with_journal();
+
+        let query = Query::match_node("n").label("User").limit(10);
+        let ix = client.execute_query(&query, Some(3), None);
+        assert_eq!(ix.program_id, sol_micro_sql::ID);
+        assert_eq!(ix.accounts[0].pubkey, graph_store);
+        assert!(ix.accounts[0].is_writable);
+        assert_eq!(ix.accounts[1].pubkey, authority);
+        assert!(ix.accounts[1].is_signer);
+        assert_eq!(
+            ix.accounts[9].pubkey,
+            Pubkey::find_program_address(&[JOURNAL_SEED, graph_store.as_ref()], &sol_micro_sql::ID)
+                .0
+        );
+        // Unused optional accounts are passed as the program id
+        assert_eq!(ix.accounts[2].pubkey, sol_micro_sql::ID);
+
+        let expected = instruction::ExecuteQuery {
+            query: "MATCH (n:User) RETURN n LIMIT 10".to_string(),
+            expected_version: Some(3),
+            partition: None,
+        };
+        assert_eq!(ix.data, expected.data());
+    }
+
+    #[test]
+    fn test_decode_result() {
+        let mut data = Vec::new();
+        VmResult::Nodes(vec![1, 2]).serialize(&mut data).unwrap();
+        assert_eq!(decode_result(&data).unwrap(), VmResult::Nodes(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_compiled_queries() {
+        const QUERY: &str = cypher!("MATCH (n:User) RETURN n LIMIT 10");
+        const OPS: &[u8] = cypher_ops!("MATCH (n:User) RETURN n LIMIT 10");
+        let ops = compile_to_opcodes(parse(QUERY).unwrap());
+        assert_eq!(Vec::<Opcode>::try_from_slice(OPS).unwrap(), ops);
+
+        let client = GraphClient::new(Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(
+            client.execute_compiled(OPS, Some(2)).data,
+            client.execute_opcodes(ops, Some(2)).data
+        );
+    }
+}
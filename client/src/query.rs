@@ -0,0 +1,520 @@
+use anchor_lang::prelude::Pubkey;
+use sol_micro_sql::cypher::{parse, CypherQuery, ParseError};
+use sol_micro_sql::graph::NodeId;
+use sol_micro_sql::lexer::compile_to_opcodes;
+use sol_micro_sql::vm::{CmpOp, Opcode, TimeField};
+use std::fmt;
+
+/// Entry point of the query builder.
+///
+/// ```
+/// use sol_micro_sql_client::Query;
+///
+/// let query = Query::match_node("n").label("User").where_id(5).limit(10);
+/// assert_eq!(
+///     query.to_string(),
+///     "MATCH (n:User) WHERE n.id = 5 RETURN n LIMIT 10"
+/// );
+/// ```
+pub struct Query;
+
+impl Query {
+    /// `MATCH (variable)`.
+    pub fn match_node(variable: &str) -> MatchQuery {
+        MatchQuery {
+            start: NodeRef::new(variable),
+            related: None,
+            mutual: false,
+            filter: None,
+            returns: Returns::Node,
+            limit: None,
+        }
+    }
+
+    /// `CREATE (variable)`.
+    pub fn create_node(variable: &str) -> CreateNode {
+        CreateNode {
+            node: NodeRef::new(variable),
+            data: None,
+            ttl: None,
+            id: None,
+        }
+    }
+
+    /// `CALL delegation.resolve(ids)`: the effective delegate of each node.
+    pub fn resolve_delegation(ids: &[NodeId]) -> ResolveDelegation {
+        ResolveDelegation { ids: ids.to_vec() }
+    }
+
+    /// `CALL cycle.find('label')`: one cycle of `label` edges, if any.
+    pub fn find_cycle(label: &str) -> FindCycle {
+        FindCycle {
+            label: label.to_string(),
+        }
+    }
+
+    /// `CREATE (from)-[:label]->(to)` between existing node ids.
+    pub fn create_edge(from: NodeId, label: &str, to: NodeId) -> CreateEdge {
+        CreateEdge {
+            from,
+            label: label.to_string(),
+            to,
+        }
+    }
+}
+
+/// A query that can be sent as Cypher text, already parsed, or compiled to
+/// opcodes up front.
+pub trait BuiltQuery: fmt::Display {
+    /// The opcodes the program would compile this query to.
+    fn to_opcodes(&self) -> Result<Vec<Opcode>, ParseError> {
+        Ok(compile_to_opcodes(self.to_cypher()?))
+    }
+
+    /// The parsed query, for `execute_parsed_query`.
+    fn to_cypher(&self) -> Result<CypherQuery, ParseError> {
+        parse(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct NodeRef {
+    variable: String,
+    label: Option<String>,
+}
+
+impl NodeRef {
+    fn new(variable: &str) -> Self {
+        NodeRef {
+            variable: variable.to_string(),
+            label: None,
+        }
+    }
+}
+
+impl fmt::Display for NodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "({}:{})", self.variable, label),
+            None => write!(f, "({})", self.variable),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Id(NodeId),
+    Mint(Pubkey),
+    Time {
+        field: TimeField,
+        op: CmpOp,
+        value: i64,
+    },
+    Between {
+        attr: String,
+        min: i64,
+        max: i64,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Returns {
+    Node,
+    Attr(String),
+    All,
+    Pairs,
+}
+
+/// Builder for `MATCH` queries. `label` and the `where_*` methods apply to
+/// the node most recently added to the pattern.
+#[derive(Debug, Clone)]
+pub struct MatchQuery {
+    start: NodeRef,
+    related: Option<(String, NodeRef)>,
+    /// The edge must also run back from the related node.
+    mutual: bool,
+    filter: Option<(String, Filter)>,
+    returns: Returns,
+    limit: Option<usize>,
+}
+
+impl MatchQuery {
+    /// Follows outgoing `edge_label` edges to `variable`.
+    pub fn out(mut self, edge_label: &str, variable: &str) -> Self {
+        self.related = Some((edge_label.to_string(), NodeRef::new(variable)));
+        self
+    }
+
+    /// Follows `edge_label` edges to `variable` nodes with an
+    /// `edge_label` edge back, such as mutual follows.
+    pub fn mutual(mut self, edge_label: &str, variable: &str) -> Self {
+        self.mutual = true;
+        self.out(edge_label, variable)
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.last_mut().label = Some(label.to_string());
+        self
+    }
+
+    pub fn where_id(self, id: NodeId) -> Self {
+        self.with_filter(Filter::Id(id))
+    }
+
+    pub fn where_mint(self, mint: Pubkey) -> Self {
+        self.with_filter(Filter::Mint(mint))
+    }
+
+    pub fn where_time(self, field: TimeField, op: CmpOp, value: i64) -> Self {
+        self.with_filter(Filter::Time { field, op, value })
+    }
+
+    /// `WHERE n.attr BETWEEN min AND max`, on an integer attribute such as
+    /// a map coordinate.
+    pub fn where_between(self, attr: &str, min: i64, max: i64) -> Self {
+        self.with_filter(Filter::Between {
+            attr: attr.to_string(),
+            min,
+            max,
+        })
+    }
+
+    /// Returns `attr` of the matched nodes instead of their ids.
+    pub fn return_attr(mut self, attr: &str) -> Self {
+        self.returns = Returns::Attr(attr.to_string());
+        self
+    }
+
+    pub fn return_all(mut self) -> Self {
+        self.returns = Returns::All;
+        self
+    }
+
+    /// Returns both nodes of each pair a `mutual` pattern matched.
+    pub fn return_pairs(mut self) -> Self {
+        self.returns = Returns::Pairs;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn last_mut(&mut self) -> &mut NodeRef {
+        match &mut self.related {
+            Some((_, node)) => node,
+            None => &mut self.start,
+        }
+    }
+
+    fn with_filter(mut self, filter: Filter) -> Self {
+        let variable = self.last_mut().variable.clone();
+        self.filter = Some((variable, filter));
+        self
+    }
+}
+
+impl fmt::Display for MatchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MATCH {}", self.start)?;
+        if let Some((edge_label, to)) = &self.related {
+            write!(f, "-[:{}]->{}", edge_label, to)?;
+            if self.mutual {
+                write!(
+                    f,
+                    ", ({})-[:{}]->({})",
+                    to.variable, edge_label, self.start.variable
+                )?;
+            }
+        }
+
+        if let Some((variable, filter)) = &self.filter {
+            match filter {
+                Filter::Id(id) => write!(f, " WHERE {}.id = {}", variable, id)?,
+                Filter::Mint(mint) => write!(f, " WHERE {}.mint = {}", variable, mint)?,
+                Filter::Time { field, op, value } => {
+                    let field = match field {
+                        TimeField::CreatedAt => "created_at",
+                        TimeField::UpdatedAt => "updated_at",
+                    };
+                    let op = match op {
+                        CmpOp::Eq => "=",
+                        CmpOp::Lt => "<",
+                        CmpOp::Le => "<=",
+                        CmpOp::Gt => ">",
+                        CmpOp::Ge => ">=",
+                    };
+                    write!(f, " WHERE {}.{} {} {}", variable, field, op, value)?
+                }
+                Filter::Between { attr, min, max } => write!(
+                    f,
+                    " WHERE {}.{} BETWEEN {} AND {}",
+                    variable, attr, min, max
+                )?,
+            }
+        }
+
+        let returned = &self
+            .related
+            .as_ref()
+            .map_or(&self.start, |(_, to)| to)
+            .variable;
+        match &self.returns {
+            Returns::Node => write!(f, " RETURN {}", returned)?,
+            Returns::Attr(attr) => write!(f, " RETURN {}.{}", returned, attr)?,
+            Returns::All => write!(f, " RETURN *")?,
+            Returns::Pairs => write!(f, " RETURN {}, {}", self.start.variable, returned)?,
+        }
+
+        // The parser rejects a MATCH without LIMIT, so none is made up here
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        Ok(())
+    }
+}
+
+impl BuiltQuery for MatchQuery {}
+
+/// Builder for `CREATE (n:Label {id: n, 0x..}) TTL n`.
+#[derive(Debug, Clone)]
+pub struct CreateNode {
+    node: NodeRef,
+    data: Option<Vec<u8>>,
+    ttl: Option<u64>,
+    id: Option<NodeId>,
+}
+
+impl CreateNode {
+    pub fn label(mut self, label: &str) -> Self {
+        self.node.label = Some(label.to_string());
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Slots until the node expires.
+    pub fn ttl(mut self, slots: u64) -> Self {
+        self.ttl = Some(slots);
+        self
+    }
+
+    /// Creates the node with this id rather than the graph's next one.
+    /// The query fails if a node already has it.
+    pub fn id(mut self, id: NodeId) -> Self {
+        self.id = Some(id);
+        self
+    }
+}
+
+impl fmt::Display for CreateNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CREATE ({}", self.node.variable)?;
+        if let Some(label) = &self.node.label {
+            write!(f, ":{}", label)?;
+        }
+        let data = self.data.as_ref().filter(|d| !d.is_empty());
+        if self.id.is_some() || data.is_some() {
+            write!(f, " {{")?;
+            if let Some(id) = self.id {
+                write!(f, "id: {}", id)?;
+                if data.is_some() {
+                    write!(f, ", ")?;
+                }
+            }
+            if let Some(data) = data {
+                write!(f, "0x")?;
+                for byte in data {
+                    write!(f, "{:02x}", byte)?;
+                }
+            }
+            write!(f, "}}")?;
+        }
+        write!(f, ")")?;
+        if let Some(ttl) = self.ttl {
+            write!(f, " TTL {}", ttl)?;
+        }
+        Ok(())
+    }
+}
+
+impl BuiltQuery for CreateNode {}
+
+/// Builder for `CREATE (from)-[:label]->(to)`.
+#[derive(Debug, Clone)]
+pub struct CreateEdge {
+    from: NodeId,
+    label: String,
+    to: NodeId,
+}
+
+impl fmt::Display for CreateEdge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CREATE ({})-[:{}]->({})", self.from, self.label, self.to)
+    }
+}
+
+impl BuiltQuery for CreateEdge {}
+
+/// Builder for `CALL delegation.resolve(id, ...)`.
+#[derive(Debug, Clone)]
+pub struct ResolveDelegation {
+    ids: Vec<NodeId>,
+}
+
+impl fmt::Display for ResolveDelegation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CALL delegation.resolve(")?;
+        for (i, id) in self.ids.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", id)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl BuiltQuery for ResolveDelegation {}
+
+/// Builder for `CALL cycle.find('label')`.
+#[derive(Debug, Clone)]
+pub struct FindCycle {
+    label: String,
+}
+
+impl fmt::Display for FindCycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CALL cycle.find('{}')", self.label)
+    }
+}
+
+impl BuiltQuery for FindCycle {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sol_micro_sql::graph::TraverseFilter;
+
+    #[test]
+    fn test_render_match() {
+        let query = Query::match_node("n")
+            .label("User")
+            .where_id(42)
+            .out("FOLLOWS", "m")
+            .label("User")
+            .return_attr("id")
+            .limit(10);
+        assert_eq!(
+            query.to_string(),
+            "MATCH (n:User)-[:FOLLOWS]->(m:User) WHERE n.id = 42 RETURN m.id LIMIT 10"
+        );
+
+        let mint = Pubkey::new_unique();
+        let query = Query::match_node("n")
+            .where_mint(mint)
+            .return_all()
+            .limit(5);
+        assert_eq!(
+            query.to_string(),
+            format!("MATCH (n) WHERE n.mint = {} RETURN * LIMIT 5", mint)
+        );
+
+        let query = Query::match_node("n")
+            .where_time(TimeField::CreatedAt, CmpOp::Ge, 1_700_000_000)
+            .limit(1);
+        assert_eq!(
+            query.to_string(),
+            "MATCH (n) WHERE n.created_at >= 1700000000 RETURN n LIMIT 1"
+        );
+
+        let query = Query::match_node("t")
+            .label("Tile")
+            .where_between("x", -5, 5)
+            .limit(50);
+        assert_eq!(
+            query.to_string(),
+            "MATCH (t:Tile) WHERE t.x BETWEEN -5 AND 5 RETURN t LIMIT 50"
+        );
+        assert!(query.to_cypher().is_ok());
+
+        let query = Query::match_node("a")
+            .label("User")
+            .mutual("FOLLOWS", "b")
+            .return_pairs()
+            .limit(20);
+        assert_eq!(
+            query.to_string(),
+            "MATCH (a:User)-[:FOLLOWS]->(b), (b)-[:FOLLOWS]->(a) RETURN a, b LIMIT 20"
+        );
+        assert!(query.to_cypher().is_ok());
+    }
+
+    #[test]
+    fn test_render_create() {
+        let query = Query::create_node("n")
+            .label("Session")
+            .data([0x12, 0x34])
+            .ttl(1500);
+        assert_eq!(query.to_string(), "CREATE (n:Session {0x1234}) TTL 1500");
+        assert_eq!(
+            Query::create_node("n")
+                .label("User")
+                .id(42)
+                .data(vec![0x01])
+                .to_string(),
+            "CREATE (n:User {id: 42, 0x01})"
+        );
+        assert_eq!(
+            Query::create_edge(1, "KNOWS", 2).to_string(),
+            "CREATE (1)-[:KNOWS]->(2)"
+        );
+    }
+
+    #[test]
+    fn test_to_opcodes() {
+        let ops = Query::match_node("n")
+            .label("User")
+            .limit(10)
+            .to_opcodes()
+            .unwrap();
+        assert!(matches!(ops[0], Opcode::SetCurrentFromAllNodes));
+        assert!(matches!(ops[1], Opcode::SetLimit(10)));
+        assert!(matches!(
+            &ops[2],
+            Opcode::TraverseOut(TraverseFilter { where_node_labels, .. })
+                if where_node_labels == &vec!["User".to_string()]
+        ));
+
+        let ops = Query::create_node("n")
+            .label("Session")
+            .ttl(5)
+            .to_opcodes()
+            .unwrap();
+        assert!(matches!(ops[0], Opcode::SetTtl(5)));
+        assert!(
+            matches!(&ops[1], Opcode::CreateNode { label, data } if label == "Session" && data.is_empty())
+        );
+
+        // Without a LIMIT the program would reject the query too
+        assert!(Query::match_node("n").to_opcodes().is_err());
+
+        let query = Query::resolve_delegation(&[3, 4]);
+        assert_eq!(query.to_string(), "CALL delegation.resolve(3, 4)");
+        assert_eq!(
+            query.to_opcodes().unwrap()[0],
+            Opcode::SetCurrentFromIds(vec![3, 4])
+        );
+
+        let query = Query::find_cycle("PLEDGES");
+        assert_eq!(query.to_string(), "CALL cycle.find('PLEDGES')");
+        assert_eq!(
+            query.to_opcodes().unwrap(),
+            vec![Opcode::FindCycle("PLEDGES".to_string())]
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Runs the program's own parser, compiler and VM off-chain against a
+//! fetched GraphStore, for free reads, write simulation and differential
+//! testing against on-chain results.
+//!
+//! The engine links the program crate itself (without its entrypoint)
+//! rather than a copy, so queries compile to the same opcodes and execute
+//! with the same semantics as on-chain. What it skips is everything around
+//! the VM that needs a transaction: roles, fees, rate limits, token gates
+//! and account resizing.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::AccountDeserialize;
+use sol_micro_sql::cypher::{parse, parse_with_params, CypherQuery, ParamValue, ParseError};
+use sol_micro_sql::graph::{GraphStore, GRAPH_LAYOUT_VERSION};
+use sol_micro_sql::lexer::compile_to_opcodes;
+use sol_micro_sql::migration::{read_header, upgrade, MigrationError};
+use sol_micro_sql::vm::{check_program, program_cost, Opcode, Vm, VmError, VmResult};
+
+#[derive(Debug)]
+pub enum EngineError {
+    NotAGraph,
+    Migration(MigrationError),
+    Parse(ParseError),
+    Vm(VmError),
+    /// Longer than the graph's `max_query_len`.
+    QueryTooLong,
+    /// More opcodes than the graph's `max_ops`.
+    TooManyOps,
+    /// Above the graph's `max_query_cost`.
+    QueryTooExpensive,
+}
+
+/// A local copy of one graph that queries run against. Writes only change
+/// the copy, so running one is a simulation of sending it. A query that
+/// fails leaves the copy as it was, as a failed transaction would.
+#[derive(Clone)]
+pub struct Engine {
+    graph: GraphStore,
+    signers: Vec<Pubkey>,
+    now: i64,
+    slot: u64,
+}
+
+impl Engine {
+    pub fn new(graph: GraphStore) -> Self {
+        Engine {
+            graph,
+            signers: Vec::new(),
+            now: 0,
+            slot: 0,
+        }
+    }
+
+    /// Decodes a fetched GraphStore account, upgrading older layouts the
+    /// way `migrate_graph` would.
+    pub fn from_account_data(data: &[u8]) -> Result<Self, EngineError> {
+        let (layout, _) = read_header(data).map_err(|_| EngineError::NotAGraph)?;
+        let graph = if layout == GRAPH_LAYOUT_VERSION {
+            GraphStore::try_deserialize(&mut &data[..]).map_err(|_| EngineError::NotAGraph)?
+        } else {
+            upgrade(data).map_err(EngineError::Migration)?
+        };
+        Ok(Engine::new(graph))
+    }
+
+    /// Signers whose node ACLs apply to `RETURN *` and `RETURN n.prop`, as
+    /// on-chain.
+    pub fn with_signers(mut self, signers: Vec<Pubkey>) -> Self {
+        self.signers = signers;
+        self
+    }
+
+    /// Unix timestamp stamped onto created nodes and edges.
+    pub fn at_time(mut self, now: i64) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Slot that node expiry is checked against; 0 disables expiry.
+    pub fn at_slot(mut self, slot: u64) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    pub fn graph(&self) -> &GraphStore {
+        &self.graph
+    }
+
+    /// Runs `query` as `execute_query` would.
+    pub fn query(&mut self, query: &str, partition: Option<u32>) -> Result<VmResult, EngineError> {
+        let cypher_query = parse(query).map_err(EngineError::Parse)?;
+        self.run_query(query.len(), cypher_query, partition)
+    }
+
+    /// Runs `query` as `execute_query_with_params` would.
+    pub fn query_with_params(
+        &mut self,
+        query: &str,
+        params: &[ParamValue],
+        partition: Option<u32>,
+    ) -> Result<VmResult, EngineError> {
+        let cypher_query = parse_with_params(query, params).map_err(EngineError::Parse)?;
+        self.run_query(query.len(), cypher_query, partition)
+    }
+
+    /// Runs raw opcodes as `execute_opcodes` would.
+    pub fn execute_opcodes(&mut self, ops: &[Opcode]) -> Result<VmResult, EngineError> {
+        check_program(ops, &self.graph.config).map_err(EngineError::Vm)?;
+        self.check_cost(ops)?;
+        self.execute(ops)
+    }
+
+    fn run_query(
+        &mut self,
+        query_len: usize,
+        cypher_query: CypherQuery,
+        partition: Option<u32>,
+    ) -> Result<VmResult, EngineError> {
+        let mut ops = compile_to_opcodes(cypher_query);
+        if let Some(partition) = partition {
+            ops.insert(0, Opcode::SetPartition(partition));
+        }
+
+        let config = &self.graph.config;
+        if query_len > config.max_query_len as usize {
+            return Err(EngineError::QueryTooLong);
+        }
+        if ops.len() > config.max_ops as usize {
+            return Err(EngineError::TooManyOps);
+        }
+        check_program(&ops, config).map_err(EngineError::Vm)?;
+        self.check_cost(&ops)?;
+
+        let mut result = self.execute(&ops)?;
+        self.graph.hide_unreadable(&mut result, &self.signers);
+        Ok(result)
+    }
+
+    fn check_cost(&self, ops: &[Opcode]) -> Result<(), EngineError> {
+        if program_cost(ops, &self.graph) > self.graph.config.max_query_cost as u64 {
+            return Err(EngineError::QueryTooExpensive);
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, ops: &[Opcode]) -> Result<VmResult, EngineError> {
+        Vm::new(&mut self.graph)
+            .at_time(self.now)
+            .at_slot(self.slot)
+            .execute(ops)
+            .map_err(EngineError::Vm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::AccountSerialize;
+    use sol_micro_sql::test_utils::empty_graph;
+
+    #[test]
+    fn test_queries_fetched_account() {
+        let mut data = Vec::new();
+        empty_graph().try_serialize(&mut data).unwrap();
+        let mut engine = Engine::from_account_data(&data).unwrap().at_time(100);
+
+        engine.query("CREATE (a:User)", None).unwrap();
+        engine.query("CREATE (b:User)", None).unwrap();
+        let (a, b) = (engine.graph().nodes[0].id, engine.graph().nodes[1].id);
+        engine
+            .query(&format!("CREATE ({})-[:KNOWS]->({})", a, b), None)
+            .unwrap();
+
+        let result = engine
+            .query("MATCH (n:User) RETURN n.id LIMIT 10", None)
+            .unwrap();
+        assert_eq!(result, VmResult::Nodes(vec![a, b]));
+        assert_eq!(engine.graph().edge_count, 1);
+        assert_eq!(engine.graph().nodes[0].created_at, 100);
+
+        assert!(matches!(
+            engine.query("MATCH (n) RETURN n", None),
+            Err(EngineError::Parse(ParseError::MissingLimit))
+        ));
+        assert!(matches!(
+            Engine::from_account_data(&[0; 8]),
+            Err(EngineError::NotAGraph)
+        ));
+    }
+
+    #[test]
+    fn test_create_stores_node_data() {
+        let mut graph = empty_graph();
+        graph.config.max_node_data_len = 2;
+        let mut engine = Engine::new(graph);
+
+        engine.query("CREATE (a:User {0x0102})", None).unwrap();
+        assert_eq!(engine.graph().nodes[0].data, vec![1, 2]);
+
+        let before = engine.graph().version;
+        assert!(matches!(
+            engine.query("CREATE (b:User {0x010203})", None),
+            Err(EngineError::Vm(VmError::DataTooLarge))
+        ));
+        assert_eq!(engine.graph().nodes.len(), 1);
+        assert_eq!(engine.graph().version, before);
+    }
+
+    #[test]
+    fn test_failed_program_changes_nothing() {
+        let mut engine = Engine::new(empty_graph());
+        engine.query("CREATE (a:User)", None).unwrap();
+        let before = engine.graph().version;
+
+        let ops = vec![
+            Opcode::CreateNode {
+                label: "User".to_string(),
+                data: Vec::new(),
+            },
+            Opcode::CreateNode {
+                label: "User".to_string(),
+                data: vec![0; 4096],
+            },
+        ];
+        assert!(matches!(
+            engine.execute_opcodes(&ops),
+            Err(EngineError::Vm(VmError::DataTooLarge))
+        ));
+        assert_eq!(engine.graph().nodes.len(), 1);
+        assert_eq!(engine.graph().version, before);
+    }
+
+    #[test]
+    fn test_matches_compiled_opcodes() {
+        let mut from_query = Engine::new(empty_graph());
+        let mut from_ops = from_query.clone();
+        let query = "CREATE (n:Post {0x01}) TTL 5";
+
+        from_query.query(query, None).unwrap();
+        let ops = compile_to_opcodes(parse(query).unwrap());
+        from_ops.execute_opcodes(&ops).unwrap();
+        let encode = |engine: &Engine| {
+            let mut data = Vec::new();
+            engine.graph().try_serialize(&mut data).unwrap();
+            data
+        };
+        assert_eq!(from_query.graph().node_count, 1);
+        assert_eq!(encode(&from_query), encode(&from_ops));
+    }
+}
@@ -0,0 +1,1222 @@
+//! Runs the program's instructions natively, through the same account
+//! validation, handlers and exit routine a validator runs, against accounts
+//! held in memory. Tests get the program's real behaviour (roles, version
+//! checks, account resizing, rollback on error) without a validator.
+//!
+//! ```ignore
+//! let mut harness = Harness::new();
+//! let graph = harness.initialize_graph("social", authority)?;
+//! harness.process(&graph.execute_query_text("CREATE (n:User)", None, None))?;
+//! let users = harness.execute(&graph, "MATCH (n:User) RETURN n LIMIT 10")?;
+//! ```
+//!
+//! Accounts are laid out in the buffer format the loader passes to an
+//! entrypoint, so `AccountInfo::resize` behaves as it does on-chain. Two
+//! things a native build cannot do are worked around:
+//!
+//! - Cross-program invocations are not available. The harness creates graph
+//!   accounts itself for `initialize_graph` and `initialize_user_graph`,
+//!   then runs their handlers, and gives graph accounts enough lamports up
+//!   front that growing them never needs the system-program transfer in
+//!   `grow_account`. Payers are not charged. Other instructions that create
+//!   accounts or move lamports (audit logs, journals, sessions, write fees)
+//!   cannot run here.
+//! - `set_return_data` is a no-op, so `query` calls the query handlers
+//!   directly, the way the `#[program]` dispatcher does, and hands back the
+//!   `VmResult` they return.
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::prelude::{
+    Account as AnchorAccount, AccountInfo, Clock, Context, Program, ProgramError, Pubkey, Rent,
+    Signer,
+};
+use anchor_lang::solana_program::entrypoint::{deserialize, MAX_PERMITTED_DATA_INCREASE};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{
+    system_program, AccountDeserialize, AccountSerialize, Accounts, AccountsExit,
+    AnchorDeserialize, Bumps, Discriminator,
+};
+use sol_micro_sql::graph::{
+    GraphSizing, GraphStore, Node, NodeId, GRAPH_STORE_SEED, USER_GRAPH_SEED,
+};
+use sol_micro_sql::vm::VmResult;
+use sol_micro_sql::{
+    instruction, sol_micro_sql as handlers, ExecuteQuery, InitializeGraph, InitializeGraphBumps,
+    InitializeUserGraph, InitializeUserGraphBumps, ReadQuery, ID,
+};
+use sol_micro_sql_client::GraphClient;
+use solana_sysvar::program_stubs::{set_syscall_stubs, SyscallStubs};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Once;
+
+/// Lamports given to graph accounts: rent for a 10 MiB account, the most
+/// any account can hold, so resizes never need a top-up.
+const GRAPH_LAMPORTS: u64 = 73_000_000_000;
+
+const NATIVE_LOADER_ID: Pubkey =
+    anchor_lang::pubkey!("NativeLoader1111111111111111111111111111111");
+const BPF_LOADER_UPGRADEABLE_ID: Pubkey =
+    anchor_lang::pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+
+/// An account as the harness stores it between instructions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+/// In-memory accounts and a clock that instructions run against.
+pub struct Harness {
+    accounts: HashMap<Pubkey, Account>,
+    clock: Clock,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Harness {
+    /// Empty accounts apart from the program and the system program, at
+    /// slot 0 and timestamp 0.
+    pub fn new() -> Self {
+        install_stubs();
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            ID,
+            Account {
+                lamports: 1,
+                data: Vec::new(),
+                owner: BPF_LOADER_UPGRADEABLE_ID,
+                executable: true,
+            },
+        );
+        accounts.insert(
+            system_program::ID,
+            Account {
+                lamports: 1,
+                data: Vec::new(),
+                owner: NATIVE_LOADER_ID,
+                executable: true,
+            },
+        );
+        Harness {
+            accounts,
+            clock: Clock::default(),
+        }
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Moves the clock to `slot`, which node expiry is checked against.
+    pub fn warp_to_slot(&mut self, slot: u64) {
+        self.clock.slot = slot;
+    }
+
+    /// Sets the timestamp stamped onto created nodes and edges.
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        self.clock.unix_timestamp = unix_timestamp;
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    pub fn set_account(&mut self, address: Pubkey, account: Account) {
+        self.accounts.insert(address, account);
+    }
+
+    /// Stores `graph` at the address its seeds derive, with its bump set,
+    /// and returns the address.
+    pub fn add_graph(&mut self, mut graph: GraphStore) -> Pubkey {
+        let (address, bump) = Pubkey::find_program_address(
+            &[
+                graph.seed_prefix(),
+                graph.name.as_bytes(),
+                graph.creator.as_ref(),
+            ],
+            &ID,
+        );
+        graph.bump = bump;
+        let mut data = Vec::new();
+        graph.try_serialize(&mut data).expect("graph serializes");
+        self.set_account(
+            address,
+            Account {
+                lamports: GRAPH_LAMPORTS,
+                data,
+                owner: ID,
+                executable: false,
+            },
+        );
+        address
+    }
+
+    /// The graph stored at `address`, if there is one.
+    pub fn graph(&self, address: &Pubkey) -> Option<GraphStore> {
+        let account = self.accounts.get(address)?;
+        GraphStore::try_deserialize(&mut &account.data[..]).ok()
+    }
+
+    /// Node `id` of the graph at `address`, deleted or not.
+    pub fn node(&self, address: &Pubkey, id: NodeId) -> Option<Node> {
+        self.graph(address)?
+            .nodes
+            .into_iter()
+            .find(|node| node.id == id)
+    }
+
+    /// Sends `initialize_graph` for the graph `name` of `authority`, who
+    /// also pays, and returns a client for it.
+    pub fn initialize_graph(
+        &mut self,
+        name: &str,
+        authority: Pubkey,
+    ) -> Result<GraphClient, ProgramError> {
+        let ix = sol_micro_sql_client::initialize_graph(
+            name,
+            GraphSizing::default(),
+            authority,
+            authority,
+        );
+        self.process(&ix)?;
+        Ok(GraphClient::new(ix.accounts[0].pubkey, authority))
+    }
+
+    /// Runs `query` on `graph` as its client's authority.
+    pub fn execute(&mut self, graph: &GraphClient, query: &str) -> Result<VmResult, ProgramError> {
+        self.query(&graph.execute_query_text(query, None, None))
+    }
+
+    /// Runs `ix` through the program's entrypoint. On error no account is
+    /// changed, as when a transaction fails.
+    pub fn process(&mut self, ix: &Instruction) -> Result<(), ProgramError> {
+        let discriminator = ix.data.get(..8).unwrap_or_default();
+        if discriminator == instruction::InitializeGraph::DISCRIMINATOR
+            || discriminator == instruction::InitializeUserGraph::DISCRIMINATOR
+        {
+            return self.invoke(ix, |program_id, accounts, data| {
+                run_initialize(program_id, accounts, data).map_err(Into::into)
+            });
+        }
+        self.invoke(ix, sol_micro_sql::entry)
+    }
+
+    /// Runs an `execute_query`, `execute_query_with_params`,
+    /// `execute_parsed_query`, `execute_read_query` or `execute_opcodes`
+    /// instruction and returns its result. On error no account is changed.
+    pub fn query(&mut self, ix: &Instruction) -> Result<VmResult, ProgramError> {
+        self.invoke(ix, |program_id, accounts, data| {
+            run_query(program_id, accounts, data).map_err(Into::into)
+        })
+    }
+
+    /// Serializes the accounts `ix` lists, runs `handler` on them and
+    /// stores them back if it succeeds.
+    fn invoke<T>(
+        &mut self,
+        ix: &Instruction,
+        handler: impl for<'info> FnOnce(
+            &Pubkey,
+            &'info [AccountInfo<'info>],
+            &[u8],
+        ) -> Result<T, ProgramError>,
+    ) -> Result<T, ProgramError> {
+        let (mut input, keys) = self.serialize(ix);
+        CLOCK.with(|clock| *clock.borrow_mut() = self.clock.clone());
+
+        // SAFETY: `input` is laid out as `deserialize` expects and outlives
+        // every AccountInfo borrowed from it.
+        let (program_id, infos, data) = unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+        let result = handler(program_id, &infos, data)?;
+        for key in keys {
+            let info = infos.iter().find(|info| *info.key == key).unwrap();
+            let account = Account {
+                lamports: info.lamports(),
+                data: info.data.borrow().to_vec(),
+                owner: *info.owner,
+                executable: info.executable,
+            };
+            self.accounts.insert(key, account);
+        }
+        Ok(result)
+    }
+
+    /// The entrypoint input for `ix`, 8-byte aligned, and the distinct
+    /// accounts in it.
+    fn serialize(&self, ix: &Instruction) -> (Vec<u64>, Vec<Pubkey>) {
+        let mut keys: Vec<Pubkey> = Vec::new();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(ix.accounts.len() as u64).to_le_bytes());
+        for (position, meta) in ix.accounts.iter().enumerate() {
+            let first = ix.accounts.iter().position(|m| m.pubkey == meta.pubkey);
+            if let Some(index) = first.filter(|index| *index < position) {
+                bytes.push(index as u8);
+                bytes.extend_from_slice(&[0; 7]);
+                continue;
+            }
+            keys.push(meta.pubkey);
+            // A key listed more than once is signer or writable if any
+            // listing says so.
+            let listings = ix.accounts.iter().filter(|m| m.pubkey == meta.pubkey);
+            let (signer, writable) = listings.fold((false, false), |(s, w), m| {
+                (s || m.is_signer, w || m.is_writable)
+            });
+            let account = self.accounts.get(&meta.pubkey).cloned().unwrap_or(Account {
+                owner: system_program::ID,
+                ..Account::default()
+            });
+
+            bytes.extend_from_slice(&[u8::MAX, signer as u8, writable as u8]);
+            bytes.push(account.executable as u8);
+            bytes.extend_from_slice(&[0; 4]);
+            bytes.extend_from_slice(meta.pubkey.as_ref());
+            bytes.extend_from_slice(account.owner.as_ref());
+            bytes.extend_from_slice(&account.lamports.to_le_bytes());
+            bytes.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&account.data);
+            bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+            bytes.resize(bytes.len().next_multiple_of(8), 0);
+            bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(ix.data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&ix.data);
+        bytes.extend_from_slice(ix.program_id.as_ref());
+
+        let mut input = vec![0u64; bytes.len().div_ceil(8)];
+        for (word, chunk) in input.iter_mut().zip(bytes.chunks(8)) {
+            let mut le = [0; 8];
+            le[..chunk.len()].copy_from_slice(chunk);
+            *word = u64::from_ne_bytes(le);
+        }
+        (input, keys)
+    }
+}
+
+/// `initialize_graph` and `initialize_user_graph`, with the system
+/// program's `create_account` done here instead of through CPI: what their
+/// `init` constraint checks, then the handler and exit routine.
+fn run_initialize<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> anchor_lang::Result<()> {
+    let (discriminator, mut args) = data.split_at(data.len().min(8));
+    let [graph_store, signer, payer, system_program, ..] = accounts else {
+        return Err(ErrorCode::AccountNotEnoughKeys.into());
+    };
+    let (name, sizing) = match discriminator == instruction::InitializeGraph::DISCRIMINATOR {
+        true => {
+            let ix = instruction::InitializeGraph::deserialize(&mut args)
+                .map_err(|_| ErrorCode::InstructionDidNotDeserialize)?;
+            (Some(ix.name), ix.sizing)
+        }
+        false => (
+            None,
+            instruction::InitializeUserGraph::deserialize(&mut args)
+                .map_err(|_| ErrorCode::InstructionDidNotDeserialize)?
+                .sizing,
+        ),
+    };
+    let seeds: Vec<&[u8]> = match &name {
+        Some(name) => vec![GRAPH_STORE_SEED, name.as_bytes(), signer.key.as_ref()],
+        None => vec![USER_GRAPH_SEED, signer.key.as_ref()],
+    };
+    let (address, bump) = Pubkey::find_program_address(&seeds, program_id);
+    if *graph_store.key != address {
+        return Err(ErrorCode::ConstraintSeeds.into());
+    }
+    if *graph_store.owner != system_program::ID || !graph_store.data_is_empty() {
+        // The system program's `AccountAlreadyInUse`.
+        return Err(ProgramError::Custom(0).into());
+    }
+    **graph_store.try_borrow_mut_lamports()? = GRAPH_LAMPORTS;
+    graph_store.resize(sizing.initial_space())?;
+    graph_store.assign(program_id);
+
+    let graph_store = AnchorAccount::try_from_unchecked(graph_store)?;
+    let signer = Signer::try_from(signer)?;
+    let payer = Signer::try_from(payer)?;
+    let system_program = Program::try_from(system_program)?;
+    match name {
+        Some(name) => {
+            let mut accounts = InitializeGraph {
+                graph_store,
+                authority: signer,
+                payer,
+                system_program,
+            };
+            let bumps = InitializeGraphBumps { graph_store: bump };
+            let ctx = Context::new(program_id, &mut accounts, &[], bumps);
+            handlers::initialize_graph(ctx, name, sizing)?;
+            accounts.exit(program_id)
+        }
+        None => {
+            let mut accounts = InitializeUserGraph {
+                graph_store,
+                user: signer,
+                payer,
+                system_program,
+            };
+            let bumps = InitializeUserGraphBumps { graph_store: bump };
+            let ctx = Context::new(program_id, &mut accounts, &[], bumps);
+            handlers::initialize_user_graph(ctx, sizing)?;
+            accounts.exit(program_id)
+        }
+    }
+}
+
+/// What `#[program]` generates for the query instructions, minus
+/// `set_return_data`: decode the arguments, validate the accounts, run the
+/// handler, then the exit routine that writes the accounts back.
+fn run_query<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> anchor_lang::Result<VmResult> {
+    enum Call {
+        Query(instruction::ExecuteQuery),
+        QueryWithParams(instruction::ExecuteQueryWithParams),
+        ParsedQuery(Box<instruction::ExecuteParsedQuery>),
+        Opcodes(instruction::ExecuteOpcodes),
+    }
+    let (discriminator, mut args) = data.split_at(data.len().min(8));
+    if discriminator == instruction::ExecuteReadQuery::DISCRIMINATOR {
+        return run_read_query(program_id, accounts, data);
+    }
+    let call = match discriminator {
+        d if d == instruction::ExecuteQuery::DISCRIMINATOR => {
+            AnchorDeserialize::deserialize(&mut args).map(Call::Query)
+        }
+        d if d == instruction::ExecuteQueryWithParams::DISCRIMINATOR => {
+            AnchorDeserialize::deserialize(&mut args).map(Call::QueryWithParams)
+        }
+        d if d == instruction::ExecuteParsedQuery::DISCRIMINATOR => {
+            AnchorDeserialize::deserialize(&mut args).map(|ix| Call::ParsedQuery(Box::new(ix)))
+        }
+        d if d == instruction::ExecuteOpcodes::DISCRIMINATOR => {
+            AnchorDeserialize::deserialize(&mut args).map(Call::Opcodes)
+        }
+        _ => return Err(ErrorCode::InstructionFallbackNotFound.into()),
+    }
+    .map_err(|_| ErrorCode::InstructionDidNotDeserialize)?;
+
+    let mut bumps = <ExecuteQuery as Bumps>::Bumps::default();
+    let mut remaining = accounts;
+    let mut execute_query = ExecuteQuery::try_accounts(
+        program_id,
+        &mut remaining,
+        data,
+        &mut bumps,
+        &mut BTreeSet::new(),
+    )?;
+    let ctx = Context::new(program_id, &mut execute_query, remaining, bumps);
+    let result = match call {
+        Call::Query(ix) => {
+            handlers::execute_query(ctx, ix.query, ix.expected_version, ix.partition)?
+        }
+        Call::QueryWithParams(ix) => handlers::execute_query_with_params(
+            ctx,
+            ix.query,
+            ix.params,
+            ix.expected_version,
+            ix.partition,
+        )?,
+        Call::ParsedQuery(ix) => {
+            let ix = *ix;
+            handlers::execute_parsed_query(ctx, ix.query, ix.expected_version, ix.partition)?
+        }
+        Call::Opcodes(ix) => handlers::execute_opcodes(ctx, ix.ops, ix.expected_version)?,
+    };
+    execute_query.exit(program_id)?;
+    Ok(result)
+}
+
+/// `run_query` for `execute_read_query`, which takes its own accounts.
+fn run_read_query<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> anchor_lang::Result<VmResult> {
+    let ix = instruction::ExecuteReadQuery::deserialize(&mut &data[8..])
+        .map_err(|_| ErrorCode::InstructionDidNotDeserialize)?;
+
+    let mut bumps = <ReadQuery as Bumps>::Bumps::default();
+    let mut remaining = accounts;
+    let mut read_query = ReadQuery::try_accounts(
+        program_id,
+        &mut remaining,
+        data,
+        &mut bumps,
+        &mut BTreeSet::new(),
+    )?;
+    let ctx = Context::new(program_id, &mut read_query, remaining, bumps);
+    let result = handlers::execute_read_query(ctx, ix.query, ix.partition)?;
+    read_query.exit(program_id)?;
+    Ok(result)
+}
+
+thread_local! {
+    /// Clock of the harness running on this thread, read by the stubs.
+    static CLOCK: RefCell<Clock> = RefCell::new(Clock::default());
+}
+
+/// Sysvar syscalls for native builds, which otherwise fail.
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = CLOCK.with(|clock| clock.borrow().clone());
+        // SAFETY: the sysvar getter passes a pointer to a `Clock`.
+        unsafe { std::ptr::write(var_addr as *mut Clock, clock) };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the sysvar getter passes a pointer to a `Rent`.
+        unsafe { std::ptr::write(var_addr as *mut Rent, Rent::default()) };
+        0
+    }
+}
+
+fn install_stubs() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        set_syscall_stubs(Box::new(Stubs));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::instruction::AccountMeta;
+    use anchor_lang::{InstructionData, ToAccountMetas};
+    use proptest::prelude::*;
+    use sol_micro_sql::accounts;
+    use sol_micro_sql::attrs::{set_attribute, AttrValue};
+    use sol_micro_sql::cypher::{parse, CypherQuery};
+    use sol_micro_sql::graph::{graph_space, EdgeRef, MAX_NODES};
+    use sol_micro_sql::metadata::{GraphMetadata, METADATA_SEED};
+    use sol_micro_sql::roles::Role;
+    use sol_micro_sql::test_utils::{small_graph, GraphBuilder};
+    use sol_micro_sql::vm::Opcode;
+    use sol_micro_sql_client::user_graph_address;
+    use sol_micro_sql_engine::Engine;
+
+    const NODE_LABELS: [&str; 3] = ["User", "Post", "City"];
+    const EDGE_LABELS: [&str; 2] = ["KNOWS", "LIKES"];
+
+    /// A graph stored in a fresh harness, and a client for its authority.
+    fn setup(mut graph: GraphStore) -> (Harness, GraphClient) {
+        graph.name = "test".to_string();
+        graph.creator = graph.authority;
+        let mut harness = Harness::new();
+        harness.warp_to_slot(10);
+        harness.set_unix_timestamp(1_000);
+        let address = harness.add_graph(graph.clone());
+        (harness, GraphClient::new(address, graph.authority))
+    }
+
+    fn encode(graph: &GraphStore) -> Vec<u8> {
+        let mut data = Vec::new();
+        graph.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_read_query_filters_on_attributes() {
+        let mut graph = small_graph();
+        let data = set_attribute(&[], "name", AttrValue::Str("bob".to_string())).unwrap();
+        graph.set_node_data(4, data, false).unwrap();
+        let (mut harness, client) = setup(graph);
+
+        let query = "MATCH (n:Town) WHERE n.name = 'bob' RETURN n LIMIT 10";
+        let read = client.execute_read_query(query, None);
+        assert_eq!(harness.query(&read), Ok(VmResult::Nodes(vec![4])));
+        assert_eq!(
+            harness.execute(&client, query),
+            Ok(VmResult::Nodes(vec![4]))
+        );
+    }
+
+    #[test]
+    fn test_query_returns_projections() {
+        let mut graph = small_graph();
+        let data = set_attribute(&[], "name", AttrValue::Str("bob".to_string())).unwrap();
+        graph.set_node_data(4, data, false).unwrap();
+        let (mut harness, client) = setup(graph);
+
+        let query = "MATCH (n:Town) RETURN n.name LIMIT 10";
+        let expected = VmResult::Values(vec![(4, Some("bob".to_string())), (5, None)]);
+        let read = client.execute_read_query(query, None);
+        assert_eq!(harness.query(&read), Ok(expected.clone()));
+        assert_eq!(harness.execute(&client, query), Ok(expected));
+
+        let read = client.execute_read_query("MATCH (n:Town) RETURN * LIMIT 10", None);
+        let Ok(VmResult::Records(nodes)) = harness.query(&read) else {
+            panic!("expected records");
+        };
+        assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(nodes[0].label.to_string(), "Town");
+    }
+
+    #[test]
+    fn test_query_filters_coordinate_range() {
+        let mut graph = small_graph();
+        for (id, x) in [(1, -4), (2, 7), (3, 12), (4, 3)] {
+            let data = set_attribute(&[], "x", AttrValue::Int(x)).unwrap();
+            graph.set_node_data(id, data, false).unwrap();
+        }
+        let (mut harness, client) = setup(graph);
+
+        let query = "MATCH (n) WHERE n.x BETWEEN -5 AND 10 RETURN n LIMIT 10";
+        let read = client.execute_read_query(query, None);
+        assert_eq!(harness.query(&read), Ok(VmResult::Nodes(vec![1, 2, 4])));
+        let query = "MATCH (n:City) WHERE n.x BETWEEN 0 AND 20 RETURN n LIMIT 10";
+        assert_eq!(
+            harness.execute(&client, query),
+            Ok(VmResult::Nodes(vec![2, 3]))
+        );
+    }
+
+    #[test]
+    fn test_query_sums_path_edges() {
+        let mut graph = small_graph();
+        for edge in graph.edges.iter_mut() {
+            let amount = match (edge.from, edge.to) {
+                (1, 2) => 10,
+                (2, 3) => 20,
+                (1, 3) => 5,
+                _ => 100,
+            };
+            edge.data = set_attribute(&[], "amount", AttrValue::Int(amount)).unwrap();
+        }
+        let (mut harness, client) = setup(graph);
+
+        // 1-2, 1-2-3 and 1-3 by rail; 1-3-1 comes back to its start
+        let query = "MATCH (a)-[r:Railway*1..2]->(b) WHERE a.id = 1 RETURN SUM(r.amount) LIMIT 1";
+        let read = client.execute_read_query(query, None);
+        assert_eq!(harness.query(&read), Ok(VmResult::Scalar(45)));
+        let query =
+            "MATCH (a)-[r:Railway*1..2]->(b:Town) WHERE a.id = 1 RETURN SUM(r.amount) LIMIT 1";
+        assert_eq!(harness.execute(&client, query), Ok(VmResult::Scalar(0)));
+    }
+
+    #[test]
+    fn test_query_returns_mutual_pairs() {
+        let (mut harness, client) = setup(small_graph());
+
+        // 1 and 3 are linked by rail both ways; 1 -> 2 -> 3 is one-way
+        let query = "MATCH (a:City)-[:Railway]->(b), (b)-[:Railway]->(a) RETURN a, b LIMIT 10";
+        let read = client.execute_read_query(query, None);
+        assert_eq!(
+            harness.query(&read),
+            Ok(VmResult::Pairs(vec![(1, 3), (3, 1)]))
+        );
+        let query = "MATCH (a:City)-[:Railway]->(b), (b)-[:Railway]->(a) RETURN b LIMIT 10";
+        assert_eq!(
+            harness.execute(&client, query),
+            Ok(VmResult::Nodes(vec![3, 1]))
+        );
+    }
+
+    #[test]
+    fn test_call_resolves_delegation() {
+        let (mut harness, client) = setup(small_graph());
+        for query in [
+            "CREATE (1)-[:DELEGATES_TO]->(2)",
+            "CREATE (2)-[:DELEGATES_TO]->(4)",
+        ] {
+            harness.execute(&client, query).unwrap();
+        }
+
+        let read = client.execute_read_query("CALL delegation.resolve(1, 3)", None);
+        assert_eq!(
+            harness.query(&read),
+            Ok(VmResult::Delegates(vec![(1, Some(4)), (3, Some(3))]))
+        );
+    }
+
+    #[test]
+    fn test_call_finds_cycle() {
+        let (mut harness, client) = setup(small_graph());
+        let read = client.execute_read_query("CALL cycle.find('Railway')", None);
+        assert_eq!(harness.query(&read), Ok(VmResult::Cycle(vec![1, 2, 3])));
+        let read = client.execute_read_query("CALL cycle.find('Highway')", None);
+        assert_eq!(harness.query(&read), Ok(VmResult::Cycle(vec![])));
+
+        // Closing a cycle fails the write, leaving the graph as it was
+        let close = |to| {
+            client.execute_opcodes(
+                vec![
+                    Opcode::CreateEdge {
+                        from: 4,
+                        to,
+                        label: "Highway".to_string(),
+                    },
+                    Opcode::RejectCycle("Highway".to_string()),
+                ],
+                None,
+            )
+        };
+        let before = harness.account(&client.graph_store).cloned();
+        assert!(harness.query(&close(2)).is_err());
+        assert_eq!(harness.account(&client.graph_store).cloned(), before);
+        assert_eq!(harness.query(&close(5)), Ok(VmResult::Nodes(vec![5])));
+    }
+
+    #[test]
+    fn test_close_graph_reclaims_children() {
+        let (mut harness, client) = setup(small_graph());
+        let recipient = Pubkey::new_unique();
+        let mut add_metadata = |graph: Pubkey| {
+            let (address, bump) =
+                Pubkey::find_program_address(&[METADATA_SEED, graph.as_ref()], &ID);
+            let metadata = GraphMetadata {
+                graph,
+                display_name: "Test".to_string(),
+                description: String::new(),
+                schema_uri: String::new(),
+                tags: Vec::new(),
+                created_at: 0,
+                bump,
+            };
+            let mut data = Vec::new();
+            metadata.try_serialize(&mut data).unwrap();
+            harness.set_account(
+                address,
+                Account {
+                    lamports: 5_000,
+                    data,
+                    owner: ID,
+                    executable: false,
+                },
+            );
+            address
+        };
+        let metadata = add_metadata(client.graph_store);
+        let foreign = add_metadata(Pubkey::new_unique());
+        let close = |authority, children: &[Pubkey]| {
+            let mut accounts = accounts::CloseGraph {
+                graph_store: client.graph_store,
+                authority,
+                recipient,
+            }
+            .to_account_metas(None);
+            accounts.extend(children.iter().map(|&key| AccountMeta::new(key, false)));
+            Instruction {
+                program_id: ID,
+                accounts,
+                data: instruction::CloseGraph {}.data(),
+            }
+        };
+
+        // Only the owner may close, and only the graph's own children
+        assert!(harness
+            .process(&close(Pubkey::new_unique(), &[metadata]))
+            .is_err());
+        assert!(harness
+            .process(&close(client.authority, &[metadata, foreign]))
+            .is_err());
+        assert!(harness.graph(&client.graph_store).is_some());
+
+        harness
+            .process(&close(client.authority, &[metadata]))
+            .unwrap();
+        assert!(harness.graph(&client.graph_store).is_none());
+        assert_eq!(
+            harness.account(&recipient).unwrap().lamports,
+            GRAPH_LAMPORTS + 5_000
+        );
+        let closed = harness.account(&metadata).unwrap();
+        assert_eq!(
+            (closed.lamports, closed.owner, closed.data.len()),
+            (0, system_program::ID, 0)
+        );
+        assert_eq!(harness.account(&foreign).unwrap().lamports, 5_000);
+    }
+
+    #[test]
+    fn test_query_runs_program_handlers() {
+        let (mut harness, client) = setup(small_graph());
+        let address = client.graph_store;
+
+        let result = harness
+            .query(&client.execute_query_text("MATCH (n:Town) RETURN n LIMIT 5", None, None))
+            .unwrap();
+        assert_eq!(result, VmResult::Nodes(vec![4, 5]));
+
+        harness
+            .query(&client.execute_query_text("CREATE (n:Town) TTL 5", Some(0), None))
+            .unwrap();
+        let graph = harness.graph(&address).unwrap();
+        assert_eq!((graph.node_count, graph.version), (6, 1));
+        assert_eq!(graph.nodes[5].created_at, 1_000);
+        assert_eq!(graph.nodes[5].expires_slot, 15);
+
+        // A failed instruction leaves the account as it was.
+        let before = harness.account(&address).cloned();
+        let stale = client.execute_query_text("CREATE (n:Town)", Some(0), None);
+        assert!(harness.query(&stale).is_err());
+        assert_eq!(harness.account(&address).cloned(), before);
+
+        let stranger = GraphClient::new(address, Pubkey::new_unique());
+        let write = stranger.execute_query_text("CREATE (n:Town)", None, None);
+        assert!(harness.query(&write).is_err());
+    }
+
+    #[test]
+    fn test_partition_confined_grant() {
+        // City(2) and the Railway edge 1 -> 2 are in partition 1
+        let mut graph = small_graph();
+        graph.nodes[1].partition = 1;
+        graph.edges[0].partition = 1;
+        let tenant = Pubkey::new_unique();
+        graph.set_role(tenant, Role::Writer, Some(1)).unwrap();
+        let (mut harness, client) = setup(graph);
+        let address = client.graph_store;
+
+        let update = |node_id| Instruction {
+            program_id: ID,
+            accounts: accounts::WriteGraph {
+                graph_store: address,
+                authority: tenant,
+                treasury: None,
+                writer_rate: None,
+                audit_log: None,
+                journal: None,
+                payer: None,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::UpdateNodeData {
+                node_id,
+                data: vec![7],
+                append: false,
+            }
+            .data(),
+        };
+        harness.process(&update(2)).unwrap();
+        assert_eq!(harness.node(&address, 2).unwrap().data, vec![7]);
+        assert!(harness.process(&update(1)).is_err());
+        assert!(harness.node(&address, 1).unwrap().data.is_empty());
+
+        let node_info = |node_id| Instruction {
+            program_id: ID,
+            accounts: accounts::GetNodeInfo {
+                graph_store: address,
+                reader: Some(tenant),
+            }
+            .to_account_metas(None),
+            data: instruction::GetNodeInfo { node_id }.data(),
+        };
+        harness.process(&node_info(2)).unwrap();
+        assert!(harness.process(&node_info(1)).is_err());
+
+        let edge_info = |index| Instruction {
+            program_id: ID,
+            accounts: accounts::GetEdgeInfo {
+                graph_store: address,
+                reader: Some(tenant),
+            }
+            .to_account_metas(None),
+            data: instruction::GetEdgeInfo {
+                edge_ref: EdgeRef::Index(index),
+            }
+            .data(),
+        };
+        harness.process(&edge_info(0)).unwrap();
+        assert!(harness.process(&edge_info(1)).is_err());
+    }
+
+    #[test]
+    fn test_clone_graph_continues_only_its_own_copy() {
+        let (mut harness, client) = setup(small_graph());
+        let authority = client.authority;
+        let graph_named = |mut graph: GraphStore, name: &str| {
+            graph.authority = authority;
+            graph.creator = authority;
+            graph.name = name.to_string();
+            graph
+        };
+        let other = harness.add_graph(graph_named(small_graph(), "other"));
+        let fork = harness.add_graph(graph_named(GraphBuilder::new().build(), "fork"));
+        let mut journaled = graph_named(GraphBuilder::new().build(), "journaled");
+        journaled.journaled = true;
+        let journaled = harness.add_graph(journaled);
+        let clone = |source, destination, cursor| Instruction {
+            program_id: ID,
+            accounts: accounts::CloneGraph {
+                source,
+                destination,
+                authority,
+                journal: None,
+                payer: None,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::CloneGraph {
+                cursor,
+                max_items: 4,
+            }
+            .data(),
+        };
+
+        assert!(harness
+            .process(&clone(client.graph_store, journaled, 0))
+            .is_err());
+
+        harness
+            .process(&clone(client.graph_store, fork, 0))
+            .unwrap();
+        // Neither another source nor a replayed cursor may continue it
+        assert!(harness.process(&clone(other, fork, 4)).is_err());
+        assert!(harness
+            .process(&clone(client.graph_store, fork, 2))
+            .is_err());
+        harness
+            .process(&clone(client.graph_store, fork, 4))
+            .unwrap();
+        harness
+            .process(&clone(client.graph_store, fork, 8))
+            .unwrap();
+
+        let copy = harness.graph(&fork).unwrap();
+        assert_eq!((copy.node_count, copy.edge_count), (5, 5));
+        assert_eq!(copy.clone_source, Pubkey::default());
+        assert!(harness
+            .process(&clone(client.graph_store, fork, 8))
+            .is_err());
+    }
+
+    /// Node labels, edges and deleted nodes of a random graph, as indexes
+    /// that `build_graph` wraps around the node count.
+    type GraphSpec = (Vec<usize>, Vec<(usize, usize, usize)>, Vec<usize>);
+
+    #[test]
+    fn test_initialize_execute_read() {
+        let mut harness = Harness::new();
+        harness.set_unix_timestamp(500);
+        let authority = Pubkey::new_unique();
+        let graph = harness.initialize_graph("social", authority).unwrap();
+        let address = graph.graph_store;
+
+        let stored = harness.graph(&address).unwrap();
+        assert_eq!(stored.name, "social");
+        assert_eq!((stored.authority, stored.creator), (authority, authority));
+        let account = harness.account(&address).unwrap();
+        assert_eq!(
+            (account.owner, account.data.len()),
+            (ID, GraphSizing::default().initial_space())
+        );
+        assert!(harness.initialize_graph("social", authority).is_err());
+
+        harness
+            .process(&graph.execute_query_text("CREATE (a:User)", None, None))
+            .unwrap();
+        harness.execute(&graph, "CREATE (b:User {0x01})").unwrap();
+        let nodes = harness.graph(&address).unwrap().nodes;
+        let (a, b) = (nodes[0].id, nodes[1].id);
+        harness
+            .execute(&graph, &format!("CREATE ({})-[:KNOWS]->({})", a, b))
+            .unwrap();
+        assert_eq!(harness.graph(&address).unwrap().edge_count, 1);
+        assert_eq!(harness.node(&address, b).unwrap().data, vec![1]);
+        assert_eq!(harness.node(&address, b).unwrap().created_at, 500);
+
+        let soft_delete = Instruction {
+            program_id: ID,
+            accounts: accounts::SoftDeleteNode {
+                graph_store: address,
+                authority,
+                journal: None,
+            }
+            .to_account_metas(None),
+            data: instruction::SoftDeleteNode { node_id: a }.data(),
+        };
+        harness.process(&soft_delete).unwrap();
+        assert!(harness.node(&address, a).unwrap().is_deleted);
+        assert_eq!(
+            harness.execute(&graph, "MATCH (n:User) RETURN n LIMIT 10"),
+            Ok(VmResult::Nodes(vec![b]))
+        );
+        let read = graph.execute_read_query("MATCH (n:User) RETURN n LIMIT 10", None);
+        assert_eq!(harness.query(&read), Ok(VmResult::Nodes(vec![b])));
+        let write = graph.execute_read_query("CREATE (c:User)", None);
+        assert!(harness.query(&write).is_err());
+        // A copy of the graph at an address that is not its PDA
+        let copy = Pubkey::new_unique();
+        harness.set_account(copy, harness.account(&address).unwrap().clone());
+        let mut forged = read.clone();
+        forged.accounts[0].pubkey = copy;
+        assert!(harness.query(&forged).is_err());
+
+        // Writes past the initial space grow the account.
+        for _ in 0..200 {
+            harness.execute(&graph, "CREATE (n:Post {0xffff})").unwrap();
+        }
+        assert!(
+            harness.account(&address).unwrap().data.len() > GraphSizing::default().initial_space()
+        );
+        assert_eq!(harness.graph(&address).unwrap().node_count, 202);
+    }
+
+    #[test]
+    fn test_add_node_grows_account() {
+        let mut harness = Harness::new();
+        let authority = Pubkey::new_unique();
+        let graph = harness.initialize_graph("direct", authority).unwrap();
+        let address = graph.graph_store;
+        let add_node = Instruction {
+            program_id: ID,
+            accounts: accounts::WriteGraph {
+                graph_store: address,
+                authority,
+                treasury: None,
+                writer_rate: None,
+                audit_log: None,
+                journal: None,
+                payer: None,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::AddNode {
+                label: "Post".to_string(),
+                data: vec![0xff; 64],
+                partition: None,
+            }
+            .data(),
+        };
+
+        for _ in 0..200 {
+            harness.process(&add_node).unwrap();
+        }
+        assert!(
+            harness.account(&address).unwrap().data.len() > GraphSizing::default().initial_space()
+        );
+        assert_eq!(harness.graph(&address).unwrap().node_count, 200);
+    }
+
+    #[test]
+    fn test_initialize_graph_with_sizing() {
+        let mut harness = Harness::new();
+        let authority = Pubkey::new_unique();
+        let sizing = GraphSizing {
+            max_nodes: 2,
+            max_edges: 1,
+            max_label_len: 8,
+            max_node_data_len: 4,
+            max_edge_data_len: 4,
+        };
+        let ix = sol_micro_sql_client::initialize_graph("small", sizing, authority, authority);
+        harness.process(&ix).unwrap();
+        let graph = GraphClient::new(ix.accounts[0].pubkey, authority);
+        let account = harness.account(&graph.graph_store).unwrap();
+        assert_eq!(account.data.len(), graph_space(&sizing.config()));
+
+        // Writes are held to the limits the account was sized for
+        harness
+            .execute(&graph, "CREATE (a:User {0x01020304})")
+            .unwrap();
+        assert!(harness
+            .execute(&graph, "CREATE (b:User {0x0102030405})")
+            .is_err());
+        assert!(harness.execute(&graph, "CREATE (b:LongLabel)").is_err());
+        harness.execute(&graph, "CREATE (b:User)").unwrap();
+        assert!(harness.execute(&graph, "CREATE (c:User)").is_err());
+        harness.execute(&graph, "CREATE (0)-[:KNOWS]->(1)").unwrap();
+        assert!(harness.execute(&graph, "CREATE (1)-[:KNOWS]->(0)").is_err());
+        assert_eq!(
+            harness.account(&graph.graph_store).unwrap().data.len(),
+            graph_space(&sizing.config())
+        );
+
+        let mut too_large = sizing;
+        too_large.max_nodes = MAX_NODES as u32 + 1;
+        let ix = sol_micro_sql_client::initialize_graph("large", too_large, authority, authority);
+        assert!(harness.process(&ix).is_err());
+    }
+
+    #[test]
+    fn test_initialize_user_graph() {
+        let mut harness = Harness::new();
+        let user = Pubkey::new_unique();
+        let address = user_graph_address(&user);
+        let ix = Instruction {
+            program_id: ID,
+            accounts: accounts::InitializeUserGraph {
+                graph_store: address,
+                user,
+                payer: user,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::InitializeUserGraph {
+                sizing: GraphSizing::default(),
+            }
+            .data(),
+        };
+        harness.process(&ix).unwrap();
+        let graph = harness.graph(&address).unwrap();
+        assert!(graph.user_graph);
+        assert_eq!(graph.authority, user);
+
+        let client = GraphClient::new(address, user);
+        harness.execute(&client, "CREATE (n:Note)").unwrap();
+        let note = harness.graph(&address).unwrap().nodes[0].id;
+        assert_eq!(
+            harness.execute(&client, "MATCH (n:Note) RETURN n LIMIT 1"),
+            Ok(VmResult::Nodes(vec![note]))
+        );
+
+        let mut parsed = parse("MATCH (n:Note) RETURN n LIMIT 1").unwrap();
+        assert_eq!(
+            harness.query(&client.execute_parsed_query(parsed.clone(), None, None)),
+            Ok(VmResult::Nodes(vec![note]))
+        );
+        if let CypherQuery::Match { limit, .. } = &mut parsed {
+            *limit = None;
+        }
+        assert!(harness
+            .query(&client.execute_parsed_query(parsed, None, None))
+            .is_err());
+    }
+
+    fn graph_strategy() -> impl Strategy<Value = GraphSpec> {
+        (
+            prop::collection::vec(0..NODE_LABELS.len(), 1..12),
+            prop::collection::vec((0..12usize, 0..12usize, 0..EDGE_LABELS.len()), 0..20),
+            prop::collection::vec(0..12usize, 0..3),
+        )
+    }
+
+    fn build_graph((labels, edges, deleted): GraphSpec) -> GraphStore {
+        let id = |i: usize| (i % labels.len() + 1) as NodeId;
+        let mut builder = GraphBuilder::new();
+        for label in &labels {
+            builder = builder.node(NODE_LABELS[*label]);
+        }
+        let mut seen = BTreeSet::new();
+        for (from, to, label) in edges {
+            if seen.insert((id(from), id(to), label)) {
+                builder = builder.edge(id(from), id(to), EDGE_LABELS[label]);
+            }
+        }
+        for node in deleted {
+            builder = builder.deleted(id(node));
+        }
+        builder.build()
+    }
+
+    /// Queries in every shape the parser knows, some of them invalid.
+    fn query_strategy() -> impl Strategy<Value = String> {
+        let node_label = prop::sample::select(NODE_LABELS.to_vec());
+        let edge_label = prop::sample::select(EDGE_LABELS.to_vec());
+        prop_oneof![
+            (
+                prop::option::of(node_label.clone()),
+                prop::option::of(0..14u64),
+                any::<bool>(),
+                prop::option::of(0..6u64),
+            )
+                .prop_map(|(label, id, all, limit)| {
+                    let mut query = match label {
+                        Some(label) => format!("MATCH (n:{})", label),
+                        None => "MATCH (n)".to_string(),
+                    };
+                    if let Some(id) = id {
+                        query += &format!(" WHERE n.id = {}", id);
+                    }
+                    query += if all { " RETURN *" } else { " RETURN n" };
+                    if let Some(limit) = limit {
+                        query += &format!(" LIMIT {}", limit);
+                    }
+                    query
+                }),
+            (0..2_000i64, 1..6u64).prop_map(|(time, limit)| format!(
+                "MATCH (n) WHERE n.created_at >= {} RETURN n LIMIT {}",
+                time, limit
+            )),
+            (
+                node_label.clone(),
+                prop::option::of(0..20u64),
+                any::<bool>()
+            )
+                .prop_map(|(label, ttl, data)| {
+                    let mut query = format!("CREATE (n:{}", label);
+                    if data {
+                        query += " {0x0102}";
+                    }
+                    query += ")";
+                    if let Some(ttl) = ttl {
+                        query += &format!(" TTL {}", ttl);
+                    }
+                    query
+                }),
+            (0..14u64, 0..14u64, edge_label.clone(), any::<bool>()).prop_map(
+                |(from, to, label, directed)| {
+                    let arrow = if directed { "->" } else { "-" };
+                    format!("CREATE ({})-[:{}]{}({})", from, label, arrow, to)
+                }
+            ),
+            (node_label.clone(), 0..20u64)
+                .prop_map(|(label, id)| format!("CREATE (n:{} {{id: {}}})", label, id)),
+            (node_label.clone(), edge_label.clone(), node_label.clone()).prop_map(
+                |(from, edge, to)| format!("CREATE (a:{})-[:{}]->(b:{})", from, edge, to)
+            ),
+            (node_label, edge_label, 1..6u64).prop_map(|(label, edge, limit)| format!(
+                "MATCH (a:{})-[:{}]->(b) RETURN b LIMIT {}",
+                label, edge, limit
+            )),
+        ]
+    }
+
+    proptest! {
+        /// The off-chain engine must agree with the program on every
+        /// result, every failure and every write.
+        #[test]
+        fn test_engine_matches_program(
+            graph in graph_strategy(),
+            queries in prop::collection::vec(query_strategy(), 1..8),
+        ) {
+            let (mut harness, client) = setup(build_graph(graph));
+            let address = client.graph_store;
+            let fresh_engine = |harness: &Harness| {
+                Engine::new(harness.graph(&address).unwrap())
+                    .with_signers(vec![client.authority])
+                    .at_time(harness.clock().unix_timestamp)
+                    .at_slot(harness.clock().slot)
+            };
+            let mut engine = fresh_engine(&harness);
+
+            for query in &queries {
+                let expected = engine.query(query, None);
+                let actual = harness.query(&client.execute_query_text(query, None, None));
+                match (expected, actual) {
+                    (Ok(expected), Ok(actual)) => prop_assert_eq!(expected, actual, "{}", query),
+                    // Neither may keep writes made before the error
+                    (Err(_), Err(_)) => {}
+                    (expected, actual) => prop_assert!(
+                        false,
+                        "{}: engine {:?}, program {:?}",
+                        query,
+                        expected,
+                        actual
+                    ),
+                }
+                prop_assert_eq!(
+                    encode(engine.graph()),
+                    encode(&harness.graph(&address).unwrap()),
+                    "{}",
+                    query
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,1918 @@
+use crate::ops::{CmpOp, NodeId, TimeField, MAX_PATH_HOPS};
+use crate::prelude::{borsh, AnchorDeserialize, AnchorSerialize, Pubkey};
+use std::str::FromStr;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CypherQuery {
+    Match {
+        match_pattern: MatchPattern,
+        where_clause: Option<WhereClause>,
+        return_clause: ReturnClause,
+        limit: Option<u32>,
+    },
+    Create {
+        create_pattern: CreatePattern,
+    },
+    /// `CALL procedure(args)`.
+    Call {
+        procedure: Procedure,
+    },
+}
+
+/// Built-in procedures for `CALL`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Procedure {
+    /// `delegation.resolve(id, ...)`: the effective delegate of each node,
+    /// at the end of its chain of `DELEGATES_TO` edges.
+    ResolveDelegation { start_ids: Vec<NodeId> },
+    /// `cycle.find('LABEL')`: one cycle of `label` edges, if the graph
+    /// has any.
+    FindCycle { label: String },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CreatePattern {
+    Node {
+        variable: String,
+        label: Option<String>,
+        data: Option<Vec<u8>>, // Node data in hex format
+        ttl: Option<u64>,      // Slots until the node expires
+        id: Option<NodeId>,    // Explicit node ID instead of the next one
+    },
+    Edge {
+        from: NodePattern,
+        from_id: Option<NodeId>, // Node ID if specified directly
+        edge: EdgePattern,
+        to: NodePattern,
+        to_id: Option<NodeId>, // Node ID if specified directly
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchPattern {
+    SingleNode {
+        variable: String,
+        label: Option<String>,
+    },
+    Relationship {
+        from: NodePattern,
+        edge: EdgePattern,
+        to: NodePattern,
+    },
+    /// `(a)-[:L]->(b), (b)-[:L]->(a)`: pairs connected both ways, such as
+    /// mutual follows. `edge` is always outgoing.
+    Mutual {
+        from: NodePattern,
+        edge: EdgePattern,
+        to: NodePattern,
+    },
+    /// `(a)-[r:L*1..3]->(b)`: paths of `min_hops` to `max_hops` outgoing
+    /// edges, named `variable`.
+    Path {
+        from: NodePattern,
+        edge: EdgePattern,
+        variable: String,
+        min_hops: u32,
+        max_hops: u32,
+        to: NodePattern,
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodePattern {
+    pub variable: String,
+    pub label: Option<String>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdgePattern {
+    pub direction: EdgeDirection,
+    pub label: Option<String>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdgeDirection {
+    Outgoing,
+    Incoming,
+    Bidirectional,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WhereClause {
+    NodeIdEq {
+        variable: String,
+        value: NodeId,
+    },
+    NodeAttrEq {
+        variable: String,
+        attr: String,
+        value: String,
+    },
+    /// `n.mint = $1`: nodes bound to an NFT mint.
+    NodeMintEq {
+        variable: String,
+        mint: Pubkey,
+    },
+    /// `n.created_at > 1700000000` and the like, on the unix timestamps
+    /// recorded by each write.
+    NodeTimeCmp {
+        variable: String,
+        field: TimeField,
+        op: CmpOp,
+        value: i64,
+    },
+    /// `n.x BETWEEN 10 AND 20`: nodes with an integer attribute in an
+    /// inclusive range.
+    NodeAttrBetween {
+        variable: String,
+        attr: String,
+        min: i64,
+        max: i64,
+    },
+    /// `a.id = 1 AND b.id = 2` on the two ends of a relationship: whether
+    /// that one edge exists, which the edge key index answers directly.
+    EdgeIdsEq {
+        from_variable: String,
+        from: NodeId,
+        to_variable: String,
+        to: NodeId,
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReturnClause {
+    NodeId {
+        variable: String,
+    },
+    NodeAttr {
+        variable: String,
+        attr: String,
+    },
+    All,
+    /// `RETURN a, b` of a mutual pattern.
+    Pair {
+        first: String,
+        second: String,
+    },
+    /// `RETURN SUM(r.attr)` over the edges of a path pattern.
+    Sum {
+        variable: String,
+        attr: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    UnexpectedToken(String),
+    InvalidSyntax(String),
+    MissingLimit,
+}
+
+/// Typed value bound to a `$1`, `$2`, ... placeholder.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParamValue {
+    Id(NodeId),
+    Int(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Pubkey(Pubkey),
+}
+
+impl ParamValue {
+    /// The single token this value stands for.
+    fn to_token(&self) -> String {
+        match self {
+            ParamValue::Id(id) => id.to_string(),
+            ParamValue::Int(value) => value.to_string(),
+            ParamValue::Str(value) => value.clone(),
+            ParamValue::Bytes(bytes) => {
+                let mut hex = String::with_capacity(2 + bytes.len() * 2);
+                hex.push_str("0x");
+                for byte in bytes {
+                    hex.push_str(&format!("{:02x}", byte));
+                }
+                hex
+            }
+            ParamValue::Pubkey(key) => key.to_string(),
+        }
+    }
+}
+
+pub fn parse(query: &str) -> Result<CypherQuery, ParseError> {
+    parse_tokens(tokenize(query.trim())?)
+}
+
+/// Parses a query whose `$n` placeholders (1-based) are bound to `params`.
+/// Values are substituted as whole tokens after tokenizing, so they can
+/// never change the structure of the query.
+pub fn parse_with_params(query: &str, params: &[ParamValue]) -> Result<CypherQuery, ParseError> {
+    let mut tokens = tokenize(query.trim())?;
+    for token in tokens.iter_mut() {
+        if let Some(position) = token.strip_prefix('$') {
+            let value = position
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| params.get(n.checked_sub(1)?))
+                .ok_or_else(|| {
+                    ParseError::InvalidSyntax(format!("Unbound parameter '{}'", token))
+                })?;
+            *token = value.to_token();
+        }
+    }
+    parse_tokens(tokens)
+}
+
+/// Checks a query that did not come from the parser, such as one decoded
+/// from Borsh, for what the parser would have rejected: a MATCH without
+/// a LIMIT.
+pub fn validate(query: &CypherQuery) -> Result<(), ParseError> {
+    match query {
+        CypherQuery::Match { limit: None, .. } => Err(ParseError::MissingLimit),
+        CypherQuery::Match {
+            match_pattern,
+            where_clause,
+            return_clause,
+            ..
+        } => check_return(match_pattern, where_clause, return_clause),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects a RETURN of two nodes unless they are the two ends of a mutual
+/// pattern, the only one that matches pairs, and a path pattern unless it
+/// returns a sum over its edges and filters only on its start node.
+fn check_return(
+    match_pattern: &MatchPattern,
+    where_clause: &Option<WhereClause>,
+    return_clause: &ReturnClause,
+) -> Result<(), ParseError> {
+    if let Some(WhereClause::EdgeIdsEq {
+        from_variable,
+        to_variable,
+        ..
+    }) = where_clause
+    {
+        let probed = matches!(
+            match_pattern,
+            MatchPattern::Relationship { from, edge, to }
+                if edge.direction == EdgeDirection::Outgoing
+                    && edge.label.is_some()
+                    && from.variable == *from_variable
+                    && to.variable == *to_variable
+        );
+        if !probed {
+            return Err(ParseError::InvalidSyntax(format!(
+                "Expected ({})-[:LABEL]->({}) for both ids",
+                from_variable, to_variable
+            )));
+        }
+    }
+    if let MatchPattern::Path { from, variable, .. } = match_pattern {
+        if !matches!(return_clause, ReturnClause::Sum { variable: summed, .. } if summed == variable)
+        {
+            return Err(ParseError::InvalidSyntax(format!(
+                "Expected RETURN SUM({}.<attribute>)",
+                variable
+            )));
+        }
+        let filtered = match where_clause {
+            Some(
+                WhereClause::NodeIdEq { variable, .. }
+                | WhereClause::NodeAttrEq { variable, .. }
+                | WhereClause::NodeMintEq { variable, .. }
+                | WhereClause::NodeTimeCmp { variable, .. }
+                | WhereClause::NodeAttrBetween { variable, .. }
+                | WhereClause::EdgeIdsEq {
+                    from_variable: variable,
+                    ..
+                },
+            ) => Some(variable),
+            None => None,
+        };
+        if filtered.is_some_and(|v| *v != from.variable) {
+            return Err(ParseError::InvalidSyntax(format!(
+                "Only '{}' may be filtered on in a path pattern",
+                from.variable
+            )));
+        }
+        return Ok(());
+    }
+    if let ReturnClause::Sum { .. } = return_clause {
+        return Err(ParseError::InvalidSyntax(
+            "SUM needs a path pattern".to_string(),
+        ));
+    }
+    let ReturnClause::Pair { first, second } = return_clause else {
+        return Ok(());
+    };
+    let ends = match match_pattern {
+        MatchPattern::Mutual { from, to, .. } => [&from.variable, &to.variable],
+        _ => {
+            return Err(ParseError::InvalidSyntax(
+                "Returning two nodes needs a mutual pattern".to_string(),
+            ))
+        }
+    };
+    if first == second || !ends.contains(&first) || !ends.contains(&second) {
+        return Err(ParseError::InvalidSyntax(format!(
+            "Expected RETURN {}, {}",
+            ends[0], ends[1]
+        )));
+    }
+    Ok(())
+}
+
+fn parse_tokens(mut tokens: Vec<String>) -> Result<CypherQuery, ParseError> {
+    if tokens.is_empty() {
+        return Err(ParseError::InvalidSyntax("Empty query".to_string()));
+    }
+
+    let first_token = tokens[0].to_uppercase();
+    if first_token == "CREATE" {
+        let create_pattern = parse_create(&mut tokens)?;
+        if !tokens.is_empty() {
+            return Err(ParseError::InvalidSyntax(format!(
+                "Unexpected tokens: {:?}",
+                tokens
+            )));
+        }
+        Ok(CypherQuery::Create { create_pattern })
+    } else if first_token == "CALL" {
+        let procedure = parse_call(&mut tokens)?;
+        if !tokens.is_empty() {
+            return Err(ParseError::InvalidSyntax(format!(
+                "Unexpected tokens: {:?}",
+                tokens
+            )));
+        }
+        Ok(CypherQuery::Call { procedure })
+    } else if first_token == "MATCH" {
+        let match_pattern = parse_match(&mut tokens)?;
+        let where_clause = parse_where(&mut tokens)?;
+        let return_clause = parse_return(&mut tokens)?;
+        check_return(&match_pattern, &where_clause, &return_clause)?;
+        let limit = parse_limit(&mut tokens)?;
+
+        if limit.is_none() {
+            return Err(ParseError::MissingLimit);
+        }
+
+        if !tokens.is_empty() {
+            return Err(ParseError::InvalidSyntax(format!(
+                "Unexpected tokens: {:?}",
+                tokens
+            )));
+        }
+
+        Ok(CypherQuery::Match {
+            match_pattern,
+            where_clause,
+            return_clause,
+            limit,
+        })
+    } else {
+        Err(ParseError::InvalidSyntax(format!(
+            "Expected MATCH, CREATE or CALL, got '{}'",
+            tokens[0]
+        )))
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+
+    for ch in input.chars() {
+        match ch {
+            ' ' | '\t' | '\n' | '\r' => {
+                if in_string {
+                    current.push(ch);
+                } else if !current.is_empty() {
+                    tokens.push(current.clone());
+                    current.clear();
+                }
+            }
+            '(' | ')' | '[' | ']' | '-' | '>' | '<' | ':' | '=' | ',' | '{' | '}' => {
+                if in_string {
+                    current.push(ch);
+                } else {
+                    if !current.is_empty() {
+                        tokens.push(current.clone());
+                        current.clear();
+                    }
+                    tokens.push(ch.to_string());
+                }
+            }
+            '\'' | '"' => {
+                if in_string {
+                    tokens.push(current.clone());
+                    current.clear();
+                    in_string = false;
+                } else {
+                    in_string = true;
+                }
+            }
+            _ => {
+                current.push(ch);
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+fn parse_create(tokens: &mut Vec<String>) -> Result<CreatePattern, ParseError> {
+    expect_keyword(tokens, "CREATE")?;
+
+    if tokens.is_empty() {
+        return Err(ParseError::InvalidSyntax(
+            "Expected pattern after CREATE".to_string(),
+        ));
+    }
+
+    let has_arrow = tokens.iter().any(|t| t == "->" || t == "<-" || t == "-");
+    if has_arrow {
+        parse_create_edge_pattern(tokens)
+    } else {
+        parse_create_node_pattern(tokens)
+    }
+}
+
+fn parse_create_node_pattern(tokens: &mut Vec<String>) -> Result<CreatePattern, ParseError> {
+    expect_char(tokens, "(")?;
+
+    let variable = expect_identifier(tokens)?;
+    let label = if peek_token(tokens) == ":" {
+        tokens.remove(0);
+        Some(expect_identifier(tokens)?)
+    } else {
+        None
+    };
+
+    // Parse properties in format { id: 42, 0x.... }, in either order
+    let mut data = None;
+    let mut id = None;
+    if peek_token(tokens) == "{" {
+        tokens.remove(0);
+        loop {
+            let token = peek_token(tokens);
+            if token.starts_with("0x") || token.starts_with("0X") {
+                if data.is_some() {
+                    return Err(ParseError::InvalidSyntax(
+                        "Node data given twice".to_string(),
+                    ));
+                }
+                let hex_str = tokens.remove(0);
+                // Remove 0x prefix and parse hex
+                let hex_bytes = hex_str.trim_start_matches("0x").trim_start_matches("0X");
+                data = Some(parse_hex_string(hex_bytes).map_err(|e| {
+                    ParseError::InvalidSyntax(format!("Invalid hex string: {}", e))
+                })?);
+            } else if token.eq_ignore_ascii_case("id") {
+                if id.is_some() {
+                    return Err(ParseError::InvalidSyntax("Node id given twice".to_string()));
+                }
+                tokens.remove(0);
+                expect_char(tokens, ":")?;
+                id = Some(expect_node_id(tokens)?);
+            } else {
+                return Err(ParseError::InvalidSyntax(
+                    "Expected id or hex string starting with 0x".to_string(),
+                ));
+            }
+
+            if peek_token(tokens) == "," {
+                tokens.remove(0);
+            } else {
+                break;
+            }
+        }
+        expect_char(tokens, "}")?;
+    }
+
+    expect_char(tokens, ")")?;
+
+    // Optional trailing TTL <slots>
+    let ttl = if peek_token(tokens).to_uppercase() == "TTL" {
+        tokens.remove(0);
+        Some(expect_number(tokens)? as u64)
+    } else {
+        None
+    };
+
+    Ok(CreatePattern::Node {
+        variable,
+        label,
+        data,
+        ttl,
+        id,
+    })
+}
+
+fn parse_create_edge_pattern(tokens: &mut Vec<String>) -> Result<CreatePattern, ParseError> {
+    expect_char(tokens, "(")?;
+
+    // Support both identifier (variable) and numeric ID
+    let from_token = if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken(
+            "Expected node identifier or ID".to_string(),
+        ));
+    } else {
+        tokens.remove(0)
+    };
+
+    let (from_var, from_id, from_label) = if from_token
+        .chars()
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or(false)
+    {
+        // It's a variable identifier
+        let label = if peek_token(tokens) == ":" {
+            tokens.remove(0);
+            Some(expect_identifier(tokens)?)
+        } else {
+            None
+        };
+        expect_char(tokens, ")")?;
+        (Some(from_token), None, label)
+    } else if from_token.chars().all(|c| c.is_ascii_digit()) {
+        // It's a numeric ID
+        let from_id = from_token
+            .parse::<NodeId>()
+            .map_err(|_| ParseError::InvalidSyntax(format!("Invalid node ID: {}", from_token)))?;
+        expect_char(tokens, ")")?;
+        (None, Some(from_id), None)
+    } else {
+        return Err(ParseError::InvalidSyntax(format!(
+            "Expected node identifier or ID, got '{}'",
+            from_token
+        )));
+    };
+
+    // Parse edge pattern: -[:LABEL]-> or <-[:LABEL]- or -[:LABEL]-
+    expect_char(tokens, "-")?;
+
+    // Check if next is [ (edge label) or >/< (direction)
+    let direction = if peek_token(tokens) == "[" {
+        // Edge label comes first, direction will be determined after ]
+        EdgeDirection::Bidirectional // Temporary, will be updated after parsing label
+    } else if peek_token(tokens) == ">" {
+        tokens.remove(0);
+        EdgeDirection::Outgoing
+    } else if peek_token(tokens) == "<" {
+        tokens.remove(0);
+        EdgeDirection::Incoming
+    } else {
+        EdgeDirection::Bidirectional
+    };
+
+    // Parse edge label if present
+    let edge_label = if peek_token(tokens) == "[" {
+        tokens.remove(0);
+        let label = if peek_token(tokens) == ":" {
+            tokens.remove(0);
+            if peek_token(tokens) == "]" {
+                None
+            } else {
+                Some(expect_identifier(tokens)?)
+            }
+        } else {
+            None
+        };
+        expect_char(tokens, "]")?;
+        label
+    } else {
+        None
+    };
+
+    // Determine final direction based on what comes after the label
+    let final_direction = if peek_token(tokens) == "-" {
+        tokens.remove(0);
+        if peek_token(tokens) == ">" {
+            tokens.remove(0);
+            EdgeDirection::Outgoing
+        } else if peek_token(tokens) == "<" {
+            tokens.remove(0);
+            EdgeDirection::Incoming
+        } else {
+            EdgeDirection::Bidirectional
+        }
+    } else if peek_token(tokens) == ">" {
+        tokens.remove(0);
+        EdgeDirection::Outgoing
+    } else if peek_token(tokens) == "<" {
+        tokens.remove(0);
+        EdgeDirection::Incoming
+    } else {
+        direction // Use the direction we determined earlier
+    };
+
+    expect_char(tokens, "(")?;
+
+    // Support both identifier (variable) and numeric ID for 'to' node
+    let to_token = if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken(
+            "Expected node identifier or ID".to_string(),
+        ));
+    } else {
+        tokens.remove(0)
+    };
+
+    let (to_var, to_id, to_label) = if to_token
+        .chars()
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or(false)
+    {
+        // It's a variable identifier
+        let label = if peek_token(tokens) == ":" {
+            tokens.remove(0);
+            Some(expect_identifier(tokens)?)
+        } else {
+            None
+        };
+        expect_char(tokens, ")")?;
+        (Some(to_token), None, label)
+    } else if to_token.chars().all(|c| c.is_ascii_digit()) {
+        // It's a numeric ID
+        let to_id = to_token
+            .parse::<NodeId>()
+            .map_err(|_| ParseError::InvalidSyntax(format!("Invalid node ID: {}", to_token)))?;
+        expect_char(tokens, ")")?;
+        (None, Some(to_id), None)
+    } else {
+        return Err(ParseError::InvalidSyntax(format!(
+            "Expected node identifier or ID, got '{}'",
+            to_token
+        )));
+    };
+
+    // Store node IDs in the pattern for CREATE edge
+    Ok(CreatePattern::Edge {
+        from: NodePattern {
+            variable: from_var.unwrap_or_default(),
+            label: from_label,
+        },
+        from_id,
+        edge: EdgePattern {
+            direction: final_direction,
+            label: edge_label,
+        },
+        to: NodePattern {
+            variable: to_var.unwrap_or_default(),
+            label: to_label,
+        },
+        to_id,
+    })
+}
+
+fn parse_call(tokens: &mut Vec<String>) -> Result<Procedure, ParseError> {
+    expect_keyword(tokens, "CALL")?;
+    let name = expect_identifier(tokens)?;
+    expect_char(tokens, "(")?;
+    let procedure = match name.as_str() {
+        "delegation.resolve" => {
+            let mut start_ids = vec![expect_node_id(tokens)?];
+            while peek_token(tokens) == "," {
+                tokens.remove(0);
+                start_ids.push(expect_node_id(tokens)?);
+            }
+            Procedure::ResolveDelegation { start_ids }
+        }
+        "cycle.find" => {
+            let label = expect_string(tokens)?;
+            if label.is_empty() {
+                return Err(ParseError::InvalidSyntax(
+                    "Expected an edge label".to_string(),
+                ));
+            }
+            Procedure::FindCycle { label }
+        }
+        _ => {
+            return Err(ParseError::InvalidSyntax(format!(
+                "Unknown procedure '{}'",
+                name
+            )))
+        }
+    };
+    expect_char(tokens, ")")?;
+    Ok(procedure)
+}
+
+fn parse_match(tokens: &mut Vec<String>) -> Result<MatchPattern, ParseError> {
+    expect_keyword(tokens, "MATCH")?;
+
+    if tokens.is_empty() {
+        return Err(ParseError::InvalidSyntax(
+            "Expected pattern after MATCH".to_string(),
+        ));
+    }
+
+    // A `-` after the pattern is a negative number, not an edge
+    let pattern_end = tokens
+        .iter()
+        .position(|t| t.eq_ignore_ascii_case("WHERE") || t.eq_ignore_ascii_case("RETURN"))
+        .unwrap_or(tokens.len());
+    let has_arrow = tokens[..pattern_end]
+        .iter()
+        .any(|t| t == "->" || t == "<-" || t == "-");
+    if !has_arrow {
+        return parse_single_node_pattern(tokens);
+    }
+    let pattern = parse_relationship_pattern(tokens)?;
+    if peek_token(tokens) != "," {
+        return Ok(pattern);
+    }
+    tokens.remove(0);
+    let reverse = parse_relationship_pattern(tokens)?;
+    mutual_pattern(pattern, reverse)
+}
+
+/// `(a)-[:L]->(b), (b)-[:L]->(a)` as one mutual pattern. Any other pair of patterns is rejected.
+fn mutual_pattern(
+    pattern: MatchPattern,
+    reverse: MatchPattern,
+) -> Result<MatchPattern, ParseError> {
+    let (Some((from, label, to)), Some((back_from, back_label, back_to))) =
+        (outgoing(pattern), outgoing(reverse))
+    else {
+        return Err(ParseError::InvalidSyntax(
+            "Only a directed pattern and its reverse may be combined".to_string(),
+        ));
+    };
+    let same_label = |a: &Option<String>, b: &Option<String>| a.is_none() || b.is_none() || a == b;
+    if from.variable == to.variable
+        || back_from.variable != to.variable
+        || back_to.variable != from.variable
+        || label != back_label
+        || !same_label(&from.label, &back_to.label)
+        || !same_label(&to.label, &back_from.label)
+    {
+        return Err(ParseError::InvalidSyntax(format!(
+            "Expected ({})-[...]->({}) reversed",
+            to.variable, from.variable
+        )));
+    }
+    Ok(MatchPattern::Mutual {
+        from: NodePattern {
+            variable: from.variable,
+            label: from.label.or(back_to.label),
+        },
+        edge: EdgePattern {
+            direction: EdgeDirection::Outgoing,
+            label,
+        },
+        to: NodePattern {
+            variable: to.variable,
+            label: to.label.or(back_from.label),
+        },
+    })
+}
+
+/// A directed relationship pattern as start node, edge label and end node
+/// of its edge.
+fn outgoing(pattern: MatchPattern) -> Option<(NodePattern, Option<String>, NodePattern)> {
+    match pattern {
+        MatchPattern::Relationship { from, edge, to } => match edge.direction {
+            EdgeDirection::Outgoing => Some((from, edge.label, to)),
+            EdgeDirection::Incoming => Some((to, edge.label, from)),
+            EdgeDirection::Bidirectional => None,
+        },
+        _ => None,
+    }
+}
+
+fn parse_single_node_pattern(tokens: &mut Vec<String>) -> Result<MatchPattern, ParseError> {
+    expect_char(tokens, "(")?;
+
+    let variable = expect_identifier(tokens)?;
+    let label = if peek_token(tokens) == ":" {
+        tokens.remove(0);
+        Some(expect_identifier(tokens)?)
+    } else {
+        None
+    };
+
+    expect_char(tokens, ")")?;
+
+    Ok(MatchPattern::SingleNode { variable, label })
+}
+
+fn parse_relationship_pattern(tokens: &mut Vec<String>) -> Result<MatchPattern, ParseError> {
+    expect_char(tokens, "(")?;
+    let from_var = expect_identifier(tokens)?;
+    let from_label = if peek_token(tokens) == ":" {
+        tokens.remove(0);
+        Some(expect_identifier(tokens)?)
+    } else {
+        None
+    };
+    expect_char(tokens, ")")?;
+
+    let mut direction = if peek_token(tokens) == "-" {
+        tokens.remove(0);
+        if peek_token(tokens) == ">" {
+            tokens.remove(0);
+            EdgeDirection::Outgoing
+        } else if peek_token(tokens) == "<" {
+            tokens.remove(0);
+            EdgeDirection::Incoming
+        } else {
+            EdgeDirection::Bidirectional
+        }
+    } else {
+        return Err(ParseError::InvalidSyntax(
+            "Expected edge pattern".to_string(),
+        ));
+    };
+
+    expect_char(tokens, "[")?;
+    // `[r:LABEL*1..3]`, where the variable and the hop range are optional
+    let mut edge_variable = None;
+    if !matches!(peek_token(tokens), ":" | "]") && !peek_token(tokens).starts_with('*') {
+        edge_variable = Some(expect_identifier(tokens)?);
+    }
+    let mut edge_label = if peek_token(tokens) == ":" {
+        tokens.remove(0);
+        if peek_token(tokens) == "]" {
+            None
+        } else {
+            Some(expect_identifier(tokens)?)
+        }
+    } else {
+        None
+    };
+    // The tokenizer keeps a range written without a space on the word
+    // before it
+    let mut hops = None;
+    for name in [&mut edge_variable, &mut edge_label].into_iter().flatten() {
+        if let Some((word, range)) = name.split_once('*') {
+            hops = Some(parse_hops(range)?);
+            *name = word.to_string();
+        }
+    }
+    if let Some(range) = peek_token(tokens).strip_prefix('*') {
+        hops = Some(parse_hops(range)?);
+        tokens.remove(0);
+    }
+    expect_char(tokens, "]")?;
+
+    match direction {
+        EdgeDirection::Outgoing => {
+            if peek_token(tokens) == "-" {
+                tokens.remove(0);
+            }
+            if peek_token(tokens) == ">" {
+                tokens.remove(0);
+            }
+        }
+        EdgeDirection::Incoming => {
+            if peek_token(tokens) == "<" {
+                tokens.remove(0);
+            }
+            if peek_token(tokens) == "-" {
+                tokens.remove(0);
+            }
+        }
+        EdgeDirection::Bidirectional => {
+            if peek_token(tokens) == "-" {
+                tokens.remove(0);
+            }
+            // `-[:LABEL]->` points away from the first node
+            if peek_token(tokens) == ">" {
+                tokens.remove(0);
+                direction = EdgeDirection::Outgoing;
+            }
+        }
+    }
+
+    expect_char(tokens, "(")?;
+    let to_var = expect_identifier(tokens)?;
+    let to_label = if peek_token(tokens) == ":" {
+        tokens.remove(0);
+        Some(expect_identifier(tokens)?)
+    } else {
+        None
+    };
+    expect_char(tokens, ")")?;
+
+    let from = NodePattern {
+        variable: from_var,
+        label: from_label,
+    };
+    let edge = EdgePattern {
+        direction,
+        label: edge_label,
+    };
+    let to = NodePattern {
+        variable: to_var,
+        label: to_label,
+    };
+    if edge_variable.is_none() && hops.is_none() {
+        return Ok(MatchPattern::Relationship { from, edge, to });
+    }
+    if edge.direction != EdgeDirection::Outgoing {
+        return Err(ParseError::InvalidSyntax(
+            "Path patterns must point from left to right".to_string(),
+        ));
+    }
+    let (min_hops, max_hops) = hops.unwrap_or((1, 1));
+    Ok(MatchPattern::Path {
+        from,
+        edge,
+        variable: edge_variable.unwrap_or_default(),
+        min_hops,
+        max_hops,
+        to,
+    })
+}
+
+/// `1..3`, `..3` or `2` after the `*` of a path, as its fewest and most
+/// hops.
+fn parse_hops(range: &str) -> Result<(u32, u32), ParseError> {
+    let invalid = || ParseError::InvalidSyntax(format!("Invalid hop range '*{}'", range));
+    let number = |n: &str| n.parse::<u32>().map_err(|_| invalid());
+    let (min, max) = match range.split_once("..") {
+        Some(("", max)) => (1, number(max)?),
+        Some((min, max)) => (number(min)?, number(max)?),
+        None => (number(range)?, number(range)?),
+    };
+    if min == 0 || min > max || max > MAX_PATH_HOPS {
+        return Err(ParseError::InvalidSyntax(format!(
+            "Hop range '*{}' must lie within 1..{}",
+            range, MAX_PATH_HOPS
+        )));
+    }
+    Ok((min, max))
+}
+
+fn parse_where(tokens: &mut Vec<String>) -> Result<Option<WhereClause>, ParseError> {
+    if tokens.is_empty() || tokens[0].to_uppercase() != "WHERE" {
+        return Ok(None);
+    }
+
+    tokens.remove(0);
+
+    let (variable, field) = expect_property(tokens)?;
+    if peek_token(tokens).eq_ignore_ascii_case("BETWEEN") {
+        if matches!(field.as_str(), "id" | "mint" | "created_at" | "updated_at") {
+            return Err(ParseError::InvalidSyntax(format!(
+                "BETWEEN is not supported on '{}'",
+                field
+            )));
+        }
+        tokens.remove(0);
+        let min = expect_int(tokens)?;
+        expect_keyword(tokens, "AND")?;
+        let max = expect_int(tokens)?;
+        return Ok(Some(WhereClause::NodeAttrBetween {
+            variable,
+            attr: field,
+            min,
+            max,
+        }));
+    }
+    let op = expect_comparison(tokens)?;
+
+    let time_field = match field.as_str() {
+        "created_at" => Some(TimeField::CreatedAt),
+        "updated_at" => Some(TimeField::UpdatedAt),
+        _ => None,
+    };
+    if let Some(field) = time_field {
+        let value = expect_number(tokens)?;
+        return Ok(Some(WhereClause::NodeTimeCmp {
+            variable,
+            field,
+            op,
+            value: i64::try_from(value).map_err(|_| {
+                ParseError::InvalidSyntax(format!("Timestamp {} out of range", value))
+            })?,
+        }));
+    }
+    if op != CmpOp::Eq {
+        return Err(ParseError::InvalidSyntax(format!(
+            "Only '=' is supported on '{}'",
+            field
+        )));
+    }
+
+    if field == "mint" {
+        let value = expect_string(tokens)?;
+        let mint = Pubkey::from_str(&value)
+            .map_err(|_| ParseError::InvalidSyntax(format!("Invalid mint '{}'", value)))?;
+        Ok(Some(WhereClause::NodeMintEq { variable, mint }))
+    } else if field == "id" {
+        let value = expect_node_id(tokens)?;
+        if !peek_token(tokens).eq_ignore_ascii_case("AND") {
+            return Ok(Some(WhereClause::NodeIdEq { variable, value }));
+        }
+        tokens.remove(0);
+        let (to_variable, to_field) = expect_property(tokens)?;
+        if to_field != "id" || expect_comparison(tokens)? != CmpOp::Eq {
+            return Err(ParseError::InvalidSyntax(
+                "Only two id equalities can be combined with AND".to_string(),
+            ));
+        }
+        let to = expect_node_id(tokens)?;
+        Ok(Some(WhereClause::EdgeIdsEq {
+            from_variable: variable,
+            from: value,
+            to_variable,
+            to,
+        }))
+    } else {
+        let str_value = expect_string(tokens)?;
+        Ok(Some(WhereClause::NodeAttrEq {
+            variable,
+            attr: field,
+            value: str_value,
+        }))
+    }
+}
+
+fn parse_return(tokens: &mut Vec<String>) -> Result<ReturnClause, ParseError> {
+    expect_keyword(tokens, "RETURN")?;
+
+    if peek_token(tokens).to_uppercase() == "*" {
+        tokens.remove(0);
+        return Ok(ReturnClause::All);
+    }
+
+    if peek_token(tokens).eq_ignore_ascii_case("SUM") && tokens.get(1).is_some_and(|t| t == "(") {
+        tokens.drain(..2);
+        let (variable, attr) = expect_property(tokens)?;
+        expect_char(tokens, ")")?;
+        return Ok(ReturnClause::Sum { variable, attr });
+    }
+
+    let variable = expect_identifier(tokens)?;
+
+    // The tokenizer keeps `n.attr` together
+    if let Some((variable, attr)) = variable.split_once('.') {
+        return Ok(ReturnClause::NodeAttr {
+            variable: variable.to_string(),
+            attr: attr.to_string(),
+        });
+    }
+    if peek_token(tokens) == "." {
+        tokens.remove(0);
+        let attr = expect_identifier(tokens)?;
+        Ok(ReturnClause::NodeAttr { variable, attr })
+    } else if peek_token(tokens) == "," {
+        tokens.remove(0);
+        let second = expect_identifier(tokens)?;
+        Ok(ReturnClause::Pair {
+            first: variable,
+            second,
+        })
+    } else {
+        Ok(ReturnClause::NodeId { variable })
+    }
+}
+
+fn parse_limit(tokens: &mut Vec<String>) -> Result<Option<u32>, ParseError> {
+    if tokens.is_empty() || tokens[0].to_uppercase() != "LIMIT" {
+        return Ok(None);
+    }
+
+    tokens.remove(0);
+    let limit = expect_number(tokens)?;
+    Ok(Some(u32::try_from(limit).unwrap_or(u32::MAX)))
+}
+
+fn expect_keyword(tokens: &mut Vec<String>, keyword: &str) -> Result<(), ParseError> {
+    if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken(format!(
+            "Expected '{}'",
+            keyword
+        )));
+    }
+
+    if tokens[0].to_uppercase() != keyword.to_uppercase() {
+        return Err(ParseError::UnexpectedToken(format!(
+            "Expected '{}', got '{}'",
+            keyword, tokens[0]
+        )));
+    }
+
+    tokens.remove(0);
+    Ok(())
+}
+
+fn expect_char(tokens: &mut Vec<String>, ch: &str) -> Result<(), ParseError> {
+    if tokens.is_empty() || tokens[0] != ch {
+        return Err(ParseError::UnexpectedToken(format!("Expected '{}'", ch)));
+    }
+
+    tokens.remove(0);
+    Ok(())
+}
+
+/// `n.field`, which the tokenizer leaves as a single token.
+fn expect_property(tokens: &mut Vec<String>) -> Result<(String, String), ParseError> {
+    let token = expect_identifier(tokens)?;
+    if let Some((variable, field)) = token.split_once('.') {
+        return Ok((variable.to_string(), field.to_string()));
+    }
+    expect_char(tokens, ".")?;
+    let field = expect_identifier(tokens)?;
+    Ok((token, field))
+}
+
+/// `=`, `<`, `<=`, `>` or `>=`; the tokenizer splits the two-character
+/// forms into two tokens.
+fn expect_comparison(tokens: &mut Vec<String>) -> Result<CmpOp, ParseError> {
+    let op = match peek_token(tokens) {
+        "=" => CmpOp::Eq,
+        "<" => CmpOp::Lt,
+        ">" => CmpOp::Gt,
+        other => {
+            return Err(ParseError::UnexpectedToken(format!(
+                "Expected comparison, got '{}'",
+                other
+            )))
+        }
+    };
+    tokens.remove(0);
+
+    if op != CmpOp::Eq && peek_token(tokens) == "=" {
+        tokens.remove(0);
+        return Ok(if op == CmpOp::Lt {
+            CmpOp::Le
+        } else {
+            CmpOp::Ge
+        });
+    }
+    Ok(op)
+}
+
+fn expect_identifier(tokens: &mut Vec<String>) -> Result<String, ParseError> {
+    if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken(
+            "Expected identifier".to_string(),
+        ));
+    }
+
+    let token = tokens.remove(0);
+    if token
+        .chars()
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or(false)
+    {
+        Ok(token)
+    } else {
+        Err(ParseError::UnexpectedToken(format!(
+            "Expected identifier, got '{}'",
+            token
+        )))
+    }
+}
+
+fn expect_number(tokens: &mut Vec<String>) -> Result<usize, ParseError> {
+    if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken("Expected number".to_string()));
+    }
+
+    let token = tokens.remove(0);
+    token
+        .parse::<usize>()
+        .map_err(|_| ParseError::InvalidSyntax(format!("Expected number, got '{}'", token)))
+}
+
+/// A signed integer; the tokenizer splits a leading `-` off.
+fn expect_int(tokens: &mut Vec<String>) -> Result<i64, ParseError> {
+    let negative = peek_token(tokens) == "-";
+    if negative {
+        tokens.remove(0);
+    }
+    if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken("Expected integer".to_string()));
+    }
+
+    let token = tokens.remove(0);
+    let digits = if negative {
+        format!("-{}", token)
+    } else {
+        token
+    };
+    digits
+        .parse::<i64>()
+        .map_err(|_| ParseError::InvalidSyntax(format!("Expected integer, got '{}'", digits)))
+}
+
+fn expect_node_id(tokens: &mut Vec<String>) -> Result<NodeId, ParseError> {
+    if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken("Expected node ID".to_string()));
+    }
+
+    let token = tokens.remove(0);
+    token
+        .parse::<NodeId>()
+        .map_err(|_| ParseError::InvalidSyntax(format!("Invalid node ID: {}", token)))
+}
+
+fn expect_string(tokens: &mut Vec<String>) -> Result<String, ParseError> {
+    if tokens.is_empty() {
+        return Err(ParseError::UnexpectedToken("Expected string".to_string()));
+    }
+
+    let token = tokens.remove(0);
+    Ok(token.trim_matches('\'').trim_matches('"').to_string())
+}
+
+fn peek_token(tokens: &[String]) -> &str {
+    if tokens.is_empty() {
+        ""
+    } else {
+        &tokens[0]
+    }
+}
+
+fn parse_hex_string(hex: &str) -> Result<Vec<u8>, String> {
+    // Remove any whitespace
+    let hex = hex.trim();
+
+    // Hex string must have even number of characters
+    if hex.len() % 2 != 0 {
+        return Err("Hex string must have even number of characters".to_string());
+    }
+
+    let mut bytes = Vec::new();
+    for i in (0..hex.len()).step_by(2) {
+        let byte_str = &hex[i..i + 2];
+        let byte = u8::from_str_radix(byte_str, 16)
+            .map_err(|e| format!("Invalid hex character: {}", e))?;
+        bytes.push(byte);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simple_match() {
+        let query = "MATCH (n:User) RETURN n.id LIMIT 10";
+        let result = parse(query);
+        assert!(result.is_ok());
+
+        let query = result.unwrap();
+        match query {
+            CypherQuery::Match { match_pattern, .. } => match match_pattern {
+                MatchPattern::SingleNode { variable, label } => {
+                    assert_eq!(variable, "n");
+                    assert_eq!(label, Some("User".to_string()));
+                }
+                _ => panic!("Expected SingleNode pattern"),
+            },
+            _ => panic!("Expected Match query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_single_node_without_label() {
+        let query = "MATCH (n) RETURN n.id LIMIT 10";
+        let result = parse(query);
+        assert!(result.is_ok());
+
+        let query = result.unwrap();
+        match query {
+            CypherQuery::Match { match_pattern, .. } => match match_pattern {
+                MatchPattern::SingleNode { variable, label } => {
+                    assert_eq!(variable, "n");
+                    assert_eq!(label, None);
+                }
+                _ => panic!("Expected SingleNode pattern"),
+            },
+            _ => panic!("Expected Match query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_return_all() {
+        let query = "MATCH (n:User) RETURN * LIMIT 10";
+        let result = parse(query);
+        assert!(result.is_ok());
+
+        let query = result.unwrap();
+        match query {
+            CypherQuery::Match { return_clause, .. } => match return_clause {
+                ReturnClause::All => {}
+                _ => panic!("Expected All return clause"),
+            },
+            _ => panic!("Expected Match query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_return_property() {
+        match parse("MATCH (n:User) RETURN n.label LIMIT 10").unwrap() {
+            CypherQuery::Match { return_clause, .. } => assert_eq!(
+                return_clause,
+                ReturnClause::NodeAttr {
+                    variable: "n".to_string(),
+                    attr: "label".to_string(),
+                }
+            ),
+            _ => panic!("Expected Match query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_match_outgoing_relationship() {
+        let query = parse("MATCH (n:User)-[:FOLLOWS]->(m:User) RETURN m LIMIT 10").unwrap();
+        match query {
+            CypherQuery::Match {
+                match_pattern: MatchPattern::Relationship { edge, to, .. },
+                ..
+            } => {
+                assert_eq!(edge.direction, EdgeDirection::Outgoing);
+                assert_eq!(edge.label.as_deref(), Some("FOLLOWS"));
+                assert_eq!(to.variable, "m");
+            }
+            _ => panic!("Expected relationship match"),
+        }
+    }
+
+    #[test]
+    fn test_parse_mutual_pattern() {
+        let expected = CypherQuery::Match {
+            match_pattern: MatchPattern::Mutual {
+                from: NodePattern {
+                    variable: "a".to_string(),
+                    label: Some("User".to_string()),
+                },
+                edge: EdgePattern {
+                    direction: EdgeDirection::Outgoing,
+                    label: Some("FOLLOWS".to_string()),
+                },
+                to: NodePattern {
+                    variable: "b".to_string(),
+                    label: Some("User".to_string()),
+                },
+            },
+            where_clause: None,
+            return_clause: ReturnClause::Pair {
+                first: "a".to_string(),
+                second: "b".to_string(),
+            },
+            limit: Some(10),
+        };
+        for query in [
+            "MATCH (a:User)-[:FOLLOWS]->(b:User), (b)-[:FOLLOWS]->(a) RETURN a, b LIMIT 10",
+            "MATCH (a:User)-[:FOLLOWS]->(b), (b:User)-[:FOLLOWS]->(a:User) RETURN a, b LIMIT 10",
+        ] {
+            assert_eq!(parse(query).unwrap(), expected, "{}", query);
+        }
+
+        for query in [
+            // Not the reverse of the first pattern
+            "MATCH (a)-[:FOLLOWS]->(b), (a)-[:FOLLOWS]->(b) RETURN a LIMIT 10",
+            "MATCH (a)-[:FOLLOWS]->(b), (b)-[:BLOCKS]->(a) RETURN a LIMIT 10",
+            "MATCH (a:User)-[:FOLLOWS]->(b), (b)-[:FOLLOWS]->(a:Bot) RETURN a LIMIT 10",
+            "MATCH (a)-[:FOLLOWS]-(b), (b)-[:FOLLOWS]-(a) RETURN a LIMIT 10",
+            // Pairs come only from mutual patterns
+            "MATCH (a)-[:FOLLOWS]->(b) RETURN a, b LIMIT 10",
+            "MATCH (a)-[:FOLLOWS]->(b), (b)-[:FOLLOWS]->(a) RETURN a, c LIMIT 10",
+        ] {
+            assert!(parse(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_parse_path_pattern() {
+        let path = |min_hops, max_hops| CypherQuery::Match {
+            match_pattern: MatchPattern::Path {
+                from: NodePattern {
+                    variable: "a".to_string(),
+                    label: None,
+                },
+                edge: EdgePattern {
+                    direction: EdgeDirection::Outgoing,
+                    label: Some("OWES".to_string()),
+                },
+                variable: "r".to_string(),
+                min_hops,
+                max_hops,
+                to: NodePattern {
+                    variable: "b".to_string(),
+                    label: None,
+                },
+            },
+            where_clause: None,
+            return_clause: ReturnClause::Sum {
+                variable: "r".to_string(),
+                attr: "amount".to_string(),
+            },
+            limit: Some(1),
+        };
+        for (query, expected) in [
+            (
+                "MATCH (a)-[r:OWES*1..3]->(b) RETURN SUM(r.amount) LIMIT 1",
+                path(1, 3),
+            ),
+            (
+                "MATCH (a)-[r:OWES *..3]->(b) RETURN sum(r.amount) LIMIT 1",
+                path(1, 3),
+            ),
+            (
+                "MATCH (a)-[r:OWES*2]->(b) RETURN SUM(r.amount) LIMIT 1",
+                path(2, 2),
+            ),
+            (
+                "MATCH (a)-[r:OWES]->(b) RETURN SUM(r.amount) LIMIT 1",
+                path(1, 1),
+            ),
+        ] {
+            assert_eq!(parse(query).unwrap(), expected, "{}", query);
+        }
+
+        for query in [
+            // Hop ranges must be bounded and non-empty
+            "MATCH (a)-[r:OWES*]->(b) RETURN SUM(r.amount) LIMIT 1",
+            "MATCH (a)-[r:OWES*0..2]->(b) RETURN SUM(r.amount) LIMIT 1",
+            "MATCH (a)-[r:OWES*3..2]->(b) RETURN SUM(r.amount) LIMIT 1",
+            "MATCH (a)-[r:OWES*1..9]->(b) RETURN SUM(r.amount) LIMIT 1",
+            "MATCH (a)-[r:OWES*1..3]-(b) RETURN SUM(r.amount) LIMIT 1",
+            // A path returns the sum over its own edges, filtered at its start
+            "MATCH (a)-[r:OWES*1..3]->(b) RETURN b LIMIT 1",
+            "MATCH (a)-[r:OWES*1..3]->(b) RETURN SUM(s.amount) LIMIT 1",
+            "MATCH (a)-[:OWES]->(b) RETURN SUM(b.amount) LIMIT 1",
+            "MATCH (a)-[r:OWES*1..3]->(b) WHERE b.id = 2 RETURN SUM(r.amount) LIMIT 1",
+        ] {
+            assert!(parse(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_parse_missing_limit() {
+        let query = "MATCH (n:User) RETURN n.id";
+        let result = parse(query);
+        assert!(result.is_err());
+
+        match result.unwrap_err() {
+            ParseError::MissingLimit => {}
+            _ => panic!("Expected MissingLimit error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_syntax() {
+        let query = "MATCH (n:User RETURN n.id LIMIT 10";
+        let result = parse(query);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_tokenize_basic() {
+        let result = tokenize("MATCH (n:User) RETURN n.id LIMIT 10");
+        assert!(result.is_ok());
+
+        let tokens = result.unwrap();
+        assert!(tokens.contains(&"MATCH".to_string()));
+        assert!(tokens.contains(&"(".to_string()));
+        assert!(tokens.contains(&"n".to_string()));
+    }
+
+    #[test]
+    fn test_tokenize_with_strings() {
+        let result = tokenize("WHERE n.name = 'John'");
+        assert!(result.is_ok());
+
+        let tokens = result.unwrap();
+        assert!(tokens.contains(&"John".to_string()));
+    }
+
+    #[test]
+    fn test_parse_multiple_whitespace() {
+        let query = "MATCH   (n:User)   RETURN   n.id   LIMIT   10";
+        let result = parse(query);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_parse_case_insensitive_keywords() {
+        let query = "match (n:User) return n.id limit 10";
+        let result = parse(query);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_parse_empty_query() {
+        let query = "";
+        let result = parse(query);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_missing_match() {
+        let query = "RETURN n.id LIMIT 10";
+        let result = parse(query);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_missing_return() {
+        let query = "MATCH (n:User) LIMIT 10";
+        let result = parse(query);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_call() {
+        assert_eq!(
+            parse("CALL delegation.resolve(7)").unwrap(),
+            CypherQuery::Call {
+                procedure: Procedure::ResolveDelegation { start_ids: vec![7] },
+            }
+        );
+        assert_eq!(
+            parse("call delegation.resolve( 1, 2 )").unwrap(),
+            CypherQuery::Call {
+                procedure: Procedure::ResolveDelegation {
+                    start_ids: vec![1, 2],
+                },
+            }
+        );
+        assert_eq!(
+            parse("CALL cycle.find('PLEDGES')").unwrap(),
+            CypherQuery::Call {
+                procedure: Procedure::FindCycle {
+                    label: "PLEDGES".to_string(),
+                },
+            }
+        );
+        assert!(parse("CALL delegation.resolve()").is_err());
+        assert!(parse("CALL delegation.undo(1)").is_err());
+        assert!(parse("CALL cycle.find()").is_err());
+        assert!(parse("CALL cycle.find('')").is_err());
+        assert!(parse("CALL delegation.resolve(1) LIMIT 5").is_err());
+    }
+
+    #[test]
+    fn test_parse_create_node() {
+        let query = "CREATE (n:Person)";
+        let result = parse(query);
+        assert!(result.is_ok());
+
+        let query = result.unwrap();
+        match query {
+            CypherQuery::Create { create_pattern } => match create_pattern {
+                CreatePattern::Node {
+                    variable,
+                    label,
+                    data,
+                    ttl,
+                    id,
+                } => {
+                    assert_eq!(variable, "n");
+                    assert_eq!(label, Some("Person".to_string()));
+                    assert_eq!(data, None);
+                    assert_eq!(ttl, None);
+                    assert_eq!(id, None);
+                }
+                _ => panic!("Expected Node create pattern"),
+            },
+            _ => panic!("Expected Create query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_create_node_with_hex_data() {
+        let query = "CREATE (n:Person {0x1234})";
+        let result = parse(query);
+        assert!(result.is_ok());
+
+        let query = result.unwrap();
+        match query {
+            CypherQuery::Create { create_pattern } => match create_pattern {
+                CreatePattern::Node {
+                    variable,
+                    label,
+                    data,
+                    ttl,
+                    id,
+                } => {
+                    assert_eq!(variable, "n");
+                    assert_eq!(label, Some("Person".to_string()));
+                    assert_eq!(data, Some(vec![0x12, 0x34]));
+                    assert_eq!(ttl, None);
+                    assert_eq!(id, None);
+                }
+                _ => panic!("Expected Node create pattern"),
+            },
+            _ => panic!("Expected Create query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_create_node_with_ttl() {
+        let result = parse("CREATE (n:Session {0x01}) TTL 1500").unwrap();
+        match result {
+            CypherQuery::Create {
+                create_pattern: CreatePattern::Node { ttl, data, .. },
+            } => {
+                assert_eq!(ttl, Some(1500));
+                assert_eq!(data, Some(vec![0x01]));
+            }
+            _ => panic!("Expected Node create pattern"),
+        }
+
+        assert!(parse("CREATE (n:Session) TTL").is_err());
+    }
+
+    #[test]
+    fn test_parse_create_node_with_id() {
+        for query in [
+            "CREATE (n:User {id: 42, 0xbeef})",
+            "CREATE (n:User {0xbeef, id: 42})",
+        ] {
+            match parse(query).unwrap() {
+                CypherQuery::Create {
+                    create_pattern: CreatePattern::Node { id, data, .. },
+                } => {
+                    assert_eq!(id, Some(42));
+                    assert_eq!(data, Some(vec![0xbe, 0xef]));
+                }
+                _ => panic!("Expected Node create pattern"),
+            }
+        }
+
+        assert!(parse("CREATE (n:User {id: 1, id: 2})").is_err());
+        assert!(parse("CREATE (n:User {id: x})").is_err());
+        assert!(parse("CREATE (n:User {name: 1})").is_err());
+    }
+
+    #[test]
+    fn test_parse_create_edge_with_ids() {
+        let query = "CREATE (1)-[:FOLLOWS]->(2)";
+        let result = parse(query);
+        assert!(result.is_ok());
+
+        let query = result.unwrap();
+        match query {
+            CypherQuery::Create { create_pattern } => match create_pattern {
+                CreatePattern::Edge {
+                    from_id,
+                    to_id,
+                    edge,
+                    ..
+                } => {
+                    assert_eq!(from_id, Some(1));
+                    assert_eq!(to_id, Some(2));
+                    assert_eq!(edge.label, Some("FOLLOWS".to_string()));
+                }
+                _ => panic!("Expected Edge create pattern"),
+            },
+            _ => panic!("Expected Create query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_node_ids_fit_node_id_width() {
+        let max = NodeId::MAX;
+        assert!(parse(&format!("CREATE ({})-[:FOLLOWS]->(1)", max)).is_ok());
+        assert!(parse(&format!("MATCH (n) WHERE n.id = {} RETURN n LIMIT 1", max)).is_ok());
+
+        // One past `u64::MAX`, and past any width
+        let mut too_large = vec!["9".repeat(40)];
+        if crate::ops::NODE_ID_LEN == 8 {
+            too_large.push("18446744073709551616".to_string());
+        }
+        for id in too_large {
+            assert!(parse(&format!("CREATE ({})-[:FOLLOWS]->(1)", id)).is_err());
+            let query = format!("MATCH (n) WHERE n.id = {} RETURN n LIMIT 1", id);
+            assert!(parse(&query).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_create_edge_with_variables() {
+        let query = "CREATE (a:User)-[:KNOWS]->(b:User)";
+        let result = parse(query);
+        assert!(result.is_ok());
+
+        let query = result.unwrap();
+        match query {
+            CypherQuery::Create { create_pattern } => {
+                match create_pattern {
+                    CreatePattern::Edge {
+                        from_id,
+                        to_id,
+                        edge,
+                        from,
+                        to,
+                    } => {
+                        // Variables are used, so IDs should be None
+                        assert_eq!(from_id, None);
+                        assert_eq!(to_id, None);
+                        assert_eq!(from.variable, "a");
+                        assert_eq!(to.variable, "b");
+                        assert_eq!(edge.label, Some("KNOWS".to_string()));
+                    }
+                    _ => panic!("Expected Edge create pattern"),
+                }
+            }
+            _ => panic!("Expected Create query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_params() {
+        let params = vec![
+            ParamValue::Str("City".to_string()),
+            ParamValue::Bytes(vec![0xab, 0x01]),
+        ];
+        let result = parse_with_params("CREATE (n:$1 { $2 })", &params).unwrap();
+
+        match result {
+            CypherQuery::Create {
+                create_pattern: CreatePattern::Node { label, data, .. },
+            } => {
+                assert_eq!(label, Some("City".to_string()));
+                assert_eq!(data, Some(vec![0xab, 0x01]));
+            }
+            _ => panic!("Expected CREATE node"),
+        }
+
+        let params = vec![ParamValue::Id(1), ParamValue::Id(2)];
+        let result = parse_with_params("CREATE ($1)-[:ROAD]->($2)", &params).unwrap();
+        match result {
+            CypherQuery::Create {
+                create_pattern: CreatePattern::Edge { from_id, to_id, .. },
+            } => {
+                assert_eq!(from_id, Some(1));
+                assert_eq!(to_id, Some(2));
+            }
+            _ => panic!("Expected CREATE edge"),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_params_cannot_inject() {
+        // A value is one token, however much syntax it contains
+        let params = vec![ParamValue::Str("User) RETURN n.id LIMIT 1".to_string())];
+        let result = parse_with_params("MATCH (n:$1) RETURN n.id LIMIT 10", &params).unwrap();
+
+        match result {
+            CypherQuery::Match {
+                match_pattern: MatchPattern::SingleNode { label, .. },
+                limit,
+                ..
+            } => {
+                assert_eq!(label, Some("User) RETURN n.id LIMIT 1".to_string()));
+                assert_eq!(limit, Some(10));
+            }
+            _ => panic!("Expected MATCH"),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_unbound_param() {
+        assert!(parse_with_params("CREATE (n:$1)", &[]).is_err());
+        assert!(parse_with_params("CREATE (n:$0)", &[ParamValue::Int(1)]).is_err());
+    }
+
+    #[test]
+    fn test_parse_where_mint() {
+        let mint = Pubkey::new_unique();
+        let result = parse_with_params(
+            "MATCH (n:Asset) WHERE n.mint = $1 RETURN n LIMIT 10",
+            &[ParamValue::Pubkey(mint)],
+        )
+        .unwrap();
+        match result {
+            CypherQuery::Match {
+                where_clause: Some(WhereClause::NodeMintEq { variable, mint: m }),
+                ..
+            } => {
+                assert_eq!(variable, "n");
+                assert_eq!(m, mint);
+            }
+            _ => panic!("Expected mint WHERE"),
+        }
+
+        assert!(parse("MATCH (n) WHERE n.mint = 'nope' RETURN n LIMIT 10").is_err());
+    }
+
+    #[test]
+    fn test_parse_where_timestamp() {
+        let params = vec![ParamValue::Int(1_700_000_000)];
+        let result = parse_with_params(
+            "MATCH (n:Post) WHERE n.created_at >= $1 RETURN n LIMIT 10",
+            &params,
+        )
+        .unwrap();
+        match result {
+            CypherQuery::Match {
+                where_clause:
+                    Some(WhereClause::NodeTimeCmp {
+                        variable,
+                        field,
+                        op,
+                        value,
+                    }),
+                ..
+            } => {
+                assert_eq!(variable, "n");
+                assert_eq!(field, TimeField::CreatedAt);
+                assert_eq!(op, CmpOp::Ge);
+                assert_eq!(value, 1_700_000_000);
+            }
+            _ => panic!("Expected timestamp WHERE"),
+        }
+
+        let result = parse("MATCH (n) WHERE n.updated_at < 5 RETURN n LIMIT 10").unwrap();
+        assert!(matches!(
+            result,
+            CypherQuery::Match {
+                where_clause: Some(WhereClause::NodeTimeCmp {
+                    field: TimeField::UpdatedAt,
+                    op: CmpOp::Lt,
+                    ..
+                }),
+                ..
+            }
+        ));
+
+        // Ordering comparisons only make sense on timestamps
+        assert!(parse("MATCH (n) WHERE n.id > 5 RETURN n LIMIT 10").is_err());
+    }
+
+    #[test]
+    fn test_parse_where_between() {
+        let between = |min, max| {
+            Some(WhereClause::NodeAttrBetween {
+                variable: "t".to_string(),
+                attr: "x".to_string(),
+                min,
+                max,
+            })
+        };
+        let where_clause = |query: CypherQuery| match query {
+            CypherQuery::Match { where_clause, .. } => where_clause,
+            _ => panic!("Expected MATCH"),
+        };
+        let query = "MATCH (t:Tile) WHERE t.x BETWEEN 10 AND 20 RETURN t LIMIT 10";
+        assert_eq!(where_clause(parse(query).unwrap()), between(10, 20));
+        let query = "MATCH (t) WHERE t.x between -20 and -10 RETURN t LIMIT 10";
+        assert_eq!(where_clause(parse(query).unwrap()), between(-20, -10));
+        let params = vec![ParamValue::Int(-3), ParamValue::Int(3)];
+        let query = "MATCH (t) WHERE t.x BETWEEN $1 AND $2 RETURN t LIMIT 10";
+        assert_eq!(
+            where_clause(parse_with_params(query, &params).unwrap()),
+            between(-3, 3)
+        );
+
+        for query in [
+            "MATCH (t) WHERE t.x BETWEEN 10 RETURN t LIMIT 10",
+            "MATCH (t) WHERE t.x BETWEEN 'a' AND 'b' RETURN t LIMIT 10",
+            "MATCH (t) WHERE t.created_at BETWEEN 1 AND 2 RETURN t LIMIT 10",
+        ] {
+            assert!(parse(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_borsh_query_round_trips_and_validates() {
+        let query = parse("MATCH (n:User) WHERE n.created_at > 3 RETURN n LIMIT 5").unwrap();
+        let bytes = borsh::to_vec(&query).unwrap();
+        let decoded = CypherQuery::try_from_slice(&bytes).unwrap();
+        assert_eq!(decoded, query);
+        assert!(validate(&decoded).is_ok());
+
+        let unlimited = CypherQuery::Match {
+            match_pattern: MatchPattern::SingleNode {
+                variable: "n".to_string(),
+                label: None,
+            },
+            where_clause: None,
+            return_clause: ReturnClause::All,
+            limit: None,
+        };
+        assert!(matches!(
+            validate(&unlimited),
+            Err(ParseError::MissingLimit)
+        ));
+    }
+}
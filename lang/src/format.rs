@@ -0,0 +1,294 @@
+//! Canonical query text. Every query prints one way, whatever its original
+//! spacing, keyword case or quoting, so normalized text can be hashed,
+//! cached and diffed.
+
+use crate::cypher::{
+    parse, CreatePattern, CypherQuery, EdgeDirection, EdgePattern, MatchPattern, NodePattern,
+    ParseError, Procedure, ReturnClause, WhereClause,
+};
+use crate::ops::{CmpOp, NodeId, TimeField};
+use std::fmt::{self, Display, Formatter};
+
+/// `query` in canonical form: keywords upper-case, single spaces, hex data
+/// in lower case.
+pub fn format_query(query: &str) -> Result<String, ParseError> {
+    Ok(parse(query)?.to_string())
+}
+
+impl Display for CypherQuery {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CypherQuery::Match {
+                match_pattern,
+                where_clause,
+                return_clause,
+                limit,
+            } => {
+                write!(f, "MATCH {}", match_pattern)?;
+                if let Some(where_clause) = where_clause {
+                    write!(f, " WHERE {}", where_clause)?;
+                }
+                write!(f, " RETURN {}", return_clause)?;
+                if let Some(limit) = limit {
+                    write!(f, " LIMIT {}", limit)?;
+                }
+                Ok(())
+            }
+            CypherQuery::Create { create_pattern } => write!(f, "CREATE {}", create_pattern),
+            CypherQuery::Call { procedure } => write!(f, "CALL {}", procedure),
+        }
+    }
+}
+
+impl Display for CreatePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CreatePattern::Node {
+                variable,
+                label,
+                data,
+                ttl,
+                id,
+            } => {
+                write!(f, "({}", variable)?;
+                if let Some(label) = label {
+                    write!(f, ":{}", label)?;
+                }
+                match (id, data) {
+                    (None, None) => {}
+                    (Some(id), None) => write!(f, " {{id: {}}}", id)?,
+                    (id, Some(data)) => {
+                        write!(f, " {{")?;
+                        if let Some(id) = id {
+                            write!(f, "id: {}, ", id)?;
+                        }
+                        write!(f, "0x")?;
+                        for byte in data {
+                            write!(f, "{:02x}", byte)?;
+                        }
+                        write!(f, "}}")?;
+                    }
+                }
+                write!(f, ")")?;
+                if let Some(ttl) = ttl {
+                    write!(f, " TTL {}", ttl)?;
+                }
+                Ok(())
+            }
+            CreatePattern::Edge {
+                from,
+                from_id,
+                edge,
+                to,
+                to_id,
+            } => {
+                write_endpoint(f, from, *from_id)?;
+                write!(f, "{}", edge)?;
+                write_endpoint(f, to, *to_id)
+            }
+        }
+    }
+}
+
+/// A CREATE endpoint: the node id if the query gave one, else the pattern.
+fn write_endpoint(f: &mut Formatter<'_>, node: &NodePattern, id: Option<NodeId>) -> fmt::Result {
+    match id {
+        Some(id) => write!(f, "({})", id),
+        None => write!(f, "{}", node),
+    }
+}
+
+impl Display for Procedure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Procedure::ResolveDelegation { start_ids } => {
+                write!(f, "delegation.resolve(")?;
+                for (i, id) in start_ids.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", id)?;
+                }
+                write!(f, ")")
+            }
+            Procedure::FindCycle { label } => write!(f, "cycle.find('{}')", label),
+        }
+    }
+}
+
+impl Display for MatchPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchPattern::SingleNode { variable, label } => write!(
+                f,
+                "{}",
+                NodePattern {
+                    variable: variable.clone(),
+                    label: label.clone(),
+                }
+            ),
+            MatchPattern::Relationship { from, edge, to } => write!(f, "{}{}{}", from, edge, to),
+            MatchPattern::Mutual { from, edge, to } => write!(
+                f,
+                "{}{}{}, ({}){}({})",
+                from, edge, to, to.variable, edge, from.variable
+            ),
+            MatchPattern::Path {
+                from,
+                edge,
+                variable,
+                min_hops,
+                max_hops,
+                to,
+            } => {
+                let label = edge
+                    .label
+                    .as_ref()
+                    .map(|label| format!(":{}", label))
+                    .unwrap_or_default();
+                write!(f, "{}-[{}{}*{}", from, variable, label, min_hops)?;
+                if max_hops != min_hops {
+                    write!(f, "..{}", max_hops)?;
+                }
+                write!(f, "]->{}", to)
+            }
+        }
+    }
+}
+
+impl Display for NodePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "({}:{})", self.variable, label),
+            None => write!(f, "({})", self.variable),
+        }
+    }
+}
+
+impl Display for EdgePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let label = match &self.label {
+            Some(label) => format!("[:{}]", label),
+            None => "[]".to_string(),
+        };
+        match self.direction {
+            EdgeDirection::Outgoing => write!(f, "-{}->", label),
+            EdgeDirection::Incoming => write!(f, "<-{}-", label),
+            EdgeDirection::Bidirectional => write!(f, "-{}-", label),
+        }
+    }
+}
+
+impl Display for WhereClause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WhereClause::NodeIdEq { variable, value } => write!(f, "{}.id = {}", variable, value),
+            WhereClause::NodeAttrEq {
+                variable,
+                attr,
+                value,
+            } => write!(f, "{}.{} = '{}'", variable, attr, value),
+            WhereClause::NodeMintEq { variable, mint } => {
+                write!(f, "{}.mint = '{}'", variable, mint)
+            }
+            WhereClause::NodeTimeCmp {
+                variable,
+                field,
+                op,
+                value,
+            } => {
+                let field = match field {
+                    TimeField::CreatedAt => "created_at",
+                    TimeField::UpdatedAt => "updated_at",
+                };
+                let op = match op {
+                    CmpOp::Eq => "=",
+                    CmpOp::Lt => "<",
+                    CmpOp::Le => "<=",
+                    CmpOp::Gt => ">",
+                    CmpOp::Ge => ">=",
+                };
+                write!(f, "{}.{} {} {}", variable, field, op, value)
+            }
+            WhereClause::NodeAttrBetween {
+                variable,
+                attr,
+                min,
+                max,
+            } => write!(f, "{}.{} BETWEEN {} AND {}", variable, attr, min, max),
+            WhereClause::EdgeIdsEq {
+                from_variable,
+                from,
+                to_variable,
+                to,
+            } => write!(
+                f,
+                "{}.id = {} AND {}.id = {}",
+                from_variable, from, to_variable, to
+            ),
+        }
+    }
+}
+
+impl Display for ReturnClause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReturnClause::NodeId { variable } => write!(f, "{}", variable),
+            ReturnClause::NodeAttr { variable, attr } => write!(f, "{}.{}", variable, attr),
+            ReturnClause::All => write!(f, "*"),
+            ReturnClause::Pair { first, second } => write!(f, "{}, {}", first, second),
+            ReturnClause::Sum { variable, attr } => write!(f, "SUM({}.{})", variable, attr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_query_normalizes() {
+        assert_eq!(
+            format_query("match  (n:User)\n where n.name = \"bob\" return n limit 10").unwrap(),
+            "MATCH (n:User) WHERE n.name = 'bob' RETURN n LIMIT 10"
+        );
+        assert_eq!(
+            format_query("MATCH (n) WHERE n.created_at >= 5 RETURN * LIMIT 1").unwrap(),
+            "MATCH (n) WHERE n.created_at >= 5 RETURN * LIMIT 1"
+        );
+        assert_eq!(
+            format_query("create (s:Session {0xABcd})   ttl 5").unwrap(),
+            "CREATE (s:Session {0xabcd}) TTL 5"
+        );
+        assert_eq!(
+            format_query("CREATE (n:User {0x01,id:42})").unwrap(),
+            "CREATE (n:User {id: 42, 0x01})"
+        );
+        assert_eq!(
+            format_query("CREATE (1)-[:KNOWS]->( 2 )").unwrap(),
+            "CREATE (1)-[:KNOWS]->(2)"
+        );
+    }
+
+    #[test]
+    fn test_formatted_query_parses_to_same_ast() {
+        for query in [
+            "MATCH (n:User) WHERE n.id = 7 RETURN n LIMIT 10",
+            "CREATE (n:Post {0x01ff}) TTL 9",
+            "CREATE (n:User {0x01, id: 42})",
+            "CREATE (n:User {id: 7})",
+            "CREATE (a:User)-[:FOLLOWS]->(b)",
+            "CREATE (3)-[:LINK]-(4)",
+            "MATCH (a:User)-[:FOLLOWS]->(b), (b:User)-[:FOLLOWS]->(a) RETURN a, b LIMIT 5",
+            "CALL delegation.resolve(4, 2)",
+            "CALL cycle.find('PLEDGES')",
+            "MATCH (t:Tile) WHERE t.x BETWEEN -5 AND 20 RETURN t LIMIT 50",
+            "MATCH (a:User)-[r:OWES*1..3]->(b) RETURN SUM(r.amount) LIMIT 1",
+            "MATCH (a)-[r*2]->(b:User) WHERE a.id = 1 RETURN SUM(r.amount) LIMIT 1",
+            "MATCH (a:User)-[:FOLLOWS]->(b) WHERE a.id = 1 AND b.id = 2 RETURN b LIMIT 1",
+        ] {
+            let parsed = parse(query).unwrap();
+            assert_eq!(parse(&parsed.to_string()).unwrap(), parsed, "{}", query);
+        }
+    }
+}
@@ -0,0 +1,756 @@
+use crate::cypher::{
+    CreatePattern, CypherQuery, EdgeDirection, MatchPattern, NodePattern, Procedure, ReturnClause,
+    WhereClause,
+};
+use crate::ops::{
+    NodeId, Opcode, Projection, TraverseFilter, DELEGATION_LABEL, MAX_DELEGATION_HOPS,
+};
+
+pub fn compile_to_opcodes(query: CypherQuery) -> Vec<Opcode> {
+    let mut opcodes = Vec::new();
+
+    match query {
+        CypherQuery::Match {
+            match_pattern,
+            where_clause,
+            return_clause,
+            limit,
+        } => {
+            let mutual = matches!(match_pattern, MatchPattern::Mutual { .. });
+            match match_pattern {
+                MatchPattern::SingleNode { variable: _, label } => {
+                    opcodes.push(Opcode::SetCurrentFromAllNodes);
+
+                    if let Some(label) = label {
+                        let filter = TraverseFilter {
+                            where_node_labels: vec![label],
+                            where_edge_labels: Vec::new(),
+                            where_not_node_labels: Vec::new(),
+                            where_not_edge_labels: Vec::new(),
+                        };
+                        opcodes.push(Opcode::TraverseOut(filter));
+                    }
+
+                    if let Some((_, filter)) = where_filter(&where_clause) {
+                        opcodes.push(filter);
+                    }
+                }
+                MatchPattern::Relationship { from, edge, to }
+                | MatchPattern::Mutual { from, edge, to } => {
+                    let probe = extract_edge_ids(&where_clause).filter(|_| !mutual);
+                    if let Some(ids) = probe {
+                        probe_edge(&mut opcodes, from, edge.label, to, ids);
+                    } else {
+                        let end_filter = select_start(&mut opcodes, &from, &where_clause);
+
+                        let filter = TraverseFilter {
+                            where_node_labels: to.label.map(|l| vec![l]).unwrap_or_default(),
+                            where_edge_labels: edge.label.into_iter().collect(),
+                            where_not_node_labels: Vec::new(),
+                            where_not_edge_labels: Vec::new(),
+                        };
+                        if mutual {
+                            opcodes.push(Opcode::MutualOut(filter));
+                        } else if !filter.where_edge_labels.is_empty() {
+                            opcodes.push(Opcode::TraverseOut(filter));
+                        }
+                        opcodes.extend(end_filter);
+                    }
+                }
+                MatchPattern::Path {
+                    from,
+                    edge,
+                    min_hops,
+                    max_hops,
+                    to,
+                    ..
+                } => {
+                    // The parser only accepts a filter on the start node
+                    select_start(&mut opcodes, &from, &where_clause);
+                    let key = match &return_clause {
+                        ReturnClause::Sum { attr, .. } => attr.clone(),
+                        _ => String::new(),
+                    };
+                    opcodes.push(Opcode::SumPaths {
+                        filter: TraverseFilter {
+                            where_node_labels: to.label.into_iter().collect(),
+                            where_edge_labels: edge.label.into_iter().collect(),
+                            where_not_node_labels: Vec::new(),
+                            where_not_edge_labels: Vec::new(),
+                        },
+                        key,
+                        min_hops,
+                        max_hops,
+                    });
+                }
+            }
+
+            // The VM caps every set built after `SetLimit`, so it goes
+            // ahead of the last scan or traversal to stop that early. A
+            // filter after it still needs every node, so then it goes last.
+            if let Some(limit) = limit {
+                let at = match opcodes.last() {
+                    Some(
+                        Opcode::FilterTime { .. }
+                        | Opcode::FilterMint(_)
+                        | Opcode::FilterAttr { .. }
+                        | Opcode::FilterRange { .. },
+                    )
+                    | None => opcodes.len(),
+                    Some(_) => opcodes.len() - 1,
+                };
+                opcodes.insert(at, Opcode::SetLimit(limit));
+            }
+
+            opcodes.push(Opcode::SaveResults);
+            opcodes.extend(projection(return_clause).map(Opcode::Project));
+        }
+        CypherQuery::Create { create_pattern } => {
+            match create_pattern {
+                CreatePattern::Node {
+                    label,
+                    data,
+                    ttl,
+                    id,
+                    ..
+                } => {
+                    if let Some(ttl) = ttl {
+                        opcodes.push(Opcode::SetTtl(ttl));
+                    }
+                    if let Some(id) = id {
+                        opcodes.push(Opcode::SetNodeId(id));
+                    }
+                    opcodes.push(Opcode::CreateNode {
+                        label: label.unwrap_or_default(),
+                        data: data.unwrap_or_default(),
+                    });
+                }
+                CreatePattern::Edge {
+                    from,
+                    from_id,
+                    edge,
+                    to,
+                    to_id,
+                } => {
+                    let label = edge.label.unwrap_or_default();
+                    let (from, from_id, to, to_id) = match edge.direction {
+                        EdgeDirection::Incoming => (to, to_id, from, from_id),
+                        _ => (from, from_id, to, to_id),
+                    };
+                    if let (Some(from), Some(to)) = (from_id, to_id) {
+                        opcodes.push(Opcode::CreateEdge { from, to, label });
+                    } else {
+                        // A variable endpoint is a new node, so both ends
+                        // are bound to registers once they exist
+                        let self_loop =
+                            from_id.is_none() && to_id.is_none() && same_variable(&from, &to);
+                        bind_endpoint(&mut opcodes, from, from_id, 0);
+                        let to_register = if self_loop {
+                            0
+                        } else {
+                            bind_endpoint(&mut opcodes, to, to_id, 1);
+                            1
+                        };
+                        opcodes.push(Opcode::CreateEdgeBetween {
+                            from: 0,
+                            to: to_register,
+                            label,
+                        });
+                    }
+                }
+            }
+        }
+        CypherQuery::Call {
+            procedure: Procedure::ResolveDelegation { start_ids },
+        } => {
+            opcodes.push(Opcode::SetCurrentFromIds(start_ids));
+            opcodes.push(Opcode::ResolveDelegates {
+                label: DELEGATION_LABEL.to_string(),
+                max_hops: MAX_DELEGATION_HOPS,
+            });
+            opcodes.push(Opcode::SaveResults);
+            opcodes.push(Opcode::Project(Projection::Delegates));
+        }
+        CypherQuery::Call {
+            procedure: Procedure::FindCycle { label },
+        } => opcodes.push(Opcode::FindCycle(label)),
+    }
+
+    opcodes
+}
+
+/// Selects the start nodes of a relationship: the node a WHERE gives the
+/// id of, else every node with `from`'s label. A filter on the start node
+/// narrows the frontier before traversing; any other is returned to apply
+/// to the end nodes.
+fn select_start(
+    opcodes: &mut Vec<Opcode>,
+    from: &NodePattern,
+    where_clause: &Option<WhereClause>,
+) -> Option<Opcode> {
+    if let Some(start_id) = extract_start_node_id(where_clause) {
+        opcodes.push(Opcode::SetCurrentFromIds(vec![start_id]));
+    } else {
+        opcodes.push(Opcode::SetCurrentFromAllNodes);
+
+        if let Some(label) = &from.label {
+            let filter = TraverseFilter {
+                where_node_labels: vec![label.clone()],
+                where_edge_labels: Vec::new(),
+                where_not_node_labels: Vec::new(),
+                where_not_edge_labels: Vec::new(),
+            };
+            opcodes.push(Opcode::TraverseOut(filter));
+        }
+    }
+
+    match where_filter(where_clause) {
+        Some((variable, filter)) if variable == from.variable => {
+            opcodes.push(filter);
+            None
+        }
+        Some((_, filter)) => Some(filter),
+        None => None,
+    }
+}
+
+/// `(a)-[:L]->(b) WHERE a.id = x AND b.id = y` as a probe of the edge key
+/// index: node `y`, if `x` passes `a`'s label and has an `L` edge to it.
+fn probe_edge(
+    opcodes: &mut Vec<Opcode>,
+    from: NodePattern,
+    edge_label: Option<String>,
+    to: NodePattern,
+    (from_id, to_id): (NodeId, NodeId),
+) {
+    opcodes.push(Opcode::SetCurrentFromIds(vec![from_id]));
+    if let Some(label) = from.label {
+        opcodes.push(Opcode::TraverseOut(TraverseFilter {
+            where_node_labels: vec![label],
+            where_edge_labels: Vec::new(),
+            where_not_node_labels: Vec::new(),
+            where_not_edge_labels: Vec::new(),
+        }));
+    }
+    opcodes.push(Opcode::ProbeEdgeTo {
+        to: to_id,
+        filter: TraverseFilter {
+            where_node_labels: to.label.into_iter().collect(),
+            where_edge_labels: edge_label.into_iter().collect(),
+            where_not_node_labels: Vec::new(),
+            where_not_edge_labels: Vec::new(),
+        },
+    });
+}
+
+/// Binds a CREATE endpoint to `register`: the node with id `id`, or a new
+/// node for a variable.
+fn bind_endpoint(opcodes: &mut Vec<Opcode>, node: NodePattern, id: Option<NodeId>, register: u8) {
+    match id {
+        Some(id) => opcodes.push(Opcode::SetCurrentFromIds(vec![id])),
+        None => opcodes.push(Opcode::CreateNode {
+            label: node.label.unwrap_or_default(),
+            data: Vec::new(),
+        }),
+    }
+    opcodes.push(Opcode::Bind(register));
+}
+
+/// Whether both endpoints name the same variable, as in `(a)-[:R]->(a)`.
+fn same_variable(from: &NodePattern, to: &NodePattern) -> bool {
+    !from.variable.is_empty() && from.variable == to.variable
+}
+
+/// The filter opcode a WHERE clause compiles to, with the variable it
+/// applies to. Id equality is handled as a start selection instead.
+fn where_filter(where_clause: &Option<WhereClause>) -> Option<(String, Opcode)> {
+    match where_clause {
+        Some(WhereClause::NodeTimeCmp {
+            variable,
+            field,
+            op,
+            value,
+        }) => Some((
+            variable.clone(),
+            Opcode::FilterTime {
+                field: *field,
+                op: *op,
+                value: *value,
+            },
+        )),
+        Some(WhereClause::NodeMintEq { variable, mint }) => {
+            Some((variable.clone(), Opcode::FilterMint(*mint)))
+        }
+        Some(WhereClause::NodeAttrEq {
+            variable,
+            attr,
+            value,
+        }) => Some((
+            variable.clone(),
+            Opcode::FilterAttr {
+                key: attr.clone(),
+                value: value.clone(),
+            },
+        )),
+        Some(WhereClause::NodeAttrBetween {
+            variable,
+            attr,
+            min,
+            max,
+        }) => Some((
+            variable.clone(),
+            Opcode::FilterRange {
+                key: attr.clone(),
+                min: *min,
+                max: *max,
+            },
+        )),
+        Some(WhereClause::NodeIdEq { .. } | WhereClause::EdgeIdsEq { .. }) | None => None,
+    }
+}
+
+/// What `Project` returns for a RETURN clause; `None` for node ids, which
+/// the VM returns by default, and for a sum, which `SumPaths` returns.
+fn projection(return_clause: ReturnClause) -> Option<Projection> {
+    match return_clause {
+        ReturnClause::NodeId { .. } => None,
+        ReturnClause::NodeAttr { attr, .. } if attr == "id" => None,
+        ReturnClause::NodeAttr { attr, .. } => Some(Projection::Property(attr)),
+        ReturnClause::All => Some(Projection::Records),
+        ReturnClause::Pair { .. } => Some(Projection::Pairs),
+        ReturnClause::Sum { .. } => None,
+    }
+}
+
+fn extract_edge_ids(where_clause: &Option<WhereClause>) -> Option<(NodeId, NodeId)> {
+    if let Some(WhereClause::EdgeIdsEq { from, to, .. }) = where_clause {
+        Some((*from, *to))
+    } else {
+        None
+    }
+}
+
+fn extract_start_node_id(where_clause: &Option<WhereClause>) -> Option<NodeId> {
+    if let Some(WhereClause::NodeIdEq { value, .. }) = where_clause {
+        Some(*value)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cypher::{
+        CypherQuery, EdgeDirection, EdgePattern, MatchPattern, NodePattern, ReturnClause,
+        WhereClause,
+    };
+    use crate::ops::{CmpOp, TimeField};
+
+    #[test]
+    fn test_compile_relationship_query() {
+        let query = CypherQuery::Match {
+            match_pattern: MatchPattern::Relationship {
+                from: NodePattern {
+                    variable: "n".to_string(),
+                    label: Some("User".to_string()),
+                },
+                edge: EdgePattern {
+                    direction: EdgeDirection::Outgoing,
+                    label: Some("FOLLOWS".to_string()),
+                },
+                to: NodePattern {
+                    variable: "m".to_string(),
+                    label: Some("User".to_string()),
+                },
+            },
+            where_clause: Some(WhereClause::NodeIdEq {
+                variable: "n".to_string(),
+                value: 42,
+            }),
+            return_clause: ReturnClause::NodeId {
+                variable: "m".to_string(),
+            },
+            limit: Some(10),
+        };
+
+        let opcodes = compile_to_opcodes(query);
+        assert!(opcodes.len() >= 3);
+    }
+
+    #[test]
+    fn test_compile_create_edge_with_variables() {
+        let compile = |query| compile_to_opcodes(crate::cypher::parse(query).unwrap());
+        let node = |label: &str| Opcode::CreateNode {
+            label: label.to_string(),
+            data: Vec::new(),
+        };
+        let edge = |from, to| Opcode::CreateEdgeBetween {
+            from,
+            to,
+            label: "KNOWS".to_string(),
+        };
+
+        assert_eq!(
+            compile("CREATE (a:User)-[:KNOWS]->(b:Admin)"),
+            vec![
+                node("User"),
+                Opcode::Bind(0),
+                node("Admin"),
+                Opcode::Bind(1),
+                edge(0, 1)
+            ]
+        );
+        assert_eq!(
+            compile("CREATE (a:User)-[:KNOWS]->(7)"),
+            vec![
+                node("User"),
+                Opcode::Bind(0),
+                Opcode::SetCurrentFromIds(vec![7]),
+                Opcode::Bind(1),
+                edge(0, 1)
+            ]
+        );
+        assert_eq!(
+            compile("CREATE (a:User)-[:KNOWS]->(a)"),
+            vec![node("User"), Opcode::Bind(0), edge(0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_compile_with_start_node_id() {
+        let query = CypherQuery::Match {
+            match_pattern: MatchPattern::Relationship {
+                from: NodePattern {
+                    variable: "n".to_string(),
+                    label: Some("User".to_string()),
+                },
+                edge: EdgePattern {
+                    direction: EdgeDirection::Outgoing,
+                    label: Some("FOLLOWS".to_string()),
+                },
+                to: NodePattern {
+                    variable: "m".to_string(),
+                    label: Some("User".to_string()),
+                },
+            },
+            where_clause: Some(WhereClause::NodeIdEq {
+                variable: "n".to_string(),
+                value: 42,
+            }),
+            return_clause: ReturnClause::NodeId {
+                variable: "m".to_string(),
+            },
+            limit: Some(10),
+        };
+
+        let opcodes = compile_to_opcodes(query);
+        assert!(opcodes.len() >= 3);
+
+        match &opcodes[0] {
+            Opcode::SetCurrentFromIds(ids) => {
+                assert_eq!(ids, &vec![42]);
+            }
+            _ => panic!("Expected SetCurrentFromIds with start node id"),
+        }
+    }
+
+    #[test]
+    fn test_compile_limit_before_last_scan() {
+        let compile = |query| compile_to_opcodes(crate::cypher::parse(query).unwrap());
+
+        let opcodes = compile("MATCH (n) RETURN n LIMIT 3");
+        assert_eq!(
+            opcodes[..2],
+            [Opcode::SetLimit(3), Opcode::SetCurrentFromAllNodes]
+        );
+
+        let opcodes = compile("MATCH (n:User)-[:FOLLOWS]->(m) RETURN m LIMIT 3");
+        assert_eq!(opcodes[opcodes.len() - 3], Opcode::SetLimit(3));
+        assert!(matches!(opcodes[opcodes.len() - 2], Opcode::TraverseOut(_)));
+
+        // Filtering after the limit could drop nodes the limit kept
+        let opcodes = compile("MATCH (n:Post) WHERE n.created_at > 100 RETURN n LIMIT 3");
+        assert!(matches!(
+            opcodes[opcodes.len() - 3],
+            Opcode::FilterTime { .. }
+        ));
+        assert_eq!(opcodes[opcodes.len() - 2], Opcode::SetLimit(3));
+    }
+
+    #[test]
+    fn test_compile_mutual_pattern() {
+        let query = crate::cypher::parse(
+            "MATCH (a:User)-[:FOLLOWS]->(b:User), (b)-[:FOLLOWS]->(a) RETURN a, b LIMIT 5",
+        )
+        .unwrap();
+        let label = |labels: &[&str]| labels.iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![
+                Opcode::SetCurrentFromAllNodes,
+                Opcode::TraverseOut(TraverseFilter {
+                    where_node_labels: label(&["User"]),
+                    where_edge_labels: Vec::new(),
+                    where_not_node_labels: Vec::new(),
+                    where_not_edge_labels: Vec::new(),
+                }),
+                Opcode::SetLimit(5),
+                Opcode::MutualOut(TraverseFilter {
+                    where_node_labels: label(&["User"]),
+                    where_edge_labels: label(&["FOLLOWS"]),
+                    where_not_node_labels: Vec::new(),
+                    where_not_edge_labels: Vec::new(),
+                }),
+                Opcode::SaveResults,
+                Opcode::Project(Projection::Pairs),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_edge_probe() {
+        let query = crate::cypher::parse(
+            "MATCH (a:User)-[:FOLLOWS]->(b:User) WHERE a.id = 1 AND b.id = 2 RETURN b LIMIT 1",
+        )
+        .unwrap();
+        let user = TraverseFilter {
+            where_node_labels: vec!["User".to_string()],
+            where_edge_labels: Vec::new(),
+            where_not_node_labels: Vec::new(),
+            where_not_edge_labels: Vec::new(),
+        };
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![
+                Opcode::SetCurrentFromIds(vec![1]),
+                Opcode::TraverseOut(user.clone()),
+                Opcode::SetLimit(1),
+                Opcode::ProbeEdgeTo {
+                    to: 2,
+                    filter: TraverseFilter {
+                        where_edge_labels: vec!["FOLLOWS".to_string()],
+                        ..user
+                    },
+                },
+                Opcode::SaveResults,
+            ]
+        );
+
+        // Both ids only make sense on the two ends of one labelled edge
+        for query in [
+            "MATCH (a)-[:FOLLOWS]->(b) WHERE b.id = 1 AND a.id = 2 RETURN b LIMIT 1",
+            "MATCH (a)-[]->(b) WHERE a.id = 1 AND b.id = 2 RETURN b LIMIT 1",
+            "MATCH (a) WHERE a.id = 1 AND a.id = 2 RETURN a LIMIT 1",
+            "MATCH (a)-[:FOLLOWS]->(b) WHERE a.id = 1 AND b.name = 'x' RETURN b LIMIT 1",
+        ] {
+            assert!(crate::cypher::parse(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_compile_range_filter() {
+        let query =
+            crate::cypher::parse("MATCH (t:Tile) WHERE t.x BETWEEN 10 AND 20 RETURN t LIMIT 5")
+                .unwrap();
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![
+                Opcode::SetCurrentFromAllNodes,
+                Opcode::TraverseOut(TraverseFilter {
+                    where_node_labels: vec!["Tile".to_string()],
+                    where_edge_labels: Vec::new(),
+                    where_not_node_labels: Vec::new(),
+                    where_not_edge_labels: Vec::new(),
+                }),
+                Opcode::FilterRange {
+                    key: "x".to_string(),
+                    min: 10,
+                    max: 20,
+                },
+                Opcode::SetLimit(5),
+                Opcode::SaveResults,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_path_sum() {
+        let query = crate::cypher::parse(
+            "MATCH (a:User)-[r:OWES*1..3]->(b) WHERE a.id = 4 RETURN SUM(r.amount) LIMIT 1",
+        )
+        .unwrap();
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![
+                Opcode::SetCurrentFromIds(vec![4]),
+                Opcode::SetLimit(1),
+                Opcode::SumPaths {
+                    filter: TraverseFilter {
+                        where_node_labels: Vec::new(),
+                        where_edge_labels: vec!["OWES".to_string()],
+                        where_not_node_labels: Vec::new(),
+                        where_not_edge_labels: Vec::new(),
+                    },
+                    key: "amount".to_string(),
+                    min_hops: 1,
+                    max_hops: 3,
+                },
+                Opcode::SaveResults,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_call() {
+        let query = crate::cypher::parse("CALL delegation.resolve(4, 2)").unwrap();
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![
+                Opcode::SetCurrentFromIds(vec![4, 2]),
+                Opcode::ResolveDelegates {
+                    label: "DELEGATES_TO".to_string(),
+                    max_hops: MAX_DELEGATION_HOPS,
+                },
+                Opcode::SaveResults,
+                Opcode::Project(Projection::Delegates),
+            ]
+        );
+
+        let query = crate::cypher::parse("CALL cycle.find('PLEDGES')").unwrap();
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![Opcode::FindCycle("PLEDGES".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_compile_create_node_with_data() {
+        let query = crate::cypher::parse("CREATE (n:User {0x01ff})").unwrap();
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![Opcode::CreateNode {
+                label: "User".to_string(),
+                data: vec![0x01, 0xff],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_compile_create_node_with_id() {
+        let query = crate::cypher::parse("CREATE (n:User {id: 42}) TTL 5").unwrap();
+        assert_eq!(
+            compile_to_opcodes(query),
+            vec![
+                Opcode::SetTtl(5),
+                Opcode::SetNodeId(42),
+                Opcode::CreateNode {
+                    label: "User".to_string(),
+                    data: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_attribute_filter() {
+        let query =
+            crate::cypher::parse("MATCH (n:User) WHERE n.name = 'bob' RETURN n LIMIT 10").unwrap();
+        let opcodes = compile_to_opcodes(query);
+        assert_eq!(
+            opcodes[opcodes.len() - 3],
+            Opcode::FilterAttr {
+                key: "name".to_string(),
+                value: "bob".to_string(),
+            }
+        );
+        assert_eq!(opcodes[opcodes.len() - 2], Opcode::SetLimit(10));
+
+        // On the start node of a relationship it runs before traversing
+        let query = crate::cypher::parse(
+            "MATCH (a:User)-[:FOLLOWS]->(b) WHERE a.age = 30 RETURN b LIMIT 5",
+        )
+        .unwrap();
+        let opcodes = compile_to_opcodes(query);
+        assert_eq!(
+            opcodes[2],
+            Opcode::FilterAttr {
+                key: "age".to_string(),
+                value: "30".to_string(),
+            }
+        );
+        assert!(matches!(opcodes[opcodes.len() - 2], Opcode::TraverseOut(_)));
+    }
+
+    #[test]
+    fn test_compile_return_projection() {
+        let compile = |query: &str| compile_to_opcodes(crate::cypher::parse(query).unwrap());
+
+        for query in [
+            "MATCH (n:User) RETURN n LIMIT 5",
+            "MATCH (n:User) RETURN n.id LIMIT 5",
+        ] {
+            assert_eq!(
+                compile(query).last(),
+                Some(&Opcode::SaveResults),
+                "{}",
+                query
+            );
+        }
+        assert_eq!(
+            compile("MATCH (n:User) RETURN n.label LIMIT 5").last(),
+            Some(&Opcode::Project(Projection::Property("label".to_string())))
+        );
+        assert_eq!(
+            compile("MATCH (a)-[:KNOWS]->(b) RETURN * LIMIT 5").last(),
+            Some(&Opcode::Project(Projection::Records))
+        );
+    }
+
+    #[test]
+    fn test_compile_time_filter() {
+        let query =
+            crate::cypher::parse("MATCH (n:Post) WHERE n.created_at > 100 RETURN n LIMIT 10")
+                .unwrap();
+        let opcodes = compile_to_opcodes(query);
+        assert!(matches!(
+            opcodes[opcodes.len() - 3],
+            Opcode::FilterTime {
+                field: TimeField::CreatedAt,
+                op: CmpOp::Gt,
+                value: 100,
+            }
+        ));
+
+        // On the start node of a relationship the filter runs before traversing
+        let query = CypherQuery::Match {
+            match_pattern: MatchPattern::Relationship {
+                from: NodePattern {
+                    variable: "a".to_string(),
+                    label: Some("User".to_string()),
+                },
+                edge: EdgePattern {
+                    direction: EdgeDirection::Outgoing,
+                    label: Some("POSTED".to_string()),
+                },
+                to: NodePattern {
+                    variable: "p".to_string(),
+                    label: Some("Post".to_string()),
+                },
+            },
+            where_clause: Some(WhereClause::NodeTimeCmp {
+                variable: "a".to_string(),
+                field: TimeField::UpdatedAt,
+                op: CmpOp::Gt,
+                value: 100,
+            }),
+            return_clause: ReturnClause::NodeId {
+                variable: "p".to_string(),
+            },
+            limit: None,
+        };
+        let opcodes = compile_to_opcodes(query);
+        assert!(matches!(opcodes[2], Opcode::FilterTime { .. }));
+        assert!(matches!(opcodes[3], Opcode::TraverseOut(_)));
+    }
+}
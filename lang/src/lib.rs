@@ -0,0 +1,108 @@
+//! The query language of sol-micro-sql: the Cypher parser, the compiler to
+//! VM opcodes and the opcode types themselves.
+//!
+//! Without the `anchor` feature this crate only needs Borsh and
+//! `solana-pubkey`, so it also builds for `wasm32-unknown-unknown` and lets
+//! frontends check queries before sending them.
+//!
+//! With the `serde` feature the AST (`CypherQuery`) and the opcodes also
+//! derive `Serialize` and `Deserialize`, so tools can snapshot compiled
+//! plans and diff them.
+
+pub mod cypher;
+pub mod format;
+pub mod lexer;
+pub mod ops;
+
+use cypher::{parse, ParseError};
+pub use format::format_query;
+use lexer::compile_to_opcodes;
+use ops::Opcode;
+
+/// Serialization derives: Anchor's inside the program, whose IDL lists
+/// these types, and plain Borsh everywhere else.
+mod prelude {
+    #[cfg(feature = "anchor")]
+    pub use anchor_lang::prelude::{borsh, AnchorDeserialize, AnchorSerialize, Pubkey};
+    #[cfg(not(feature = "anchor"))]
+    pub use borsh::{
+        self, BorshDeserialize as AnchorDeserialize, BorshSerialize as AnchorSerialize,
+    };
+    #[cfg(not(feature = "anchor"))]
+    pub use solana_pubkey::Pubkey;
+}
+
+/// Upper bound on query text per instruction.
+pub const MAX_QUERY_LEN: usize = 4096;
+/// Upper bound on compiled opcodes per instruction.
+pub const MAX_OPS: usize = 100;
+
+/// What a query compiles to, as far as it can be known without the graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryInfo {
+    /// Opcodes, counted against the graph's `max_ops`.
+    pub ops: usize,
+    /// Write opcodes, each of which is charged the graph's write fee.
+    pub writes: usize,
+}
+
+/// The program `query` compiles to, scoped to `partition` if given, exactly
+/// as `execute_query` would run it.
+pub fn explain(query: &str, partition: Option<u32>) -> Result<Vec<Opcode>, ParseError> {
+    let mut ops = compile_to_opcodes(parse(query)?);
+    if let Some(partition) = partition {
+        ops.insert(0, Opcode::SetPartition(partition));
+    }
+    Ok(ops)
+}
+
+/// Parses and compiles `query` as the program would, without running it.
+pub fn check_query(query: &str) -> Result<QueryInfo, ParseError> {
+    let ops = compile_to_opcodes(parse(query)?);
+    Ok(QueryInfo {
+        ops: ops.len(),
+        writes: ops.iter().filter(|op| op.is_write()).count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_query() {
+        assert_eq!(
+            check_query("MATCH (n:User) RETURN n LIMIT 10").unwrap(),
+            QueryInfo { ops: 4, writes: 0 }
+        );
+        assert_eq!(
+            check_query("CREATE (n:Session) TTL 5").unwrap(),
+            QueryInfo { ops: 2, writes: 1 }
+        );
+        assert!(check_query("MATCH (n:User) RETURN n").is_err());
+    }
+
+    #[test]
+    fn test_explain_scopes_partition() {
+        let ops = explain("MATCH (n:User) RETURN n LIMIT 10", Some(3)).unwrap();
+        assert!(matches!(ops[0], Opcode::SetPartition(3)));
+        assert!(matches!(ops[1], Opcode::SetCurrentFromAllNodes));
+        assert_eq!(ops.len(), 5);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_plan_round_trips_through_json() {
+        let query = parse("MATCH (n:User) WHERE n.created_at > 5 RETURN n LIMIT 3").unwrap();
+        let json = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            serde_json::from_str::<cypher::CypherQuery>(&json).unwrap(),
+            query
+        );
+
+        let ops = compile_to_opcodes(query);
+        let json = serde_json::to_value(&ops).unwrap();
+        assert_eq!(json[0], "SetCurrentFromAllNodes");
+        assert_eq!(serde_json::from_value::<Vec<Opcode>>(json).unwrap(), ops);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::prelude::{borsh, AnchorDeserialize, AnchorSerialize, Pubkey};
+
+/// Node ids take 8 bytes, or 16 with the `wide-ids` feature. Like the
+/// label type, the choice fixes the account layout.
+#[cfg(not(feature = "wide-ids"))]
+pub type NodeId = u64;
+#[cfg(feature = "wide-ids")]
+pub type NodeId = u128;
+/// Bytes of a Borsh-encoded `NodeId`.
+pub const NODE_ID_LEN: usize = std::mem::size_of::<NodeId>();
+pub type PartitionId = u32;
+/// Registers `Bind` and `CreateEdgeBetween` address.
+pub const REGISTERS: usize = 2;
+/// Edge label `CALL delegation.resolve` follows.
+pub const DELEGATION_LABEL: &str = "DELEGATES_TO";
+/// Longest delegation chain `CALL delegation.resolve` follows.
+pub const MAX_DELEGATION_HOPS: u32 = 32;
+/// Longest variable-length path, as in `[r:OWES*1..8]`.
+pub const MAX_PATH_HOPS: u32 = 8;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraverseFilter {
+    pub where_node_labels: Vec<String>,
+    pub where_edge_labels: Vec<String>,
+    pub where_not_node_labels: Vec<String>,
+    pub where_not_edge_labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, AnchorSerialize, AnchorDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Opcode {
+    SetCurrentFromAllNodes,
+    SetCurrentFromIds(Vec<NodeId>),
+    TraverseOut(TraverseFilter),
+    /// Caps every node set a later opcode builds, the saved results and
+    /// the returned nodes at this many.
+    SetLimit(u32),
+    /// Scopes every following opcode to a single partition.
+    SetPartition(PartitionId),
+    SaveResults,
+    CreateNode {
+        label: String,
+        data: Vec<u8>,
+    },
+    CreateEdge {
+        from: NodeId,
+        to: NodeId,
+        label: String,
+    },
+    /// Nodes created by the following opcodes expire this many slots from
+    /// now.
+    SetTtl(u64),
+    /// Keeps the current nodes bound to this NFT mint.
+    FilterMint(Pubkey),
+    /// Keeps the current nodes whose timestamp `field` compares to `value`
+    /// under `op`.
+    FilterTime {
+        field: TimeField,
+        op: CmpOp,
+        value: i64,
+    },
+    /// Stores the one node in the current set, such as the node just
+    /// created, in a register for `CreateEdgeBetween`.
+    Bind(u8),
+    /// `CreateEdge` between the nodes bound to registers `from` and `to`,
+    /// for endpoints whose ids are only known once the program runs.
+    CreateEdgeBetween {
+        from: u8,
+        to: u8,
+        label: String,
+    },
+    /// The next `CreateNode` takes this id instead of the graph's next
+    /// one, and fails if a node already has it.
+    SetNodeId(NodeId),
+    /// Keeps the current nodes whose data holds attribute `key` equal to
+    /// `value`: the string itself, or an integer or account written out.
+    FilterAttr {
+        key: String,
+        value: String,
+    },
+    /// Returns this of each result node instead of its id.
+    Project(Projection),
+    /// Like `TraverseOut` one edge deep, but keeps only the neighbours
+    /// with a matching edge back, such as mutual follows. Remembers each
+    /// start node and neighbour pair for `Projection::Pairs`.
+    MutualOut(TraverseFilter),
+    /// Follows each current node's first `label` edge, and the next node's,
+    /// to a node without one: its effective delegate. A chain that loops,
+    /// or is still going after `max_hops` edges, resolves to none. Keeps
+    /// the current nodes and remembers each delegate for
+    /// `Projection::Delegates`.
+    ResolveDelegates {
+        label: String,
+        max_hops: u32,
+    },
+    /// Sums each current node's integer attribute `key`, 0 if it has
+    /// none, into the node at the end of its chain of `label` edges, found
+    /// as `ResolveDelegates` finds it; weight on a chain that resolves to
+    /// none is dropped. The chain ends become the current nodes, and
+    /// `Projection::Weights` returns their sums.
+    PropagateWeight {
+        label: String,
+        key: String,
+        max_hops: u32,
+    },
+    /// Returns the total, over every path of `min_hops` to `max_hops`
+    /// `filter` edges from a current node that visits no node twice and
+    /// ends on a node passing `filter`'s node labels, of the integer
+    /// attribute `key` of the edges on it (0 for an edge without one).
+    /// An edge on several paths counts once for each.
+    SumPaths {
+        filter: TraverseFilter,
+        key: String,
+        min_hops: u32,
+        max_hops: u32,
+    },
+    /// Looks for a cycle of edges labelled with this label between visible
+    /// nodes anywhere in scope, and returns one: each node followed by the
+    /// one its edge leads to, the last leading back to the first. Empty if
+    /// there is none.
+    FindCycle(String),
+    /// Fails the program with `CycleDetected` if `FindCycle` would find a
+    /// cycle, so a write that closes one is rolled back. Graphs that must
+    /// stay acyclic list the label with `set_acyclic_labels` instead, which
+    /// every write path enforces.
+    RejectCycle(String),
+    /// Keeps the current nodes whose data holds integer attribute `key`
+    /// between `min` and `max` inclusive, such as an x or y coordinate
+    /// within a map region. One per axis bounds a box.
+    FilterRange {
+        key: String,
+        min: i64,
+        max: i64,
+    },
+    /// Like `TraverseOut` one edge deep to the single node `to`: keeps it
+    /// if it passes `filter`'s node labels and a current node has an edge
+    /// to it with one of `filter`'s edge labels, else empties the set.
+    /// Each label is a lookup in the edge key index, not an adjacency scan.
+    ProbeEdgeTo {
+        to: NodeId,
+        filter: TraverseFilter,
+    },
+}
+
+/// What a query returns of each node, from its RETURN clause.
+#[derive(Debug, Clone, PartialEq, AnchorSerialize, AnchorDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    /// `RETURN n.label`, `n.created_at` and the like, or an attribute in
+    /// the node's data.
+    Property(String),
+    /// `RETURN *`: the whole node.
+    Records,
+    /// `RETURN a, b` of a mutual pattern: each pair `MutualOut` found
+    /// whose second node is in the result.
+    Pairs,
+    /// `CALL delegation.resolve`: each result node with the delegate
+    /// `ResolveDelegates` found for it.
+    Delegates,
+    /// Each result node with the weight `PropagateWeight` summed into it.
+    Weights,
+}
+
+/// Node timestamps a query can filter on.
+#[derive(Debug, Clone, Copy, PartialEq, AnchorSerialize, AnchorDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeField {
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, AnchorSerialize, AnchorDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CmpOp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    pub fn holds(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl Opcode {
+    /// Whether this opcode mutates the graph.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Opcode::CreateNode { .. }
+                | Opcode::CreateEdge { .. }
+                | Opcode::CreateEdgeBetween { .. }
+        )
+    }
+
+    /// Whether this opcode only sets up the writes after it: a node id,
+    /// a register binding or a TTL.
+    pub fn is_write_setup(&self) -> bool {
+        matches!(
+            self,
+            Opcode::SetNodeId(_) | Opcode::Bind(_) | Opcode::SetTtl(_)
+        )
+    }
+
+    /// Whether this opcode reads node data, which structure-only decodes
+    /// of a graph leave empty.
+    pub fn reads_node_data(&self) -> bool {
+        matches!(
+            self,
+            Opcode::FilterAttr { .. }
+                | Opcode::FilterRange { .. }
+                | Opcode::Project(_)
+                | Opcode::PropagateWeight { .. }
+        )
+    }
+}
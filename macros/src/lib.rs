@@ -0,0 +1,76 @@
+//! Queries checked when the Rust code using them compiles: a malformed
+//! query, or one over the program's query length or opcode limits, is a
+//! compile error instead of a failed transaction.
+//!
+//! ```
+//! use sol_micro_sql_macros::{cypher, cypher_ops};
+//!
+//! const FRIENDS: &str = cypher!("MATCH (n:User) RETURN n LIMIT 10");
+//! const CREATE_SESSION: &[u8] = cypher_ops!("CREATE (n:Session) TTL 5");
+//! ```
+//!
+//! ```compile_fail
+//! # use sol_micro_sql_macros::cypher;
+//! const BROKEN: &str = cypher!("MATCH (n:User) RETURN n");
+//! ```
+//!
+//! A graph's config may set lower limits than the compile-time caps
+//! checked here.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use sol_micro_sql_lang::cypher::parse;
+use sol_micro_sql_lang::lexer::compile_to_opcodes;
+use sol_micro_sql_lang::ops::Opcode;
+use sol_micro_sql_lang::{MAX_OPS, MAX_QUERY_LEN};
+use syn::{parse_macro_input, LitStr};
+
+/// Compiles `query` as the program would, with the failure as a message.
+fn compile(query: &str) -> Result<Vec<Opcode>, String> {
+    if query.len() > MAX_QUERY_LEN {
+        return Err(format!(
+            "query is {} bytes, over the limit of {}",
+            query.len(),
+            MAX_QUERY_LEN
+        ));
+    }
+    let ops = compile_to_opcodes(parse(query).map_err(|e| format!("invalid query: {:?}", e))?);
+    if ops.len() > MAX_OPS {
+        return Err(format!(
+            "query compiles to {} opcodes, over the limit of {}",
+            ops.len(),
+            MAX_OPS
+        ));
+    }
+    Ok(ops)
+}
+
+/// The query text as a `&'static str`, once it has been checked to parse
+/// and fit the program's limits. Pass it to `execute_query`.
+#[proc_macro]
+pub fn cypher(input: TokenStream) -> TokenStream {
+    let query = parse_macro_input!(input as LitStr);
+    match compile(&query.value()) {
+        Ok(_) => quote!(#query).into(),
+        Err(message) => syn::Error::new(query.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// The opcodes the query compiles to, Borsh-encoded as a `&'static [u8]`:
+/// the `ops` argument of `execute_opcodes` exactly as it goes on the wire,
+/// so the program skips parsing.
+#[proc_macro]
+pub fn cypher_ops(input: TokenStream) -> TokenStream {
+    let query = parse_macro_input!(input as LitStr);
+    match compile(&query.value()) {
+        Ok(ops) => {
+            let bytes = borsh::to_vec(&ops).expect("opcodes serialize");
+            quote!(&[#(#bytes),*]).into()
+        }
+        Err(message) => syn::Error::new(query.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
@@ -0,0 +1,127 @@
+use crate::graph::NodeId;
+use anchor_lang::prelude::*;
+
+/// Attribute keys one graph's oracle may be allowed to write.
+pub const MAX_ORACLE_KEYS: usize = 8;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum AttrValue {
+    Int(i64),
+    Str(String),
+    /// A reference to an on-chain account, checked against the account
+    /// itself when written. `owner`, when set, is the program that owned it.
+    Account {
+        address: Pubkey,
+        owner: Option<Pubkey>,
+    },
+}
+
+/// One named value in a node's data. Node data that is a Borsh-encoded
+/// `Vec<Attribute>` (or empty) can be read and updated per attribute.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub key: String,
+    pub value: AttrValue,
+}
+
+/// One oracle-pushed value.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct AttrUpdate {
+    pub node_id: NodeId,
+    pub key: String,
+    pub value: i64,
+}
+
+/// The attributes in `data`, or `None` if it is not attribute-encoded.
+pub fn decode_attributes(data: &[u8]) -> Option<Vec<Attribute>> {
+    if data.is_empty() {
+        return Some(Vec::new());
+    }
+    Vec::<Attribute>::try_from_slice(data).ok()
+}
+
+/// Whether `data` holds attribute `key` and it reads as `value`: a string
+/// equal to it, or an integer or account address that prints as it. Data
+/// that is not attribute-encoded holds no attributes.
+pub fn attribute_equals(data: &[u8], key: &str, value: &str) -> bool {
+    attribute_text(data, key).is_some_and(|text| text == value)
+}
+
+/// Integer attribute `key` in `data`, if there is one.
+pub fn attribute_int(data: &[u8], key: &str) -> Option<i64> {
+    let attribute = decode_attributes(data)?
+        .into_iter()
+        .find(|a| a.key == key)?;
+    match attribute.value {
+        AttrValue::Int(i) => Some(i),
+        _ => None,
+    }
+}
+
+/// Attribute `key` in `data` written out: a string as is, an integer or
+/// account address printed. `None` if there is no such attribute.
+pub fn attribute_text(data: &[u8], key: &str) -> Option<String> {
+    let attribute = decode_attributes(data)?
+        .into_iter()
+        .find(|a| a.key == key)?;
+    Some(match attribute.value {
+        AttrValue::Str(s) => s,
+        AttrValue::Int(i) => i.to_string(),
+        AttrValue::Account { address, .. } => address.to_string(),
+    })
+}
+
+/// `data` with `key` set to `value`, or `None` if it is not
+/// attribute-encoded.
+pub fn set_attribute(data: &[u8], key: &str, value: AttrValue) -> Option<Vec<u8>> {
+    let mut attributes = decode_attributes(data)?;
+    match attributes.iter_mut().find(|a| a.key == key) {
+        Some(attribute) => attribute.value = value,
+        None => attributes.push(Attribute {
+            key: key.to_string(),
+            value,
+        }),
+    }
+    let mut encoded = Vec::new();
+    // Writing into a Vec cannot fail
+    attributes.serialize(&mut encoded).unwrap();
+    Some(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_attribute_upserts() {
+        let data = set_attribute(&[], "price", AttrValue::Int(10)).unwrap();
+        let data = set_attribute(&data, "score", AttrValue::Int(3)).unwrap();
+        let data = set_attribute(&data, "price", AttrValue::Int(12)).unwrap();
+
+        let attributes = decode_attributes(&data).unwrap();
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes[0].value, AttrValue::Int(12));
+        assert_eq!(attributes[1].key, "score");
+
+        let address = Pubkey::new_unique();
+        let data = set_attribute(
+            &data,
+            "vault",
+            AttrValue::Account {
+                address,
+                owner: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            decode_attributes(&data).unwrap()[2].value,
+            AttrValue::Account {
+                address,
+                owner: None
+            }
+        );
+
+        // Opaque blobs are left alone
+        assert!(set_attribute(&[0xde, 0xad], "price", AttrValue::Int(1)).is_none());
+    }
+}
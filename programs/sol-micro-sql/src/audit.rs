@@ -0,0 +1,130 @@
+use crate::graph::{NodeId, NODE_ID_LEN};
+use anchor_lang::prelude::*;
+
+pub const AUDIT_LOG_SEED: &[u8] = b"audit_log";
+/// Entries kept before the oldest ones are overwritten.
+pub const AUDIT_LOG_CAPACITY: usize = 64;
+
+/// Kind of write an audit entry records.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AuditOp {
+    Query,
+    Batch,
+    Opcodes,
+    Proposal,
+    AddNode,
+    AddEdge,
+    UpdateNodeData,
+    DeleteNode,
+    DeleteByLabel,
+    OraclePush,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub signer: Pubkey,
+    pub slot: u64,
+    pub op: AuditOp,
+    /// Write operations applied.
+    pub writes: u32,
+    /// Node the write targeted, or 0 when it has no single target.
+    pub target: NodeId,
+    /// Graph version once the write was applied.
+    pub version: u64,
+}
+
+impl AuditEntry {
+    pub const SERIALIZED_SIZE: usize = 32 + 8 + 1 + 4 + NODE_ID_LEN + 8;
+}
+
+/// Append-only ring buffer of the writes made to one graph. Once enabled,
+/// every write instruction must pass it and appends an entry.
+#[account]
+pub struct AuditLog {
+    pub graph: Pubkey,
+    /// Entries ever recorded; the newest lives at `(total - 1) % capacity`.
+    pub total: u64,
+    pub entries: Vec<AuditEntry>,
+    pub bump: u8,
+}
+
+impl AuditLog {
+    pub const SPACE: usize = 8 + 32 + 8 + 4 + AUDIT_LOG_CAPACITY * AuditEntry::SERIALIZED_SIZE + 1;
+
+    /// Appends `entry`, overwriting the oldest one once the log is full.
+    pub fn record(&mut self, entry: AuditEntry) {
+        if self.entries.len() < AUDIT_LOG_CAPACITY {
+            self.entries.push(entry);
+        } else {
+            let slot = (self.total % AUDIT_LOG_CAPACITY as u64) as usize;
+            self.entries[slot] = entry;
+        }
+        self.total += 1;
+    }
+
+    /// Up to `limit` entries, newest first, after skipping the `skip` newest.
+    pub fn recent(&self, skip: usize, limit: usize) -> Vec<AuditEntry> {
+        let len = self.entries.len();
+        (skip..len.min(skip.saturating_add(limit)))
+            .map(|i| {
+                let index = (self.total - 1 - i as u64) % AUDIT_LOG_CAPACITY as u64;
+                self.entries[index as usize]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(version: u64) -> AuditEntry {
+        AuditEntry {
+            signer: Pubkey::default(),
+            slot: version,
+            op: AuditOp::Query,
+            writes: 1,
+            target: 0,
+            version,
+        }
+    }
+
+    #[test]
+    fn test_audit_log_wraps() {
+        let mut log = AuditLog {
+            graph: Pubkey::new_unique(),
+            total: 0,
+            entries: Vec::new(),
+            bump: 255,
+        };
+        for version in 1..=AUDIT_LOG_CAPACITY as u64 + 3 {
+            log.record(entry(version));
+        }
+
+        assert_eq!(log.entries.len(), AUDIT_LOG_CAPACITY);
+        let newest: Vec<_> = log.recent(0, 3).iter().map(|e| e.version).collect();
+        assert_eq!(newest, vec![67, 66, 65]);
+        let oldest: Vec<_> = log
+            .recent(AUDIT_LOG_CAPACITY - 2, 10)
+            .iter()
+            .map(|e| e.version)
+            .collect();
+        assert_eq!(oldest, vec![5, 4]);
+    }
+
+    #[test]
+    fn test_audit_log_space_fits_capacity() {
+        let mut log = AuditLog {
+            graph: Pubkey::new_unique(),
+            total: 0,
+            entries: Vec::new(),
+            bump: 255,
+        };
+        for version in 0..AUDIT_LOG_CAPACITY as u64 {
+            log.record(entry(version));
+        }
+        let mut buf = Vec::new();
+        log.try_serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), AuditLog::SPACE);
+    }
+}
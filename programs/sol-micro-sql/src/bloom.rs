@@ -0,0 +1,99 @@
+use crate::graph::NodeId;
+use anchor_lang::prelude::*;
+
+pub const BLOOM_BYTES: usize = 64;
+const BLOOM_BITS: u64 = (BLOOM_BYTES * 8) as u64;
+const BLOOM_HASHES: u64 = 3;
+
+/// Fixed-size bloom filter over node ids. A negative answer is exact, a
+/// positive one still needs a scan to confirm.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NodeBloom {
+    pub bits: [u8; BLOOM_BYTES],
+}
+
+impl Default for NodeBloom {
+    fn default() -> Self {
+        Self {
+            bits: [0; BLOOM_BYTES],
+        }
+    }
+}
+
+impl NodeBloom {
+    pub fn insert(&mut self, id: NodeId) {
+        for seed in 0..BLOOM_HASHES {
+            let bit = bit_position(id, seed);
+            self.bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+    }
+
+    pub fn might_contain(&self, id: NodeId) -> bool {
+        (0..BLOOM_HASHES).all(|seed| {
+            let bit = bit_position(id, seed);
+            self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.bits = [0; BLOOM_BYTES];
+    }
+}
+
+fn bit_position(id: NodeId, seed: u64) -> u64 {
+    // splitmix64 over the folded id, salted per hash function
+    let folded = id.to_le_bytes().chunks(8).fold(0, |x, word| {
+        x ^ u64::from_le_bytes(word.try_into().unwrap())
+    });
+    let mut x = folded ^ seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    x % BLOOM_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inserted_ids_are_found() {
+        let mut bloom = NodeBloom::default();
+        for id in 0..50 {
+            bloom.insert(id);
+        }
+
+        for id in 0..50 {
+            assert!(bloom.might_contain(id));
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_rejects_everything() {
+        let bloom = NodeBloom::default();
+
+        assert!(!bloom.might_contain(0));
+        assert!(!bloom.might_contain(42));
+        assert!(!bloom.might_contain(NodeId::MAX));
+    }
+
+    #[test]
+    fn test_sparse_filter_rejects_most_missing_ids() {
+        let mut bloom = NodeBloom::default();
+        for id in 1..=5 {
+            bloom.insert(id);
+        }
+
+        let false_positives = (1000..2000).filter(|id| bloom.might_contain(*id)).count();
+        assert!(false_positives < 50);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut bloom = NodeBloom::default();
+        bloom.insert(7);
+        bloom.clear();
+
+        assert!(!bloom.might_contain(7));
+    }
+}
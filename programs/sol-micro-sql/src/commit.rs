@@ -0,0 +1,49 @@
+use anchor_lang::prelude::*;
+use solana_sha256_hasher::hashv;
+
+pub const COMMIT_SEED: &[u8] = b"commit";
+
+/// A hidden write, committed as a hash and revealed in a later slot, so a
+/// competing writer cannot see and front-run it.
+#[account]
+pub struct WriteCommitment {
+    pub committer: Pubkey,
+    /// `commitment_hash` of the query that will be revealed.
+    pub hash: [u8; 32],
+    /// Slot of the commit; the reveal must land in a later one.
+    pub slot: u64,
+    pub bump: u8,
+}
+
+impl WriteCommitment {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 1;
+}
+
+/// Hash binding a query to its salt and committer, so a commitment cannot
+/// be revealed by anyone else.
+pub fn commitment_hash(query: &str, salt: &[u8; 32], committer: &Pubkey) -> [u8; 32] {
+    hashv(&[query.as_bytes(), salt, committer.as_ref()]).to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commitment_hash_binds_inputs() {
+        let committer = Pubkey::new_unique();
+        let salt = [7u8; 32];
+        let hash = commitment_hash("CREATE (n:Claim)", &salt, &committer);
+
+        assert_eq!(hash, commitment_hash("CREATE (n:Claim)", &salt, &committer));
+        assert_ne!(hash, commitment_hash("CREATE (n:Other)", &salt, &committer));
+        assert_ne!(
+            hash,
+            commitment_hash("CREATE (n:Claim)", &[8u8; 32], &committer)
+        );
+        assert_ne!(
+            hash,
+            commitment_hash("CREATE (n:Claim)", &salt, &Pubkey::new_unique())
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use crate::graph::{Edge, GraphStore, NodeId};
+use anchor_lang::prelude::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct CompactionProgress {
+    pub removed_nodes: u64,
+    pub removed_edges: u64,
+    /// Node slot the next pass starts from.
+    pub cursor: u32,
+    /// `true` once the pass reached the end of node storage.
+    pub done: bool,
+}
+
+/// Outcome of one `delete_label_batch` pass.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct LabelDeletion {
+    pub removed_nodes: u64,
+    pub removed_edges: u64,
+    /// Node slot to pass as `cursor` next, or `None` once the scan is done.
+    pub next_cursor: Option<u32>,
+}
+
+/// Entries physically removed by one `collect_garbage` pass.
+#[derive(Default)]
+pub struct Reclaimed {
+    pub nodes: Vec<NodeId>,
+    pub edges: Vec<Edge>,
+}
+
+impl GraphStore {
+    /// Scans at most `max_slots` node slots from the stored cursor, physically
+    /// removing soft-deleted nodes, and nodes expired by `slot`, found there
+    /// (with their edges). Call repeatedly until `done` to compact the whole
+    /// graph.
+    pub fn compact(&mut self, max_slots: usize, slot: u64) -> Option<CompactionProgress> {
+        self.collect_garbage(max_slots, slot)
+            .map(|(progress, _)| progress)
+    }
+
+    /// Physically removes nodes labelled `label` (and their edges) among
+    /// at most `max_slots` node slots from `cursor`. Protected nodes are
+    /// kept. Call again with `next_cursor` until it is `None`.
+    pub fn delete_label_batch(
+        &mut self,
+        label: &str,
+        cursor: usize,
+        max_slots: usize,
+    ) -> Option<LabelDeletion> {
+        let start = cursor.min(self.nodes.len());
+        let end = start.saturating_add(max_slots).min(self.nodes.len());
+
+        let ids: Vec<NodeId> = self.nodes[start..end]
+            .iter()
+            .filter(|n| n.label == label)
+            .map(|n| n.id)
+            .collect();
+        let (removed_nodes, removed_edges) = self.remove_nodes(&ids)?;
+
+        // Removed slots shift the remaining nodes down
+        let next = end - removed_nodes as usize;
+        let next_cursor = (next < self.nodes.len()).then_some(next as u32);
+        if next_cursor.is_none() {
+            self.rebuild_node_bloom();
+        }
+        Some(LabelDeletion {
+            removed_nodes,
+            removed_edges,
+            next_cursor,
+        })
+    }
+
+    /// Same pass as `compact`, also returning every node and edge it removed.
+    pub fn collect_garbage(
+        &mut self,
+        max_slots: usize,
+        slot: u64,
+    ) -> Option<(CompactionProgress, Reclaimed)> {
+        let start = (self.compaction_cursor as usize).min(self.nodes.len());
+        let end = start.saturating_add(max_slots).min(self.nodes.len());
+
+        // Protected tombstones stay, as in `remove_nodes`
+        let tombstones: Vec<NodeId> = self.nodes[start..end]
+            .iter()
+            .filter(|n| (n.is_deleted || n.is_expired(slot)) && !n.is_protected)
+            .map(|n| n.id)
+            .collect();
+        let edges: Vec<Edge> = self
+            .edges
+            .iter()
+            .filter(|e| tombstones.contains(&e.from) || tombstones.contains(&e.to))
+            .cloned()
+            .collect();
+        let (removed_nodes, removed_edges) = self.remove_nodes(&tombstones)?;
+
+        // Removed slots shift the remaining nodes down
+        let next = end - removed_nodes as usize;
+        let done = next >= self.nodes.len();
+        self.compaction_cursor = if done { 0 } else { next as u32 };
+        // Passes leave removed ids in the bloom; clear them once per scan
+        if done {
+            self.rebuild_node_bloom();
+        }
+
+        let progress = CompactionProgress {
+            removed_nodes,
+            removed_edges,
+            cursor: self.compaction_cursor,
+            done,
+        };
+        Some((
+            progress,
+            Reclaimed {
+                nodes: tombstones,
+                edges,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{assert_indexes_consistent, GraphBuilder};
+
+    fn graph_with_tombstones() -> GraphStore {
+        GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .edge(1, 2, "FOLLOWS")
+            .edge(2, 3, "FOLLOWS")
+            .edge(4, 5, "FOLLOWS")
+            .edge(5, 6, "FOLLOWS")
+            .edge(6, 1, "FOLLOWS")
+            .deleted(2)
+            .deleted(5)
+            .build()
+    }
+
+    #[test]
+    fn test_compact_in_one_pass() {
+        let mut graph = graph_with_tombstones();
+
+        let progress = graph.compact(100, 0).unwrap();
+
+        assert_eq!(progress.removed_nodes, 2);
+        assert_eq!(progress.removed_edges, 4);
+        assert!(progress.done);
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.edges.len(), 1);
+        assert!(graph.has_edge(6, 1, "FOLLOWS"));
+    }
+
+    #[test]
+    fn test_compact_resumes_from_cursor() {
+        let mut graph = graph_with_tombstones();
+
+        let first = graph.compact(3, 0).unwrap();
+        assert_eq!(first.removed_nodes, 1);
+        assert!(!first.done);
+        assert_eq!(first.cursor, 2);
+        assert_indexes_consistent(&graph);
+
+        let second = graph.compact(3, 0).unwrap();
+        assert_eq!(second.removed_nodes, 1);
+        assert!(second.done);
+        assert_eq!(second.cursor, 0);
+
+        let ids: Vec<_> = graph.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![1, 3, 4, 6]);
+        assert_indexes_consistent(&graph);
+    }
+
+    #[test]
+    fn test_collect_garbage_reports_reclaimed() {
+        let mut graph = graph_with_tombstones();
+        graph.set_node_protected(5, true);
+
+        let (progress, reclaimed) = graph.collect_garbage(100, 0).unwrap();
+
+        assert_eq!(reclaimed.nodes, vec![2]);
+        let pairs: Vec<_> = reclaimed.edges.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(pairs, vec![(1, 2), (2, 3)]);
+        assert_eq!(progress.removed_nodes, 1);
+        assert_eq!(progress.removed_edges, 2);
+        assert!(graph.get_node_by_id(5).is_some());
+    }
+
+    #[test]
+    fn test_collect_garbage_reclaims_expired() {
+        let mut graph = graph_with_tombstones();
+        for node in graph.nodes.iter_mut() {
+            node.expires_slot = match node.id {
+                3 => 50,
+                4 => 200,
+                _ => 0,
+            };
+        }
+
+        // Node 4 has not expired yet at slot 100
+        let (_, reclaimed) = graph.collect_garbage(100, 100).unwrap();
+        assert_eq!(reclaimed.nodes, vec![2, 3, 5]);
+        assert!(graph.get_node_by_id(4).is_some());
+
+        let (_, reclaimed) = graph.collect_garbage(100, 200).unwrap();
+        assert_eq!(reclaimed.nodes, vec![4]);
+    }
+
+    #[test]
+    fn test_delete_label_batch() {
+        let mut graph = graph_with_tombstones();
+        for id in [3, 4] {
+            graph.nodes.iter_mut().find(|n| n.id == id).unwrap().label = "Temp".into();
+        }
+        graph.set_node_protected(4, true);
+
+        let first = graph.delete_label_batch("Temp", 0, 3).unwrap();
+        assert_eq!(first.removed_nodes, 1);
+        assert_eq!(first.removed_edges, 1);
+        assert_eq!(first.next_cursor, Some(2));
+
+        let second = graph.delete_label_batch("Temp", 2, 10).unwrap();
+        assert_eq!(second.removed_nodes, 0);
+        assert_eq!(second.next_cursor, None);
+
+        assert!(graph.get_node_by_id(3).is_none());
+        assert!(graph.get_node_by_id(4).is_some());
+    }
+}
@@ -0,0 +1,366 @@
+use anchor_lang::prelude::*;
+use solana_sha256_hasher::hashv;
+
+pub const COMPRESSED_NODES_SEED: &[u8] = b"compressed_nodes";
+/// Levels of the tree; it holds up to `2^COMPRESSED_TREE_DEPTH` nodes.
+pub const COMPRESSED_TREE_DEPTH: usize = 20;
+/// Recent changes kept so proofs against an older root can be fast-forwarded.
+pub const COMPRESSED_CHANGELOG_LEN: usize = 8;
+
+/// Hash committing to one compressed node's payload. The label is length
+/// prefixed so no (label, data) split collides with another.
+pub fn leaf_hash(index: u64, label: &str, data: &[u8]) -> [u8; 32] {
+    hashv(&[
+        &[0],
+        &index.to_le_bytes(),
+        &(label.len() as u32).to_le_bytes(),
+        label.as_bytes(),
+        data,
+    ])
+    .to_bytes()
+}
+
+fn parent_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hashv(&[&[1], left, right]).to_bytes()
+}
+
+/// Root of an empty subtree at each level, leaves first.
+fn empty_subtrees() -> [[u8; 32]; COMPRESSED_TREE_DEPTH + 1] {
+    let mut empty = [[0u8; 32]; COMPRESSED_TREE_DEPTH + 1];
+    for level in 1..=COMPRESSED_TREE_DEPTH {
+        empty[level] = parent_hash(&empty[level - 1], &empty[level - 1]);
+    }
+    empty
+}
+
+/// One change to the tree: the leaf it touched, the new nodes on that
+/// leaf's path (leaves first) and the root it produced.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ChangeLog {
+    pub index: u64,
+    pub path: [[u8; 32]; COMPRESSED_TREE_DEPTH],
+    pub root: [u8; 32],
+}
+
+impl ChangeLog {
+    pub const SERIALIZED_SIZE: usize = 8 + 32 * COMPRESSED_TREE_DEPTH + 32;
+}
+
+/// Node storage for write-heavy graphs: payloads live off-chain (in the
+/// `CompressedNodeAppended` events) and only the Merkle root is kept here.
+/// Reads verify a payload against the root with a proof.
+///
+/// Like a concurrent Merkle tree, the last `COMPRESSED_CHANGELOG_LEN`
+/// changes are kept, so a proof built against any of their roots is
+/// fast-forwarded instead of rejected when other writes land first.
+#[account]
+pub struct CompressedNodes {
+    pub graph: Pubkey,
+    pub root: [u8; 32],
+    pub leaf_count: u64,
+    /// Latest left-child node at each level, from which appends rebuild the
+    /// root without a proof.
+    pub filled: [[u8; 32]; COMPRESSED_TREE_DEPTH],
+    /// Changes ever recorded, counting the empty tree; the newest lives at
+    /// `(changes - 1) % COMPRESSED_CHANGELOG_LEN`.
+    pub changes: u64,
+    pub changelog: Vec<ChangeLog>,
+    pub bump: u8,
+}
+
+impl CompressedNodes {
+    pub const SPACE: usize = 8
+        + 32
+        + 32
+        + 8
+        + 32 * COMPRESSED_TREE_DEPTH
+        + 8
+        + 4
+        + COMPRESSED_CHANGELOG_LEN * ChangeLog::SERIALIZED_SIZE
+        + 1;
+
+    pub fn empty_root() -> [u8; 32] {
+        empty_subtrees()[COMPRESSED_TREE_DEPTH]
+    }
+
+    /// Resets the store to an empty tree.
+    pub fn reset(&mut self) {
+        self.root = Self::empty_root();
+        self.leaf_count = 0;
+        self.filled = Default::default();
+        self.changes = 0;
+        self.changelog = Vec::new();
+        self.record(ChangeLog {
+            index: 0,
+            path: empty_subtrees()[..COMPRESSED_TREE_DEPTH]
+                .try_into()
+                .unwrap(),
+            root: self.root,
+        });
+    }
+
+    /// Appends `leaf` and returns its index, or `None` once the tree is full.
+    /// Appends need no proof, and earlier proofs stay usable through the
+    /// changelog.
+    pub fn append(&mut self, leaf: [u8; 32]) -> Option<u64> {
+        let index = self.leaf_count;
+        if index >> COMPRESSED_TREE_DEPTH != 0 {
+            return None;
+        }
+        let mut path = [[0u8; 32]; COMPRESSED_TREE_DEPTH];
+        let mut node = leaf;
+        for (level, empty) in empty_subtrees()
+            .iter()
+            .take(COMPRESSED_TREE_DEPTH)
+            .enumerate()
+        {
+            path[level] = node;
+            if (index >> level) & 1 == 0 {
+                self.filled[level] = node;
+                node = parent_hash(&node, empty);
+            } else {
+                node = parent_hash(&self.filled[level], &node);
+            }
+        }
+        self.root = node;
+        self.leaf_count += 1;
+        self.record(ChangeLog {
+            index,
+            path,
+            root: node,
+        });
+        Some(index)
+    }
+
+    /// Whether `leaf` sits at `index` under the current root. `proof` holds
+    /// the sibling at each level, leaves first, and may be built against
+    /// any root still in the changelog.
+    pub fn verify(&self, index: u64, leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+        index < self.leaf_count && self.fast_forward(index, leaf, proof).is_some()
+    }
+
+    /// Replaces the leaf at `index`, proven by `proof` as in `verify`.
+    /// Returns whether the proof held.
+    pub fn replace(
+        &mut self,
+        index: u64,
+        old_leaf: [u8; 32],
+        new_leaf: [u8; 32],
+        proof: &[[u8; 32]],
+    ) -> bool {
+        if index >= self.leaf_count {
+            return false;
+        }
+        let Some(proof) = self.fast_forward(index, old_leaf, proof) else {
+            return false;
+        };
+        let Some(path) = self.path(index, new_leaf, &proof) else {
+            return false;
+        };
+
+        // Keep the append frontier in step when the leaf lies under it
+        let last = self.leaf_count - 1;
+        for (level, node) in path.nodes.iter().enumerate() {
+            if index >> level == (last >> level) & !1 {
+                self.filled[level] = *node;
+            }
+        }
+        self.root = path.root;
+        self.record(ChangeLog {
+            index,
+            path: path.nodes,
+            root: path.root,
+        });
+        true
+    }
+
+    fn record(&mut self, change: ChangeLog) {
+        if self.changelog.len() < COMPRESSED_CHANGELOG_LEN {
+            self.changelog.push(change);
+        } else {
+            let slot = (self.changes % COMPRESSED_CHANGELOG_LEN as u64) as usize;
+            self.changelog[slot] = change;
+        }
+        self.changes += 1;
+    }
+
+    /// Brings `proof` for `leaf` at `index` up to the current root. Finds the
+    /// logged root the proof was built against, then swaps in the sibling
+    /// from each later change. `None` if no logged root matches or `index`
+    /// itself changed since.
+    fn fast_forward(
+        &self,
+        index: u64,
+        leaf: [u8; 32],
+        proof: &[[u8; 32]],
+    ) -> Option<[[u8; 32]; COMPRESSED_TREE_DEPTH]> {
+        let root = self.path(index, leaf, proof)?.root;
+        let change_at =
+            |seq: u64| &self.changelog[(seq % COMPRESSED_CHANGELOG_LEN as u64) as usize];
+        let oldest = self.changes - self.changelog.len() as u64;
+        let from = (oldest..self.changes)
+            .rev()
+            .find(|&seq| change_at(seq).root == root)?;
+
+        let mut proof: [[u8; 32]; COMPRESSED_TREE_DEPTH] = proof.try_into().ok()?;
+        for seq in from + 1..self.changes {
+            let change = change_at(seq);
+            if change.index == index {
+                return None;
+            }
+            // The paths meet above the highest differing bit; below it the
+            // changed path's node is this leaf's sibling
+            let level = (63 - (change.index ^ index).leading_zeros()) as usize;
+            proof[level] = change.path[level];
+        }
+        Some(proof)
+    }
+
+    fn path(&self, index: u64, leaf: [u8; 32], proof: &[[u8; 32]]) -> Option<MerklePath> {
+        if proof.len() != COMPRESSED_TREE_DEPTH {
+            return None;
+        }
+        let mut nodes = [[0u8; 32]; COMPRESSED_TREE_DEPTH];
+        let mut node = leaf;
+        for (level, sibling) in proof.iter().enumerate() {
+            nodes[level] = node;
+            node = if (index >> level) & 1 == 0 {
+                parent_hash(&node, sibling)
+            } else {
+                parent_hash(sibling, &node)
+            };
+        }
+        Some(MerklePath { nodes, root: node })
+    }
+}
+
+/// Nodes from a leaf up to (not including) the root, and the root.
+struct MerklePath {
+    nodes: [[u8; 32]; COMPRESSED_TREE_DEPTH],
+    root: [u8; 32],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_tree() -> CompressedNodes {
+        let mut tree = CompressedNodes {
+            graph: Pubkey::new_unique(),
+            root: [0u8; 32],
+            leaf_count: 0,
+            filled: [[0u8; 32]; COMPRESSED_TREE_DEPTH],
+            changes: 0,
+            changelog: Vec::new(),
+            bump: 255,
+        };
+        tree.reset();
+        tree
+    }
+
+    /// Proof for `index` computed from every leaf, as an indexer would.
+    fn proof_for(leaves: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+        let mut level_nodes = leaves.to_vec();
+        let mut proof = Vec::new();
+        let mut index = index;
+        for empty in empty_subtrees().iter().take(COMPRESSED_TREE_DEPTH) {
+            proof.push(level_nodes.get(index ^ 1).copied().unwrap_or(*empty));
+            level_nodes = level_nodes
+                .chunks(2)
+                .map(|pair| parent_hash(&pair[0], pair.get(1).unwrap_or(empty)))
+                .collect();
+            index /= 2;
+        }
+        proof
+    }
+
+    #[test]
+    fn test_append_and_verify() {
+        let mut tree = empty_tree();
+        let leaves: Vec<_> = (0..5).map(|i| leaf_hash(i, "Post", &[i as u8])).collect();
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(tree.append(*leaf), Some(i as u64));
+        }
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert!(tree.verify(i as u64, *leaf, &proof_for(&leaves, i)));
+        }
+        assert!(!tree.verify(1, leaves[2], &proof_for(&leaves, 1)));
+        assert!(!tree.verify(5, [0; 32], &proof_for(&leaves, 5)));
+    }
+
+    #[test]
+    fn test_replace_keeps_appends_consistent() {
+        let mut tree = empty_tree();
+        let mut leaves: Vec<_> = (0..3).map(|i| leaf_hash(i, "Post", &[])).collect();
+        for leaf in &leaves {
+            tree.append(*leaf);
+        }
+
+        let updated = leaf_hash(2, "Post", &[9]);
+        let proof = proof_for(&leaves, 2);
+        assert!(!tree.replace(2, leaves[1], updated, &proof));
+        assert!(tree.replace(2, leaves[2], updated, &proof));
+        leaves[2] = updated;
+
+        // An append after the replace builds on the updated leaf
+        let next = leaf_hash(3, "Post", &[]);
+        tree.append(next);
+        leaves.push(next);
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert!(tree.verify(i as u64, *leaf, &proof_for(&leaves, i)));
+        }
+    }
+
+    #[test]
+    fn test_leaf_hash_separates_label_from_data() {
+        assert_ne!(leaf_hash(0, "ab", b"c"), leaf_hash(0, "a", b"bc"));
+        assert_ne!(leaf_hash(0, "", b"ab"), leaf_hash(0, "ab", b""));
+    }
+
+    #[test]
+    fn test_stale_proofs_fast_forward() {
+        let mut tree = empty_tree();
+        let mut leaves: Vec<_> = (0..4).map(|i| leaf_hash(i, "Post", &[])).collect();
+        for leaf in &leaves {
+            tree.append(*leaf);
+        }
+        let stale_0 = proof_for(&leaves, 0);
+        let stale_3 = proof_for(&leaves, 3);
+
+        // Another writer updates leaf 1 and appends leaf 4
+        let updated = leaf_hash(1, "Post", &[7]);
+        assert!(tree.replace(1, leaves[1], updated, &proof_for(&leaves, 1)));
+        leaves[1] = updated;
+        let next = leaf_hash(4, "Post", &[]);
+        tree.append(next);
+        leaves.push(next);
+
+        assert!(tree.verify(0, leaves[0], &stale_0));
+        let replaced = leaf_hash(3, "Post", &[8]);
+        assert!(tree.replace(3, leaves[3], replaced, &stale_3));
+        leaves[3] = replaced;
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert!(tree.verify(i as u64, *leaf, &proof_for(&leaves, i)));
+        }
+
+        // A proof whose own leaf changed since is rejected
+        assert!(!tree.replace(3, leaf_hash(3, "Post", &[]), replaced, &stale_3));
+    }
+
+    #[test]
+    fn test_proofs_older_than_changelog_are_rejected() {
+        let mut tree = empty_tree();
+        let mut leaves = vec![leaf_hash(0, "Post", &[])];
+        tree.append(leaves[0]);
+        let stale = proof_for(&leaves, 0);
+        for i in 1..=COMPRESSED_CHANGELOG_LEN as u64 {
+            let leaf = leaf_hash(i, "Post", &[]);
+            tree.append(leaf);
+            leaves.push(leaf);
+        }
+
+        assert!(!tree.verify(0, leaves[0], &stale));
+        assert!(tree.verify(0, leaves[0], &proof_for(&leaves, 0)));
+    }
+}
@@ -0,0 +1,72 @@
+use crate::vm::{Opcode, VmCheckpoint};
+use anchor_lang::prelude::*;
+
+pub const CURSOR_SEED: &[u8] = b"cursor";
+
+/// A read query run over several `resume_query` transactions, for queries
+/// that need more compute than one transaction has. Each step picks up
+/// from `checkpoint`; the graph must not change in between, so the steps
+/// together return what one uninterrupted run would.
+#[account]
+pub struct QueryCursor {
+    pub graph: Pubkey,
+    pub owner: Pubkey,
+    /// Graph version the query runs against; a write since makes the
+    /// cursor stale.
+    pub version: u64,
+    /// Slot node expiry is checked against in every step.
+    pub slot: u64,
+    pub ops: Vec<Opcode>,
+    pub checkpoint: VmCheckpoint,
+    pub bump: u8,
+}
+
+impl QueryCursor {
+    /// Space of a cursor with no program; `open_query_cursor` grows it.
+    pub const SPACE: usize =
+        8 + 32 + 32 + 8 + 8 + 4 + (4 + 4 + 4 + 1 + 1 + 1 + 4 + 4 + 4 + 1 + 1) + 1;
+
+    /// Account size the cursor needs as it is now.
+    pub fn space(&self) -> usize {
+        let mut encoded = Vec::new();
+        // Writing into a Vec cannot fail
+        self.serialize(&mut encoded).unwrap();
+        8 + encoded.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TraversalCheckpoint;
+
+    #[test]
+    fn test_space_matches_borsh() {
+        let mut cursor = QueryCursor {
+            graph: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            version: 3,
+            slot: 40,
+            ops: Vec::new(),
+            checkpoint: VmCheckpoint::default(),
+            bump: 255,
+        };
+        assert_eq!(cursor.space(), QueryCursor::SPACE);
+
+        cursor.ops = vec![Opcode::SetLimit(5), Opcode::SetCurrentFromAllNodes];
+        cursor.checkpoint = VmCheckpoint {
+            pc: 1,
+            current_set: vec![1, 2],
+            limit: Some(5),
+            traversal: Some(TraversalCheckpoint {
+                found: vec![3],
+                frontier: vec![0, 1],
+                visited: vec![0b11],
+            }),
+            ..VmCheckpoint::default()
+        };
+        let mut buf = Vec::new();
+        cursor.try_serialize(&mut buf).unwrap();
+        assert_eq!(cursor.space(), buf.len());
+    }
+}
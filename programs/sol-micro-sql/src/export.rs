@@ -0,0 +1,274 @@
+//! Off-chain exports of a graph for analysis tools: GraphML for Gephi,
+//! yEd or Neo4j's APOC importer, a plain JSON shape, and Graphviz DOT for
+//! quick looks at small graphs. Deleted nodes and the edges touching them
+//! are left out.
+
+use crate::graph::{GraphStore, Node};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn live_nodes(graph: &GraphStore) -> impl Iterator<Item = &Node> {
+    graph.nodes.iter().filter(|n| !n.is_deleted)
+}
+
+/// The graph as JSON:
+///
+/// ```json
+/// {
+///   "nodes": [{ "id": "1", "label": "User", "data": "0a0b", "partition": 0,
+///               "created_at": 1700000000, "updated_at": 1700000000,
+///               "expires_slot": 0, "mint": null }],
+///   "edges": [{ "from": "1", "to": "2", "label": "FOLLOWS", "data": "",
+///               "partition": 0, "created_at": 1700000000 }]
+/// }
+/// ```
+///
+/// Ids are decimal strings, since they do not fit a JSON number, and data
+/// is hex. `mint` is the base58 NFT mint or null.
+pub fn to_json(graph: &GraphStore) -> Value {
+    let nodes: Vec<Value> = live_nodes(graph)
+        .map(|n| {
+            json!({
+                "id": n.id.to_string(),
+                "label": n.label.as_str(),
+                "data": hex(&n.data),
+                "partition": n.partition,
+                "created_at": n.created_at,
+                "updated_at": n.updated_at,
+                "expires_slot": n.expires_slot,
+                "mint": n.mint.map(|m| m.to_string()),
+            })
+        })
+        .collect();
+    let live: HashSet<_> = live_nodes(graph).map(|n| n.id).collect();
+    let edges: Vec<Value> = graph
+        .edges
+        .iter()
+        .filter(|e| live.contains(&e.from) && live.contains(&e.to))
+        .map(|e| {
+            json!({
+                "from": e.from.to_string(),
+                "to": e.to.to_string(),
+                "label": e.label.as_str(),
+                "data": hex(&e.data),
+                "partition": e.partition,
+                "created_at": e.created_at,
+            })
+        })
+        .collect();
+    json!({ "nodes": nodes, "edges": edges })
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The graph as a directed GraphML document. Node and edge fields become
+/// `<data>` attributes with the same names as in `to_json`.
+pub fn to_graphml(graph: &GraphStore) -> String {
+    let mut out = String::new();
+    // Writing into a String cannot fail
+    let _ = writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    );
+    for (id, domain, kind) in [
+        ("label", "node", "string"),
+        ("data", "node", "string"),
+        ("partition", "node", "long"),
+        ("created_at", "node", "long"),
+        ("updated_at", "node", "long"),
+        ("mint", "node", "string"),
+        ("label", "edge", "string"),
+        ("data", "edge", "string"),
+        ("partition", "edge", "long"),
+        ("created_at", "edge", "long"),
+    ] {
+        let _ = writeln!(
+            out,
+            r#"  <key id="{domain}_{id}" for="{domain}" attr.name="{id}" attr.type="{kind}"/>"#
+        );
+    }
+    let _ = writeln!(
+        out,
+        r#"  <graph id="{}" edgedefault="directed">"#,
+        escape_xml(&graph.name)
+    );
+
+    let mut live = HashSet::new();
+    for node in live_nodes(graph) {
+        live.insert(node.id);
+        let _ = writeln!(out, r#"    <node id="n{}">"#, node.id);
+        let _ = writeln!(
+            out,
+            r#"      <data key="node_label">{}</data>"#,
+            escape_xml(&node.label)
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="node_data">{}</data>"#,
+            hex(&node.data)
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="node_partition">{}</data>"#,
+            node.partition
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="node_created_at">{}</data>"#,
+            node.created_at
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="node_updated_at">{}</data>"#,
+            node.updated_at
+        );
+        if let Some(mint) = node.mint {
+            let _ = writeln!(out, r#"      <data key="node_mint">{}</data>"#, mint);
+        }
+        let _ = writeln!(out, "    </node>");
+    }
+    for (index, edge) in graph.edges.iter().enumerate() {
+        if !live.contains(&edge.from) || !live.contains(&edge.to) {
+            continue;
+        }
+        let _ = writeln!(
+            out,
+            r#"    <edge id="e{}" source="n{}" target="n{}">"#,
+            index, edge.from, edge.to
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="edge_label">{}</data>"#,
+            escape_xml(&edge.label)
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="edge_data">{}</data>"#,
+            hex(&edge.data)
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="edge_partition">{}</data>"#,
+            edge.partition
+        );
+        let _ = writeln!(
+            out,
+            r#"      <data key="edge_created_at">{}</data>"#,
+            edge.created_at
+        );
+        let _ = writeln!(out, "    </edge>");
+    }
+    let _ = writeln!(out, "  </graph>");
+    let _ = writeln!(out, "</graphml>");
+    out
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The graph as a Graphviz digraph, e.g. for `dot -Tsvg`. Nodes show their
+/// id and label, edges their label. With `label` set only nodes with that
+/// label, and the edges between them, are drawn.
+pub fn to_dot(graph: &GraphStore, label: Option<&str>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph \"{}\" {{", escape_dot(&graph.name));
+    let mut shown = HashSet::new();
+    for node in live_nodes(graph).filter(|n| label.is_none_or(|l| n.label == l)) {
+        shown.insert(node.id);
+        let _ = writeln!(
+            out,
+            "  n{} [label=\"{}\\n{}\"];",
+            node.id,
+            node.id,
+            escape_dot(&node.label)
+        );
+    }
+    for edge in &graph.edges {
+        if shown.contains(&edge.from) && shown.contains(&edge.to) {
+            let _ = writeln!(
+                out,
+                "  n{} -> n{} [label=\"{}\"];",
+                edge.from,
+                edge.to,
+                escape_dot(&edge.label)
+            );
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::GraphBuilder;
+
+    fn export_graph() -> GraphStore {
+        let mut graph = GraphBuilder::new()
+            .node_with("User", vec![0xab], 0)
+            .node_with("User", vec![0xab], 0)
+            .node_with("Gone", vec![0xab], 0)
+            .deleted(3)
+            .edge(1, 2, "FOLLOWS")
+            .edge(1, 3, "FOLLOWS")
+            .build();
+        graph.name = "a<b".to_string();
+        graph
+    }
+
+    #[test]
+    fn test_to_json_skips_deleted() {
+        let graph = export_graph();
+        let value = to_json(&graph);
+        assert_eq!(value["nodes"].as_array().unwrap().len(), 2);
+        assert_eq!(value["nodes"][0]["data"], "ab");
+        assert_eq!(value["nodes"][0]["id"], "1");
+        assert_eq!(value["edges"].as_array().unwrap().len(), 1);
+        assert_eq!(value["edges"][0]["to"], "2");
+    }
+
+    #[test]
+    fn test_to_graphml() {
+        let graph = export_graph();
+        let xml = to_graphml(&graph);
+        assert!(xml.contains(r#"<graph id="a&lt;b" edgedefault="directed">"#));
+        assert_eq!(xml.matches("<node ").count(), 2);
+        assert_eq!(xml.matches("<edge ").count(), 1);
+        assert!(xml.contains(r#"<edge id="e0" source="n1" target="n2">"#));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut graph = export_graph();
+        graph.nodes[1].label = "Bot\"1".into();
+        let dot = to_dot(&graph, None);
+        assert!(dot.starts_with("digraph \"a<b\" {\n"));
+        assert!(dot.contains("  n2 [label=\"2\\nBot\\\"1\"];\n"));
+        assert!(dot.contains("  n1 -> n2 [label=\"FOLLOWS\"];\n"));
+        assert!(!dot.contains("n3"));
+
+        let dot = to_dot(&graph, Some("User"));
+        assert!(dot.contains("  n1 [label=\"1\\nUser\"];\n"));
+        assert!(!dot.contains("n2"));
+    }
+}
@@ -1,43 +1,819 @@
+use crate::bloom::{NodeBloom, BLOOM_BYTES};
+use crate::hooks::Mutation;
+use crate::roles::{NodeAcl, RoleGrant, MAX_MULTISIG_SIGNERS, MAX_ROLES};
 use anchor_lang::prelude::*;
-
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use anchor_lang::Discriminator;
+
+pub const GRAPH_STORE_SEED: &[u8] = b"graph_store";
+/// Prefix of per-wallet graphs, addressed as `[USER_GRAPH_SEED, user]`.
+pub const USER_GRAPH_SEED: &[u8] = b"user_graph";
+/// Bump whenever the `GraphStore` layout changes, and teach
+/// `migration::upgrade` to read the previous one.
+pub const GRAPH_LAYOUT_VERSION: u8 = 1;
+/// Names are used as a PDA seed, which is capped at 32 bytes.
+pub const MAX_GRAPH_NAME_LEN: usize = 32;
+
+// The same aliases as `sol_micro_sql_lang::ops`, repeated because the IDL
+// build only resolves aliases declared in this crate
+#[cfg(not(feature = "wide-ids"))]
+pub type NodeId = u64;
+#[cfg(feature = "wide-ids")]
 pub type NodeId = u128;
+pub type PartitionId = u32;
+#[cfg(not(feature = "fixed-labels"))]
+pub type Label = String;
+#[cfg(feature = "fixed-labels")]
+pub type Label = sol_micro_sql_types::graph::FixedLabel;
+
+pub use sol_micro_sql_lang::ops::TraverseFilter;
+pub use sol_micro_sql_types::graph::{to_label, Edge, Node, FIXED_LABEL_LEN, NODE_ID_LEN};
+const _: () = assert!(std::mem::size_of::<NodeId>() == NODE_ID_LEN);
+
+/// Hard caps on graph size, to bound scans and account growth.
+pub const MAX_NODES: usize = 1000;
+pub const MAX_EDGES: usize = 5000;
+/// Edge index or offset in the CSR adjacency. Two bytes hold every index
+/// up to `MAX_EDGES`, half of what `u32` entries took on dense graphs.
+pub type AdjacencyIndex = u16;
+const _: () = assert!(MAX_EDGES <= AdjacencyIndex::MAX as usize);
+/// Where `nodes` starts in the account: after the discriminator and the
+/// fixed-size fields ahead of it.
+const NODES_OFFSET: usize = 8 + 1 + 32 + 8 + 8 + NODE_ID_LEN + 8;
+/// Caps on per-record sizes.
+#[cfg(not(feature = "fixed-labels"))]
+pub const MAX_LABEL_LEN: usize = 64;
+#[cfg(feature = "fixed-labels")]
+pub const MAX_LABEL_LEN: usize = FIXED_LABEL_LEN;
+pub const MAX_NODE_DATA_LEN: usize = 1024;
+pub const MAX_EDGE_DATA_LEN: usize = 256;
+/// Cap on edge labels a graph may keep acyclic.
+pub const MAX_ACYCLIC_LABELS: usize = 8;
+/// Cap on nodes a single traversal may reach.
+pub const MAX_TRAVERSAL_NODES: usize = MAX_NODES;
+/// Cap on nodes one level of a traversal may hold.
+pub const MAX_FRONTIER: usize = MAX_NODES;
+/// Cap on a program's estimated cost; no program of `MAX_OPS` opcodes can
+/// exceed it, so a graph at the cap never rejects a query.
+pub const MAX_QUERY_COST: usize = crate::MAX_OPS * (MAX_NODES + MAX_EDGES);
+
+/// Per-graph limits. Admins may tune them up to the compile-time caps.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct GraphConfig {
+    pub max_query_len: u32,
+    pub max_ops: u32,
+    pub max_label_len: u32,
+    pub max_node_data_len: u32,
+    pub max_edge_data_len: u32,
+    /// Most nodes one `TraverseOut` may return before the query fails.
+    pub max_traversal_nodes: u32,
+    /// Highest `program_cost` a query may have; costlier ones are rejected
+    /// before they run.
+    pub max_query_cost: u32,
+    /// Most nodes one level of a `TraverseOut` may hold. A wider level
+    /// makes the traversal continue by iterative deepening, which keeps
+    /// only the current path instead of the whole level.
+    pub max_frontier: u32,
+    /// Most nodes and edges the graph may hold, deleted ones included.
+    pub max_nodes: u32,
+    pub max_edges: u32,
+}
 
-#[derive(Debug, Clone)]
-pub struct TraverseFilter {
-    pub where_node_labels: Vec<String>,
-    pub where_edge_labels: Vec<String>,
-    pub where_not_node_labels: Vec<String>,
-    pub where_not_edge_labels: Vec<String>,
+impl GraphConfig {
+    pub const SERIALIZED_SIZE: usize = 10 * 4;
+
+    /// Whether every limit is non-zero and within its compile-time cap.
+    pub fn is_valid(&self) -> bool {
+        let within = |value: u32, cap: usize| value > 0 && value as usize <= cap;
+        within(self.max_query_len, crate::MAX_QUERY_LEN)
+            && within(self.max_ops, crate::MAX_OPS)
+            && within(self.max_label_len, MAX_LABEL_LEN)
+            && within(self.max_node_data_len, MAX_NODE_DATA_LEN)
+            && within(self.max_edge_data_len, MAX_EDGE_DATA_LEN)
+            && within(self.max_traversal_nodes, MAX_TRAVERSAL_NODES)
+            && within(self.max_query_cost, MAX_QUERY_COST)
+            && within(self.max_frontier, MAX_FRONTIER)
+            && within(self.max_nodes, MAX_NODES)
+            && within(self.max_edges, MAX_EDGES)
+    }
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct Node {
-    pub id: NodeId,
-    pub label: String,
-    pub data: Vec<u8>,
-    pub outgoing_edge_indices: Vec<u32>,
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self {
+            max_query_len: crate::MAX_QUERY_LEN as u32,
+            max_ops: crate::MAX_OPS as u32,
+            max_label_len: MAX_LABEL_LEN as u32,
+            max_node_data_len: MAX_NODE_DATA_LEN as u32,
+            max_edge_data_len: MAX_EDGE_DATA_LEN as u32,
+            max_traversal_nodes: MAX_TRAVERSAL_NODES as u32,
+            max_query_cost: MAX_QUERY_COST as u32,
+            max_frontier: MAX_FRONTIER as u32,
+            max_nodes: MAX_NODES as u32,
+            max_edges: MAX_EDGES as u32,
+        }
+    }
+}
+
+/// What `initialize_graph` sizes a new graph for. The same numbers become
+/// the graph's record limits, so it never admits records its account was
+/// not sized for.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct GraphSizing {
+    pub max_nodes: u32,
+    pub max_edges: u32,
+    pub max_label_len: u32,
+    pub max_node_data_len: u32,
+    pub max_edge_data_len: u32,
+}
+
+impl GraphSizing {
+    /// The default limits, with this sizing's record caps.
+    pub fn config(&self) -> GraphConfig {
+        GraphConfig {
+            max_label_len: self.max_label_len,
+            max_node_data_len: self.max_node_data_len,
+            max_edge_data_len: self.max_edge_data_len,
+            max_nodes: self.max_nodes,
+            max_edges: self.max_edges,
+            ..GraphConfig::default()
+        }
+    }
+
+    /// Bytes the account is created with: `graph_space` of `config`, up
+    /// to what one instruction may allocate. Writes and `realloc_graph`
+    /// grow larger graphs from there.
+    pub fn initial_space(&self) -> usize {
+        graph_space(&self.config()).min(MAX_PERMITTED_DATA_INCREASE)
+    }
+}
+
+impl Default for GraphSizing {
+    fn default() -> Self {
+        let config = GraphConfig::default();
+        Self {
+            max_nodes: config.max_nodes,
+            max_edges: config.max_edges,
+            max_label_len: config.max_label_len,
+            max_node_data_len: config.max_node_data_len,
+            max_edge_data_len: config.max_edge_data_len,
+        }
+    }
+}
+
+/// Serialized sizes of the `GraphStore` fields whose length varies,
+/// length prefixes included.
+struct VariableSizes {
+    records: usize,
+    edge_offsets: usize,
+    adjacency: usize,
+    edge_key_index: usize,
+    pending_authority: usize,
+    name: usize,
+    roles: usize,
+    multisig_signers: usize,
+    node_acls: usize,
+    oracle_keys: usize,
+    acyclic_labels: usize,
+}
+
+impl VariableSizes {
+    /// Bytes of the whole account, discriminator included: the fixed-size
+    /// fields in declaration order, with the variable ones filled in.
+    const fn account_size(&self) -> usize {
+        NODES_OFFSET
+            + self.records
+            + self.edge_offsets
+            + self.adjacency
+            + BLOOM_BYTES
+            + self.edge_key_index
+            + 4
+            + self.pending_authority
+            + self.name
+            + 32
+            + 1
+            + self.roles
+            + 1
+            + self.multisig_signers
+            + 1
+            + 1
+            + 8
+            + 32
+            + 1
+            + 32
+            + 8
+            + GraphConfig::SERIALIZED_SIZE
+            + 1
+            + 1
+            + 1
+            + self.node_acls
+            + 4
+            + 8
+            + 1
+            + 1
+            + 32
+            + self.oracle_keys
+            + self.acyclic_labels
+            + 32
+            + 4
+    }
+}
+
+/// Bytes of every `GraphStore` field but the records and their indexes,
+/// with the name, roles, multisig signers and pending authority at their
+/// caps. Node ACLs, oracle keys and acyclic labels grow the account as
+/// they are added.
+const HEADER_SPACE: usize = VariableSizes {
+    records: 4 + 4,
+    edge_offsets: 4,
+    adjacency: 4,
+    edge_key_index: 4,
+    pending_authority: 1 + 32,
+    name: 4 + MAX_GRAPH_NAME_LEN,
+    roles: 4 + RoleGrant::MAX_SERIALIZED_SIZE * MAX_ROLES,
+    multisig_signers: 4 + 32 * MAX_MULTISIG_SIGNERS,
+    node_acls: 4,
+    oracle_keys: 4,
+    acyclic_labels: 4,
+}
+.account_size();
+
+/// Bytes a graph needs with `config.max_nodes` nodes and `max_edges`
+/// edges, each at its label and data caps.
+pub fn graph_space(config: &GraphConfig) -> usize {
+    let (nodes, edges) = (config.max_nodes as usize, config.max_edges as usize);
+    let label = if cfg!(feature = "fixed-labels") {
+        FIXED_LABEL_LEN
+    } else {
+        4 + config.max_label_len as usize
+    };
+    // With a label table every record holds an index into a table that
+    // may list one label per record
+    let (record_label, table) = if cfg!(feature = "label-table") {
+        (2, 4 + (nodes + edges) * label)
+    } else {
+        (label, 0)
+    };
+    let node = NODE_ID_LEN
+        + record_label
+        + (4 + config.max_node_data_len as usize)
+        + 1
+        + 1
+        + 4
+        + 8
+        + 8
+        + 8
+        + (1 + 32);
+    let edge = 2 * NODE_ID_LEN + record_label + 4 + (4 + config.max_edge_data_len as usize) + 8;
+
+    HEADER_SPACE + table + nodes * node + edges * edge + 2 * (nodes + 1) + 2 * edges + 4 * edges
+}
+
+/// How a caller names an edge: by storage index or by its unique key.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum EdgeRef {
+    Index(u32),
+    Key {
+        from: NodeId,
+        to: NodeId,
+        label: String,
+    },
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct Edge {
-    pub from: NodeId,
-    pub to: NodeId,
-    pub label: String,
+pub struct NodePage {
+    pub nodes: Vec<Node>,
+    /// Offset to request next, or `None` once the last slot was reached.
+    pub next_offset: Option<u32>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct GraphCapacity {
+    pub account_bytes: u64,
+    pub used_bytes: u64,
+    pub free_bytes: u64,
+    pub free_node_slots: u64,
+    pub free_edge_slots: u64,
 }
 
-#[account]
+#[cfg_attr(not(feature = "label-table"), account)]
+#[cfg_attr(feature = "label-table", derive(Clone))]
 pub struct GraphStore {
+    /// Layout of the rest of the account. Together with `authority` this is
+    /// the stable header every layout starts with.
+    pub layout_version: u8,
     pub authority: Pubkey,
     pub node_count: u64,
     pub edge_count: u64,
     pub nonce: NodeId,
+    /// Incremented on every mutation, for optimistic concurrency checks.
+    pub version: u64,
     pub nodes: Vec<Node>,
     pub edges: Vec<Edge>,
+    /// Compressed sparse row adjacency: the outgoing edge indices of
+    /// `nodes[i]` are `adjacency[edge_offsets[i]..edge_offsets[i + 1]]`.
+    pub edge_offsets: Vec<AdjacencyIndex>,
+    pub adjacency: Vec<AdjacencyIndex>,
+    pub node_bloom: NodeBloom,
+    /// Edge indices sorted by `(from, to, label)` for O(log m) lookups.
+    pub edge_key_index: Vec<u32>,
+    /// Node slot where the next compaction pass resumes.
+    pub compaction_cursor: u32,
+    /// Proposed new authority; must accept before ownership moves.
+    pub pending_authority: Option<Pubkey>,
+    /// PDA seeds: `[seed_prefix(), name, creator]`. The creator is kept
+    /// separately from `authority` so the address survives handoffs.
+    pub name: String,
+    pub creator: Pubkey,
+    pub bump: u8,
+    /// Roles granted to accounts other than the authority.
+    pub roles: Vec<RoleGrant>,
+    /// When set, read queries need at least the `Reader` role.
+    pub restrict_reads: bool,
+    /// When `multisig_threshold` is non-zero, owner and admin operations
+    /// need that many approvals from `multisig_signers`.
+    pub multisig_signers: Vec<Pubkey>,
+    pub multisig_threshold: u8,
+    /// Rejects every node/edge mutation while set.
+    pub frozen: bool,
+    /// Lamports charged per mutating query or direct write, paid to `treasury`.
+    pub write_fee_lamports: u64,
+    pub treasury: Pubkey,
+    /// Emit a `NodeMatched` event for every node a query returns.
+    pub emit_match_events: bool,
+    /// Mint whose holders may write; `Pubkey::default()` disables the gate.
+    pub gate_mint: Pubkey,
+    /// Balance of `gate_mint` a writer must hold (1 for an NFT).
+    pub gate_min_amount: u64,
+    /// Query and record limits enforced on this graph.
+    pub config: GraphConfig,
+    /// Writes must go through `commit_write` and `reveal_write`.
+    pub commit_reveal_writes: bool,
+    /// Created by `initialize_user_graph`; see `seed_prefix`.
+    pub user_graph: bool,
+    /// Every program-served read, including node and edge lookups, needs a
+    /// signer with the `Reader` role. The account data itself stays public.
+    pub private: bool,
+    /// Per-node read restrictions; see `NodeAcl`.
+    pub node_acls: Vec<NodeAcl>,
+    /// Writes a non-admin signer may make per `rate_window_slots`; 0 means
+    /// unlimited.
+    pub max_writes_per_window: u32,
+    pub rate_window_slots: u64,
+    /// Writes must append to the graph's `AuditLog`.
+    pub audit_log: bool,
+    /// Writes must be recorded in the graph's `Journal`.
+    pub journaled: bool,
+    /// Key allowed to push `oracle_keys` attributes with `push_attributes`;
+    /// the default key disables it.
+    pub oracle: Pubkey,
+    pub oracle_keys: Vec<String>,
+    /// Edge labels that may never form a cycle; every write that would
+    /// close one fails with `CycleDetected`.
+    pub acyclic_labels: Vec<String>,
+    /// Graph an unfinished `clone_graph` copies from, and the cursor its
+    /// next chunk starts at; the default key when no copy is under way.
+    pub clone_source: Pubkey,
+    pub clone_cursor: u32,
+}
+
+/// Whether `label` is one of `labels`, whichever way labels are stored.
+fn has_label(labels: &[String], label: &str) -> bool {
+    labels.iter().any(|l| l == label)
 }
 
 impl GraphStore {
     pub fn get_node_by_id(&self, id: NodeId) -> Option<&Node> {
-        self.nodes.iter().find(|n| n.id == id)
+        self.node_index(id).map(|index| &self.nodes[index])
+    }
+
+    pub fn node_index(&self, id: NodeId) -> Option<usize> {
+        if !self.node_bloom.might_contain(id) {
+            return None;
+        }
+        self.nodes.iter().position(|n| n.id == id)
+    }
+
+    pub fn outgoing_edge_indices(&self, node_index: usize) -> &[AdjacencyIndex] {
+        match (
+            self.edge_offsets.get(node_index),
+            self.edge_offsets.get(node_index + 1),
+        ) {
+            (Some(&start), Some(&end)) => &self.adjacency[start as usize..end as usize],
+            _ => &[],
+        }
+    }
+
+    /// Records `edge_index` as an outgoing edge of `nodes[node_index]`.
+    pub(crate) fn link_outgoing_edge(&mut self, node_index: usize, edge_index: u32) {
+        let insert_at = self.edge_offsets[node_index + 1] as usize;
+        self.adjacency
+            .insert(insert_at, edge_index as AdjacencyIndex);
+        for offset in &mut self.edge_offsets[node_index + 1..] {
+            *offset += 1;
+        }
+    }
+
+    fn edge_key(&self, edge_index: u32) -> (NodeId, NodeId, &str) {
+        let edge = &self.edges[edge_index as usize];
+        (edge.from, edge.to, edge.label.as_str())
+    }
+
+    /// Index of the edge `from -[label]-> to`, if one exists.
+    pub fn find_edge(&self, from: NodeId, to: NodeId, label: &str) -> Option<u32> {
+        self.edge_key_index
+            .binary_search_by(|&i| self.edge_key(i).cmp(&(from, to, label)))
+            .ok()
+            .map(|position| self.edge_key_index[position])
+    }
+
+    /// Index of the edge named by `edge_ref`, if it exists.
+    pub fn resolve_edge(&self, edge_ref: &EdgeRef) -> Option<u32> {
+        match edge_ref {
+            EdgeRef::Index(index) => ((*index as usize) < self.edges.len()).then_some(*index),
+            EdgeRef::Key { from, to, label } => self.find_edge(*from, *to, label),
+        }
+    }
+
+    pub fn has_edge(&self, from: NodeId, to: NodeId, label: &str) -> bool {
+        self.find_edge(from, to, label).is_some()
+    }
+
+    /// Whether one of `start_nodes` has an edge to `to` with one of
+    /// `filter`'s edge labels in `partition`, found through the key index.
+    pub fn has_edge_to(
+        &self,
+        start_nodes: &[NodeId],
+        to: NodeId,
+        filter: &TraverseFilter,
+        partition: Option<PartitionId>,
+    ) -> bool {
+        let labels = filter
+            .where_edge_labels
+            .iter()
+            .filter(|label| !has_label(&filter.where_not_edge_labels, label));
+        start_nodes.iter().any(|&from| {
+            labels
+                .clone()
+                .filter_map(|label| self.find_edge(from, to, label))
+                .any(|index| partition.is_none_or(|p| self.edges[index as usize].partition == p))
+        })
+    }
+
+    /// Whether adding `from -[label]-> to` would close a cycle of `label`
+    /// edges while `label` is one of `acyclic_labels`. Soft-deleted nodes
+    /// count, since they can be restored.
+    pub fn closes_cycle(&self, from: NodeId, to: NodeId, label: &str) -> bool {
+        if !has_label(&self.acyclic_labels, label) {
+            return false;
+        }
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![to];
+        while let Some(id) = stack.pop() {
+            if id == from {
+                return true;
+            }
+            let Some(index) = self.node_index(id) else {
+                continue;
+            };
+            if std::mem::replace(&mut seen[index], true) {
+                continue;
+            }
+            stack.extend(
+                self.outgoing_edge_indices(index)
+                    .iter()
+                    .map(|&i| &self.edges[i as usize])
+                    .filter(|edge| edge.label.as_str() == label)
+                    .map(|edge| edge.to),
+            );
+        }
+        false
+    }
+
+    /// Inserts `edges[edge_index]` into the sorted key index.
+    pub(crate) fn index_edge_key(&mut self, edge_index: u32) {
+        let key = self.edge_key(edge_index);
+        let position = self
+            .edge_key_index
+            .partition_point(|&i| self.edge_key(i) < key);
+        self.edge_key_index.insert(position, edge_index);
+    }
+
+    pub fn rebuild_edge_key_index(&mut self) {
+        let mut index: Vec<u32> = (0..self.edges.len() as u32).collect();
+        index.sort_by(|&a, &b| self.edge_key(a).cmp(&self.edge_key(b)));
+        self.edge_key_index = index;
+    }
+
+    /// Drops the entries of removed nodes and edges from the adjacency and
+    /// edge key index and renumbers the rest, once storage has shifted down
+    /// over them. `removed_nodes[i]` tells whether old node slot `i` went;
+    /// `edge_map` gives each old edge index its new one, if it was kept.
+    pub(crate) fn remap_indexes(&mut self, removed_nodes: &[bool], edge_map: &[Option<u32>]) {
+        let remap = |edge_index: &AdjacencyIndex| edge_map[*edge_index as usize];
+        let mut offsets = Vec::with_capacity(self.nodes.len() + 1);
+        offsets.push(0);
+        let mut adjacency = Vec::with_capacity(self.edges.len());
+        for (index, _) in removed_nodes.iter().enumerate().filter(|(_, r)| !**r) {
+            adjacency.extend(
+                self.outgoing_edge_indices(index)
+                    .iter()
+                    .filter_map(remap)
+                    .map(|e| e as AdjacencyIndex),
+            );
+            offsets.push(adjacency.len() as AdjacencyIndex);
+        }
+        self.edge_offsets = offsets;
+        self.adjacency = adjacency;
+        self.edge_key_index = self
+            .edge_key_index
+            .iter()
+            .filter_map(|&e| edge_map[e as usize])
+            .collect();
+    }
+
+    pub fn rebuild_node_bloom(&mut self) {
+        self.node_bloom.clear();
+        for node in &self.nodes {
+            self.node_bloom.insert(node.id);
+        }
+    }
+
+    /// Recomputes the CSR adjacency from `edges`, keeping edge order per node.
+    pub fn rebuild_adjacency(&mut self) {
+        let index_by_id: std::collections::BTreeMap<NodeId, usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.id, index))
+            .collect();
+        let mut counts: Vec<AdjacencyIndex> = vec![0; self.nodes.len()];
+        let positions: Vec<Option<usize>> = self
+            .edges
+            .iter()
+            .map(|e| index_by_id.get(&e.from).copied())
+            .collect();
+        for index in positions.iter().flatten() {
+            counts[*index] += 1;
+        }
+
+        self.edge_offsets = Vec::with_capacity(self.nodes.len() + 1);
+        self.edge_offsets.push(0);
+        for count in &counts {
+            let last = *self.edge_offsets.last().unwrap();
+            self.edge_offsets.push(last + count);
+        }
+
+        let mut cursor = self.edge_offsets[..self.nodes.len()].to_vec();
+        self.adjacency = vec![0; self.edges.len()];
+        for (edge_index, position) in positions.iter().enumerate() {
+            if let Some(index) = position {
+                self.adjacency[cursor[*index] as usize] = edge_index as AdjacencyIndex;
+                cursor[*index] += 1;
+            }
+        }
+        self.adjacency
+            .truncate(*self.edge_offsets.last().unwrap() as usize);
+    }
+
+    /// Like `get_node_by_id`, but hides soft-deleted nodes.
+    pub fn get_live_node_by_id(&self, id: NodeId) -> Option<&Node> {
+        self.get_node_by_id(id).filter(|n| !n.is_deleted)
+    }
+
+    /// Live node lookup restricted to a partition (`None` sees every partition).
+    pub fn get_scoped_node_by_id(
+        &self,
+        id: NodeId,
+        partition: Option<PartitionId>,
+    ) -> Option<&Node> {
+        self.scoped_node_index(id, partition)
+            .map(|index| &self.nodes[index])
+    }
+
+    pub fn scoped_node_index(&self, id: NodeId, partition: Option<PartitionId>) -> Option<usize> {
+        self.node_index(id).filter(|&index| {
+            let node = &self.nodes[index];
+            !node.is_deleted && partition.is_none_or(|p| node.partition == p)
+        })
+    }
+
+    pub fn live_node_ids(&self, partition: Option<PartitionId>) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|n| !n.is_deleted)
+            .filter(|n| partition.is_none_or(|p| n.partition == p))
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Returns the live nodes stored in slots `offset..offset + count` that
+    /// `signers` may read past their ACL and partition scope, stopping early
+    /// once the encoded page would exceed `max_bytes`.
+    pub fn get_nodes_page(
+        &self,
+        offset: usize,
+        count: usize,
+        max_bytes: usize,
+        signers: &[Pubkey],
+    ) -> NodePage {
+        // Vec length prefix + Option tag + u32 offset
+        let mut page_bytes = 4 + 1 + 4;
+        let mut nodes = Vec::new();
+        let mut cursor = offset;
+
+        while cursor < self.nodes.len() && cursor < offset.saturating_add(count) {
+            let node = &self.nodes[cursor];
+            if !node.is_deleted
+                && self.can_read_node(node.id, signers)
+                && self.in_partition_scope(signers, node.partition)
+            {
+                let size = node.serialized_size();
+                if page_bytes + size > max_bytes {
+                    break;
+                }
+                page_bytes += size;
+                nodes.push(node.clone());
+            }
+            cursor += 1;
+        }
+
+        let next_offset = if cursor < self.nodes.len() {
+            Some(cursor as u32)
+        } else {
+            None
+        };
+
+        NodePage { nodes, next_offset }
+    }
+
+    /// Decodes a GraphStore account like `try_deserialize`, but steps over
+    /// each node's data instead of copying it, for queries that only walk
+    /// the graph's structure. Every node comes back with empty `data`.
+    pub fn try_deserialize_structure(data: &[u8]) -> Result<GraphStore> {
+        require!(
+            data.len() >= NODES_OFFSET + 4 && data[..8] == *GraphStore::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        let not_decoded = |_| anchor_lang::error::ErrorCode::AccountDidNotDeserialize;
+
+        #[cfg(feature = "label-table")]
+        let graph = crate::label_table::read_graph(&mut &data[8..], false).map_err(not_decoded)?;
+
+        #[cfg(not(feature = "label-table"))]
+        let graph = {
+            use std::io::Read;
+
+            let mut rest = &data[NODES_OFFSET..];
+            let count = u32::deserialize(&mut rest).map_err(not_decoded)? as usize;
+            let mut nodes = Vec::with_capacity(count.min(MAX_NODES));
+            for _ in 0..count {
+                nodes.push(Node::deserialize_without_data(&mut rest).map_err(not_decoded)?);
+            }
+
+            // Decode the header and everything after the nodes in place, with
+            // an empty node list standing in for the nodes already read
+            let no_nodes = 0u32.to_le_bytes();
+            let mut reader = data[8..NODES_OFFSET].chain(&no_nodes[..]).chain(rest);
+            let mut graph = GraphStore::deserialize_reader(&mut reader).map_err(not_decoded)?;
+            graph.nodes = nodes;
+            graph
+        };
+        Ok(graph)
+    }
+
+    /// Bytes the account currently needs, discriminator included.
+    pub fn serialized_size(&self) -> usize {
+        let adjacency_index = std::mem::size_of::<AdjacencyIndex>();
+        VariableSizes {
+            records: self.records_size(),
+            edge_offsets: 4 + adjacency_index * self.edge_offsets.len(),
+            adjacency: 4 + adjacency_index * self.adjacency.len(),
+            edge_key_index: 4 + 4 * self.edge_key_index.len(),
+            pending_authority: 1 + self.pending_authority.map_or(0, |_| 32),
+            name: 4 + self.name.len(),
+            roles: 4 + self
+                .roles
+                .iter()
+                .map(RoleGrant::serialized_size)
+                .sum::<usize>(),
+            multisig_signers: 4 + 32 * self.multisig_signers.len(),
+            node_acls: 4 + self
+                .node_acls
+                .iter()
+                .map(NodeAcl::serialized_size)
+                .sum::<usize>(),
+            oracle_keys: 4 + self.oracle_keys.iter().map(|k| 4 + k.len()).sum::<usize>(),
+            acyclic_labels: 4 + self
+                .acyclic_labels
+                .iter()
+                .map(|l| 4 + l.len())
+                .sum::<usize>(),
+        }
+        .account_size()
+    }
+
+    /// Bytes `nodes` and `edges` take in the account, with the label
+    /// table when there is one.
+    #[cfg(not(feature = "label-table"))]
+    fn records_size(&self) -> usize {
+        (4 + self.nodes.iter().map(Node::serialized_size).sum::<usize>())
+            + (4 + self.edges.iter().map(Edge::serialized_size).sum::<usize>())
+    }
+
+    #[cfg(feature = "label-table")]
+    fn records_size(&self) -> usize {
+        crate::label_table::records_size(self)
+    }
+
+    /// First PDA seed. A user graph lives at `[USER_GRAPH_SEED, user]`,
+    /// which is the same address as `[USER_GRAPH_SEED, name, creator]`
+    /// with its empty name, so every instruction can check one seed list.
+    pub fn seed_prefix(&self) -> &'static [u8] {
+        if self.user_graph {
+            USER_GRAPH_SEED
+        } else {
+            GRAPH_STORE_SEED
+        }
+    }
+
+    /// Remaining room in an account of `account_bytes` bytes.
+    pub fn capacity(&self, account_bytes: usize) -> GraphCapacity {
+        let used_bytes = self.serialized_size();
+        GraphCapacity {
+            account_bytes: account_bytes as u64,
+            used_bytes: used_bytes as u64,
+            free_bytes: account_bytes.saturating_sub(used_bytes) as u64,
+            free_node_slots: (self.config.max_nodes as usize).saturating_sub(self.nodes.len())
+                as u64,
+            free_edge_slots: (self.config.max_edges as usize).saturating_sub(self.edges.len())
+                as u64,
+        }
+    }
+
+    /// Hands out the next node id from the nonce, stepping over ids that
+    /// were taken explicitly with `SetNodeId`. Explicit ids never move the
+    /// nonce, so a huge one cannot exhaust it.
+    pub fn next_node_id(&mut self) -> Option<NodeId> {
+        let mut id = self.nonce;
+        while self.node_index(id).is_some() {
+            id = id.checked_add(1)?;
+        }
+        self.nonce = id.checked_add(1)?;
+        Some(id)
+    }
+
+    pub fn bump_version(&mut self) -> Option<u64> {
+        self.version = self.version.checked_add(1)?;
+        Some(self.version)
+    }
+
+    /// Marks a node as deleted (or restores it) without touching its edges.
+    /// Returns `None` if no node with this id exists.
+    pub fn set_node_deleted(&mut self, id: NodeId, deleted: bool) -> Option<()> {
+        let node_index = self.node_index(id)?;
+        self.nodes[node_index].is_deleted = deleted;
+        if deleted {
+            self.on_mutation(Mutation::NodeDeleted { node_index })
+        } else {
+            self.on_mutation(Mutation::NodeRestored { node_index })
+        }
+    }
+
+    /// Binds a node to an NFT mint. Returns `None` if no node with this id
+    /// exists.
+    pub fn set_node_mint(&mut self, id: NodeId, mint: Pubkey) -> Option<()> {
+        let node_index = self.node_index(id)?;
+        self.nodes[node_index].mint = Some(mint);
+        self.on_mutation(Mutation::NodeUpdated { node_index })
+    }
+
+    /// Returns `None` if no node with this id exists.
+    pub fn set_node_protected(&mut self, id: NodeId, protected: bool) -> Option<()> {
+        let node_index = self.node_index(id)?;
+        self.nodes[node_index].is_protected = protected;
+        self.on_mutation(Mutation::NodeUpdated { node_index })
+    }
+
+    /// Records `now` as the node's last write time.
+    /// Returns `None` if no node with this id exists.
+    pub fn touch_node(&mut self, id: NodeId, now: i64) -> Option<()> {
+        let node_index = self.node_index(id)?;
+        self.nodes[node_index].updated_at = now;
+        Some(())
+    }
+
+    /// Replaces a node's data, or appends to it when `append` is set.
+    /// Returns `None` if no node with this id exists.
+    pub fn set_node_data(&mut self, id: NodeId, data: Vec<u8>, append: bool) -> Option<()> {
+        let node_index = self.node_index(id)?;
+        let node = &mut self.nodes[node_index];
+        if append {
+            node.data.extend_from_slice(&data);
+        } else {
+            node.data = data;
+        }
+        self.on_mutation(Mutation::NodeUpdated { node_index })
     }
 
     pub fn traverse_out(
@@ -45,115 +821,475 @@ impl GraphStore {
         start_nodes: &[NodeId],
         filter: &TraverseFilter,
         limit: Option<usize>,
+    ) -> Vec<NodeId> {
+        self.traverse_out_in_partition(start_nodes, filter, limit, None)
+    }
+
+    /// Same as `traverse_out`, but never leaves the given partition.
+    pub fn traverse_out_in_partition(
+        &self,
+        start_nodes: &[NodeId],
+        filter: &TraverseFilter,
+        limit: Option<usize>,
+        partition: Option<PartitionId>,
+    ) -> Vec<NodeId> {
+        self.traverse_out_at(start_nodes, filter, limit, partition, 0)
+    }
+
+    /// Same as `traverse_out_in_partition`, but nodes expired at `slot` are
+    /// neither returned nor traversed through.
+    pub fn traverse_out_at(
+        &self,
+        start_nodes: &[NodeId],
+        filter: &TraverseFilter,
+        limit: Option<usize>,
+        partition: Option<PartitionId>,
+        slot: u64,
     ) -> Vec<NodeId> {
         let mut result = Vec::new();
-        let mut visited = std::collections::HashSet::new();
-        let mut queue = std::collections::VecDeque::new();
-
-        // Check and add start nodes if they match the node label filters
-        // (edge filters don't apply to start nodes since we don't traverse to them)
-        for &node_id in start_nodes {
-            if let Some(node) = self.get_node_by_id(node_id) {
-                // Check node label filters for start nodes
-                let node_matches = if !filter.where_node_labels.is_empty() {
-                    filter.where_node_labels.contains(&node.label)
-                } else {
-                    true
-                };
+        self.traverse_out_into(
+            start_nodes,
+            filter,
+            limit,
+            partition,
+            slot,
+            &mut ExecutionCache::default(),
+            &mut result,
+        );
+        result
+    }
 
-                let node_not_matches = if !filter.where_not_node_labels.is_empty() {
-                    filter.where_not_node_labels.contains(&node.label)
-                } else {
-                    false
-                };
+    /// `traverse_out_at` writing into `result`, which it clears first.
+    /// `cache` keeps the buffers, node positions and node-label filter
+    /// results of earlier calls, so a program that traverses several times
+    /// pays for each only once.
+    ///
+    /// The traversal goes breadth-first, one level at a time. If a level
+    /// would grow past `config.max_frontier` nodes, it instead deepens
+    /// from the last complete level one depth at a time, walking each path
+    /// depth-first, so it never holds more than that level and one path.
+    /// Either way it reaches the same nodes, shallowest first.
+    #[allow(clippy::too_many_arguments)]
+    pub fn traverse_out_into(
+        &self,
+        start_nodes: &[NodeId],
+        filter: &TraverseFilter,
+        limit: Option<usize>,
+        partition: Option<PartitionId>,
+        slot: u64,
+        cache: &mut ExecutionCache,
+        result: &mut Vec<NodeId>,
+    ) {
+        let mut unlimited = u64::MAX;
+        self.traverse_out_resumable(
+            start_nodes,
+            filter,
+            limit,
+            partition,
+            slot,
+            cache,
+            result,
+            None,
+            &mut unlimited,
+        );
+    }
 
-                if node_matches && !node_not_matches {
-                    result.push(node_id);
-                }
+    /// `traverse_out_into` that can stop between two breadth-first levels.
+    /// Expanding a node takes one unit of `budget` plus one per outgoing
+    /// edge; once it is spent, the traversal returns a checkpoint instead
+    /// of starting the next level, and passing that back as `resume` (with
+    /// the same graph and arguments) carries on from there with the same
+    /// outcome as an uninterrupted run. Deepening past a too-wide level
+    /// runs to the end without stopping.
+    #[allow(clippy::too_many_arguments)]
+    pub fn traverse_out_resumable(
+        &self,
+        start_nodes: &[NodeId],
+        filter: &TraverseFilter,
+        limit: Option<usize>,
+        partition: Option<PartitionId>,
+        slot: u64,
+        cache: &mut ExecutionCache,
+        result: &mut Vec<NodeId>,
+        resume: Option<TraversalCheckpoint>,
+        budget: &mut u64,
+    ) -> Option<TraversalCheckpoint> {
+        result.clear();
+        cache.sync(self);
+        let memo = cache.node_filter(filter, self.nodes.len());
+        let ExecutionCache {
+            visited,
+            frontier,
+            next,
+            path,
+            depths,
+            positions,
+            node_filters,
+            ..
+        } = cache;
+        visited.clear();
+        frontier.clear();
+        let passes = &mut node_filters[memo].passes;
+
+        let visible = |id: NodeId| {
+            positions
+                .get(&id)
+                .map(|&index| index as usize)
+                .filter(|&index| {
+                    let node = &self.nodes[index];
+                    !node.is_deleted
+                        && partition.is_none_or(|p| node.partition == p)
+                        && !node.is_expired(slot)
+                })
+        };
+        let mut node_matches = |index: usize| match passes[index] {
+            NODE_PASSES => true,
+            NODE_FAILS => false,
+            _ => {
+                let label = &self.nodes[index].label;
+                let matches = (filter.where_node_labels.is_empty()
+                    || has_label(&filter.where_node_labels, label))
+                    && !has_label(&filter.where_not_node_labels, label);
+                passes[index] = if matches { NODE_PASSES } else { NODE_FAILS };
+                matches
+            }
+        };
+        let edge_matches = |edge: &Edge| {
+            (filter.where_edge_labels.is_empty()
+                || has_label(&filter.where_edge_labels, &edge.label))
+                && !has_label(&filter.where_not_edge_labels, &edge.label)
+                && partition.is_none_or(|p| edge.partition == p)
+        };
+        let full = |result: &Vec<NodeId>| limit.is_some_and(|limit| result.len() >= limit);
+
+        if let Some(checkpoint) = resume {
+            result.extend(checkpoint.found);
+            frontier.extend(checkpoint.frontier.iter().map(|&index| index as usize));
+            visited.extend(
+                self.nodes
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| {
+                        checkpoint
+                            .visited
+                            .get(index / 8)
+                            .is_some_and(|bits| bits & (1 << (index % 8)) != 0)
+                    })
+                    .map(|(_, node)| node.id),
+            );
+        } else {
+            // Check and add start nodes if they match the node label filters
+            // (edge filters don't apply to start nodes since we don't traverse to them)
+            for &node_id in start_nodes {
+                if let Some(index) = visible(node_id) {
+                    if node_matches(index) && !full(result) {
+                        result.push(node_id);
+                    }
 
-                queue.push_back(node_id);
-                visited.insert(node_id);
+                    frontier.push(index);
+                    visited.insert(node_id);
+                }
             }
         }
 
         // If edge filters are empty, we only filter start nodes, don't traverse
-        let should_traverse =
-            !filter.where_edge_labels.is_empty() || !filter.where_not_edge_labels.is_empty();
-
-        if should_traverse {
-            while let Some(current_id) = queue.pop_front() {
-                if let Some(limit) = limit {
-                    if result.len() >= limit {
-                        break;
+        if filter.where_edge_labels.is_empty() && filter.where_not_edge_labels.is_empty() {
+            return None;
+        }
+
+        // Breadth-first, one level at a time, only through matching nodes
+        let max_frontier = self.config.max_frontier as usize;
+        'levels: while !frontier.is_empty() {
+            if *budget == 0 {
+                // Nodes that are not in the graph are never visible, so
+                // only the reached nodes that are need to be kept
+                let mut reached = vec![0u8; self.nodes.len().div_ceil(8)];
+                for index in visited.iter().filter_map(|id| positions.get(id)) {
+                    reached[*index as usize / 8] |= 1 << (index % 8);
+                }
+                return Some(TraversalCheckpoint {
+                    found: std::mem::take(result),
+                    frontier: frontier.iter().map(|&index| index as u32).collect(),
+                    visited: reached,
+                });
+            }
+            next.clear();
+            for &current in frontier.iter() {
+                if full(result) {
+                    return None;
+                }
+                let outgoing = self.outgoing_edge_indices(current);
+                *budget = budget.saturating_sub(1 + outgoing.len() as u64);
+                for &edge_index in outgoing {
+                    let Some(edge) = self.edges.get(edge_index as usize) else {
+                        continue;
+                    };
+                    if !edge_matches(edge) || !visited.insert(edge.to) {
+                        continue;
                     }
+                    let Some(target) = visible(edge.to).filter(|&t| node_matches(t)) else {
+                        continue;
+                    };
+                    result.push(edge.to);
+                    if full(result) {
+                        return None;
+                    }
+                    if next.len() == max_frontier {
+                        break 'levels;
+                    }
+                    next.push(target);
                 }
+            }
+            std::mem::swap(frontier, next);
+        }
+        if frontier.is_empty() {
+            return None;
+        }
 
-                if let Some(current_node) = self.get_node_by_id(current_id) {
-                    for &edge_index in &current_node.outgoing_edge_indices {
-                        if let Some(edge) = self.edges.get(edge_index as usize) {
-                            // Check edge label filters
-                            let edge_matches = if !filter.where_edge_labels.is_empty() {
-                                filter.where_edge_labels.contains(&edge.label)
-                            } else {
-                                true
-                            };
-
-                            let edge_not_matches = if !filter.where_not_edge_labels.is_empty() {
-                                filter.where_not_edge_labels.contains(&edge.label)
-                            } else {
-                                false
-                            };
-
-                            if edge_matches && !edge_not_matches {
-                                let target_id = edge.to;
-
-                                if !visited.contains(&target_id) {
-                                    visited.insert(target_id);
-
-                                    if let Some(target_node) = self.get_node_by_id(target_id) {
-                                        // Check node label filters
-                                        let node_matches = if !filter.where_node_labels.is_empty() {
-                                            filter.where_node_labels.contains(&target_node.label)
-                                        } else {
-                                            true
-                                        };
-
-                                        let node_not_matches =
-                                            if !filter.where_not_node_labels.is_empty() {
-                                                filter
-                                                    .where_not_node_labels
-                                                    .contains(&target_node.label)
-                                            } else {
-                                                false
-                                            };
-
-                                        if node_matches && !node_not_matches {
-                                            result.push(target_id);
-
-                                            if let Some(limit) = limit {
-                                                if result.len() >= limit {
-                                                    return result;
-                                                }
-                                            }
-
-                                            queue.push_back(target_id);
-                                        }
-                                    }
-                                }
-                            }
+        // The next level is too wide: deepen from this one instead, one
+        // depth per round, until a round finds nothing at its depth bound.
+        // `depths` holds the shallowest depth each node was expanded at in
+        // the round, or the bound for nodes reached only there.
+        for bound in 1..=self.nodes.len() {
+            depths.clear();
+            for &root in frontier.iter() {
+                depths.insert(root, 0);
+                path.clear();
+                path.push((root, 0));
+                while let Some((current, cursor)) = path.last_mut() {
+                    let Some(&edge_index) = self.outgoing_edge_indices(*current).get(*cursor)
+                    else {
+                        path.pop();
+                        continue;
+                    };
+                    *cursor += 1;
+                    let depth = path.len();
+                    let Some(edge) = self.edges.get(edge_index as usize) else {
+                        continue;
+                    };
+                    if !edge_matches(edge) {
+                        continue;
+                    }
+                    let Some(target) = visible(edge.to).filter(|&t| node_matches(t)) else {
+                        visited.insert(edge.to);
+                        continue;
+                    };
+                    if visited.insert(edge.to) {
+                        result.push(edge.to);
+                        if full(result) {
+                            return None;
                         }
                     }
+                    if depths.get(&target).is_some_and(|&d| d <= depth) {
+                        continue;
+                    }
+                    depths.insert(target, depth);
+                    if depth < bound {
+                        path.push((target, 0));
+                    }
                 }
             }
+            if !depths.values().any(|&depth| depth == bound) {
+                return None;
+            }
         }
+        None
+    }
 
-        result
+    /// Pairs of a start node and a neighbour one `filter`-matching edge
+    /// away that has a matching edge back, such as mutual follows, in
+    /// `pairs`, which it clears first. Neighbours must pass the node-label
+    /// filter; both nodes must be visible in `partition` at `slot`. Each
+    /// way back is a lookup in the sorted edge key index, or a scan of the
+    /// neighbour's edges when no edge label is given. Expanding a start
+    /// node spends one unit of `budget` plus one per outgoing edge.
+    #[allow(clippy::too_many_arguments)]
+    pub fn mutual_out(
+        &self,
+        start_nodes: &[NodeId],
+        filter: &TraverseFilter,
+        limit: Option<usize>,
+        partition: Option<PartitionId>,
+        slot: u64,
+        cache: &mut ExecutionCache,
+        pairs: &mut Vec<(NodeId, NodeId)>,
+        budget: &mut u64,
+    ) {
+        pairs.clear();
+        cache.sync(self);
+        let memo = cache.node_filter(filter, self.nodes.len());
+        let ExecutionCache {
+            visited,
+            positions,
+            node_filters,
+            ..
+        } = cache;
+        let passes = &mut node_filters[memo].passes;
+
+        let visible = |id: NodeId| {
+            positions
+                .get(&id)
+                .map(|&index| index as usize)
+                .filter(|&index| {
+                    let node = &self.nodes[index];
+                    !node.is_deleted
+                        && partition.is_none_or(|p| node.partition == p)
+                        && !node.is_expired(slot)
+                })
+        };
+        let mut node_matches = |index: usize| match passes[index] {
+            NODE_PASSES => true,
+            NODE_FAILS => false,
+            _ => {
+                let label = &self.nodes[index].label;
+                let matches = (filter.where_node_labels.is_empty()
+                    || has_label(&filter.where_node_labels, label))
+                    && !has_label(&filter.where_not_node_labels, label);
+                passes[index] = if matches { NODE_PASSES } else { NODE_FAILS };
+                matches
+            }
+        };
+        let edge_matches = |edge: &Edge| {
+            (filter.where_edge_labels.is_empty()
+                || has_label(&filter.where_edge_labels, &edge.label))
+                && !has_label(&filter.where_not_edge_labels, &edge.label)
+                && partition.is_none_or(|p| edge.partition == p)
+        };
+        let edge_back = |from: usize, to: NodeId| {
+            let from_id = self.nodes[from].id;
+            if filter.where_edge_labels.is_empty() {
+                self.outgoing_edge_indices(from).iter().any(|&i| {
+                    self.edges
+                        .get(i as usize)
+                        .is_some_and(|edge| edge.to == to && edge_matches(edge))
+                })
+            } else {
+                filter.where_edge_labels.iter().any(|label| {
+                    self.find_edge(from_id, to, label)
+                        .is_some_and(|i| edge_matches(&self.edges[i as usize]))
+                })
+            }
+        };
+
+        for &start in start_nodes {
+            let Some(index) = visible(start) else {
+                continue;
+            };
+            let outgoing = self.outgoing_edge_indices(index);
+            *budget = budget.saturating_sub(1 + outgoing.len() as u64);
+            // Several matching edges may lead to the same neighbour
+            visited.clear();
+            for &edge_index in outgoing {
+                let Some(edge) = self.edges.get(edge_index as usize) else {
+                    continue;
+                };
+                if edge.to == start || !edge_matches(edge) || !visited.insert(edge.to) {
+                    continue;
+                }
+                let Some(target) = visible(edge.to).filter(|&t| node_matches(t)) else {
+                    continue;
+                };
+                if edge_back(target, start) {
+                    pairs.push((start, edge.to));
+                    if limit.is_some_and(|limit| pairs.len() >= limit) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where `traverse_out_resumable` stopped: between two breadth-first
+/// levels, with the level still to expand. Only valid for the graph as it
+/// was, since nodes are kept by position.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct TraversalCheckpoint {
+    /// Nodes found so far, in the order found.
+    pub found: Vec<NodeId>,
+    /// Positions of the nodes in the level to expand next.
+    pub frontier: Vec<u32>,
+    /// Bitmap of reached nodes, by position.
+    pub visited: Vec<u8>,
+}
+
+const NODE_UNCHECKED: u8 = 0;
+const NODE_PASSES: u8 = 1;
+const NODE_FAILS: u8 = 2;
+
+/// State one VM execution keeps across opcodes: traversal buffers, node
+/// positions by id, and which nodes pass each node-label filter seen so
+/// far, so repeated lookups and repeated label filters are not redone.
+/// Only valid while nodes are appended, never removed, reordered or
+/// relabeled, which holds within one execution.
+#[derive(Default)]
+pub struct ExecutionCache {
+    visited: std::collections::HashSet<NodeId>,
+    /// Node positions in the current and next breadth-first levels.
+    frontier: Vec<usize>,
+    next: Vec<usize>,
+    /// Depth-first path while deepening: node position and next edge.
+    path: Vec<(usize, usize)>,
+    depths: std::collections::HashMap<usize, usize>,
+    positions: std::collections::HashMap<NodeId, u32>,
+    /// `nodes[..indexed]` are in `positions`.
+    indexed: usize,
+    node_filters: Vec<NodeFilterMemo>,
+}
+
+/// Results of one node-label filter, by node position.
+struct NodeFilterMemo {
+    labels: Vec<String>,
+    not_labels: Vec<String>,
+    passes: Vec<u8>,
+}
+
+impl ExecutionCache {
+    /// Position of node `id` in `graph.nodes`, like `GraphStore::node_index`
+    /// without the scan.
+    pub fn node_index(&mut self, graph: &GraphStore, id: NodeId) -> Option<usize> {
+        self.sync(graph);
+        self.positions.get(&id).map(|&index| index as usize)
+    }
+
+    pub fn get_node<'g>(&mut self, graph: &'g GraphStore, id: NodeId) -> Option<&'g Node> {
+        self.node_index(graph, id).map(|index| &graph.nodes[index])
+    }
+
+    /// Indexes the nodes appended since the last call.
+    fn sync(&mut self, graph: &GraphStore) {
+        for (index, node) in graph.nodes.iter().enumerate().skip(self.indexed) {
+            // The first node with an id wins, as in `node_index`
+            self.positions.entry(node.id).or_insert(index as u32);
+        }
+        self.indexed = graph.nodes.len();
+    }
+
+    /// Index of the memo for `filter`'s node labels, sized for `nodes`.
+    fn node_filter(&mut self, filter: &TraverseFilter, nodes: usize) -> usize {
+        let memo = match self.node_filters.iter().position(|memo| {
+            memo.labels == filter.where_node_labels
+                && memo.not_labels == filter.where_not_node_labels
+        }) {
+            Some(memo) => memo,
+            None => {
+                self.node_filters.push(NodeFilterMemo {
+                    labels: filter.where_node_labels.clone(),
+                    not_labels: filter.where_not_node_labels.clone(),
+                    passes: Vec::new(),
+                });
+                self.node_filters.len() - 1
+            }
+        };
+        self.node_filters[memo].passes.resize(nodes, NODE_UNCHECKED);
+        memo
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{empty_graph, large_graph, small_graph, GraphBuilder};
     use anchor_lang::prelude::Pubkey;
 
     fn create_filter(node_label: &str, edge_label: &str) -> TraverseFilter {
@@ -165,102 +1301,9 @@ mod tests {
         }
     }
 
-    // Test graph schema:
-    //
-    //     City(1) ──Railway──> City(2) ──Railway──> City(3)
-    //       │                      │                    │
-    //       │                      │                    │
-    //       │                      └──Highway──> Town(4) │
-    //       │                                           │
-    //       └────────────Railway────────────────────────┘
-    //                    (cycle)
-    //
-    //     Town(5) (isolated node)
-    //
-    fn create_small_test_graph() -> GraphStore {
-        let authority = Pubkey::new_unique();
-
-        let mut nodes = Vec::new();
-        let mut edges = Vec::new();
-
-        nodes.push(Node {
-            id: 1,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![0, 1],
-        });
-
-        nodes.push(Node {
-            id: 2,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![2, 3],
-        });
-
-        nodes.push(Node {
-            id: 3,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![4],
-        });
-
-        nodes.push(Node {
-            id: 4,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
-
-        nodes.push(Node {
-            id: 5,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
-
-        edges.push(Edge {
-            from: 1,
-            to: 2,
-            label: "Railway".to_string(),
-        });
-
-        edges.push(Edge {
-            from: 1,
-            to: 3,
-            label: "Railway".to_string(),
-        });
-
-        edges.push(Edge {
-            from: 2,
-            to: 3,
-            label: "Railway".to_string(),
-        });
-
-        edges.push(Edge {
-            from: 2,
-            to: 4,
-            label: "Highway".to_string(),
-        });
-
-        edges.push(Edge {
-            from: 3,
-            to: 1,
-            label: "Railway".to_string(),
-        });
-
-        GraphStore {
-            authority,
-            node_count: 5,
-            edge_count: 5,
-            nonce: 6,
-            nodes,
-            edges,
-        }
-    }
-
     #[test]
     fn test_traverse_out_simple() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "Railway");
         let result = graph.traverse_out(&[1], &filter, None);
@@ -273,7 +1316,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_with_limit() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "Railway");
         let result = graph.traverse_out(&[1], &filter, Some(1));
@@ -283,7 +1326,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_wrong_edge_label() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "NONEXISTENT");
         let result = graph.traverse_out(&[1], &filter, None);
@@ -294,7 +1337,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_wrong_node_label() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("Town", "Railway");
         let result = graph.traverse_out(&[1], &filter, None);
@@ -304,7 +1347,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_multiple_start_nodes() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "Railway");
         let result = graph.traverse_out(&[1, 2], &filter, None);
@@ -317,7 +1360,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_handles_cycles() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "Railway");
         let result = graph.traverse_out(&[1], &filter, None);
@@ -330,7 +1373,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_different_edge_types() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("Town", "Highway");
         let result = graph.traverse_out(&[2], &filter, None);
@@ -341,7 +1384,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_nonexistent_start_node() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "Railway");
         let result = graph.traverse_out(&[999], &filter, None);
@@ -351,7 +1394,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_empty_start_nodes() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "Railway");
         let result = graph.traverse_out(&[], &filter, None);
@@ -361,7 +1404,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_multi_hop() {
-        let graph = create_small_test_graph();
+        let graph = small_graph();
 
         let filter = create_filter("City", "Railway");
         let result = graph.traverse_out(&[1], &filter, None);
@@ -372,227 +1415,642 @@ mod tests {
         assert!(result.contains(&3));
     }
 
-    // Large test graph schema:
-    //
-    //     City(1) ──Railway──> City(2) ──Railway──> City(3) ──Railway──> City(4)
-    //       │                      │                    │                    │
-    //       │                      │                    │                    │
-    //       │                      └──Highway──> Town(5) │                    │
-    //       │                                           │                    │
-    //       └──Highway──> Town(6)                      │                    │
-    //                                                      │                    │
-    //     City(7) ──Railway──> City(8) ──Highway──> Town(9) ──Highway──> Town(10)
-    //       │                      │
-    //       │                      │
-    //       └──Railway──> City(2) ──┘
-    //
-    //     Town(11) ──Highway──> Town(12) ──Highway──> Town(13)
-    //       │
-    //       └──Highway──> City(1)
-    //
-    fn create_large_test_graph() -> GraphStore {
-        let authority = Pubkey::new_unique();
+    #[test]
+    fn test_traverse_out_large_graph_simple_railway() {
+        let graph = large_graph();
 
-        let mut nodes = Vec::new();
-        let mut edges = Vec::new();
+        let filter = create_filter("City", "Railway");
+        let result = graph.traverse_out(&[1], &filter, None);
 
-        nodes.push(Node {
-            id: 1,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![0, 1],
-        });
+        assert_eq!(result.len(), 4);
+        assert!(result.contains(&1)); // Start node is included
+        assert!(result.contains(&2));
+        assert!(result.contains(&3));
+        assert!(result.contains(&4));
+    }
 
-        nodes.push(Node {
-            id: 2,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![2, 3],
-        });
+    #[test]
+    fn test_traverse_out_large_graph_simple_highway() {
+        let graph = large_graph();
 
-        nodes.push(Node {
-            id: 3,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![4],
-        });
+        let filter = create_filter("Town", "Highway");
+        let result = graph.traverse_out(&[11], &filter, None);
 
-        nodes.push(Node {
-            id: 4,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
+        assert_eq!(result.len(), 3);
+        assert!(result.contains(&12));
+        assert!(result.contains(&13));
+        assert!(result.contains(&11));
+    }
 
-        nodes.push(Node {
-            id: 5,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
+    #[test]
+    fn test_wide_level_deepens_to_same_nodes() {
+        // 1 fans out to 2..=7, each of which leads on to one more node
+        let mut builder = GraphBuilder::new().node("Hub");
+        for _ in 0..12 {
+            builder = builder.node("Leaf");
+        }
+        for child in 2..=7 {
+            builder = builder
+                .edge(1, child, "Link")
+                .edge(child, child + 6, "Link");
+        }
+        builder = builder.edge(13, 8, "Link").edge(8, 2, "Link");
+        let mut graph = builder.build();
+        let filter = TraverseFilter {
+            where_node_labels: Vec::new(),
+            where_edge_labels: vec!["Link".to_string()],
+            where_not_node_labels: Vec::new(),
+            where_not_edge_labels: Vec::new(),
+        };
+        let wide = graph.traverse_out(&[1], &filter, None);
+        assert_eq!(wide.len(), 13);
+
+        graph.config.max_frontier = 2;
+        let mut deepened = graph.traverse_out(&[1], &filter, None);
+        assert_eq!(deepened[..3], [1, 2, 3]);
+        deepened.sort();
+        assert_eq!(deepened, (1..=13).collect::<Vec<_>>());
+
+        // Shallowest first: the six children before any grandchild
+        let limited = graph.traverse_out(&[1], &filter, Some(7));
+        assert_eq!(limited, vec![1, 2, 3, 4, 5, 6, 7]);
+    }
 
-        nodes.push(Node {
-            id: 6,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
+    #[test]
+    fn test_traverse_out_into_reuses_cache() {
+        let graph = large_graph();
+        let mut cache = ExecutionCache::default();
+        let mut result = Vec::new();
 
-        nodes.push(Node {
-            id: 7,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![5, 6],
-        });
+        for (start, node_label, edge_label) in [
+            (1, "City", "Railway"),
+            (11, "Town", "Highway"),
+            (1, "City", "Railway"),
+        ] {
+            let filter = create_filter(node_label, edge_label);
+            graph.traverse_out_into(&[start], &filter, None, None, 0, &mut cache, &mut result);
+            assert_eq!(result, graph.traverse_out(&[start], &filter, None));
+        }
+    }
 
-        nodes.push(Node {
-            id: 8,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![7],
-        });
+    #[test]
+    fn test_traverse_out_skips_soft_deleted_nodes() {
+        let mut graph = small_graph();
+        graph.set_node_deleted(2, true);
 
-        nodes.push(Node {
-            id: 9,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![8],
-        });
+        let filter = create_filter("City", "Railway");
+        let result = graph.traverse_out(&[1], &filter, None);
 
-        nodes.push(Node {
-            id: 10,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
+        assert_eq!(result.len(), 2);
+        assert!(result.contains(&1));
+        assert!(result.contains(&3));
+        assert!(!result.contains(&2));
+    }
 
-        nodes.push(Node {
-            id: 11,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![9, 10],
-        });
+    #[test]
+    fn test_traverse_out_soft_deleted_start_node() {
+        let mut graph = small_graph();
+        graph.set_node_deleted(1, true);
 
-        nodes.push(Node {
-            id: 12,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![11],
-        });
+        let filter = create_filter("City", "Railway");
+        let result = graph.traverse_out(&[1], &filter, None);
 
-        nodes.push(Node {
-            id: 13,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
+        assert!(result.is_empty());
+    }
 
-        edges.push(Edge {
-            from: 1,
-            to: 2,
-            label: "Railway".to_string(),
-        });
+    #[test]
+    fn test_undelete_restores_traversal() {
+        let mut graph = small_graph();
+        graph.set_node_deleted(2, true);
+        assert!(graph.set_node_deleted(2, false).is_some());
 
-        edges.push(Edge {
-            from: 1,
-            to: 6,
-            label: "Highway".to_string(),
-        });
+        let filter = create_filter("City", "Railway");
+        let result = graph.traverse_out(&[1], &filter, None);
 
-        edges.push(Edge {
-            from: 2,
-            to: 3,
-            label: "Railway".to_string(),
-        });
+        assert_eq!(result.len(), 3);
+        assert!(result.contains(&2));
+    }
+
+    #[test]
+    fn test_set_node_deleted_missing_node() {
+        let mut graph = small_graph();
+
+        assert!(graph.set_node_deleted(999, true).is_none());
+    }
 
-        edges.push(Edge {
-            from: 2,
+    #[test]
+    fn test_traverse_out_stays_in_partition() {
+        let mut graph = small_graph();
+        graph.nodes[1].partition = 7; // City(2)
+
+        let filter = create_filter("City", "Railway");
+        let result = graph.traverse_out_in_partition(&[1], &filter, None, Some(0));
+
+        assert_eq!(result.len(), 2);
+        assert!(result.contains(&1));
+        assert!(result.contains(&3));
+        assert!(!result.contains(&2));
+    }
+
+    #[test]
+    fn test_traverse_out_skips_edges_from_other_partition() {
+        let mut graph = small_graph();
+        graph.edges[0].partition = 7; // 1 -> 2
+        graph.edges[1].partition = 7; // 1 -> 3
+
+        let filter = create_filter("City", "Railway");
+        let result = graph.traverse_out_in_partition(&[1], &filter, None, Some(0));
+
+        assert_eq!(result, vec![1]);
+    }
+
+    #[test]
+    fn test_live_node_ids_by_partition() {
+        let mut graph = small_graph();
+        graph.nodes[3].partition = 2;
+        graph.nodes[4].partition = 2;
+
+        assert_eq!(graph.live_node_ids(Some(2)), vec![4, 5]);
+        assert_eq!(graph.live_node_ids(None).len(), 5);
+    }
+
+    #[test]
+    fn test_rebuild_adjacency_groups_edges_by_node() {
+        let graph = small_graph();
+
+        assert_eq!(graph.edge_offsets, vec![0, 2, 4, 5, 5, 5]);
+        assert_eq!(graph.outgoing_edge_indices(0), &[0, 1]);
+        assert_eq!(graph.outgoing_edge_indices(1), &[2, 3]);
+        assert_eq!(graph.outgoing_edge_indices(2), &[4]);
+        assert!(graph.outgoing_edge_indices(3).is_empty());
+    }
+
+    #[test]
+    fn test_adjacency_holds_wide_edge_indices() {
+        let mut builder = GraphBuilder::new().node("A").node("B");
+        for i in 0..300 {
+            builder = builder.edge(1, 2, &format!("L{}", i));
+        }
+        let mut graph = builder.edge(2, 1, "Back").build();
+
+        assert_eq!(graph.outgoing_edge_indices(0).len(), 300);
+        assert_eq!(graph.outgoing_edge_indices(0)[299], 299);
+        assert_eq!(graph.outgoing_edge_indices(1), &[300]);
+
+        let adjacency = graph.adjacency.clone();
+        graph.rebuild_adjacency();
+        assert_eq!(graph.adjacency, adjacency);
+    }
+
+    #[test]
+    fn test_link_outgoing_edge_shifts_later_rows() {
+        let mut graph = small_graph();
+
+        graph.edges.push(Edge {
+            from: 1,
             to: 5,
-            label: "Highway".to_string(),
+            label: "Road".into(),
+            partition: 0,
+            data: Vec::new(),
+            created_at: 0,
         });
+        graph.link_outgoing_edge(0, 5);
 
-        edges.push(Edge {
-            from: 3,
-            to: 4,
-            label: "Railway".to_string(),
-        });
+        assert_eq!(graph.outgoing_edge_indices(0), &[0, 1, 5]);
+        assert_eq!(graph.outgoing_edge_indices(1), &[2, 3]);
+        assert_eq!(graph.outgoing_edge_indices(2), &[4]);
+        assert_eq!(graph.edge_offsets, vec![0, 3, 5, 6, 6, 6]);
+    }
 
-        edges.push(Edge {
-            from: 7,
-            to: 2,
-            label: "Railway".to_string(),
-        });
+    #[test]
+    fn test_node_serialized_size_matches_borsh() {
+        let graph = small_graph();
+        let mut node = graph.nodes[0].clone();
+        node.data = vec![1, 2, 3];
 
-        edges.push(Edge {
-            from: 7,
-            to: 8,
-            label: "Railway".to_string(),
-        });
+        let mut buf = Vec::new();
+        node.serialize(&mut buf).unwrap();
 
-        edges.push(Edge {
-            from: 8,
-            to: 9,
-            label: "Highway".to_string(),
-        });
+        assert_eq!(node.serialized_size(), buf.len());
+    }
 
-        edges.push(Edge {
-            from: 9,
-            to: 10,
-            label: "Highway".to_string(),
-        });
+    #[test]
+    fn test_resolve_edge() {
+        let graph = small_graph();
+
+        assert_eq!(graph.resolve_edge(&EdgeRef::Index(2)), Some(2));
+        assert_eq!(graph.resolve_edge(&EdgeRef::Index(99)), None);
+        assert_eq!(
+            graph.resolve_edge(&EdgeRef::Key {
+                from: 2,
+                to: 3,
+                label: "Railway".to_string(),
+            }),
+            Some(2)
+        );
+        assert_eq!(
+            graph.resolve_edge(&EdgeRef::Key {
+                from: 3,
+                to: 2,
+                label: "Railway".to_string(),
+            }),
+            None
+        );
+    }
 
-        edges.push(Edge {
-            from: 11,
-            to: 1,
-            label: "Highway".to_string(),
-        });
+    #[test]
+    fn test_set_node_data() {
+        let mut graph = small_graph();
+        let version = graph.version;
 
-        edges.push(Edge {
-            from: 11,
-            to: 12,
-            label: "Highway".to_string(),
-        });
+        graph.set_node_data(1, vec![1, 2], false).unwrap();
+        graph.set_node_data(1, vec![3], true).unwrap();
+
+        assert_eq!(graph.get_node_by_id(1).unwrap().data, vec![1, 2, 3]);
+        assert_eq!(graph.version, version + 2);
+        assert!(graph.set_node_data(42, vec![], false).is_none());
+    }
+
+    #[test]
+    fn test_edge_serialized_size_matches_borsh() {
+        let graph = small_graph();
+        let mut edge = graph.edges[0].clone();
+        edge.data = vec![9; 10];
+
+        let mut buf = Vec::new();
+        edge.serialize(&mut buf).unwrap();
+
+        assert_eq!(edge.serialized_size(), buf.len());
+    }
+
+    #[test]
+    fn test_get_nodes_page() {
+        let graph = small_graph();
+
+        let page = graph.get_nodes_page(0, 2, usize::MAX, &[]);
+        assert_eq!(
+            page.nodes.iter().map(|n| n.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(page.next_offset, Some(2));
+
+        let page = graph.get_nodes_page(2, 10, usize::MAX, &[]);
+        assert_eq!(
+            page.nodes.iter().map(|n| n.id).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+        assert_eq!(page.next_offset, None);
+    }
+
+    #[test]
+    fn test_get_nodes_page_skips_soft_deleted() {
+        let mut graph = small_graph();
+        graph.set_node_deleted(2, true);
+
+        let page = graph.get_nodes_page(0, 3, usize::MAX, &[]);
+        assert_eq!(
+            page.nodes.iter().map(|n| n.id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(page.next_offset, Some(3));
+    }
 
-        edges.push(Edge {
-            from: 12,
-            to: 13,
-            label: "Highway".to_string(),
+    #[test]
+    fn test_get_nodes_page_respects_byte_budget() {
+        let graph = small_graph();
+        let one_node = 9 + graph.nodes[0].serialized_size();
+
+        let page = graph.get_nodes_page(0, 5, one_node, &[]);
+        assert_eq!(page.nodes.len(), 1);
+        assert_eq!(page.next_offset, Some(1));
+    }
+
+    #[test]
+    fn test_get_nodes_page_hides_acl_restricted() {
+        let mut graph = small_graph();
+        let owner = Pubkey::new_unique();
+        graph
+            .set_node_acl(NodeAcl {
+                node_id: 2,
+                owner,
+                owner_only: true,
+                readers: Vec::new(),
+            })
+            .unwrap();
+
+        let ids = |signers: &[Pubkey]| {
+            graph
+                .get_nodes_page(0, 3, usize::MAX, signers)
+                .nodes
+                .iter()
+                .map(|n| n.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&[Pubkey::new_unique()]), vec![1, 3]);
+        assert_eq!(ids(&[owner]), vec![1, 2, 3]);
+        assert_eq!(ids(&[graph.authority]), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_get_nodes_page_hides_other_partitions() {
+        let mut graph = small_graph();
+        graph.nodes[1].partition = 1;
+        let tenant = Pubkey::new_unique();
+        graph
+            .set_role(tenant, crate::roles::Role::Reader, Some(1))
+            .unwrap();
+
+        let ids = |signers: &[Pubkey]| {
+            graph
+                .get_nodes_page(0, 3, usize::MAX, signers)
+                .nodes
+                .iter()
+                .map(|n| n.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&[tenant]), vec![2]);
+        assert_eq!(ids(&[]), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_closes_cycle() {
+        // 1 -> 2 -> 3 depends, 3 -> 1 links
+        let mut graph = GraphBuilder::new()
+            .node("Task")
+            .node("Task")
+            .node("Task")
+            .edge(1, 2, "DEPENDS")
+            .edge(2, 3, "DEPENDS")
+            .edge(3, 1, "LINKS")
+            .build();
+        assert!(!graph.closes_cycle(3, 1, "DEPENDS"));
+
+        graph.acyclic_labels.push("DEPENDS".to_string());
+        assert!(graph.closes_cycle(3, 1, "DEPENDS"));
+        assert!(graph.closes_cycle(2, 2, "DEPENDS"));
+        assert!(!graph.closes_cycle(1, 3, "DEPENDS"));
+        assert!(!graph.closes_cycle(1, 3, "LINKS"));
+
+        // A soft-deleted node on the path still counts
+        graph.set_node_deleted(2, true).unwrap();
+        assert!(graph.closes_cycle(3, 1, "DEPENDS"));
+    }
+
+    #[test]
+    fn test_add_node_appends_empty_row() {
+        let mut graph = small_graph();
+        let mut node = graph.nodes[0].clone();
+        node.id = 6;
+
+        assert_eq!(graph.add_node(node), Some(5));
+        assert_eq!(graph.edge_offsets.len(), graph.nodes.len() + 1);
+        assert!(graph.outgoing_edge_indices(5).is_empty());
+    }
+
+    #[test]
+    fn test_node_lookup_uses_bloom() {
+        let mut graph = small_graph();
+
+        assert_eq!(graph.node_index(3), Some(2));
+
+        // A node missing from the bloom filter is treated as absent
+        graph.node_bloom.clear();
+        assert_eq!(graph.node_index(3), None);
+
+        graph.rebuild_node_bloom();
+        assert_eq!(graph.node_index(3), Some(2));
+    }
+
+    #[test]
+    fn test_find_edge_by_triple() {
+        let graph = small_graph();
+
+        assert_eq!(graph.find_edge(2, 4, "Highway"), Some(3));
+        assert_eq!(graph.find_edge(3, 1, "Railway"), Some(4));
+        assert!(!graph.has_edge(2, 4, "Railway"));
+        assert!(!graph.has_edge(4, 2, "Highway"));
+    }
+
+    #[test]
+    fn test_index_edge_key_keeps_order() {
+        let mut graph = small_graph();
+
+        graph.edges.push(Edge {
+            from: 1,
+            to: 5,
+            label: "Road".into(),
+            partition: 0,
+            data: Vec::new(),
+            created_at: 0,
         });
+        graph.index_edge_key(5);
+
+        assert_eq!(graph.find_edge(1, 5, "Road"), Some(5));
+        assert_eq!(graph.find_edge(2, 3, "Railway"), Some(2));
+        let keys: Vec<_> = graph
+            .edge_key_index
+            .iter()
+            .map(|&i| graph.edge_key(i))
+            .collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+    }
 
-        GraphStore {
-            authority,
-            node_count: 13,
-            edge_count: 12,
-            nonce: 14,
-            nodes,
-            edges,
-        }
+    #[test]
+    fn test_serialized_size_matches_account_encoding() {
+        let graph = large_graph();
+
+        let mut buf = Vec::new();
+        graph.try_serialize(&mut buf).unwrap();
+
+        assert_eq!(graph.serialized_size(), buf.len());
     }
 
     #[test]
-    fn test_traverse_out_large_graph_simple_railway() {
-        let graph = create_large_test_graph();
+    fn test_serialized_size_matches_encoding() {
+        let size = |graph: &GraphStore| 8 + graph.try_to_vec().unwrap().len();
+
+        let graph = empty_graph();
+        assert_eq!(graph.serialized_size(), size(&graph));
+
+        let mut graph = small_graph();
+        graph.pending_authority = Some(Pubkey::new_unique());
+        graph.roles.push(RoleGrant {
+            key: Pubkey::new_unique(),
+            role: crate::roles::Role::Writer,
+            partition: Some(3),
+        });
+        graph.multisig_signers = vec![Pubkey::new_unique(); 2];
+        graph
+            .set_node_acl(NodeAcl {
+                node_id: 1,
+                owner: Pubkey::new_unique(),
+                owner_only: false,
+                readers: vec![Pubkey::new_unique()],
+            })
+            .unwrap();
+        graph.oracle_keys.push("score".to_string());
+        graph.acyclic_labels.push("Railway".to_string());
+        assert_eq!(graph.serialized_size(), size(&graph));
+    }
 
-        let filter = create_filter("City", "Railway");
-        let result = graph.traverse_out(&[1], &filter, None);
+    #[test]
+    fn test_capacity() {
+        let graph = small_graph();
+        let used = graph.serialized_size();
 
-        assert_eq!(result.len(), 4);
-        assert!(result.contains(&1)); // Start node is included
-        assert!(result.contains(&2));
-        assert!(result.contains(&3));
-        assert!(result.contains(&4));
+        let capacity = graph.capacity(used + 100);
+
+        assert_eq!(capacity.used_bytes, used as u64);
+        assert_eq!(capacity.free_bytes, 100);
+        assert_eq!(capacity.free_node_slots, (MAX_NODES - 5) as u64);
+        assert_eq!(capacity.free_edge_slots, (MAX_EDGES - 5) as u64);
+        assert_eq!(graph.capacity(10).free_bytes, 0);
     }
 
     #[test]
-    fn test_traverse_out_large_graph_simple_highway() {
-        let graph = create_large_test_graph();
+    fn test_graph_space_fits_a_full_graph() {
+        let sizing = GraphSizing {
+            max_nodes: 3,
+            max_edges: 4,
+            max_label_len: 8,
+            max_node_data_len: 10,
+            max_edge_data_len: 6,
+        };
+        let config = sizing.config();
+        assert!(config.is_valid());
+
+        // Every record at its caps, with its own label, and the bounded
+        // header fields full
+        let mut graph = crate::test_utils::empty_graph();
+        graph.config = config;
+        graph.nodes = (0..3)
+            .map(|i| Node {
+                id: i,
+                label: to_label(format!("Node{:04}", i).as_str()),
+                data: vec![0; 10],
+                is_deleted: false,
+                is_protected: false,
+                partition: 0,
+                created_at: 0,
+                updated_at: 0,
+                expires_slot: 0,
+                mint: Some(Pubkey::new_unique()),
+            })
+            .collect();
+        graph.edges = (0..4)
+            .map(|i| Edge {
+                from: 0,
+                to: 1,
+                label: to_label(format!("Edge{:04}", i).as_str()),
+                partition: 0,
+                data: vec![0; 6],
+                created_at: 0,
+            })
+            .collect();
+        graph.edge_offsets = vec![0; 4];
+        graph.adjacency = vec![0; 4];
+        graph.edge_key_index = vec![0; 4];
+        graph.pending_authority = Some(Pubkey::new_unique());
+        graph.name = "n".repeat(MAX_GRAPH_NAME_LEN);
+        graph.roles = (0..MAX_ROLES)
+            .map(|_| RoleGrant {
+                key: Pubkey::new_unique(),
+                role: crate::roles::Role::Reader,
+                partition: Some(0),
+            })
+            .collect();
+        graph.multisig_signers = vec![Pubkey::new_unique(); MAX_MULTISIG_SIGNERS];
+
+        let mut buf = Vec::new();
+        graph.try_serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), graph_space(&config));
+        assert_eq!(sizing.initial_space(), buf.len());
+
+        assert_eq!(
+            GraphSizing::default().initial_space(),
+            MAX_PERMITTED_DATA_INCREASE
+        );
+    }
 
-        let filter = create_filter("Town", "Highway");
-        let result = graph.traverse_out(&[11], &filter, None);
+    #[test]
+    fn test_graph_config_validation() {
+        let config = GraphConfig::default();
+        assert!(config.is_valid());
 
-        assert_eq!(result.len(), 3);
-        assert!(result.contains(&12));
-        assert!(result.contains(&13));
-        assert!(result.contains(&11));
+        let mut buf = Vec::new();
+        config.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), GraphConfig::SERIALIZED_SIZE);
+
+        assert!(!GraphConfig {
+            max_ops: 0,
+            ..config
+        }
+        .is_valid());
+        assert!(!GraphConfig {
+            max_label_len: MAX_LABEL_LEN as u32 + 1,
+            ..config
+        }
+        .is_valid());
+        assert!(!GraphConfig {
+            max_query_cost: 0,
+            ..config
+        }
+        .is_valid());
+        assert!(!GraphConfig {
+            max_frontier: MAX_FRONTIER as u32 + 1,
+            ..config
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_user_graph_seeds() {
+        let mut graph = small_graph();
+        assert_eq!(graph.seed_prefix(), GRAPH_STORE_SEED);
+
+        let user = Pubkey::new_unique();
+        graph.user_graph = true;
+        graph.name = String::new();
+        graph.creator = user;
+
+        let (expected, _) =
+            Pubkey::find_program_address(&[USER_GRAPH_SEED, user.as_ref()], &crate::ID);
+        let (actual, _) = Pubkey::find_program_address(
+            &[
+                graph.seed_prefix(),
+                graph.name.as_bytes(),
+                graph.creator.as_ref(),
+            ],
+            &crate::ID,
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_structure_decode_skips_node_data() {
+        let graph = GraphBuilder::new()
+            .node_with("User", vec![7; 500], 0)
+            .node("User")
+            .node_with("Post", vec![1, 2, 3], 1)
+            .edge(1, 3, "WROTE")
+            .build();
+        let mut data = Vec::new();
+        graph.try_serialize(&mut data).unwrap();
+        data.resize(data.len() + 64, 0);
+
+        let structure = GraphStore::try_deserialize_structure(&data).unwrap();
+        let mut expected = graph.clone();
+        for node in &mut expected.nodes {
+            node.data.clear();
+        }
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        structure.try_serialize(&mut left).unwrap();
+        expected.try_serialize(&mut right).unwrap();
+        assert_eq!(left, right);
+
+        data[0] ^= 0xff;
+        assert!(GraphStore::try_deserialize_structure(&data).is_err());
+        data[0] ^= 0xff;
+        assert!(GraphStore::try_deserialize_structure(&data[..200]).is_err());
     }
 }
@@ -0,0 +1,465 @@
+use crate::graph::{AdjacencyIndex, Edge, GraphStore, Node, NodeId};
+use crate::vm::VmError;
+use anchor_lang::prelude::*;
+use std::result::Result as StdResult;
+
+/// Derived state that `rebuild_index` recomputes, in rebuild order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum IndexKind {
+    /// `node_count` and `edge_count`.
+    Counters,
+    /// `edge_offsets` and `adjacency`.
+    Adjacency,
+    NodeBloom,
+    EdgeKeyIndex,
+}
+
+impl IndexKind {
+    /// The index rebuilt after this one, if any.
+    pub fn next(self) -> Option<IndexKind> {
+        match self {
+            IndexKind::Counters => Some(IndexKind::Adjacency),
+            IndexKind::Adjacency => Some(IndexKind::NodeBloom),
+            IndexKind::NodeBloom => Some(IndexKind::EdgeKeyIndex),
+            IndexKind::EdgeKeyIndex => None,
+        }
+    }
+}
+
+/// The graph's size, counters, nonce and version at one point, so nodes
+/// and edges appended after it can be undone with `rollback_to`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AppendMark {
+    nodes: usize,
+    edges: usize,
+    node_count: u64,
+    edge_count: u64,
+    nonce: NodeId,
+    version: u64,
+}
+
+/// A change to primary node/edge storage that derived indexes must observe.
+#[derive(Debug, Clone, Copy)]
+pub enum Mutation {
+    NodeAdded {
+        node_index: usize,
+    },
+    EdgeAdded {
+        edge_index: u32,
+    },
+    NodeDeleted {
+        node_index: usize,
+    },
+    NodeRestored {
+        node_index: usize,
+    },
+    NodeUpdated {
+        node_index: usize,
+    },
+    /// Nodes and edges were physically removed; the adjacency and edge key
+    /// index were remapped.
+    Removed {
+        nodes: u64,
+        edges: u64,
+    },
+}
+
+impl GraphStore {
+    /// Appends a node and updates every derived index. Returns its slot.
+    pub fn add_node(&mut self, node: Node) -> Option<usize> {
+        self.nodes.push(node);
+        let node_index = self.nodes.len() - 1;
+        self.on_mutation(Mutation::NodeAdded { node_index })?;
+        Some(node_index)
+    }
+
+    /// Appends an edge and updates every derived index. The `from` node must
+    /// already exist. Returns the edge index.
+    pub fn add_edge(&mut self, edge: Edge) -> Option<u32> {
+        self.node_index(edge.from)?;
+        self.edges.push(edge);
+        let edge_index = (self.edges.len() - 1) as u32;
+        self.on_mutation(Mutation::EdgeAdded { edge_index })?;
+        Some(edge_index)
+    }
+
+    /// Single place where indexes, counters and the version are kept in
+    /// sync with `nodes`/`edges`. Every mutation path must end up here.
+    pub(crate) fn on_mutation(&mut self, mutation: Mutation) -> Option<()> {
+        match mutation {
+            Mutation::NodeAdded { node_index } => {
+                if self.edge_offsets.is_empty() {
+                    self.edge_offsets.push(0);
+                }
+                self.edge_offsets
+                    .push(self.adjacency.len() as AdjacencyIndex);
+                self.node_bloom.insert(self.nodes[node_index].id);
+                self.node_count = self.node_count.checked_add(1)?;
+            }
+            Mutation::EdgeAdded { edge_index } => {
+                let from = self.edges[edge_index as usize].from;
+                let from_index = self.node_index(from)?;
+                self.link_outgoing_edge(from_index, edge_index);
+                self.index_edge_key(edge_index);
+                self.edge_count = self.edge_count.checked_add(1)?;
+            }
+            Mutation::NodeDeleted { .. }
+            | Mutation::NodeRestored { .. }
+            | Mutation::NodeUpdated { .. } => {}
+            Mutation::Removed { nodes, edges } => {
+                self.node_count = self.node_count.checked_sub(nodes)?;
+                self.edge_count = self.edge_count.checked_sub(edges)?;
+            }
+        }
+
+        self.bump_version()?;
+        Some(())
+    }
+
+    /// Physically removes the given nodes together with every edge that
+    /// touches them, remapping the adjacency and edge key index in one
+    /// linear pass. The node bloom keeps the removed ids, which only costs
+    /// false positives; compaction rebuilds it once a scan finishes.
+    /// Protected nodes are kept. Returns `(nodes, edges)` removed.
+    pub fn remove_nodes(&mut self, ids: &[NodeId]) -> Option<(u64, u64)> {
+        let ids: std::collections::BTreeSet<NodeId> = ids
+            .iter()
+            .copied()
+            .filter(|id| !self.get_node_by_id(*id).is_some_and(|n| n.is_protected))
+            .collect();
+        let removed_nodes: Vec<bool> = self.nodes.iter().map(|n| ids.contains(&n.id)).collect();
+        let mut kept_edges = 0;
+        let edge_map: Vec<Option<u32>> = self
+            .edges
+            .iter()
+            .map(|e| {
+                let kept = !ids.contains(&e.from) && !ids.contains(&e.to);
+                kept_edges += kept as u32;
+                kept.then(|| kept_edges - 1)
+            })
+            .collect();
+        let nodes_before = self.nodes.len();
+        let edges_before = self.edges.len();
+
+        self.nodes.retain(|n| !ids.contains(&n.id));
+        self.edges
+            .retain(|e| !ids.contains(&e.from) && !ids.contains(&e.to));
+
+        let nodes = (nodes_before - self.nodes.len()) as u64;
+        let edges = (edges_before - self.edges.len()) as u64;
+        if nodes == 0 && edges == 0 {
+            return Some((0, 0));
+        }
+
+        self.remap_indexes(&removed_nodes, &edge_map);
+        self.on_mutation(Mutation::Removed { nodes, edges })?;
+        Some((nodes, edges))
+    }
+
+    /// Copies up to `max_items` entries of `source` (its nodes, then its
+    /// edges) starting at `cursor`, keeping node ids. Every entry is held to
+    /// this graph's limits and acyclic labels, as a write would be. Returns
+    /// the cursor of the next chunk, or `None` once the copy is complete.
+    pub fn copy_chunk_from(
+        &mut self,
+        source: &GraphStore,
+        cursor: usize,
+        max_items: usize,
+    ) -> StdResult<Option<u32>, VmError> {
+        let total = source.nodes.len() + source.edges.len();
+        let end = cursor.saturating_add(max_items).min(total);
+        for i in cursor..end {
+            if let Some(node) = source.nodes.get(i) {
+                if self.nodes.len() >= self.config.max_nodes as usize {
+                    return Err(VmError::GraphLimitExceeded);
+                }
+                if node.label.len() > self.config.max_label_len as usize {
+                    return Err(VmError::LabelTooLong);
+                }
+                if node.data.len() > self.config.max_node_data_len as usize {
+                    return Err(VmError::DataTooLarge);
+                }
+                self.add_node(node.clone()).ok_or(VmError::Overflow)?;
+                // The copy stays as restricted as the original
+                if let Some(acl) = source.node_acl(node.id) {
+                    self.set_node_acl(acl.clone()).ok_or(VmError::Overflow)?;
+                }
+            } else {
+                let edge = &source.edges[i - source.nodes.len()];
+                if self.edges.len() >= self.config.max_edges as usize {
+                    return Err(VmError::GraphLimitExceeded);
+                }
+                if edge.label.len() > self.config.max_label_len as usize {
+                    return Err(VmError::LabelTooLong);
+                }
+                if edge.data.len() > self.config.max_edge_data_len as usize {
+                    return Err(VmError::DataTooLarge);
+                }
+                if self.closes_cycle(edge.from, edge.to, &edge.label) {
+                    return Err(VmError::CycleDetected);
+                }
+                self.add_edge(edge.clone()).ok_or(VmError::NodeNotFound)?;
+            }
+        }
+        self.nonce = self.nonce.max(source.nonce);
+
+        Ok((end < total).then_some(end as u32))
+    }
+
+    /// Recomputes one derived index from primary node/edge storage, so a
+    /// large graph can be repaired one index per transaction.
+    pub fn rebuild_index(&mut self, kind: IndexKind) {
+        match kind {
+            IndexKind::Counters => {
+                self.node_count = self.nodes.len() as u64;
+                self.edge_count = self.edges.len() as u64;
+            }
+            IndexKind::Adjacency => self.rebuild_adjacency(),
+            IndexKind::NodeBloom => self.rebuild_node_bloom(),
+            IndexKind::EdgeKeyIndex => self.rebuild_edge_key_index(),
+        }
+    }
+
+    /// Keeps only the first `nodes` nodes and `edges` edges, undoing later
+    /// appends, and rebuilds the counters and indexes.
+    pub fn truncate_to(&mut self, nodes: usize, edges: usize) {
+        self.nodes.truncate(nodes);
+        self.edges.truncate(edges);
+        self.rebuild_index(IndexKind::Counters);
+        self.rebuild_indexes();
+    }
+
+    pub fn append_mark(&self) -> AppendMark {
+        AppendMark {
+            nodes: self.nodes.len(),
+            edges: self.edges.len(),
+            node_count: self.node_count,
+            edge_count: self.edge_count,
+            nonce: self.nonce,
+            version: self.version,
+        }
+    }
+
+    /// Undoes every node and edge appended since `mark`, with the ids they
+    /// took and the version bumps they made. Changes to nodes that were
+    /// already there are not undone.
+    pub fn rollback_to(&mut self, mark: AppendMark) {
+        if self.append_mark() == mark {
+            return;
+        }
+        self.truncate_to(mark.nodes, mark.edges);
+        self.node_count = mark.node_count;
+        self.edge_count = mark.edge_count;
+        self.nonce = mark.nonce;
+        self.version = mark.version;
+    }
+
+    /// Recomputes every derived index from primary node/edge storage.
+    pub fn rebuild_indexes(&mut self) {
+        self.rebuild_adjacency();
+        self.rebuild_node_bloom();
+        self.rebuild_edge_key_index();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{assert_indexes_consistent, empty_graph, GraphBuilder};
+
+    /// `records` rebuilt one `add_node`/`add_edge` at a time.
+    fn replay(records: &GraphStore) -> GraphStore {
+        let mut graph = empty_graph();
+        for node in &records.nodes {
+            graph.add_node(node.clone()).unwrap();
+        }
+        for edge in &records.edges {
+            graph.add_edge(edge.clone()).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn test_add_node_updates_indexes() {
+        let records = GraphBuilder::new().node("User").build();
+        let mut graph = empty_graph();
+
+        assert_eq!(graph.add_node(records.nodes[0].clone()), Some(0));
+
+        assert_eq!(graph.node_count, 1);
+        assert_eq!(graph.version, 1);
+        assert_eq!(graph.edge_offsets, vec![0, 0]);
+        assert!(graph.node_bloom.might_contain(1));
+    }
+
+    #[test]
+    fn test_add_edge_updates_indexes() {
+        let graph = replay(
+            &GraphBuilder::new()
+                .node("User")
+                .node("User")
+                .edge(1, 2, "FOLLOWS")
+                .build(),
+        );
+
+        assert_eq!(graph.edge_count, 1);
+        assert_eq!(graph.version, 3);
+        assert_eq!(graph.outgoing_edge_indices(0), &[0]);
+        assert!(graph.has_edge(1, 2, "FOLLOWS"));
+    }
+
+    #[test]
+    fn test_add_edge_from_missing_node() {
+        let records = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .edge(1, 2, "FOLLOWS")
+            .build();
+        let mut graph = empty_graph();
+
+        assert_eq!(graph.add_edge(records.edges[0].clone()), None);
+        assert!(graph.edges.is_empty());
+    }
+
+    #[test]
+    fn test_incremental_indexes_match_rebuild() {
+        let built = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .edge(3, 1, "FOLLOWS")
+            .edge(1, 2, "FOLLOWS")
+            .edge(3, 4, "FOLLOWS")
+            .edge(1, 4, "FOLLOWS")
+            .build();
+
+        let graph = replay(&built);
+
+        assert_eq!(graph.edge_offsets, built.edge_offsets);
+        assert_eq!(graph.adjacency, built.adjacency);
+        assert_eq!(graph.edge_key_index, built.edge_key_index);
+        assert_eq!(graph.node_bloom.bits, built.node_bloom.bits);
+    }
+
+    #[test]
+    fn test_remove_nodes_drops_touching_edges() {
+        let mut graph = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("User")
+            .edge(1, 2, "FOLLOWS")
+            .edge(2, 3, "FOLLOWS")
+            .edge(1, 3, "FOLLOWS")
+            .build();
+
+        assert_eq!(graph.remove_nodes(&[2]), Some((1, 2)));
+
+        assert_eq!(graph.node_count, 2);
+        assert_eq!(graph.edge_count, 1);
+        assert!(graph.get_node_by_id(2).is_none());
+        assert_eq!(graph.find_edge(1, 3, "FOLLOWS"), Some(0));
+        assert_eq!(graph.outgoing_edge_indices(0), &[0]);
+        assert_indexes_consistent(&graph);
+    }
+
+    #[test]
+    fn test_remove_nodes_noop_keeps_version() {
+        let mut graph = GraphBuilder::new().node("User").build();
+        let version = graph.version;
+
+        assert_eq!(graph.remove_nodes(&[42]), Some((0, 0)));
+        assert_eq!(graph.version, version);
+    }
+
+    #[test]
+    fn test_remove_nodes_keeps_protected() {
+        let mut graph = GraphBuilder::new().node("User").node("User").build();
+        graph.set_node_protected(1, true);
+
+        assert_eq!(graph.remove_nodes(&[1, 2]), Some((1, 0)));
+        assert!(graph.get_node_by_id(1).is_some());
+        assert!(graph.get_node_by_id(2).is_none());
+    }
+
+    #[test]
+    fn test_copy_chunk_from() {
+        let source = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("User")
+            .edge(1, 2, "FOLLOWS")
+            .edge(2, 3, "FOLLOWS")
+            .deleted(3)
+            .build();
+
+        let mut copy = empty_graph();
+        assert_eq!(copy.copy_chunk_from(&source, 0, 2).unwrap(), Some(2));
+        assert_eq!(copy.copy_chunk_from(&source, 2, 2).unwrap(), Some(4));
+        assert_eq!(copy.copy_chunk_from(&source, 4, 2).unwrap(), None);
+
+        assert_eq!(copy.node_count, 3);
+        assert_eq!(copy.edge_count, 2);
+        assert_eq!(copy.nonce, 4);
+        assert!(copy.nodes[2].is_deleted);
+        assert!(copy.has_edge(2, 3, "FOLLOWS"));
+        assert_eq!(copy.edge_offsets, source.edge_offsets);
+        assert_eq!(copy.edge_key_index, source.edge_key_index);
+    }
+
+    #[test]
+    fn test_copy_chunk_from_holds_copy_to_its_limits() {
+        let source = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .edge(1, 2, "DEPENDS")
+            .edge(2, 1, "DEPENDS")
+            .build();
+
+        let mut small = empty_graph();
+        small.config.max_nodes = 1;
+        assert!(matches!(
+            small.copy_chunk_from(&source, 0, 4),
+            Err(VmError::GraphLimitExceeded)
+        ));
+
+        let mut acyclic = empty_graph();
+        acyclic.acyclic_labels.push("DEPENDS".to_string());
+        assert!(matches!(
+            acyclic.copy_chunk_from(&source, 0, 4),
+            Err(VmError::CycleDetected)
+        ));
+        assert!(acyclic.has_edge(1, 2, "DEPENDS"));
+    }
+
+    #[test]
+    fn test_rebuild_index_repairs_corruption() {
+        let mut graph = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("User")
+            .edge(2, 1, "FOLLOWS")
+            .edge(1, 3, "FOLLOWS")
+            .build();
+        let offsets = graph.edge_offsets.clone();
+        let adjacency = graph.adjacency.clone();
+        let key_index = graph.edge_key_index.clone();
+
+        graph.node_count = 0;
+        graph.adjacency.reverse();
+        graph.edge_key_index.clear();
+        graph.node_bloom.clear();
+
+        let mut kind = Some(IndexKind::Counters);
+        while let Some(k) = kind {
+            graph.rebuild_index(k);
+            kind = k.next();
+        }
+
+        assert_eq!(graph.node_count, 3);
+        assert_eq!(graph.edge_offsets, offsets);
+        assert_eq!(graph.adjacency, adjacency);
+        assert_eq!(graph.edge_key_index, key_index);
+        assert!(graph.node_bloom.might_contain(2));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::graph::{GraphStore, NodeId, NODE_ID_LEN};
+use crate::vm::{Opcode, TimeField};
+use anchor_lang::prelude::*;
+
+pub const JOURNAL_SEED: &[u8] = b"journal";
+/// Changes kept before the oldest ones are dropped.
+pub const MAX_JOURNAL_ENTRIES: usize = 128;
+
+/// A change to a graph that `Journal::rewind` can undo. Node data updates
+/// are not journaled, so rewound graphs carry current node data.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum JournalChange {
+    /// Nodes and/or edges were appended to storage holding this many.
+    Appended { nodes: u32, edges: u32 },
+    /// A node's soft-delete flag was set to `deleted`.
+    DeletedFlag { node_id: NodeId, deleted: bool },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct JournalEntry {
+    pub slot: u64,
+    pub change: JournalChange,
+}
+
+impl JournalEntry {
+    /// Size of the largest variant.
+    pub const SERIALIZED_SIZE: usize = 8 + 1 + NODE_ID_LEN + 1;
+}
+
+/// Whether a rewound graph answers `ops` as of its slot. Node data, mints
+/// and update times are not journaled, so programs reading them would
+/// answer from current state instead.
+pub fn rewind_answers(ops: &[Opcode]) -> bool {
+    !ops.iter().any(|op| {
+        op.reads_node_data()
+            || matches!(
+                op,
+                Opcode::FilterMint(_)
+                    | Opcode::FilterTime {
+                        field: TimeField::UpdatedAt,
+                        ..
+                    }
+            )
+    })
+}
+
+/// Bounded history of a graph's writes, oldest first, for reconstructing
+/// the graph as of an earlier slot.
+#[account]
+pub struct Journal {
+    pub graph: Pubkey,
+    /// Earliest slot whose state can still be reconstructed.
+    pub since_slot: u64,
+    pub entries: Vec<JournalEntry>,
+    pub bump: u8,
+}
+
+impl Journal {
+    pub const SPACE: usize =
+        8 + 32 + 8 + 4 + MAX_JOURNAL_ENTRIES * JournalEntry::SERIALIZED_SIZE + 1;
+
+    /// Appends a change made at `slot`, dropping the oldest one when full.
+    pub fn record(&mut self, slot: u64, change: JournalChange) {
+        if self.entries.len() == MAX_JOURNAL_ENTRIES {
+            // States before the dropped change can no longer be rebuilt
+            self.since_slot = self.entries.remove(0).slot;
+        }
+        self.entries.push(JournalEntry { slot, change });
+    }
+
+    /// Physically removing nodes shifts storage, so history before `slot`
+    /// is discarded.
+    pub fn reset(&mut self, slot: u64) {
+        self.entries.clear();
+        self.since_slot = slot;
+    }
+
+    /// Undoes on `graph` every change journaled after `slot`, leaving the
+    /// state as of the end of that slot. Returns `None` when history that
+    /// far back is no longer kept.
+    pub fn rewind(&self, graph: &mut GraphStore, slot: u64) -> Option<()> {
+        if slot < self.since_slot {
+            return None;
+        }
+
+        let mut lengths = None;
+        for entry in self.entries.iter().rev().take_while(|e| e.slot > slot) {
+            match entry.change {
+                JournalChange::Appended { nodes, edges } => lengths = Some((nodes, edges)),
+                JournalChange::DeletedFlag { node_id, deleted } => {
+                    graph.set_node_deleted(node_id, !deleted)?;
+                }
+            }
+        }
+        if let Some((nodes, edges)) = lengths {
+            graph.truncate_to(nodes as usize, edges as usize);
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cypher::parse;
+    use crate::graph::{Edge, Node};
+    use crate::lexer::compile_to_opcodes;
+    use crate::test_utils::GraphBuilder;
+
+    /// A root node that has posted once.
+    fn graph_with_root() -> GraphStore {
+        GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .edge(1, 2, "Posted")
+            .build()
+    }
+
+    fn empty_journal() -> Journal {
+        Journal {
+            graph: Pubkey::new_unique(),
+            since_slot: 10,
+            entries: Vec::new(),
+            bump: 255,
+        }
+    }
+
+    fn append_node(graph: &mut GraphStore, journal: &mut Journal, slot: u64) -> NodeId {
+        journal.record(
+            slot,
+            JournalChange::Appended {
+                nodes: graph.nodes.len() as u32,
+                edges: graph.edges.len() as u32,
+            },
+        );
+        let id = graph.next_node_id().unwrap();
+        let (post, posted) = (graph.nodes[1].clone(), graph.edges[0].clone());
+        graph.add_node(Node { id, ..post }).unwrap();
+        graph.add_edge(Edge { to: id, ..posted }).unwrap();
+        id
+    }
+
+    #[test]
+    fn test_rewind_restores_earlier_state() {
+        let mut graph = graph_with_root();
+        let (nodes, edges) = (graph.nodes.len(), graph.edges.len());
+        let mut journal = empty_journal();
+
+        let first = append_node(&mut graph, &mut journal, 20);
+        let second = append_node(&mut graph, &mut journal, 30);
+        graph.set_node_deleted(first, true).unwrap();
+        journal.record(
+            40,
+            JournalChange::DeletedFlag {
+                node_id: first,
+                deleted: true,
+            },
+        );
+
+        let mut at_30 = graph.clone();
+        journal.rewind(&mut at_30, 30).unwrap();
+        assert!(!at_30.get_node_by_id(first).unwrap().is_deleted);
+        assert!(at_30.get_node_by_id(second).is_some());
+
+        let mut at_25 = graph.clone();
+        journal.rewind(&mut at_25, 25).unwrap();
+        assert!(at_25.get_node_by_id(second).is_none());
+        assert_eq!(at_25.edges.len(), edges + 1);
+        assert_eq!(at_25.node_count, nodes as u64 + 1);
+
+        let mut at_10 = graph.clone();
+        journal.rewind(&mut at_10, 10).unwrap();
+        assert_eq!(at_10.nodes.len(), nodes);
+        assert!(journal.rewind(&mut graph.clone(), 9).is_none());
+    }
+
+    #[test]
+    fn test_record_drops_oldest_when_full() {
+        let mut journal = empty_journal();
+        for slot in 0..MAX_JOURNAL_ENTRIES as u64 + 2 {
+            journal.record(100 + slot, JournalChange::Appended { nodes: 0, edges: 0 });
+        }
+        assert_eq!(journal.entries.len(), MAX_JOURNAL_ENTRIES);
+        assert_eq!(journal.since_slot, 101);
+
+        journal.reset(500);
+        assert!(journal.entries.is_empty());
+        assert_eq!(journal.since_slot, 500);
+    }
+
+    #[test]
+    fn test_rewind_answers_only_structural_queries() {
+        let answers = |query: &str| rewind_answers(&compile_to_opcodes(parse(query).unwrap()));
+        assert!(answers("MATCH (n:User) RETURN n LIMIT 5"));
+        assert!(answers("MATCH (a)-[:FOLLOWS]->(b) RETURN b LIMIT 5"));
+        assert!(!answers("MATCH (n:User) RETURN * LIMIT 5"));
+        assert!(!answers("MATCH (n:User) RETURN n.name LIMIT 5"));
+        assert!(!answers(
+            "MATCH (n:User) WHERE n.x BETWEEN 1 AND 2 RETURN n LIMIT 5"
+        ));
+    }
+}
@@ -0,0 +1,490 @@
+//! The `label-table` account layout. Each distinct label is stored once, in
+//! a table between the header and the nodes, and nodes and edges store a
+//! two-byte index into it. Graphs where a few labels cover most nodes and
+//! edges, such as one dominant relationship type, shrink by the label's
+//! length on every record that repeats it.
+//!
+//! In memory nothing changes: `Node::label` and `Edge::label` still hold
+//! the label itself, and the table is rebuilt on every write.
+
+use crate::graph::{GraphStore, Label, NodeId, PartitionId, MAX_EDGES, MAX_NODES};
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use sol_micro_sql_types::graph::label_size;
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Read, Write};
+
+/// Index of a label in the table.
+type LabelIndex = u16;
+const _: () = assert!(MAX_NODES + MAX_EDGES <= LabelIndex::MAX as usize);
+
+/// Distinct labels in first-use order, nodes before edges.
+struct LabelTable<'a> {
+    labels: Vec<&'a Label>,
+    index: BTreeMap<&'a Label, LabelIndex>,
+}
+
+impl<'a> LabelTable<'a> {
+    fn new(graph: &'a GraphStore) -> Self {
+        let mut table = LabelTable {
+            labels: Vec::new(),
+            index: BTreeMap::new(),
+        };
+        let labels = graph.nodes.iter().map(|n| &n.label);
+        for label in labels.chain(graph.edges.iter().map(|e| &e.label)) {
+            if !table.index.contains_key(label) {
+                table.index.insert(label, table.labels.len() as LabelIndex);
+                table.labels.push(label);
+            }
+        }
+        table
+    }
+
+    fn index_of(&self, label: &Label) -> LabelIndex {
+        self.index[label]
+    }
+}
+
+fn read_label<R: Read>(reader: &mut R, labels: &[Label]) -> std::io::Result<Label> {
+    let index = LabelIndex::deserialize_reader(reader)?;
+    labels
+        .get(index as usize)
+        .cloned()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "label index out of range"))
+}
+
+/// Reads past `len` bytes without keeping them.
+fn skip<R: Read>(reader: &mut R, mut len: usize) -> std::io::Result<()> {
+    let mut chunk = [0u8; 64];
+    while len > 0 {
+        let n = len.min(chunk.len());
+        reader.read_exact(&mut chunk[..n])?;
+        len -= n;
+    }
+    Ok(())
+}
+
+/// Bytes the label table, nodes and edges take in the account.
+pub(crate) fn records_size(graph: &GraphStore) -> usize {
+    let table = LabelTable::new(graph);
+    let label_bytes = |label: &Label| label_size(label) - std::mem::size_of::<LabelIndex>();
+    (4 + table.labels.iter().map(|l| label_size(l)).sum::<usize>())
+        + (4 + graph
+            .nodes
+            .iter()
+            .map(|n| n.serialized_size() - label_bytes(&n.label))
+            .sum::<usize>())
+        + (4 + graph
+            .edges
+            .iter()
+            .map(|e| e.serialized_size() - label_bytes(&e.label))
+            .sum::<usize>())
+}
+
+impl AnchorSerialize for GraphStore {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let GraphStore {
+            layout_version,
+            authority,
+            node_count,
+            edge_count,
+            nonce,
+            version,
+            nodes,
+            edges,
+            edge_offsets,
+            adjacency,
+            node_bloom,
+            edge_key_index,
+            compaction_cursor,
+            pending_authority,
+            name,
+            creator,
+            bump,
+            roles,
+            restrict_reads,
+            multisig_signers,
+            multisig_threshold,
+            frozen,
+            write_fee_lamports,
+            treasury,
+            emit_match_events,
+            gate_mint,
+            gate_min_amount,
+            config,
+            commit_reveal_writes,
+            user_graph,
+            private,
+            node_acls,
+            max_writes_per_window,
+            rate_window_slots,
+            audit_log,
+            journaled,
+            oracle,
+            oracle_keys,
+            acyclic_labels,
+            clone_source,
+            clone_cursor,
+        } = self;
+        layout_version.serialize(writer)?;
+        authority.serialize(writer)?;
+        node_count.serialize(writer)?;
+        edge_count.serialize(writer)?;
+        nonce.serialize(writer)?;
+        version.serialize(writer)?;
+
+        let table = LabelTable::new(self);
+        table.labels.serialize(writer)?;
+        (nodes.len() as u32).serialize(writer)?;
+        for node in nodes {
+            node.id.serialize(writer)?;
+            table.index_of(&node.label).serialize(writer)?;
+            node.data.serialize(writer)?;
+            node.is_deleted.serialize(writer)?;
+            node.is_protected.serialize(writer)?;
+            node.partition.serialize(writer)?;
+            node.created_at.serialize(writer)?;
+            node.updated_at.serialize(writer)?;
+            node.expires_slot.serialize(writer)?;
+            node.mint.serialize(writer)?;
+        }
+        (edges.len() as u32).serialize(writer)?;
+        for edge in edges {
+            edge.from.serialize(writer)?;
+            edge.to.serialize(writer)?;
+            table.index_of(&edge.label).serialize(writer)?;
+            edge.partition.serialize(writer)?;
+            edge.data.serialize(writer)?;
+            edge.created_at.serialize(writer)?;
+        }
+
+        edge_offsets.serialize(writer)?;
+        adjacency.serialize(writer)?;
+        node_bloom.serialize(writer)?;
+        edge_key_index.serialize(writer)?;
+        compaction_cursor.serialize(writer)?;
+        pending_authority.serialize(writer)?;
+        name.serialize(writer)?;
+        creator.serialize(writer)?;
+        bump.serialize(writer)?;
+        roles.serialize(writer)?;
+        restrict_reads.serialize(writer)?;
+        multisig_signers.serialize(writer)?;
+        multisig_threshold.serialize(writer)?;
+        frozen.serialize(writer)?;
+        write_fee_lamports.serialize(writer)?;
+        treasury.serialize(writer)?;
+        emit_match_events.serialize(writer)?;
+        gate_mint.serialize(writer)?;
+        gate_min_amount.serialize(writer)?;
+        config.serialize(writer)?;
+        commit_reveal_writes.serialize(writer)?;
+        user_graph.serialize(writer)?;
+        private.serialize(writer)?;
+        node_acls.serialize(writer)?;
+        max_writes_per_window.serialize(writer)?;
+        rate_window_slots.serialize(writer)?;
+        audit_log.serialize(writer)?;
+        journaled.serialize(writer)?;
+        oracle.serialize(writer)?;
+        oracle_keys.serialize(writer)?;
+        acyclic_labels.serialize(writer)?;
+        clone_source.serialize(writer)?;
+        clone_cursor.serialize(writer)
+    }
+}
+
+impl AnchorDeserialize for GraphStore {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        read_graph(reader, true)
+    }
+}
+
+/// Decodes a graph in this layout, leaving every node's `data` empty
+/// unless `node_data` is set.
+pub(crate) fn read_graph<R: Read>(reader: &mut R, node_data: bool) -> std::io::Result<GraphStore> {
+    let layout_version = u8::deserialize_reader(reader)?;
+    let authority = Pubkey::deserialize_reader(reader)?;
+    let node_count = u64::deserialize_reader(reader)?;
+    let edge_count = u64::deserialize_reader(reader)?;
+    let nonce = NodeId::deserialize_reader(reader)?;
+    let version = u64::deserialize_reader(reader)?;
+
+    let labels = Vec::<Label>::deserialize_reader(reader)?;
+    let count = u32::deserialize_reader(reader)? as usize;
+    let mut nodes = Vec::with_capacity(count.min(MAX_NODES));
+    for _ in 0..count {
+        let id = NodeId::deserialize_reader(reader)?;
+        let label = read_label(reader, &labels)?;
+        let data = if node_data {
+            Vec::<u8>::deserialize_reader(reader)?
+        } else {
+            let len = u32::deserialize_reader(reader)? as usize;
+            skip(reader, len)?;
+            Vec::new()
+        };
+        nodes.push(crate::graph::Node {
+            id,
+            label,
+            data,
+            is_deleted: AnchorDeserialize::deserialize_reader(reader)?,
+            is_protected: AnchorDeserialize::deserialize_reader(reader)?,
+            partition: AnchorDeserialize::deserialize_reader(reader)?,
+            created_at: AnchorDeserialize::deserialize_reader(reader)?,
+            updated_at: AnchorDeserialize::deserialize_reader(reader)?,
+            expires_slot: AnchorDeserialize::deserialize_reader(reader)?,
+            mint: AnchorDeserialize::deserialize_reader(reader)?,
+        });
+    }
+    let count = u32::deserialize_reader(reader)? as usize;
+    let mut edges = Vec::with_capacity(count.min(MAX_EDGES));
+    for _ in 0..count {
+        edges.push(crate::graph::Edge {
+            from: AnchorDeserialize::deserialize_reader(reader)?,
+            to: AnchorDeserialize::deserialize_reader(reader)?,
+            label: read_label(reader, &labels)?,
+            partition: PartitionId::deserialize_reader(reader)?,
+            data: AnchorDeserialize::deserialize_reader(reader)?,
+            created_at: AnchorDeserialize::deserialize_reader(reader)?,
+        });
+    }
+
+    Ok(GraphStore {
+        layout_version,
+        authority,
+        node_count,
+        edge_count,
+        nonce,
+        version,
+        nodes,
+        edges,
+        edge_offsets: AnchorDeserialize::deserialize_reader(reader)?,
+        adjacency: AnchorDeserialize::deserialize_reader(reader)?,
+        node_bloom: AnchorDeserialize::deserialize_reader(reader)?,
+        edge_key_index: AnchorDeserialize::deserialize_reader(reader)?,
+        compaction_cursor: AnchorDeserialize::deserialize_reader(reader)?,
+        pending_authority: AnchorDeserialize::deserialize_reader(reader)?,
+        name: AnchorDeserialize::deserialize_reader(reader)?,
+        creator: AnchorDeserialize::deserialize_reader(reader)?,
+        bump: AnchorDeserialize::deserialize_reader(reader)?,
+        roles: AnchorDeserialize::deserialize_reader(reader)?,
+        restrict_reads: AnchorDeserialize::deserialize_reader(reader)?,
+        multisig_signers: AnchorDeserialize::deserialize_reader(reader)?,
+        multisig_threshold: AnchorDeserialize::deserialize_reader(reader)?,
+        frozen: AnchorDeserialize::deserialize_reader(reader)?,
+        write_fee_lamports: AnchorDeserialize::deserialize_reader(reader)?,
+        treasury: AnchorDeserialize::deserialize_reader(reader)?,
+        emit_match_events: AnchorDeserialize::deserialize_reader(reader)?,
+        gate_mint: AnchorDeserialize::deserialize_reader(reader)?,
+        gate_min_amount: AnchorDeserialize::deserialize_reader(reader)?,
+        config: AnchorDeserialize::deserialize_reader(reader)?,
+        commit_reveal_writes: AnchorDeserialize::deserialize_reader(reader)?,
+        user_graph: AnchorDeserialize::deserialize_reader(reader)?,
+        private: AnchorDeserialize::deserialize_reader(reader)?,
+        node_acls: AnchorDeserialize::deserialize_reader(reader)?,
+        max_writes_per_window: AnchorDeserialize::deserialize_reader(reader)?,
+        rate_window_slots: AnchorDeserialize::deserialize_reader(reader)?,
+        audit_log: AnchorDeserialize::deserialize_reader(reader)?,
+        journaled: AnchorDeserialize::deserialize_reader(reader)?,
+        oracle: AnchorDeserialize::deserialize_reader(reader)?,
+        oracle_keys: AnchorDeserialize::deserialize_reader(reader)?,
+        acyclic_labels: AnchorDeserialize::deserialize_reader(reader)?,
+        clone_source: AnchorDeserialize::deserialize_reader(reader)?,
+        clone_cursor: AnchorDeserialize::deserialize_reader(reader)?,
+    })
+}
+
+// What `#[account]` generates, around the hand-written encoding above
+
+impl Discriminator for GraphStore {
+    const DISCRIMINATOR: &'static [u8] = &[147, 57, 227, 168, 203, 10, 236, 3];
+}
+
+impl Owner for GraphStore {
+    fn owner() -> Pubkey {
+        crate::ID
+    }
+}
+
+impl AccountSerialize for GraphStore {
+    fn try_serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer
+            .write_all(Self::DISCRIMINATOR)
+            .and_then(|_| self.serialize(writer))
+            .map_err(|_| ErrorCode::AccountDidNotSerialize.into())
+    }
+}
+
+impl AccountDeserialize for GraphStore {
+    fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+        if buf.len() < Self::DISCRIMINATOR.len() {
+            return Err(ErrorCode::AccountDiscriminatorNotFound.into());
+        }
+        if buf[..Self::DISCRIMINATOR.len()] != *Self::DISCRIMINATOR {
+            return Err(
+                error!(ErrorCode::AccountDiscriminatorMismatch).with_account_name("GraphStore")
+            );
+        }
+        Self::try_deserialize_unchecked(buf)
+    }
+
+    fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+        let mut data = &buf[Self::DISCRIMINATOR.len()..];
+        Self::deserialize(&mut data).map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
+    }
+}
+
+/// The stored layout, spelled out as plain structs so the IDL describes
+/// the account as it is encoded rather than as it is held in memory.
+#[cfg(feature = "idl-build")]
+mod idl {
+    use super::*;
+    use crate::bloom::NodeBloom;
+    use crate::graph::{AdjacencyIndex, GraphConfig};
+    use crate::roles::{NodeAcl, RoleGrant};
+    use anchor_lang::idl::types::IdlTypeDef;
+    use anchor_lang::idl::IdlBuild;
+
+    #[derive(AnchorSerialize)]
+    pub struct StoredNode {
+        pub id: NodeId,
+        pub label: LabelIndex,
+        pub data: Vec<u8>,
+        pub is_deleted: bool,
+        pub is_protected: bool,
+        pub partition: PartitionId,
+        pub created_at: i64,
+        pub updated_at: i64,
+        pub expires_slot: u64,
+        pub mint: Option<Pubkey>,
+    }
+
+    #[derive(AnchorSerialize)]
+    pub struct StoredEdge {
+        pub from: NodeId,
+        pub to: NodeId,
+        pub label: LabelIndex,
+        pub partition: PartitionId,
+        pub data: Vec<u8>,
+        pub created_at: i64,
+    }
+
+    #[derive(AnchorSerialize)]
+    pub struct StoredGraphStore {
+        pub layout_version: u8,
+        pub authority: Pubkey,
+        pub node_count: u64,
+        pub edge_count: u64,
+        pub nonce: NodeId,
+        pub version: u64,
+        pub labels: Vec<Label>,
+        pub nodes: Vec<StoredNode>,
+        pub edges: Vec<StoredEdge>,
+        pub edge_offsets: Vec<AdjacencyIndex>,
+        pub adjacency: Vec<AdjacencyIndex>,
+        pub node_bloom: NodeBloom,
+        pub edge_key_index: Vec<u32>,
+        pub compaction_cursor: u32,
+        pub pending_authority: Option<Pubkey>,
+        pub name: String,
+        pub creator: Pubkey,
+        pub bump: u8,
+        pub roles: Vec<RoleGrant>,
+        pub restrict_reads: bool,
+        pub multisig_signers: Vec<Pubkey>,
+        pub multisig_threshold: u8,
+        pub frozen: bool,
+        pub write_fee_lamports: u64,
+        pub treasury: Pubkey,
+        pub emit_match_events: bool,
+        pub gate_mint: Pubkey,
+        pub gate_min_amount: u64,
+        pub config: GraphConfig,
+        pub commit_reveal_writes: bool,
+        pub user_graph: bool,
+        pub private: bool,
+        pub node_acls: Vec<NodeAcl>,
+        pub max_writes_per_window: u32,
+        pub rate_window_slots: u64,
+        pub audit_log: bool,
+        pub journaled: bool,
+        pub oracle: Pubkey,
+        pub oracle_keys: Vec<String>,
+        pub acyclic_labels: Vec<String>,
+        pub clone_source: Pubkey,
+        pub clone_cursor: u32,
+    }
+
+    impl IdlBuild for GraphStore {
+        fn create_type() -> Option<IdlTypeDef> {
+            StoredGraphStore::create_type().map(|ty| IdlTypeDef {
+                name: Self::get_full_path(),
+                ..ty
+            })
+        }
+
+        fn insert_types(types: &mut std::collections::BTreeMap<String, IdlTypeDef>) {
+            StoredGraphStore::insert_types(types);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::to_label;
+    use crate::test_utils::{large_graph, small_graph};
+
+    #[test]
+    fn test_discriminator_matches_account_attribute() {
+        let hash = solana_sha256_hasher::hash(b"account:GraphStore");
+        assert_eq!(GraphStore::DISCRIMINATOR, &hash.to_bytes()[..8]);
+    }
+
+    #[test]
+    fn test_shared_labels_stored_once() {
+        let mut graph = large_graph();
+        for edge in &mut graph.edges {
+            edge.label = to_label("Road");
+        }
+        let label = to_label("Road");
+        assert_eq!(
+            LabelTable::new(&graph)
+                .labels
+                .iter()
+                .filter(|l| ***l == label)
+                .count(),
+            1
+        );
+
+        let mut buf = Vec::new();
+        graph.try_serialize(&mut buf).unwrap();
+        assert_eq!(graph.serialized_size(), buf.len());
+
+        let decoded = GraphStore::try_deserialize(&mut &buf[..]).unwrap();
+        assert!(decoded.edges.iter().all(|e| e.label == label));
+        for (decoded, node) in decoded.nodes.iter().zip(&graph.nodes) {
+            assert_eq!(decoded.label, node.label);
+            assert_eq!(decoded.data, node.data);
+        }
+    }
+
+    #[test]
+    fn test_rejects_label_index_out_of_range() {
+        let graph = small_graph();
+        let mut buf = Vec::new();
+        graph.try_serialize(&mut buf).unwrap();
+
+        let header = 8 + 1 + 32 + 8 + 8 + size_of::<NodeId>() + 8;
+        let table = 4 + LabelTable::new(&graph)
+            .labels
+            .iter()
+            .map(|l| label_size(l))
+            .sum::<usize>();
+        // The first node's label index follows the node count and its id
+        let at = header + table + 4 + size_of::<NodeId>();
+        buf[at..at + 2].copy_from_slice(&LabelIndex::MAX.to_le_bytes());
+
+        assert!(GraphStore::try_deserialize(&mut &buf[..]).is_err());
+    }
+}
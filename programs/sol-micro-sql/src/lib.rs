@@ -1,13 +1,63 @@
-mod cypher;
-mod graph;
-mod lexer;
-mod vm;
+pub mod attrs;
+pub mod audit;
+pub mod bloom;
+pub mod commit;
+pub mod compaction;
+pub mod compressed;
+pub mod cursor;
+#[cfg(feature = "export")]
+pub mod export;
+pub mod graph;
+pub mod hooks;
+pub mod journal;
+#[cfg(feature = "label-table")]
+mod label_table;
+pub mod lint;
+pub mod metadata;
+pub mod migration;
+pub mod proposal;
+pub mod rate_limit;
+pub mod receipt;
+pub mod roles;
+pub mod session;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod vm;
 
-use crate::cypher::{parse, CypherQuery};
-use crate::graph::GraphStore;
+pub use sol_micro_sql_lang::{cypher, explain, lexer, MAX_OPS, MAX_QUERY_LEN};
+
+use crate::attrs::{set_attribute, AttrUpdate, AttrValue, MAX_ORACLE_KEYS};
+use crate::audit::{AuditEntry, AuditLog, AuditOp, AUDIT_LOG_SEED};
+use crate::bloom::NodeBloom;
+use crate::commit::{commitment_hash, WriteCommitment, COMMIT_SEED};
+use crate::compaction::{CompactionProgress, LabelDeletion};
+use crate::compressed::{leaf_hash, CompressedNodes, COMPRESSED_NODES_SEED};
+use crate::cursor::{QueryCursor, CURSOR_SEED};
+use crate::cypher::{parse, parse_with_params, validate, CypherQuery, ParamValue};
+use crate::graph::{
+    to_label, Edge, EdgeRef, GraphCapacity, GraphConfig, GraphSizing, GraphStore, Node, NodeId,
+    NodePage, GRAPH_LAYOUT_VERSION, GRAPH_STORE_SEED, MAX_ACYCLIC_LABELS, MAX_GRAPH_NAME_LEN,
+    USER_GRAPH_SEED,
+};
+use crate::hooks::IndexKind;
+use crate::journal::{rewind_answers, Journal, JournalChange, JOURNAL_SEED};
 use crate::lexer::compile_to_opcodes;
-use crate::vm::{Vm, VmError, VmResult};
+use crate::metadata::{GraphMetadata, METADATA_SEED};
+use crate::migration::{read_header, upgrade, MigrationError};
+use crate::proposal::{Proposal, MAX_PROPOSAL_OPS, PROPOSAL_SEED};
+use crate::rate_limit::{WriterRate, WRITER_RATE_SEED};
+use crate::receipt::QueryReceipt;
+use crate::roles::{NodeAcl, Role, MAX_ACL_READERS, MAX_MULTISIG_SIGNERS};
+use crate::session::{SessionKey, MAX_SESSION_LABELS, SESSION_SEED};
+use crate::vm::{
+    check_program, estimate_program, program_cost, Opcode, QueryEstimate, QueryPage, QueryPlan, Vm,
+    VmError, VmResult,
+};
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use anchor_lang::solana_program::program::MAX_RETURN_DATA;
+use anchor_lang::system_program;
+use anchor_spl::token_interface::{Mint, TokenAccount};
 
 declare_id!("9jJqjrdiJTYo9vYftpxJoLrLeuBn2qEQEX8Au1P8r1Gj");
 
@@ -15,90 +65,4001 @@ declare_id!("9jJqjrdiJTYo9vYftpxJoLrLeuBn2qEQEX8Au1P8r1Gj");
 pub mod sol_micro_sql {
     use super::*;
 
-    pub fn initialize_graph(ctx: Context<InitializeGraph>) -> Result<()> {
+    /// Creates the graph `name`, with an account sized for `sizing` and
+    /// record limits taken from it.
+    pub fn initialize_graph(
+        ctx: Context<InitializeGraph>,
+        name: String,
+        sizing: GraphSizing,
+    ) -> Result<()> {
+        require!(name.len() <= MAX_GRAPH_NAME_LEN, ErrorCode::NameTooLong);
+        let config = sizing.config();
+        require!(config.is_valid(), ErrorCode::InvalidConfig);
+
+        let graph = &mut ctx.accounts.graph_store;
+        init_graph_store(
+            graph,
+            name,
+            ctx.accounts.authority.key(),
+            ctx.bumps.graph_store,
+            false,
+            config,
+        );
+
+        msg!(
+            "GraphStore '{}' initialized by: {:?}",
+            graph.name,
+            ctx.accounts.authority.key()
+        );
+        Ok(())
+    }
+
+    /// Creates the private graph of `user` at `[USER_GRAPH_SEED, user]`,
+    /// with the user as authority. It runs on the same query engine and
+    /// instructions as shared graphs.
+    pub fn initialize_user_graph(
+        ctx: Context<InitializeUserGraph>,
+        sizing: GraphSizing,
+    ) -> Result<()> {
+        let config = sizing.config();
+        require!(config.is_valid(), ErrorCode::InvalidConfig);
+
+        let user = ctx.accounts.user.key();
+        let graph = &mut ctx.accounts.graph_store;
+        init_graph_store(
+            graph,
+            String::new(),
+            user,
+            ctx.bumps.graph_store,
+            true,
+            config,
+        );
+
+        msg!("User graph initialized for: {:?}", user);
+        Ok(())
+    }
+
+    /// Runs a query. The `VmResult` is Borsh-encoded into the transaction
+    /// return data (see `VmResult` for the layout), so clients and CPI callers
+    /// can read it with `get_return_data` instead of parsing logs.
+    pub fn execute_query(
+        ctx: Context<ExecuteQuery>,
+        query: String,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Result<VmResult> {
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        run_query(
+            ctx,
+            query.len(),
+            cypher_query,
+            expected_version,
+            partition,
+            false,
+        )
+    }
+
+    /// Like `execute_query`, but returns a `QueryReceipt` hashing the graph
+    /// version, query and result, and emits the hash so downstream programs
+    /// and off-chain verifiers can tie the result to that graph state.
+    pub fn execute_query_with_receipt(
+        ctx: Context<ExecuteQuery>,
+        query: String,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Result<QueryReceipt> {
+        let version = ctx.accounts.graph_store.version;
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        let result = run_query(
+            ctx,
+            query.len(),
+            cypher_query,
+            expected_version,
+            partition,
+            false,
+        )?;
+
+        let receipt = QueryReceipt::new(version, &query, result);
+        require!(
+            receipt.serialized_size() <= MAX_RETURN_DATA,
+            ErrorCode::ReturnDataTooLarge
+        );
+        emit!(QueryReceiptIssued {
+            version,
+            query_hash: receipt.query_hash,
+            receipt_hash: receipt.receipt_hash,
+        });
+        Ok(receipt)
+    }
+
+    /// Like `execute_query`, with `$1`, `$2`, ... placeholders bound to
+    /// typed `params` instead of being spliced into the query text.
+    pub fn execute_query_with_params(
+        ctx: Context<ExecuteQuery>,
+        query: String,
+        params: Vec<ParamValue>,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Result<VmResult> {
+        let cypher_query =
+            parse_with_params(&query, &params).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        run_query(
+            ctx,
+            query.len(),
+            cypher_query,
+            expected_version,
+            partition,
+            false,
+        )
+    }
+
+    /// Like `execute_query`, but takes the query already parsed, so clients
+    /// that build queries in code skip tokenizing and parsing on-chain. It is
+    /// checked for what the parser would reject and compiled as usual.
+    pub fn execute_parsed_query(
+        ctx: Context<ExecuteQuery>,
+        query: CypherQuery,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Result<VmResult> {
+        validate(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        // No text to bound; the transaction size limits the encoded query
+        run_query(ctx, 0, query, expected_version, partition, false)
+    }
+
+    /// Commits to a write by its `commitment_hash` without revealing it.
+    /// Only Writers may commit; the write itself is checked on reveal.
+    pub fn commit_write(ctx: Context<CommitWrite>, hash: [u8; 32]) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.any_has_role(
+                &signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts),
+                Role::Writer
+            ),
+            ErrorCode::Unauthorized
+        );
+
+        let commitment = &mut ctx.accounts.commitment;
+        commitment.committer = ctx.accounts.authority.key();
+        commitment.hash = hash;
+        commitment.slot = Clock::get()?.slot;
+        commitment.bump = ctx.bumps.commitment;
+
+        emit!(WriteCommitted {
+            committer: commitment.committer,
+            hash,
+            slot: commitment.slot,
+        });
+        Ok(())
+    }
+
+    /// Reveals and runs a committed write in a later slot than its commit,
+    /// closing the commitment. Writes apply in reveal order.
+    pub fn reveal_write(
+        ctx: Context<ExecuteQuery>,
+        query: String,
+        salt: [u8; 32],
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Result<VmResult> {
+        let authority = ctx.accounts.authority.key();
+        let commitment = ctx
+            .accounts
+            .commitment
+            .as_ref()
+            .ok_or(ErrorCode::CommitmentMismatch)?;
+        require!(
+            commitment.hash == commitment_hash(&query, &salt, &authority),
+            ErrorCode::CommitmentMismatch
+        );
+        require!(
+            Clock::get()?.slot > commitment.slot,
+            ErrorCode::RevealTooEarly
+        );
+        let hash = commitment.hash;
+        commitment.close(ctx.accounts.authority.to_account_info())?;
+
+        emit!(WriteRevealed {
+            committer: authority,
+            hash,
+        });
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        run_query(
+            ctx,
+            query.len(),
+            cypher_query,
+            expected_version,
+            partition,
+            true,
+        )
+    }
+
+    /// Creates the caller's write counter for a rate-limited graph.
+    pub fn init_writer_rate(ctx: Context<InitWriterRate>) -> Result<()> {
+        let rate = &mut ctx.accounts.writer_rate;
+        rate.graph = ctx.accounts.graph_store.key();
+        rate.writer = ctx.accounts.authority.key();
+        rate.window_start = 0;
+        rate.current_writes = 0;
+        rate.previous_writes = 0;
+        rate.bump = ctx.bumps.writer_rate;
+        Ok(())
+    }
+
+    /// Creates the graph's audit log. From then on every write must pass it
+    /// and appends an entry. Admins only.
+    pub fn init_audit_log(ctx: Context<InitAuditLog>) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        let log = &mut ctx.accounts.audit_log;
+        log.graph = graph.key();
+        log.total = 0;
+        log.entries = Vec::new();
+        log.bump = ctx.bumps.audit_log;
+        graph.audit_log = true;
+        Ok(())
+    }
+
+    /// Returns up to `limit` audit log entries, newest first, after skipping
+    /// the `skip` newest.
+    pub fn get_audit_log(
+        ctx: Context<GetAuditLog>,
+        skip: u16,
+        limit: u16,
+    ) -> Result<Vec<AuditEntry>> {
+        check_private_read(
+            &ctx.accounts.graph_store,
+            ctx.accounts.reader.as_ref(),
+            ctx.remaining_accounts,
+        )?;
+        let max = (MAX_RETURN_DATA - 4) / AuditEntry::SERIALIZED_SIZE;
+        Ok(ctx
+            .accounts
+            .audit_log
+            .recent(skip as usize, (limit as usize).min(max)))
+    }
+
+    /// Creates the graph's journal. From then on every write must pass it,
+    /// and `execute_query_as_of` can read the graph as of any slot it still
+    /// covers. Admins only.
+    pub fn init_journal(ctx: Context<InitJournal>) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        let journal = &mut ctx.accounts.journal;
+        journal.graph = graph.key();
+        journal.since_slot = Clock::get()?.slot;
+        journal.entries = Vec::new();
+        journal.bump = ctx.bumps.journal;
+        graph.journaled = true;
+        Ok(())
+    }
+
+    /// Runs a read query against the graph as it was at the end of `slot`,
+    /// rebuilt from the journal. The journal does not record node data,
+    /// mints or update times, so queries reading them fail with
+    /// `HistoryUnavailable` rather than answer from current state.
+    pub fn execute_query_as_of(
+        ctx: Context<QueryAsOf>,
+        query: String,
+        slot: u64,
+    ) -> Result<VmResult> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            query.len() <= graph.config.max_query_len as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        require!(
+            !matches!(cypher_query, CypherQuery::Create { .. }),
+            ErrorCode::QueryExecutionFailed
+        );
+        let mut ops = compile_to_opcodes(cypher_query);
+        require!(rewind_answers(&ops), ErrorCode::HistoryUnavailable);
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        authorize_query(graph, &signers, &ops, None)?;
+        require!(
+            ops.len() <= graph.config.max_ops as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+        scope_partition(graph, &signers, None, &mut ops)?;
+        check_query_cost(graph, program_cost(&ops, graph))?;
+
+        // The graph account is read-only here, so rewinding it in memory is
+        // never written back
+        let graph = &mut ctx.accounts.graph_store;
+        ctx.accounts
+            .journal
+            .rewind(graph, slot)
+            .ok_or(ErrorCode::HistoryUnavailable)?;
+        let mut vm = Vm::new(graph).at_slot(slot);
+        let mut result = vm.execute(&ops).map_err(map_vm_error)?;
+        graph.hide_unreadable(&mut result, &signers);
+        require!(
+            result.serialized_size() <= MAX_RETURN_DATA,
+            ErrorCode::ReturnDataTooLarge
+        );
+        Ok(result)
+    }
+
+    /// Runs a read query without Anchor decoding the graph account: node data
+    /// is stepped over instead of copied, so traversals of graphs with large
+    /// payloads cost what they would on bare nodes. Results are node ids, so
+    /// only attribute filters need the data; those decode it after all.
+    /// Queries that write are rejected.
+    pub fn execute_read_query(
+        ctx: Context<ReadQuery>,
+        query: String,
+        partition: Option<u32>,
+    ) -> Result<VmResult> {
+        let info = ctx.accounts.graph_store.to_account_info();
+        let mut graph = GraphStore::try_deserialize_structure(&info.try_borrow_data()?)?;
+        let address = Pubkey::create_program_address(
+            &[
+                graph.seed_prefix(),
+                graph.name.as_bytes(),
+                graph.creator.as_ref(),
+                &[graph.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| anchor_lang::error::ErrorCode::ConstraintSeeds)?;
+        require_keys_eq!(
+            address,
+            info.key(),
+            anchor_lang::error::ErrorCode::ConstraintSeeds
+        );
+        require!(
+            query.len() <= graph.config.max_query_len as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        let mut ops = compile_to_opcodes(cypher_query);
+        require!(
+            !ops.iter().any(Opcode::is_write),
+            ErrorCode::QueryExecutionFailed
+        );
+        if ops.iter().any(Opcode::reads_node_data) {
+            graph = GraphStore::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        }
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        authorize_query(&graph, &signers, &ops, None)?;
+        require!(
+            ops.len() <= graph.config.max_ops as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+        scope_partition(&graph, &signers, partition, &mut ops)?;
+        check_query_cost(&graph, program_cost(&ops, &graph))?;
+
+        let clock = Clock::get()?;
+        let mut vm = Vm::new(&mut graph)
+            .at_time(clock.unix_timestamp)
+            .at_slot(clock.slot);
+        let mut result = vm.execute(&ops).map_err(map_vm_error)?;
+        graph.hide_unreadable(&mut result, &signers);
+        emit_node_matches(&graph, &result.node_ids());
+        require!(
+            result.serialized_size() <= MAX_RETURN_DATA,
+            ErrorCode::ReturnDataTooLarge
+        );
+        Ok(result)
+    }
+
+    /// Opens an empty proposal for staging writes across transactions.
+    pub fn open_proposal(ctx: Context<OpenProposal>) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.any_has_role(
+                &signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts),
+                Role::Writer
+            ),
+            ErrorCode::Unauthorized
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.graph = graph.key();
+        proposal.proposer = ctx.accounts.authority.key();
+        proposal.ops = Vec::new();
+        proposal.bump = ctx.bumps.proposal;
+        Ok(())
+    }
+
+    /// Appends write opcodes, and the `SetNodeId`, `Bind` and `SetTtl`
+    /// opcodes that set them up, to the caller's proposal, growing its
+    /// account. Nothing touches the graph until `commit_proposal`.
+    pub fn stage_proposal(ctx: Context<StageProposal>, ops: Vec<Opcode>) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        check_program(&ops, &graph.config).map_err(map_vm_error)?;
+        require!(
+            ops.iter().all(|op| op.is_write() || op.is_write_setup()),
+            ErrorCode::InvalidProposal
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
+        require!(
+            proposal.ops.len() + ops.len() <= MAX_PROPOSAL_OPS,
+            ErrorCode::ProposalTooLarge
+        );
+        let needed = proposal.space_with(&ops);
+        proposal.ops.extend(ops);
+
+        let info = proposal.to_account_info();
+        if needed > info.data_len() {
+            require!(
+                needed - info.data_len() <= MAX_PERMITTED_DATA_INCREASE,
+                ErrorCode::ProposalTooLarge
+            );
+            grow_account(
+                &info,
+                needed,
+                ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.system_program,
+            )?;
+        }
+
+        emit!(ProposalStaged {
+            proposer: proposal.proposer,
+            staged_ops: proposal.ops.len() as u32,
+        });
+        Ok(())
+    }
+
+    /// Applies every staged write in one transaction and closes the
+    /// proposal. Any failing write aborts the whole commit.
+    pub fn commit_proposal(
+        ctx: Context<CommitProposal>,
+        expected_version: Option<u64>,
+    ) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        if let Some(expected) = expected_version {
+            require!(graph.version == expected, ErrorCode::VersionMismatch);
+        }
+
+        let mut ops = std::mem::take(&mut ctx.accounts.proposal.ops);
+        let writes = ops.iter().filter(|op| op.is_write()).count() as u64;
+        require!(writes > 0, ErrorCode::InvalidProposal);
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        authorize_query(graph, &signers, &ops, None)?;
+        scope_partition(graph, &signers, None, &mut ops)?;
+        require!(!graph.commit_reveal_writes, ErrorCode::CommitRevealRequired);
+        check_token_gate(
+            graph,
+            ctx.accounts.authority.key,
+            ctx.accounts.gate_token_account.as_deref(),
+        )?;
+        charge_write_fee(
+            graph,
+            &ctx.accounts.authority,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            writes,
+        )?;
+        check_write_rate(
+            graph,
+            &signers,
+            ctx.accounts.writer_rate.as_deref_mut(),
+            writes,
+        )?;
+
+        let graph = &mut ctx.accounts.graph_store;
+        let appended = JournalChange::Appended {
+            nodes: graph.nodes.len() as u32,
+            edges: graph.edges.len() as u32,
+        };
+        let clock = Clock::get()?;
+        let mut vm = Vm::new(graph)
+            .at_time(clock.unix_timestamp)
+            .at_slot(clock.slot);
+        vm.execute(&ops).map_err(map_vm_error)?;
+        update_journal(
+            graph,
+            ctx.accounts.journal.as_deref_mut(),
+            |journal, slot| journal.record(slot, appended),
+        )?;
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::Proposal,
+            writes,
+            0,
+        )?;
+        fit_account_to(
+            &ctx.accounts.graph_store,
+            ctx.accounts
+                .payer
+                .as_ref()
+                .unwrap_or(&ctx.accounts.authority),
+            &ctx.accounts.system_program,
+        )?;
+
+        emit!(ProposalCommitted {
+            proposer: ctx.accounts.authority.key(),
+            applied_ops: writes as u32,
+            version: ctx.accounts.graph_store.version,
+        });
+        Ok(())
+    }
+
+    /// Discards a proposal without applying it.
+    pub fn cancel_proposal(_ctx: Context<CancelProposal>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Opens a cursor that runs a read query over several `resume_query`
+    /// transactions, for queries too large for one. The graph's cost
+    /// threshold does not apply: each step's visit budget bounds its compute.
+    pub fn open_query_cursor(
+        ctx: Context<OpenQueryCursor>,
+        query: String,
+        partition: Option<u32>,
+    ) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            query.len() <= graph.config.max_query_len as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        let mut ops = compile_to_opcodes(cypher_query);
+        require!(
+            !ops.iter().any(Opcode::is_write),
+            ErrorCode::QueryExecutionFailed
+        );
+        require!(
+            ops.len() <= graph.config.max_ops as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        authorize_query(graph, &signers, &ops, None)?;
+        scope_partition(graph, &signers, partition, &mut ops)?;
+
+        let cursor = &mut ctx.accounts.cursor;
+        cursor.graph = graph.key();
+        cursor.owner = ctx.accounts.authority.key();
+        cursor.version = graph.version;
+        cursor.slot = Clock::get()?.slot;
+        cursor.ops = ops;
+        cursor.checkpoint = Default::default();
+        cursor.bump = ctx.bumps.cursor;
+        fit_cursor(
+            cursor,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )
+    }
+
+    /// Runs the cursor's query on from where the last step stopped, for
+    /// about `visits` node and edge visits. Returns the result and closes the
+    /// cursor once the query finishes, or `None` after saving its progress.
+    /// Fails if the graph changed since the cursor was opened.
+    pub fn resume_query(ctx: Context<ResumeQuery>, visits: u32) -> Result<Option<VmResult>> {
+        require!(visits > 0, ErrorCode::QueryExecutionFailed);
+        let graph = &ctx.accounts.graph_store;
+        let cursor = &mut ctx.accounts.cursor;
+        require!(graph.version == cursor.version, ErrorCode::CursorStale);
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        authorize_query(graph, &signers, &cursor.ops, None)?;
+
+        // The graph account is read-only here, so nothing the VM does to it
+        // in memory is written back
+        let graph = &mut ctx.accounts.graph_store;
+        let state: &mut QueryCursor = cursor;
+        let mut vm = Vm::new(graph).at_slot(state.slot);
+        let Some(mut result) = vm
+            .resume(&state.ops, &mut state.checkpoint, visits as u64)
+            .map_err(map_vm_error)?
+        else {
+            return fit_cursor(
+                cursor,
+                &ctx.accounts.authority,
+                &ctx.accounts.system_program,
+            )
+            .map(|_| None);
+        };
+
+        graph.hide_unreadable(&mut result, &signers);
+        emit_node_matches(graph, &result.node_ids());
+        require!(
+            result.serialized_size() <= MAX_RETURN_DATA,
+            ErrorCode::ReturnDataTooLarge
+        );
+        cursor.close(ctx.accounts.authority.to_account_info())?;
+        Ok(Some(result))
+    }
+
+    /// Abandons a query cursor before it finishes.
+    pub fn close_query_cursor(_ctx: Context<CloseQueryCursor>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs several queries in order against the same graph. The whole batch
+    /// shares one query-length and opcode budget and fails as a unit.
+    pub fn batch_execute(
+        ctx: Context<ExecuteQuery>,
+        queries: Vec<String>,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Result<Vec<VmResult>> {
+        let graph = &ctx.accounts.graph_store;
+        if let Some(expected) = expected_version {
+            require!(graph.version == expected, ErrorCode::VersionMismatch);
+        }
+
+        let total_len: usize = queries.iter().map(String::len).sum();
+        require!(
+            total_len <= graph.config.max_query_len as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        let mut programs = Vec::with_capacity(queries.len());
+        let mut total_ops = 0;
+        let mut writes = 0;
+        for query in &queries {
+            let cypher_query = parse(query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+
+            if matches!(cypher_query, CypherQuery::Create { .. }) {
+                writes += 1;
+            }
+
+            let mut ops = compile_to_opcodes(cypher_query);
+            authorize_query(graph, &signers, &ops, ctx.accounts.session.as_deref_mut())?;
+            total_ops += ops.len();
+            scope_partition(graph, &signers, partition, &mut ops)?;
+            programs.push(ops);
+        }
+        require!(
+            total_ops <= graph.config.max_ops as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+        let total_cost = programs.iter().map(|ops| program_cost(ops, graph)).sum();
+        check_query_cost(graph, total_cost)?;
+        if writes > 0 {
+            require!(!graph.commit_reveal_writes, ErrorCode::CommitRevealRequired);
+            check_token_gate(
+                graph,
+                ctx.accounts.authority.key,
+                ctx.accounts.gate_token_account.as_deref(),
+            )?;
+        }
+        charge_write_fee(
+            graph,
+            &ctx.accounts.authority,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            writes,
+        )?;
+        check_write_rate(
+            graph,
+            &signers,
+            ctx.accounts.writer_rate.as_deref_mut(),
+            writes,
+        )?;
+
+        let graph = &mut ctx.accounts.graph_store;
+        let appended = JournalChange::Appended {
+            nodes: graph.nodes.len() as u32,
+            edges: graph.edges.len() as u32,
+        };
+        let mut results = Vec::with_capacity(programs.len());
+        let clock = Clock::get()?;
+        for ops in &programs {
+            let mut vm = Vm::new(graph)
+                .at_time(clock.unix_timestamp)
+                .at_slot(clock.slot);
+            let mut result = vm.execute(ops).map_err(map_vm_error)?;
+            graph.hide_unreadable(&mut result, &signers);
+            emit_node_matches(graph, &result.node_ids());
+            results.push(result);
+        }
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::Batch,
+            writes,
+            0,
+        )?;
+        if writes > 0 {
+            update_journal(
+                graph,
+                ctx.accounts.journal.as_deref_mut(),
+                |journal, slot| journal.record(slot, appended),
+            )?;
+
+            fit_graph_account(ctx.accounts)?;
+        }
+        let size = 4 + results.iter().map(VmResult::serialized_size).sum::<usize>();
+        require!(size <= MAX_RETURN_DATA, ErrorCode::ReturnDataTooLarge);
+        Ok(results)
+    }
+
+    /// Runs a read query and returns the slice of its node ids starting at
+    /// `cursor`. Follow `next_cursor` across transactions for the rest.
+    pub fn execute_query_page(
+        ctx: Context<ExecuteQuery>,
+        query: String,
+        cursor: u32,
+        expected_version: Option<u64>,
+        partition: Option<u32>,
+    ) -> Result<QueryPage> {
+        let graph = &ctx.accounts.graph_store;
+        if let Some(expected) = expected_version {
+            require!(graph.version == expected, ErrorCode::VersionMismatch);
+        }
+        require!(
+            query.len() <= graph.config.max_query_len as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        // Re-running a write for every page would repeat it
+        require!(
+            !matches!(cypher_query, CypherQuery::Create { .. }),
+            ErrorCode::QueryExecutionFailed
+        );
+        let mut ops = compile_to_opcodes(cypher_query);
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        authorize_query(graph, &signers, &ops, None)?;
+
+        let version = graph.version;
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            ops.len() <= graph.config.max_ops as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+        scope_partition(graph, &signers, partition, &mut ops)?;
+        check_query_cost(graph, program_cost(&ops, graph))?;
+
+        let clock = Clock::get()?;
+        let mut vm = Vm::new(graph)
+            .at_time(clock.unix_timestamp)
+            .at_slot(clock.slot);
+        let result = vm.execute(&ops).map_err(map_vm_error)?;
+        let page = result.page(cursor as usize, MAX_RETURN_DATA, version);
+        emit_node_matches(graph, &page.ids);
+        Ok(page)
+    }
+
+    /// Runs a precompiled program, skipping tokenize/parse/compile. The
+    /// opcodes go through `check_program` before anything executes.
+    pub fn execute_opcodes(
+        ctx: Context<ExecuteQuery>,
+        ops: Vec<Opcode>,
+        expected_version: Option<u64>,
+    ) -> Result<VmResult> {
+        let graph = &ctx.accounts.graph_store;
+        if let Some(expected) = expected_version {
+            require!(graph.version == expected, ErrorCode::VersionMismatch);
+        }
+
+        check_program(&ops, &graph.config).map_err(map_vm_error)?;
+        check_query_cost(graph, program_cost(&ops, graph))?;
+
+        let writes = ops.iter().filter(|op| op.is_write()).count() as u64;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        authorize_query(graph, &signers, &ops, ctx.accounts.session.as_deref_mut())?;
+        let mut ops = ops;
+        scope_partition(graph, &signers, None, &mut ops)?;
+        if writes > 0 {
+            require!(!graph.commit_reveal_writes, ErrorCode::CommitRevealRequired);
+            check_token_gate(
+                graph,
+                ctx.accounts.authority.key,
+                ctx.accounts.gate_token_account.as_deref(),
+            )?;
+        }
+        charge_write_fee(
+            graph,
+            &ctx.accounts.authority,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            writes,
+        )?;
+        check_write_rate(
+            graph,
+            &signers,
+            ctx.accounts.writer_rate.as_deref_mut(),
+            writes,
+        )?;
+
+        let graph = &mut ctx.accounts.graph_store;
+        let appended = JournalChange::Appended {
+            nodes: graph.nodes.len() as u32,
+            edges: graph.edges.len() as u32,
+        };
+        let clock = Clock::get()?;
+        let mut vm = Vm::new(graph)
+            .at_time(clock.unix_timestamp)
+            .at_slot(clock.slot);
+        let mut result = vm.execute(&ops).map_err(map_vm_error)?;
+        graph.hide_unreadable(&mut result, &signers);
+        emit_node_matches(graph, &result.node_ids());
+        require!(
+            result.serialized_size() <= MAX_RETURN_DATA,
+            ErrorCode::ReturnDataTooLarge
+        );
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::Opcodes,
+            writes,
+            0,
+        )?;
+        if writes > 0 {
+            update_journal(
+                graph,
+                ctx.accounts.journal.as_deref_mut(),
+                |journal, slot| journal.record(slot, appended),
+            )?;
+
+            fit_graph_account(ctx.accounts)?;
+        }
+        Ok(result)
+    }
+
+    pub fn get_node_info(ctx: Context<GetNodeInfo>, node_id: NodeId) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        check_private_read(graph, ctx.accounts.reader.as_ref(), ctx.remaining_accounts)?;
+        let signers = ctx
+            .accounts
+            .reader
+            .as_ref()
+            .map(|r| signer_keys(r.key(), ctx.remaining_accounts))
+            .unwrap_or_default();
+        require!(
+            graph.can_read_node(node_id, &signers),
+            ErrorCode::Unauthorized
+        );
+
+        let node_index = graph
+            .scoped_node_index(node_id, None)
+            .filter(|&index| graph.in_partition_scope(&signers, graph.nodes[index].partition))
+            .ok_or(ErrorCode::NodeNotFound)?;
+        let node = &graph.nodes[node_index];
+
+        msg!(
+            "Node {}: label='{}', partition={}, outgoing_edges={}",
+            node_id,
+            node.label,
+            node.partition,
+            graph.outgoing_edge_indices(node_index).len()
+        );
+
+        Ok(())
+    }
+
+    /// Looks up an edge by index or by `(from, to, label)` and returns it.
+    pub fn get_edge_info(ctx: Context<GetEdgeInfo>, edge_ref: EdgeRef) -> Result<Edge> {
+        let graph = &ctx.accounts.graph_store;
+        check_private_read(graph, ctx.accounts.reader.as_ref(), ctx.remaining_accounts)?;
+        let signers = ctx
+            .accounts
+            .reader
+            .as_ref()
+            .map(|r| signer_keys(r.key(), ctx.remaining_accounts))
+            .unwrap_or_default();
+
+        let edge_index = graph
+            .resolve_edge(&edge_ref)
+            .filter(|&index| {
+                graph.in_partition_scope(&signers, graph.edges[index as usize].partition)
+            })
+            .ok_or(ErrorCode::EdgeNotFound)?;
+        let edge = &graph.edges[edge_index as usize];
+
+        msg!(
+            "Edge {}: {} -[{}]-> {}, partition={}, data_len={}",
+            edge_index,
+            edge.from,
+            edge.label,
+            edge.to,
+            edge.partition,
+            edge.data.len()
+        );
+
+        Ok(edge.clone())
+    }
+
+    /// Pages through live nodes in slot order. Nodes whose ACL the reader
+    /// does not pass are left out of the page.
+    pub fn get_nodes_page(ctx: Context<GetNodesPage>, offset: u32, count: u16) -> Result<NodePage> {
+        let graph = &ctx.accounts.graph_store;
+        check_private_read(graph, ctx.accounts.reader.as_ref(), ctx.remaining_accounts)?;
+        let signers = ctx
+            .accounts
+            .reader
+            .as_ref()
+            .map(|r| signer_keys(r.key(), ctx.remaining_accounts))
+            .unwrap_or_default();
+        Ok(graph.get_nodes_page(offset as usize, count as usize, MAX_RETURN_DATA, &signers))
+    }
+
+    pub fn get_capacity(ctx: Context<GetCapacity>) -> Result<GraphCapacity> {
+        let graph = &ctx.accounts.graph_store;
+        let account_bytes = graph.to_account_info().data_len();
+        let capacity = graph.capacity(account_bytes);
+
+        msg!(
+            "Capacity: {}/{} bytes used, {} node slots, {} edge slots free",
+            capacity.used_bytes,
+            capacity.account_bytes,
+            capacity.free_node_slots,
+            capacity.free_edge_slots
+        );
+        Ok(capacity)
+    }
+
+    /// Compiles a query without running it and returns its opcode count,
+    /// cost and a bound on visited nodes. Meant for simulation, to pick a
+    /// compute budget for the real call.
+    pub fn estimate_query(
+        ctx: Context<EstimateQuery>,
+        query: String,
+        partition: Option<u32>,
+    ) -> Result<QueryEstimate> {
+        let graph = &ctx.accounts.graph_store;
+        check_private_read(graph, ctx.accounts.reader.as_ref(), ctx.remaining_accounts)?;
+        require!(
+            query.len() <= graph.config.max_query_len as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+
+        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        let mut ops = compile_to_opcodes(cypher_query);
+        if let Some(partition) = partition {
+            ops.insert(0, Opcode::SetPartition(partition));
+        }
+        let estimate = estimate_program(&ops, graph);
+
+        msg!(
+            "Estimate: {} ops, cost {}, at most {} nodes visited",
+            estimate.op_count,
+            estimate.cost,
+            estimate.max_visited_nodes
+        );
+        Ok(estimate)
+    }
+
+    /// Compiles `query` without running it and returns the opcode program
+    /// with its estimate, so callers can see what their Cypher turns into.
+    pub fn explain_query(
+        ctx: Context<EstimateQuery>,
+        query: String,
+        partition: Option<u32>,
+    ) -> Result<QueryPlan> {
+        let graph = &ctx.accounts.graph_store;
+        check_private_read(graph, ctx.accounts.reader.as_ref(), ctx.remaining_accounts)?;
+        require!(
+            query.len() <= graph.config.max_query_len as usize,
+            ErrorCode::QueryExecutionFailed
+        );
+
+        let ops = explain(&query, partition).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+        for op in &ops {
+            msg!("{:?}", op);
+        }
+        let plan = QueryPlan {
+            estimate: estimate_program(&ops, graph),
+            ops,
+        };
+        require!(
+            plan.try_to_vec()?.len() <= MAX_RETURN_DATA,
+            ErrorCode::ReturnDataTooLarge
+        );
+        Ok(plan)
+    }
+
+    pub fn soft_delete_node(ctx: Context<SoftDeleteNode>, node_id: NodeId) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let node = graph
+            .get_live_node_by_id(node_id)
+            .ok_or(ErrorCode::NodeNotFound)?;
+        require!(!node.is_protected, ErrorCode::NodeProtected);
+        graph
+            .set_node_deleted(node_id, true)
+            .ok_or(ErrorCode::NodeNotFound)?;
+        graph.touch_node(node_id, Clock::get()?.unix_timestamp);
+        update_journal(
+            graph,
+            ctx.accounts.journal.as_deref_mut(),
+            |journal, slot| {
+                journal.record(
+                    slot,
+                    JournalChange::DeletedFlag {
+                        node_id,
+                        deleted: true,
+                    },
+                )
+            },
+        )?;
+
+        emit!(NodeDeleted { node_id });
+        Ok(())
+    }
+
+    pub fn undelete_node(ctx: Context<SoftDeleteNode>, node_id: NodeId) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let was_deleted = graph
+            .get_node_by_id(node_id)
+            .ok_or(ErrorCode::NodeNotFound)?
+            .is_deleted;
+        graph
+            .set_node_deleted(node_id, false)
+            .ok_or(ErrorCode::NodeNotFound)?;
+        graph.touch_node(node_id, Clock::get()?.unix_timestamp);
+        if was_deleted {
+            update_journal(
+                graph,
+                ctx.accounts.journal.as_deref_mut(),
+                |journal, slot| {
+                    journal.record(
+                        slot,
+                        JournalChange::DeletedFlag {
+                            node_id,
+                            deleted: false,
+                        },
+                    )
+                },
+            )?;
+        }
+
+        emit!(NodeRestored { node_id });
+        Ok(())
+    }
+
+    pub fn set_node_protected(
+        ctx: Context<SetNodeProtected>,
+        node_id: NodeId,
+        protected: bool,
+    ) -> Result<()> {
         let graph = &mut ctx.accounts.graph_store;
-        graph.authority = ctx.accounts.authority.key();
-        graph.node_count = 0;
-        graph.edge_count = 0;
-        graph.nonce = 0;
-        graph.nodes = Vec::new();
-        graph.edges = Vec::new();
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        graph
+            .set_node_protected(node_id, protected)
+            .ok_or(ErrorCode::NodeNotFound)?;
+        graph.touch_node(node_id, Clock::get()?.unix_timestamp);
+
+        emit!(NodeProtectionChanged { node_id, protected });
+        Ok(())
+    }
+
+    pub fn compact_graph(ctx: Context<CompactGraph>, max_slots: u16) -> Result<CompactionProgress> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let progress = graph
+            .compact(max_slots as usize, Clock::get()?.slot)
+            .ok_or(ErrorCode::Overflow)?;
+        if progress.removed_nodes > 0 {
+            update_journal(graph, ctx.accounts.journal.as_deref_mut(), Journal::reset)?;
+        }
+
+        emit!(GraphCompacted {
+            removed_nodes: progress.removed_nodes,
+            removed_edges: progress.removed_edges,
+            cursor: progress.cursor,
+            done: progress.done,
+        });
+        Ok(progress)
+    }
+
+    /// Recomputes one derived index from node and edge storage to recover
+    /// from a bug or migration. Start at `Counters` and call again with the
+    /// returned kind until it is `None`.
+    pub fn rebuild_indices(
+        ctx: Context<RebuildIndices>,
+        index: IndexKind,
+    ) -> Result<Option<IndexKind>> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.rebuild_index(index);
+
+        emit!(IndexRebuilt { index });
+        Ok(index.next())
+    }
+
+    /// Copies `source` into an empty graph in chunks of `max_items` nodes
+    /// and edges, e.g. to fork a template or stage destructive changes.
+    /// The signers must be able to read every ACL-restricted node of
+    /// `source`, since they administer the copy. Returns the cursor for the
+    /// next call, or `None` when done; a later call must pass that cursor
+    /// and the same `source`.
+    pub fn clone_graph(
+        ctx: Context<CloneGraph>,
+        cursor: u32,
+        max_items: u16,
+    ) -> Result<Option<u32>> {
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        let source = &ctx.accounts.source;
+        let destination = &mut ctx.accounts.destination;
+        require!(
+            signers.iter().any(|k| source.can_read(k)),
+            ErrorCode::Unauthorized
+        );
+        require!(source.can_read_all_nodes(&signers), ErrorCode::Unauthorized);
+        require!(destination.is_admin(&signers), ErrorCode::Unauthorized);
+        require!(!destination.frozen, ErrorCode::GraphFrozen);
+        if cursor == 0 {
+            require!(
+                destination.nodes.is_empty() && destination.edges.is_empty(),
+                ErrorCode::GraphNotEmpty
+            );
+        } else {
+            require!(
+                destination.clone_source == source.key() && destination.clone_cursor == cursor,
+                ErrorCode::InvalidClone
+            );
+        }
+
+        let appended = JournalChange::Appended {
+            nodes: destination.nodes.len() as u32,
+            edges: destination.edges.len() as u32,
+        };
+        let next_cursor = destination
+            .copy_chunk_from(source, cursor as usize, max_items as usize)
+            .map_err(|e| match e {
+                VmError::GraphLimitExceeded => ErrorCode::GraphLimitExceeded,
+                VmError::LabelTooLong => ErrorCode::LabelTooLong,
+                VmError::DataTooLarge => ErrorCode::DataTooLarge,
+                e => map_vm_error(e),
+            })?;
+        (destination.clone_source, destination.clone_cursor) = match next_cursor {
+            Some(next) => (source.key(), next),
+            None => (Pubkey::default(), 0),
+        };
+        update_journal(
+            destination,
+            ctx.accounts.journal.as_deref_mut(),
+            |journal, slot| journal.record(slot, appended),
+        )?;
+        fit_account_to(
+            &ctx.accounts.destination,
+            ctx.accounts
+                .payer
+                .as_ref()
+                .unwrap_or(&ctx.accounts.authority),
+            &ctx.accounts.system_program,
+        )?;
+
+        emit!(GraphCloned {
+            source: ctx.accounts.source.key(),
+            destination: ctx.accounts.destination.key(),
+            cursor: next_cursor.unwrap_or(0),
+            done: next_cursor.is_none(),
+        });
+        Ok(next_cursor)
+    }
+
+    /// Like `compact_graph`, but emits a `NodeReclaimed` or `EdgeReclaimed`
+    /// event for every entry it removes. `max_work` bounds the node slots
+    /// scanned; call until `done`.
+    pub fn garbage_collect(
+        ctx: Context<CompactGraph>,
+        max_work: u16,
+    ) -> Result<CompactionProgress> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let (progress, reclaimed) = graph
+            .collect_garbage(max_work as usize, Clock::get()?.slot)
+            .ok_or(ErrorCode::Overflow)?;
+        if progress.removed_nodes > 0 {
+            update_journal(graph, ctx.accounts.journal.as_deref_mut(), Journal::reset)?;
+        }
+
+        for node_id in reclaimed.nodes {
+            emit!(NodeReclaimed { node_id });
+        }
+        for edge in reclaimed.edges {
+            emit!(EdgeReclaimed {
+                from: edge.from,
+                to: edge.to,
+                label: edge.label.to_string(),
+            });
+        }
+        emit!(GraphCompacted {
+            removed_nodes: progress.removed_nodes,
+            removed_edges: progress.removed_edges,
+            cursor: progress.cursor,
+            done: progress.done,
+        });
+        Ok(progress)
+    }
+
+    pub fn transfer_authority(
+        ctx: Context<TransferAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_owner(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.pending_authority = Some(new_authority);
+
+        emit!(AuthorityTransferProposed {
+            current_authority: graph.authority,
+            pending_authority: new_authority,
+        });
+        Ok(())
+    }
+
+    pub fn cancel_authority_transfer(ctx: Context<TransferAuthority>) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_owner(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.pending_authority = None;
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        let new_authority = ctx.accounts.new_authority.key();
+        require!(
+            graph.pending_authority == Some(new_authority),
+            ErrorCode::Unauthorized
+        );
+
+        let previous_authority = graph.authority;
+        graph.authority = new_authority;
+        graph.pending_authority = None;
+
+        emit!(AuthorityTransferred {
+            previous_authority,
+            new_authority,
+        });
+        Ok(())
+    }
+
+    /// Creates the graph's metadata account, describing it for explorers
+    /// and SDKs.
+    pub fn init_graph_metadata(
+        ctx: Context<InitGraphMetadata>,
+        display_name: String,
+        description: String,
+        schema_uri: String,
+        tags: Vec<String>,
+    ) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            GraphMetadata::fits(&display_name, &description, &schema_uri, &tags),
+            ErrorCode::MetadataTooLarge
+        );
+
+        let metadata = &mut ctx.accounts.metadata;
+        metadata.graph = graph.key();
+        metadata.display_name = display_name;
+        metadata.description = description;
+        metadata.schema_uri = schema_uri;
+        metadata.tags = tags;
+        metadata.created_at = Clock::get()?.unix_timestamp;
+        metadata.bump = ctx.bumps.metadata;
+
+        emit!(GraphMetadataChanged {
+            graph: metadata.graph,
+        });
+        Ok(())
+    }
+
+    /// Replaces the descriptive fields of the graph's metadata account.
+    pub fn update_graph_metadata(
+        ctx: Context<UpdateGraphMetadata>,
+        display_name: String,
+        description: String,
+        schema_uri: String,
+        tags: Vec<String>,
+    ) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            GraphMetadata::fits(&display_name, &description, &schema_uri, &tags),
+            ErrorCode::MetadataTooLarge
+        );
+
+        let metadata = &mut ctx.accounts.metadata;
+        metadata.display_name = display_name;
+        metadata.description = description;
+        metadata.schema_uri = schema_uri;
+        metadata.tags = tags;
+
+        emit!(GraphMetadataChanged {
+            graph: metadata.graph,
+        });
+        Ok(())
+    }
+
+    /// Delegates write access to `key` until `expires_slot`, for at most
+    /// `max_write_ops` creates whose labels are in `labels` (any if empty).
+    /// The key then signs queries as `authority` with the session passed.
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        key: Pubkey,
+        expires_slot: u64,
+        labels: Vec<String>,
+        max_write_ops: u32,
+    ) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            labels.len() <= MAX_SESSION_LABELS
+                && labels
+                    .iter()
+                    .all(|l| l.len() <= graph.config.max_label_len as usize),
+            ErrorCode::InvalidSessionScope
+        );
+
+        let session = &mut ctx.accounts.session;
+        session.granter = ctx.accounts.authority.key();
+        session.key = key;
+        session.expires_slot = expires_slot;
+        session.labels = labels;
+        session.max_write_ops = max_write_ops;
+        session.write_ops_used = 0;
+        session.bump = ctx.bumps.session;
+
+        emit!(SessionCreated {
+            key,
+            expires_slot,
+            max_write_ops,
+        });
+        Ok(())
+    }
+
+    /// Closes a session early. Admins and the session key itself may revoke.
+    pub fn revoke_session(ctx: Context<RevokeSession>) -> Result<()> {
+        let key = ctx.accounts.session.key;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            ctx.accounts.graph_store.is_admin(&signers) || signers.contains(&key),
+            ErrorCode::Unauthorized
+        );
+
+        emit!(SessionRevoked { key });
+        Ok(())
+    }
+
+    /// Grows the account by `additional` bytes, rent paid by `payer`. A
+    /// single call can add at most `MAX_PERMITTED_DATA_INCREASE` bytes.
+    pub fn realloc_graph(ctx: Context<ReallocGraph>, additional: u32) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            additional as usize <= MAX_PERMITTED_DATA_INCREASE,
+            ErrorCode::GraphLimitExceeded
+        );
+
+        // Anchor's `realloc` constraint has already resized the account and
+        // topped it up to rent exemption from `payer`
+        emit!(GraphResized {
+            account_bytes: graph.to_account_info().data_len() as u64,
+        });
+        Ok(())
+    }
+
+    /// Rewrites a GraphStore stored with an older layout in the current one,
+    /// growing the account (rent paid by `payer`) if the new layout is larger.
+    /// Owner only, checked on the upgraded graph so a multisig set under the
+    /// old layout still needs its quorum.
+    pub fn migrate_graph(ctx: Context<MigrateGraph>) -> Result<()> {
+        let info = ctx.accounts.graph_store.to_account_info();
+        let (from_layout, _) =
+            read_header(&info.try_borrow_data()?).map_err(map_migration_error)?;
+        let graph = upgrade(&info.try_borrow_data()?).map_err(map_migration_error)?;
+        require!(
+            graph.is_owner(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        let mut bytes = Vec::with_capacity(graph.serialized_size());
+        graph.try_serialize(&mut bytes)?;
+
+        if bytes.len() > info.data_len() {
+            grow_account(
+                &info,
+                bytes.len(),
+                ctx.accounts.payer.to_account_info(),
+                &ctx.accounts.system_program,
+            )?;
+        }
+        info.try_borrow_mut_data()?[..bytes.len()].copy_from_slice(&bytes);
+
+        emit!(GraphMigrated {
+            from_layout,
+            to_layout: GRAPH_LAYOUT_VERSION,
+        });
+        Ok(())
+    }
+
+    /// Closes the graph, moving its rent to `recipient`. Its child accounts
+    /// (journal, audit log, compressed node store, metadata, sessions,
+    /// cursors, writer counters, commitments and proposals) are closed too
+    /// when passed as writable remaining accounts. Owner only.
+    pub fn close_graph(ctx: Context<CloseGraph>) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.is_owner(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        let recipient = ctx.accounts.recipient.to_account_info();
+        let mut children = 0u32;
+        for account in ctx
+            .remaining_accounts
+            .iter()
+            .filter(|a| a.is_writable && *a.owner == crate::ID)
+        {
+            require!(
+                is_graph_child(account, &graph.key()),
+                ErrorCode::InvalidChildAccount
+            );
+            close_child_account(account, &recipient)?;
+            children += 1;
+        }
+
+        // Anchor's `close` constraint zeroes the account and moves its
+        // lamports to the recipient once the instruction succeeds
+        emit!(GraphClosed {
+            recipient: ctx.accounts.recipient.key(),
+            node_count: graph.node_count,
+            edge_count: graph.edge_count,
+            children,
+        });
+        Ok(())
+    }
+
+    /// Inserts a node from Borsh arguments, skipping the Cypher pipeline,
+    /// into `partition` as `execute_query` would. Returns the new node id.
+    pub fn add_node(
+        ctx: Context<WriteGraph>,
+        label: String,
+        data: Vec<u8>,
+        partition: Option<u32>,
+    ) -> Result<NodeId> {
+        let graph = &mut ctx.accounts.graph_store;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            graph.any_has_role(&signers, Role::Writer),
+            ErrorCode::Unauthorized
+        );
+        let partition = graph
+            .scoped_partition(&signers, partition)
+            .ok_or(ErrorCode::Unauthorized)?;
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+        require!(!graph.commit_reveal_writes, ErrorCode::CommitRevealRequired);
+        charge_write_fee(
+            graph,
+            &ctx.accounts.authority,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            1,
+        )?;
+        check_write_rate(graph, &signers, ctx.accounts.writer_rate.as_deref_mut(), 1)?;
+        require!(
+            label.len() <= graph.config.max_label_len as usize,
+            ErrorCode::LabelTooLong
+        );
+        require!(
+            data.len() <= graph.config.max_node_data_len as usize,
+            ErrorCode::DataTooLarge
+        );
+        require!(
+            graph.nodes.len() < graph.config.max_nodes as usize,
+            ErrorCode::GraphLimitExceeded
+        );
+
+        let node_id = graph.next_node_id().ok_or(ErrorCode::Overflow)?;
+        let appended = JournalChange::Appended {
+            nodes: graph.nodes.len() as u32,
+            edges: graph.edges.len() as u32,
+        };
+        let now = Clock::get()?.unix_timestamp;
+        graph
+            .add_node(Node {
+                id: node_id,
+                label: to_label(label),
+                data,
+                is_deleted: false,
+                is_protected: false,
+                partition: partition.unwrap_or_default(),
+                created_at: now,
+                updated_at: now,
+                expires_slot: 0,
+                mint: None,
+            })
+            .ok_or(ErrorCode::Overflow)?;
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::AddNode,
+            1,
+            node_id,
+        )?;
+        update_journal(
+            graph,
+            ctx.accounts.journal.as_deref_mut(),
+            |journal, slot| journal.record(slot, appended),
+        )?;
+
+        emit!(NodeAdded {
+            node_id,
+            node_count: graph.node_count,
+        });
+        fit_written_graph(ctx.accounts)?;
+        Ok(node_id)
+    }
+
+    /// Structured counterpart to `CREATE (a)-[:LABEL]->(b)`. With a
+    /// `partition`, both endpoints must be in it, as they must for a
+    /// partition-scoped query.
+    pub fn add_edge(
+        ctx: Context<WriteGraph>,
+        from: NodeId,
+        to: NodeId,
+        label: String,
+        data: Vec<u8>,
+        partition: Option<u32>,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            graph.any_has_role(&signers, Role::Writer),
+            ErrorCode::Unauthorized
+        );
+        let partition = graph
+            .scoped_partition(&signers, partition)
+            .ok_or(ErrorCode::Unauthorized)?;
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+        require!(!graph.commit_reveal_writes, ErrorCode::CommitRevealRequired);
+        charge_write_fee(
+            graph,
+            &ctx.accounts.authority,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            1,
+        )?;
+        check_write_rate(graph, &signers, ctx.accounts.writer_rate.as_deref_mut(), 1)?;
+        require!(
+            label.len() <= graph.config.max_label_len as usize,
+            ErrorCode::LabelTooLong
+        );
+        require!(
+            data.len() <= graph.config.max_edge_data_len as usize,
+            ErrorCode::DataTooLarge
+        );
+        require!(
+            graph.edges.len() < graph.config.max_edges as usize,
+            ErrorCode::GraphLimitExceeded
+        );
+        require!(
+            [from, to].iter().all(|&id| graph
+                .get_live_node_by_id(id)
+                .is_some_and(|node| partition.is_none_or(|p| node.partition == p))),
+            ErrorCode::NodeNotFound
+        );
+        require!(!graph.has_edge(from, to, &label), ErrorCode::DuplicateEdge);
+        require!(
+            !graph.closes_cycle(from, to, &label),
+            ErrorCode::CycleDetected
+        );
+
+        let appended = JournalChange::Appended {
+            nodes: graph.nodes.len() as u32,
+            edges: graph.edges.len() as u32,
+        };
+        graph
+            .add_edge(Edge {
+                from,
+                to,
+                label: to_label(label),
+                partition: partition.unwrap_or_default(),
+                data,
+                created_at: Clock::get()?.unix_timestamp,
+            })
+            .ok_or(ErrorCode::NodeNotFound)?;
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::AddEdge,
+            1,
+            from,
+        )?;
+        update_journal(
+            graph,
+            ctx.accounts.journal.as_deref_mut(),
+            |journal, slot| journal.record(slot, appended),
+        )?;
+
+        emit!(EdgeAdded {
+            from,
+            to,
+            edge_count: graph.edge_count,
+        });
+        fit_written_graph(ctx.accounts)
+    }
+
+    /// Physically removes a node and every edge touching it. Unlike
+    /// `soft_delete_node` this cannot be undone.
+    pub fn delete_node(ctx: Context<WriteGraph>, node_id: NodeId) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let node = graph
+            .get_node_by_id(node_id)
+            .ok_or(ErrorCode::NodeNotFound)?;
+        require!(!node.is_protected, ErrorCode::NodeProtected);
+
+        let (_, removed_edges) = graph.remove_nodes(&[node_id]).ok_or(ErrorCode::Overflow)?;
+        graph.remove_node_acl(node_id);
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::DeleteNode,
+            1,
+            node_id,
+        )?;
+        update_journal(graph, ctx.accounts.journal.as_deref_mut(), Journal::reset)?;
+
+        emit!(NodeRemoved {
+            node_id,
+            removed_edges,
+        });
+        Ok(())
+    }
+
+    /// Removes every unprotected node labelled `label`, with its edges,
+    /// scanning at most `max_slots` node slots from `cursor`. Call again with
+    /// the returned `next_cursor` until it is `None`.
+    pub fn delete_by_label(
+        ctx: Context<WriteGraph>,
+        label: String,
+        cursor: u32,
+        max_slots: u16,
+    ) -> Result<LabelDeletion> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let deletion = graph
+            .delete_label_batch(&label, cursor as usize, max_slots as usize)
+            .ok_or(ErrorCode::Overflow)?;
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::DeleteByLabel,
+            deletion.removed_nodes,
+            0,
+        )?;
+        if deletion.removed_nodes > 0 {
+            update_journal(graph, ctx.accounts.journal.as_deref_mut(), Journal::reset)?;
+        }
+
+        emit!(LabelDeleted {
+            label,
+            removed_nodes: deletion.removed_nodes,
+            removed_edges: deletion.removed_edges,
+            done: deletion.next_cursor.is_none(),
+        });
+        Ok(deletion)
+    }
+
+    /// Overwrites a node's data blob, or appends to it when `append` is set.
+    pub fn update_node_data(
+        ctx: Context<WriteGraph>,
+        node_id: NodeId,
+        data: Vec<u8>,
+        append: bool,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            graph.any_has_role(&signers, Role::Writer),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+        charge_write_fee(
+            graph,
+            &ctx.accounts.authority,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            1,
+        )?;
+        check_write_rate(graph, &signers, ctx.accounts.writer_rate.as_deref_mut(), 1)?;
+
+        let node = graph
+            .get_live_node_by_id(node_id)
+            .filter(|node| graph.in_partition_scope(&signers, node.partition))
+            .ok_or(ErrorCode::NodeNotFound)?;
+        let data_len = if append {
+            node.data.len() + data.len()
+        } else {
+            data.len()
+        };
+        require!(
+            data_len <= graph.config.max_node_data_len as usize,
+            ErrorCode::DataTooLarge
+        );
+
+        graph
+            .set_node_data(node_id, data, append)
+            .ok_or(ErrorCode::Overflow)?;
+        graph.touch_node(node_id, Clock::get()?.unix_timestamp);
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::UpdateNodeData,
+            1,
+            node_id,
+        )?;
+
+        emit!(NodeDataUpdated {
+            node_id,
+            data_len: data_len as u32,
+        });
+        fit_written_graph(ctx.accounts)
+    }
+
+    /// Sets integer attributes on nodes for the graph's oracle without going
+    /// through the query parser. Every key must be one of `oracle_keys`.
+    pub fn push_attributes(ctx: Context<WriteGraph>, updates: Vec<AttrUpdate>) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.oracle != Pubkey::default() && ctx.accounts.authority.key() == graph.oracle,
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let now = Clock::get()?.unix_timestamp;
+        let writes = updates.len() as u64;
+        for AttrUpdate {
+            node_id,
+            key,
+            value,
+        } in updates
+        {
+            require!(graph.oracle_keys.contains(&key), ErrorCode::Unauthorized);
+            let node = graph
+                .get_live_node_by_id(node_id)
+                .ok_or(ErrorCode::NodeNotFound)?;
+            let data = set_attribute(&node.data, &key, AttrValue::Int(value))
+                .ok_or(ErrorCode::NotAttributeData)?;
+            require!(
+                data.len() <= graph.config.max_node_data_len as usize,
+                ErrorCode::DataTooLarge
+            );
+            graph
+                .set_node_data(node_id, data, false)
+                .ok_or(ErrorCode::Overflow)?;
+            graph.touch_node(node_id, now);
+
+            emit!(AttributeUpdated {
+                node_id,
+                key,
+                value,
+            });
+        }
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::OraclePush,
+            writes,
+            0,
+        )?;
+        fit_written_graph(ctx.accounts)
+    }
+
+    /// Binds a node to an NFT mint for provenance queries such as
+    /// `MATCH (n) WHERE n.mint = $1`. The mint must have no decimals and a
+    /// supply of one, and a bound node cannot be rebound.
+    pub fn bind_mint(ctx: Context<BindMint>, node_id: NodeId) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            graph.any_has_role(&signers, Role::Writer),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+
+        let mint = &ctx.accounts.mint;
+        require!(mint.decimals == 0 && mint.supply == 1, ErrorCode::NotAnNft);
+        let node = graph
+            .get_live_node_by_id(node_id)
+            .filter(|node| graph.in_partition_scope(&signers, node.partition))
+            .ok_or(ErrorCode::NodeNotFound)?;
+        require!(node.mint.is_none(), ErrorCode::MintAlreadyBound);
+
+        graph
+            .set_node_mint(node_id, mint.key())
+            .ok_or(ErrorCode::Overflow)?;
+        graph.touch_node(node_id, Clock::get()?.unix_timestamp);
+        fit_account_to(
+            &ctx.accounts.graph_store,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        emit!(MintBound {
+            node_id,
+            mint: mint.key(),
+        });
+        Ok(())
+    }
+
+    /// Stores a reference to `account` in attribute `key` of a node. When
+    /// `expected_owner` is given the account must be owned by that program,
+    /// so the reference is known to point at e.g. a token account or PDA.
+    pub fn set_account_attribute(
+        ctx: Context<SetAccountAttribute>,
+        node_id: NodeId,
+        key: String,
+        expected_owner: Option<Pubkey>,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            graph.any_has_role(&signers, Role::Writer),
+            ErrorCode::Unauthorized
+        );
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+        require!(
+            key.len() <= graph.config.max_label_len as usize,
+            ErrorCode::LabelTooLong
+        );
+
+        let account = &ctx.accounts.account;
+        if let Some(owner) = expected_owner {
+            require_keys_eq!(*account.owner, owner, ErrorCode::AccountOwnerMismatch);
+        }
+        let address = account.key();
+        let node = graph
+            .get_live_node_by_id(node_id)
+            .filter(|node| graph.in_partition_scope(&signers, node.partition))
+            .ok_or(ErrorCode::NodeNotFound)?;
+        let data = set_attribute(
+            &node.data,
+            &key,
+            AttrValue::Account {
+                address,
+                owner: expected_owner,
+            },
+        )
+        .ok_or(ErrorCode::NotAttributeData)?;
+        require!(
+            data.len() <= graph.config.max_node_data_len as usize,
+            ErrorCode::DataTooLarge
+        );
+
+        graph
+            .set_node_data(node_id, data, false)
+            .ok_or(ErrorCode::Overflow)?;
+        graph.touch_node(node_id, Clock::get()?.unix_timestamp);
+        fit_account_to(
+            &ctx.accounts.graph_store,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        emit!(AccountAttributeSet {
+            node_id,
+            key,
+            address,
+            owner: expected_owner,
+        });
+        Ok(())
+    }
+
+    /// Grants (or changes) the role of `key`, confined to `partition` if
+    /// given: its queries and writes then only see and create records of
+    /// that partition. Admins only.
+    pub fn grant_role(
+        ctx: Context<WriteGraph>,
+        key: Pubkey,
+        role: Role,
+        partition: Option<u32>,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph
+            .set_role(key, role, partition)
+            .ok_or(ErrorCode::RoleListFull)?;
+
+        emit!(RoleGranted {
+            key,
+            role,
+            partition,
+        });
+        Ok(())
+    }
+
+    /// Creates the graph's compressed node store, for nodes whose payloads
+    /// live off-chain under a Merkle root. Admins only.
+    pub fn init_compressed_nodes(ctx: Context<InitCompressedNodes>) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        let store = &mut ctx.accounts.compressed_nodes;
+        store.graph = graph.key();
+        store.reset();
+        store.bump = ctx.bumps.compressed_nodes;
+        Ok(())
+    }
+
+    /// Appends a compressed node and returns its index. Only its hash is
+    /// stored; the payload is emitted in `CompressedNodeAppended` for
+    /// indexers to keep.
+    pub fn append_compressed_node(
+        ctx: Context<WriteCompressedNodes>,
+        label: String,
+        data: Vec<u8>,
+    ) -> Result<u64> {
+        let graph = &ctx.accounts.graph_store;
+        check_compressed_write(graph, &ctx.accounts.authority, ctx.remaining_accounts)?;
+        check_compressed_payload(graph, &label, &data)?;
+
+        let store = &mut ctx.accounts.compressed_nodes;
+        let index = store.leaf_count;
+        store
+            .append(leaf_hash(index, &label, &data))
+            .ok_or(ErrorCode::GraphLimitExceeded)?;
+
+        emit!(CompressedNodeAppended {
+            index,
+            label,
+            data,
+            root: store.root,
+        });
+        Ok(index)
+    }
+
+    /// Replaces a compressed node's payload, proven with the sibling hashes
+    /// in `proof`, leaves first, against the current root or a recent one
+    /// still in the changelog.
+    pub fn update_compressed_node(
+        ctx: Context<WriteCompressedNodes>,
+        index: u64,
+        old_label: String,
+        old_data: Vec<u8>,
+        label: String,
+        data: Vec<u8>,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let graph = &ctx.accounts.graph_store;
+        check_compressed_write(graph, &ctx.accounts.authority, ctx.remaining_accounts)?;
+        check_compressed_payload(graph, &label, &data)?;
+
+        let store = &mut ctx.accounts.compressed_nodes;
+        require!(
+            store.replace(
+                index,
+                leaf_hash(index, &old_label, &old_data),
+                leaf_hash(index, &label, &data),
+                &proof,
+            ),
+            ErrorCode::InvalidProof
+        );
+
+        emit!(CompressedNodeUpdated {
+            index,
+            label,
+            data,
+            root: store.root,
+        });
+        Ok(())
+    }
+
+    /// Fails unless `label` and `data` are the payload of compressed node
+    /// `index`, proven by `proof` against the current root or a recent one
+    /// still in the changelog.
+    pub fn verify_compressed_node(
+        ctx: Context<ReadCompressedNodes>,
+        index: u64,
+        label: String,
+        data: Vec<u8>,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        check_private_read(
+            &ctx.accounts.graph_store,
+            ctx.accounts.reader.as_ref(),
+            ctx.remaining_accounts,
+        )?;
+        require!(
+            ctx.accounts
+                .compressed_nodes
+                .verify(index, leaf_hash(index, &label, &data), &proof),
+            ErrorCode::InvalidProof
+        );
+        Ok(())
+    }
+
+    /// Restricts who may read `node_id` through `get_node_info`, `RETURN *`
+    /// and `RETURN n.prop`. Admins and the current ACL owner may change it.
+    pub fn set_node_acl(
+        ctx: Context<WriteGraph>,
+        node_id: NodeId,
+        owner: Pubkey,
+        owner_only: bool,
+        readers: Vec<Pubkey>,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            graph.is_admin(&signers)
+                || graph
+                    .node_acl(node_id)
+                    .is_some_and(|acl| signers.contains(&acl.owner)),
+            ErrorCode::Unauthorized
+        );
+        require!(readers.len() <= MAX_ACL_READERS, ErrorCode::AclListFull);
+        require!(
+            graph.get_node_by_id(node_id).is_some(),
+            ErrorCode::NodeNotFound
+        );
+
+        graph
+            .set_node_acl(NodeAcl {
+                node_id,
+                owner,
+                owner_only,
+                readers,
+            })
+            .ok_or(ErrorCode::AclListFull)?;
+
+        emit!(NodeAclChanged {
+            node_id,
+            restricted: true,
+        });
+        fit_written_graph(ctx.accounts)
+    }
+
+    /// Makes `node_id` readable by anyone who can read the graph again.
+    pub fn clear_node_acl(ctx: Context<WriteGraph>, node_id: NodeId) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+        require!(
+            graph.is_admin(&signers)
+                || graph
+                    .node_acl(node_id)
+                    .is_some_and(|acl| signers.contains(&acl.owner)),
+            ErrorCode::Unauthorized
+        );
+
+        graph
+            .remove_node_acl(node_id)
+            .ok_or(ErrorCode::NodeNotFound)?;
+
+        emit!(NodeAclChanged {
+            node_id,
+            restricted: false,
+        });
+        Ok(())
+    }
+
+    pub fn revoke_role(ctx: Context<WriteGraph>, key: Pubkey) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.remove_role(&key).ok_or(ErrorCode::RoleNotFound)?;
+
+        emit!(RoleRevoked { key });
+        Ok(())
+    }
+
+    /// Limits read queries to accounts holding at least the `Reader` role.
+    pub fn set_read_restricted(ctx: Context<WriteGraph>, restricted: bool) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.restrict_reads = restricted;
+        Ok(())
+    }
+
+    /// Extends restricted reads to node, edge and page lookups, so nothing
+    /// the program serves reaches callers without the `Reader` role.
+    pub fn set_private(ctx: Context<WriteGraph>, private: bool) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.private = private;
+        Ok(())
+    }
+
+    /// Caps non-admin writers at `max_writes` per `window_slots` slots;
+    /// `max_writes` of 0 lifts the limit.
+    pub fn set_rate_limit(
+        ctx: Context<WriteGraph>,
+        max_writes: u32,
+        window_slots: u64,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            max_writes == 0 || window_slots > 0,
+            ErrorCode::InvalidConfig
+        );
+
+        graph.max_writes_per_window = max_writes;
+        graph.rate_window_slots = window_slots;
+
+        emit!(RateLimitChanged {
+            max_writes_per_window: max_writes,
+            rate_window_slots: window_slots,
+        });
+        Ok(())
+    }
+
+    /// Sets the oracle allowed to push the attributes named in `keys`, or
+    /// disables pushes with the default key. Admins only.
+    pub fn set_oracle(ctx: Context<WriteGraph>, oracle: Pubkey, keys: Vec<String>) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            keys.len() <= MAX_ORACLE_KEYS
+                && keys
+                    .iter()
+                    .all(|k| k.len() <= graph.config.max_label_len as usize),
+            ErrorCode::InvalidConfig
+        );
+
+        graph.oracle = oracle;
+        graph.oracle_keys = keys.clone();
+        fit_written_graph(ctx.accounts)?;
+
+        emit!(OracleChanged { oracle, keys });
+        Ok(())
+    }
+
+    /// Sets the edge labels the graph keeps acyclic: every write that adds
+    /// an edge closing a cycle of one of them fails with `CycleDetected`.
+    /// A label whose edges already form a cycle is refused. Admins only.
+    pub fn set_acyclic_labels(ctx: Context<WriteGraph>, labels: Vec<String>) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            labels.len() <= MAX_ACYCLIC_LABELS
+                && labels
+                    .iter()
+                    .all(|l| l.len() <= graph.config.max_label_len as usize),
+            ErrorCode::InvalidConfig
+        );
+        let check: Vec<Opcode> = labels
+            .iter()
+            .map(|l| Opcode::RejectCycle(l.clone()))
+            .collect();
+        Vm::new(graph).execute(&check).map_err(map_vm_error)?;
+
+        graph.acyclic_labels = labels.clone();
+        fit_written_graph(ctx.accounts)?;
+
+        emit!(AcyclicLabelsChanged { labels });
+        Ok(())
+    }
+
+    /// Halts every node/edge mutation while leaving reads available.
+    pub fn set_frozen(ctx: Context<WriteGraph>, frozen: bool) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.frozen = frozen;
+
+        emit!(GraphFrozenChanged { frozen });
+        Ok(())
+    }
+
+    /// Requires node and edge creation to go through `commit_write` and
+    /// `reveal_write`, for graphs where write order is competitive.
+    pub fn set_commit_reveal(ctx: Context<WriteGraph>, enabled: bool) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.commit_reveal_writes = enabled;
+        Ok(())
+    }
+
+    /// Charges `lamports` to the signer of every mutating query or direct
+    /// write, paid into `treasury`. Zero disables the fee.
+    pub fn set_write_fee(ctx: Context<WriteGraph>, lamports: u64, treasury: Pubkey) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.write_fee_lamports = lamports;
+        graph.treasury = treasury;
+
+        emit!(WriteFeeChanged { lamports, treasury });
+        Ok(())
+    }
+
+    /// Requires query writers to hold at least `min_amount` of `mint`, checked
+    /// against a token account passed with the query. Works for fungible
+    /// mints and single NFTs; `None` removes the gate.
+    pub fn set_token_gate(
+        ctx: Context<WriteGraph>,
+        mint: Option<Pubkey>,
+        min_amount: u64,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.gate_mint = mint.unwrap_or_default();
+        graph.gate_min_amount = min_amount;
+
+        emit!(TokenGateChanged {
+            mint: graph.gate_mint,
+            min_amount,
+        });
+        Ok(())
+    }
+
+    /// Replaces the graph's query and record limits. Each must be non-zero
+    /// and no larger than the program's compile-time cap.
+    pub fn set_graph_config(ctx: Context<WriteGraph>, config: GraphConfig) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(config.is_valid(), ErrorCode::InvalidConfig);
+
+        graph.config = config;
+
+        emit!(GraphConfigChanged { config });
+        Ok(())
+    }
+
+    /// Opts in to one `NodeMatched` event per node a query returns or
+    /// creates, for indexers that follow the graph incrementally.
+    pub fn set_match_events(ctx: Context<WriteGraph>, enabled: bool) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_admin(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+
+        graph.emit_match_events = enabled;
+        Ok(())
+    }
+
+    /// Hands owner and admin operations to an M-of-N signer set. Approvals
+    /// are the signers among the authority and the remaining accounts. A
+    /// threshold of 0 returns control to the single authority.
+    pub fn set_multisig(
+        ctx: Context<WriteGraph>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        let graph = &mut ctx.accounts.graph_store;
+        require!(
+            graph.is_owner(&signer_keys(
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts
+            )),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            signers.len() <= MAX_MULTISIG_SIGNERS && threshold as usize <= signers.len(),
+            ErrorCode::InvalidMultisig
+        );
+        let mut unique = signers.clone();
+        unique.sort();
+        unique.dedup();
+        require!(unique.len() == signers.len(), ErrorCode::InvalidMultisig);
+
+        graph.multisig_signers = signers;
+        graph.multisig_threshold = threshold;
+
+        emit!(MultisigChanged {
+            signers: graph.multisig_signers.len() as u8,
+            threshold,
+        });
+        Ok(())
+    }
+}
+
+/// Shared tail of `execute_query` and `execute_query_with_params`.
+fn run_query(
+    ctx: Context<ExecuteQuery>,
+    query_len: usize,
+    cypher_query: CypherQuery,
+    expected_version: Option<u64>,
+    partition: Option<u32>,
+    revealed: bool,
+) -> Result<VmResult> {
+    let graph = &ctx.accounts.graph_store;
+    if let Some(expected) = expected_version {
+        require!(graph.version == expected, ErrorCode::VersionMismatch);
+    }
+
+    let has_create = matches!(cypher_query, CypherQuery::Create { .. });
+    let signers = signer_keys(ctx.accounts.authority.key(), ctx.remaining_accounts);
+
+    let mut ops = compile_to_opcodes(cypher_query);
+    // Oversized CREATE data fails here, before any fee is charged
+    check_program(&ops, &graph.config).map_err(map_vm_error)?;
+    scope_partition(graph, &signers, partition, &mut ops)?;
+
+    authorize_query(graph, &signers, &ops, ctx.accounts.session.as_deref_mut())?;
+    if has_create {
+        require!(
+            revealed || !graph.commit_reveal_writes,
+            ErrorCode::CommitRevealRequired
+        );
+        check_token_gate(
+            graph,
+            ctx.accounts.authority.key,
+            ctx.accounts.gate_token_account.as_deref(),
+        )?;
+        charge_write_fee(
+            graph,
+            &ctx.accounts.authority,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            1,
+        )?;
+        check_write_rate(graph, &signers, ctx.accounts.writer_rate.as_deref_mut(), 1)?;
+    }
+
+    let graph = &mut ctx.accounts.graph_store;
+    require!(
+        query_len <= graph.config.max_query_len as usize,
+        ErrorCode::QueryExecutionFailed
+    );
+    check_query_cost(graph, program_cost(&ops, graph))?;
+
+    let appended = JournalChange::Appended {
+        nodes: graph.nodes.len() as u32,
+        edges: graph.edges.len() as u32,
+    };
+    let clock = Clock::get()?;
+    let mut vm = Vm::new(graph)
+        .at_time(clock.unix_timestamp)
+        .at_slot(clock.slot);
+    let mut result = vm.execute(&ops).map_err(map_vm_error)?;
+    graph.hide_unreadable(&mut result, &signers);
+    emit_node_matches(graph, &result.node_ids());
+    require!(
+        result.serialized_size() <= MAX_RETURN_DATA,
+        ErrorCode::ReturnDataTooLarge
+    );
+    if has_create {
+        record_audit(
+            graph,
+            ctx.accounts.audit_log.as_deref_mut(),
+            ctx.accounts.authority.key(),
+            AuditOp::Query,
+            1,
+            0,
+        )?;
+        update_journal(
+            graph,
+            ctx.accounts.journal.as_deref_mut(),
+            |journal, slot| journal.record(slot, appended),
+        )?;
+        fit_graph_account(ctx.accounts)?;
+    }
+    Ok(result)
+}
+
+/// Grows the graph account when a write outgrew it, with rent from `payer`
+/// or, if none was passed, the authority.
+fn fit_graph_account(accounts: &ExecuteQuery) -> Result<()> {
+    fit_account_to(
+        &accounts.graph_store,
+        accounts.payer.as_ref().unwrap_or(&accounts.authority),
+        &accounts.system_program,
+    )
+}
+
+/// `fit_graph_account` for the direct write instructions.
+fn fit_written_graph(accounts: &WriteGraph) -> Result<()> {
+    fit_account_to(
+        &accounts.graph_store,
+        accounts.payer.as_ref().unwrap_or(&accounts.authority),
+        &accounts.system_program,
+    )
+}
+
+/// Grows a query cursor to fit its program and checkpoint, rent paid by
+/// `payer`.
+fn fit_cursor<'info>(
+    cursor: &Account<'info, QueryCursor>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let needed = cursor.space();
+    let info = cursor.to_account_info();
+    if needed <= info.data_len() {
+        return Ok(());
+    }
+    require!(
+        needed - info.data_len() <= MAX_PERMITTED_DATA_INCREASE,
+        ErrorCode::CursorTooLarge
+    );
+    grow_account(&info, needed, payer.to_account_info(), system_program)
+}
+
+/// Grows the graph account to fit its contents, rent paid by `payer`.
+fn fit_account_to<'info>(
+    graph_store: &Account<'info, GraphStore>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let needed = graph_store.serialized_size();
+    let info = graph_store.to_account_info();
+    if needed <= info.data_len() {
+        return Ok(());
+    }
+    require!(
+        needed - info.data_len() <= MAX_PERMITTED_DATA_INCREASE,
+        ErrorCode::GraphLimitExceeded
+    );
+    grow_account(&info, needed, payer.to_account_info(), system_program)
+}
+
+/// Resizes `info` to `len` bytes, topping it up to rent exemption from `payer`.
+fn grow_account<'info>(
+    info: &AccountInfo<'info>,
+    len: usize,
+    payer: AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(len);
+    let top_up = rent.saturating_sub(info.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer,
+                    to: info.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    info.resize(len)?;
+    Ok(())
+}
+
+/// Sets every field of a freshly created GraphStore.
+fn init_graph_store(
+    graph: &mut GraphStore,
+    name: String,
+    authority: Pubkey,
+    bump: u8,
+    user_graph: bool,
+    config: GraphConfig,
+) {
+    graph.layout_version = GRAPH_LAYOUT_VERSION;
+    graph.authority = authority;
+    graph.node_count = 0;
+    graph.edge_count = 0;
+    graph.nonce = 0;
+    graph.version = 0;
+    graph.nodes = Vec::new();
+    graph.edges = Vec::new();
+    graph.edge_offsets = vec![0];
+    graph.adjacency = Vec::new();
+    graph.node_bloom = NodeBloom::default();
+    graph.edge_key_index = Vec::new();
+    graph.compaction_cursor = 0;
+    graph.pending_authority = None;
+    graph.name = name;
+    graph.creator = authority;
+    graph.bump = bump;
+    graph.roles = Vec::new();
+    graph.restrict_reads = false;
+    graph.multisig_signers = Vec::new();
+    graph.multisig_threshold = 0;
+    graph.frozen = false;
+    graph.write_fee_lamports = 0;
+    graph.treasury = Pubkey::default();
+    graph.emit_match_events = false;
+    graph.gate_mint = Pubkey::default();
+    graph.gate_min_amount = 0;
+    graph.config = config;
+    graph.commit_reveal_writes = false;
+    graph.user_graph = user_graph;
+    graph.private = false;
+    graph.node_acls = Vec::new();
+    graph.max_writes_per_window = 0;
+    graph.rate_window_slots = 0;
+    graph.audit_log = false;
+    graph.journaled = false;
+    graph.oracle = Pubkey::default();
+    graph.oracle_keys = Vec::new();
+    graph.acyclic_labels = Vec::new();
+    graph.clone_source = Pubkey::default();
+    graph.clone_cursor = 0;
+}
+
+/// Emits one `NodeMatched` per returned node when the graph opted in.
+fn emit_node_matches(graph: &GraphStore, ids: &[NodeId]) {
+    if !graph.emit_match_events {
+        return;
+    }
+    for &node_id in ids {
+        if let Some(node) = graph.get_node_by_id(node_id) {
+            emit!(NodeMatched {
+                node_id,
+                label: node.label.to_string(),
+            });
+        }
+    }
+}
+
+/// Writers may mutate an unfrozen graph, and so may a live session key whose
+/// scope covers every write in `ops`; anyone allowed to read may query.
+fn authorize_query(
+    graph: &GraphStore,
+    signers: &[Pubkey],
+    ops: &[Opcode],
+    session: Option<&mut SessionKey>,
+) -> Result<()> {
+    let writes = ops.iter().filter(|op| op.is_write()).count() as u32;
+    if writes > 0 {
+        if !graph.any_has_role(signers, Role::Writer) {
+            let session = session.ok_or(ErrorCode::Unauthorized)?;
+            require!(
+                session.permits(signers, Clock::get()?.slot, ops),
+                ErrorCode::SessionInvalid
+            );
+            session.write_ops_used += writes;
+        }
+        require!(!graph.frozen, ErrorCode::GraphFrozen);
+    } else {
+        require!(
+            signers.iter().any(|k| graph.can_read(k)),
+            ErrorCode::Unauthorized
+        );
+    }
+    Ok(())
+}
+
+/// Confines `ops` to the partition `signers` may run them in, chosen by
+/// `scoped_partition` from `requested` or the program's own leading
+/// `SetPartition`. Called after the `max_ops` check, so the inserted
+/// `SetPartition` does not count against it.
+fn scope_partition(
+    graph: &GraphStore,
+    signers: &[Pubkey],
+    requested: Option<u32>,
+    ops: &mut Vec<Opcode>,
+) -> Result<()> {
+    let mut requested = requested;
+    if let Some(Opcode::SetPartition(partition)) = ops.first() {
+        requested = requested.or(Some(*partition));
+        ops.remove(0);
+    }
+    require!(
+        !ops.iter().any(|op| matches!(op, Opcode::SetPartition(_))),
+        ErrorCode::QueryExecutionFailed
+    );
+    let partition = graph
+        .scoped_partition(signers, requested)
+        .ok_or(ErrorCode::Unauthorized)?;
+    if let Some(partition) = partition {
+        ops.insert(0, Opcode::SetPartition(partition));
+    }
+    Ok(())
+}
+
+/// Rejects programs whose estimated cost is above the graph's configured
+/// threshold, before they run.
+fn check_query_cost(graph: &GraphStore, cost: u64) -> Result<()> {
+    require!(
+        cost <= graph.config.max_query_cost as u64,
+        ErrorCode::QueryTooExpensive
+    );
+    Ok(())
+}
+
+/// On a rate-limited graph, counts `writes` against the signer's
+/// `WriterRate` window and rejects them past the limit. Admins are exempt.
+fn check_write_rate(
+    graph: &GraphStore,
+    signers: &[Pubkey],
+    rate: Option<&mut WriterRate>,
+    writes: u64,
+) -> Result<()> {
+    if graph.max_writes_per_window == 0 || writes == 0 || graph.is_admin(signers) {
+        return Ok(());
+    }
+    let rate = rate.ok_or(ErrorCode::RateLimited)?;
+    let writes = u32::try_from(writes).map_err(|_| ErrorCode::RateLimited)?;
+    require!(
+        rate.try_record(
+            Clock::get()?.slot,
+            writes,
+            graph.max_writes_per_window,
+            graph.rate_window_slots,
+        ),
+        ErrorCode::RateLimited
+    );
+    Ok(())
+}
+
+/// Appends a write to the graph's audit log, which must be passed once the
+/// log is enabled.
+fn record_audit(
+    graph: &GraphStore,
+    log: Option<&mut AuditLog>,
+    signer: Pubkey,
+    op: AuditOp,
+    writes: u64,
+    target: NodeId,
+) -> Result<()> {
+    if writes == 0 {
+        return Ok(());
+    }
+    let Some(log) = log else {
+        require!(!graph.audit_log, ErrorCode::AuditLogRequired);
+        return Ok(());
+    };
+    log.record(AuditEntry {
+        signer,
+        slot: Clock::get()?.slot,
+        op,
+        writes: u32::try_from(writes).unwrap_or(u32::MAX),
+        target,
+        version: graph.version,
+    });
+    Ok(())
+}
+
+/// Compressed node writes need the `Writer` role on an unfrozen graph.
+fn check_compressed_write(
+    graph: &GraphStore,
+    authority: &Signer,
+    remaining: &[AccountInfo],
+) -> Result<()> {
+    require!(
+        graph.any_has_role(&signer_keys(authority.key(), remaining), Role::Writer),
+        ErrorCode::Unauthorized
+    );
+    require!(!graph.frozen, ErrorCode::GraphFrozen);
+    Ok(())
+}
+
+/// Compressed payloads obey the same size limits as stored nodes.
+fn check_compressed_payload(graph: &GraphStore, label: &str, data: &[u8]) -> Result<()> {
+    require!(
+        label.len() <= graph.config.max_label_len as usize,
+        ErrorCode::LabelTooLong
+    );
+    require!(
+        data.len() <= graph.config.max_node_data_len as usize,
+        ErrorCode::DataTooLarge
+    );
+    Ok(())
+}
+
+/// Applies `update` to the graph's journal at the current slot. The journal
+/// must be passed once the graph is journaled.
+fn update_journal(
+    graph: &GraphStore,
+    journal: Option<&mut Journal>,
+    update: impl FnOnce(&mut Journal, u64),
+) -> Result<()> {
+    match journal {
+        Some(journal) => update(journal, Clock::get()?.slot),
+        None => require!(!graph.journaled, ErrorCode::JournalRequired),
+    }
+    Ok(())
+}
+
+/// On a private graph, lookups need a signer holding the `Reader` role.
+fn check_private_read(
+    graph: &GraphStore,
+    reader: Option<&Signer>,
+    remaining: &[AccountInfo],
+) -> Result<()> {
+    if !graph.private {
+        return Ok(());
+    }
+    let reader = reader.ok_or(ErrorCode::Unauthorized)?;
+    require!(
+        signer_keys(reader.key(), remaining)
+            .iter()
+            .any(|k| graph.can_read(k)),
+        ErrorCode::Unauthorized
+    );
+    Ok(())
+}
+
+/// On a token-gated graph, writers must pass a token account they own
+/// holding enough of the gate mint.
+fn check_token_gate(
+    graph: &GraphStore,
+    holder: &Pubkey,
+    token_account: Option<&TokenAccount>,
+) -> Result<()> {
+    if !graph.is_token_gated() {
+        return Ok(());
+    }
+    let account = token_account.ok_or(ErrorCode::TokenGateFailed)?;
+    require!(
+        graph.passes_token_gate(holder, &account.owner, &account.mint, account.amount),
+        ErrorCode::TokenGateFailed
+    );
+    Ok(())
+}
+
+/// Moves `writes` times the configured write fee from `payer` to the treasury.
+fn charge_write_fee<'info>(
+    graph: &GraphStore,
+    payer: &Signer<'info>,
+    treasury: Option<&UncheckedAccount<'info>>,
+    system_program: &Program<'info, System>,
+    writes: u64,
+) -> Result<()> {
+    let lamports = graph
+        .write_fee_lamports
+        .checked_mul(writes)
+        .ok_or(ErrorCode::Overflow)?;
+    if lamports == 0 {
+        return Ok(());
+    }
+
+    let treasury = treasury.ok_or(ErrorCode::TreasuryMissing)?;
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: treasury.to_account_info(),
+            },
+        ),
+        lamports,
+    )
+}
+
+/// Whether `account` is one of the child accounts of `graph`. Most store
+/// their graph first; sessions and commitments only have it as a seed.
+fn is_graph_child(account: &AccountInfo, graph: &Pubkey) -> bool {
+    let Ok(data) = account.try_borrow_data() else {
+        return false;
+    };
+    let Some(discriminator) = data.get(..8) else {
+        return false;
+    };
+    let derives = |seeds: &[&[u8]]| {
+        Pubkey::create_program_address(seeds, &crate::ID).is_ok_and(|k| k == account.key())
+    };
+
+    if discriminator == SessionKey::DISCRIMINATOR {
+        SessionKey::try_deserialize(&mut &data[..])
+            .is_ok_and(|s| derives(&[SESSION_SEED, graph.as_ref(), s.key.as_ref(), &[s.bump]]))
+    } else if discriminator == WriteCommitment::DISCRIMINATOR {
+        WriteCommitment::try_deserialize(&mut &data[..]).is_ok_and(|c| {
+            derives(&[
+                COMMIT_SEED,
+                graph.as_ref(),
+                c.committer.as_ref(),
+                c.hash.as_ref(),
+                &[c.bump],
+            ])
+        })
+    } else {
+        [
+            AuditLog::DISCRIMINATOR,
+            CompressedNodes::DISCRIMINATOR,
+            GraphMetadata::DISCRIMINATOR,
+            Journal::DISCRIMINATOR,
+            Proposal::DISCRIMINATOR,
+            QueryCursor::DISCRIMINATOR,
+            WriterRate::DISCRIMINATOR,
+        ]
+        .contains(&discriminator)
+            && data.get(8..40) == Some(graph.as_ref())
+    }
+}
+
+/// Closes `account` as Anchor's `close` constraint does: its lamports go
+/// to `recipient` and it is handed back to the system program, emptied.
+fn close_child_account(account: &AccountInfo, recipient: &AccountInfo) -> Result<()> {
+    let lamports = recipient
+        .lamports()
+        .checked_add(account.lamports())
+        .ok_or(ErrorCode::Overflow)?;
+    **recipient.try_borrow_mut_lamports()? = lamports;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.assign(&system_program::ID);
+    account.resize(0)?;
+    Ok(())
+}
+
+/// The primary signer followed by every remaining account that also signed.
+fn signer_keys(primary: Pubkey, remaining: &[AccountInfo]) -> Vec<Pubkey> {
+    std::iter::once(primary)
+        .chain(remaining.iter().filter(|a| a.is_signer).map(|a| a.key()))
+        .collect()
+}
+
+fn map_migration_error(e: MigrationError) -> ErrorCode {
+    match e {
+        MigrationError::NotAGraph => ErrorCode::NotAGraph,
+        MigrationError::AlreadyCurrent => ErrorCode::AlreadyMigrated,
+        MigrationError::UnsupportedLayout(_) => ErrorCode::UnsupportedLayout,
+    }
+}
+
+fn map_vm_error(e: VmError) -> ErrorCode {
+    match e {
+        VmError::NodeNotFound => ErrorCode::NodeNotFound,
+        VmError::DuplicateEdge => ErrorCode::DuplicateEdge,
+        VmError::DuplicateNodeId => ErrorCode::DuplicateNodeId,
+        VmError::Overflow => ErrorCode::Overflow,
+        VmError::TraversalBudgetExceeded => ErrorCode::TraversalBudgetExceeded,
+        VmError::CycleDetected => ErrorCode::CycleDetected,
+        VmError::DataTooLarge | VmError::LabelTooLong | VmError::GraphLimitExceeded => {
+            ErrorCode::QueryExecutionFailed
+        }
+        _ => ErrorCode::QueryExecutionFailed,
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(name: String, sizing: GraphSizing)]
+pub struct InitializeGraph<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = sizing.initial_space(),
+        seeds = [GRAPH_STORE_SEED, name.as_bytes(), authority.key().as_ref()],
+        bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+
+    /// Funds the account's rent; may differ from `authority`.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(sizing: GraphSizing)]
+pub struct InitializeUserGraph<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = sizing.initial_space(),
+        seeds = [USER_GRAPH_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub user: Signer<'info>,
+
+    /// Funds the account's rent; may differ from `user`.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteQuery<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    /// Caller whose role is checked. Extra signers may be passed as remaining
+    /// accounts and count as co-signers for the write check. Pays write fees.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Receives write fees; must match the configured treasury
+    #[account(mut, address = graph_store.treasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+
+    /// Writer's holding of the gate mint on token-gated graphs.
+    pub gate_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Sponsors rent when a write grows the account; defaults to `authority`.
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+
+    /// Commitment consumed by `reveal_write`.
+    #[account(
+        mut,
+        seeds = [
+            COMMIT_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref(),
+            commitment.hash.as_ref()
+        ],
+        bump = commitment.bump
+    )]
+    pub commitment: Option<Account<'info, WriteCommitment>>,
+
+    /// Delegated write access when `authority` is a session key.
+    #[account(
+        mut,
+        seeds = [SESSION_SEED, graph_store.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, SessionKey>>,
+
+    /// Caller's write counter; required on rate-limited graphs unless the
+    /// caller is an admin.
+    #[account(
+        mut,
+        seeds = [
+            WRITER_RATE_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = writer_rate.bump
+    )]
+    pub writer_rate: Option<Account<'info, WriterRate>>,
+
+    /// Graph's audit log; required once it is enabled.
+    #[account(
+        mut,
+        seeds = [AUDIT_LOG_SEED, graph_store.key().as_ref()],
+        bump = audit_log.bump
+    )]
+    pub audit_log: Option<Account<'info, AuditLog>>,
+
+    /// Graph's journal; required once the graph is journaled.
+    #[account(
+        mut,
+        seeds = [JOURNAL_SEED, graph_store.key().as_ref()],
+        bump = journal.bump
+    )]
+    pub journal: Option<Account<'info, Journal>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetNodeInfo<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    /// Required on private graphs; must hold the `Reader` role.
+    pub reader: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct GetEdgeInfo<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    /// Required on private graphs; must hold the `Reader` role.
+    pub reader: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct GetNodesPage<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    /// Required on private graphs; must hold the `Reader` role.
+    pub reader: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct GetCapacity<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+}
+
+#[derive(Accounts)]
+pub struct EstimateQuery<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    /// Required on private graphs; must hold the `Reader` role.
+    pub reader: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct SoftDeleteNode<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+    /// Graph's journal; required once the graph is journaled.
+    #[account(
+        mut,
+        seeds = [JOURNAL_SEED, graph_store.key().as_ref()],
+        bump = journal.bump
+    )]
+    pub journal: Option<Account<'info, Journal>>,
+}
+
+#[derive(Accounts)]
+pub struct SetNodeProtected<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CompactGraph<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+    /// Graph's journal; required once the graph is journaled.
+    #[account(
+        mut,
+        seeds = [JOURNAL_SEED, graph_store.key().as_ref()],
+        bump = journal.bump
+    )]
+    pub journal: Option<Account<'info, Journal>>,
+}
+
+#[derive(Accounts)]
+pub struct RebuildIndices<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloneGraph<'info> {
+    #[account(
+        seeds = [
+            source.seed_prefix(),
+            source.name.as_bytes(),
+            source.creator.as_ref()
+        ],
+        bump = source.bump
+    )]
+    pub source: Account<'info, GraphStore>,
+
+    #[account(
+        mut,
+        seeds = [
+            destination.seed_prefix(),
+            destination.name.as_bytes(),
+            destination.creator.as_ref()
+        ],
+        bump = destination.bump,
+        constraint = destination.key() != source.key() @ ErrorCode::InvalidClone
+    )]
+    pub destination: Account<'info, GraphStore>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Destination's journal; required once it is journaled.
+    #[account(
+        mut,
+        seeds = [JOURNAL_SEED, destination.key().as_ref()],
+        bump = journal.bump
+    )]
+    pub journal: Option<Account<'info, Journal>>,
+
+    /// Sponsors rent as the copy grows `destination`; defaults to
+    /// `authority`.
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TransferAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(additional: u32)]
+pub struct ReallocGraph<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump,
+        realloc = graph_store.to_account_info().data_len() + additional as usize,
+        realloc::payer = payer,
+        realloc::zero = false
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateGraph<'info> {
+    /// CHECK: May hold an older layout that `Account<GraphStore>` cannot
+    /// decode; the discriminator and header are checked in the function
+    #[account(mut, owner = crate::ID)]
+    pub graph_store: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseGraph<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump,
+        close = recipient
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Only receives the reclaimed rent lamports
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitGraphMetadata<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = GraphMetadata::SPACE,
+        seeds = [METADATA_SEED, graph_store.key().as_ref()],
+        bump
+    )]
+    pub metadata: Account<'info, GraphMetadata>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGraphMetadata<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        mut,
+        seeds = [METADATA_SEED, graph_store.key().as_ref()],
+        bump = metadata.bump
+    )]
+    pub metadata: Account<'info, GraphMetadata>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(hash: [u8; 32])]
+pub struct CommitWrite<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = WriteCommitment::SPACE,
+        seeds = [
+            COMMIT_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref(),
+            hash.as_ref()
+        ],
+        bump
+    )]
+    pub commitment: Account<'info, WriteCommitment>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitWriterRate<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = WriterRate::SPACE,
+        seeds = [
+            WRITER_RATE_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump
+    )]
+    pub writer_rate: Account<'info, WriterRate>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitAuditLog<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AuditLog::SPACE,
+        seeds = [AUDIT_LOG_SEED, graph_store.key().as_ref()],
+        bump
+    )]
+    pub audit_log: Account<'info, AuditLog>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BindMint<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAccountAttribute<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    /// CHECK: Only its address and owner are read, and checked against the
+    /// expected owner
+    pub account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitCompressedNodes<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = CompressedNodes::SPACE,
+        seeds = [COMPRESSED_NODES_SEED, graph_store.key().as_ref()],
+        bump
+    )]
+    pub compressed_nodes: Account<'info, CompressedNodes>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WriteCompressedNodes<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        mut,
+        seeds = [COMPRESSED_NODES_SEED, graph_store.key().as_ref()],
+        bump = compressed_nodes.bump
+    )]
+    pub compressed_nodes: Account<'info, CompressedNodes>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReadCompressedNodes<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        seeds = [COMPRESSED_NODES_SEED, graph_store.key().as_ref()],
+        bump = compressed_nodes.bump
+    )]
+    pub compressed_nodes: Account<'info, CompressedNodes>,
+
+    /// Required on private graphs; must hold the `Reader` role.
+    pub reader: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct InitJournal<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Journal::SPACE,
+        seeds = [JOURNAL_SEED, graph_store.key().as_ref()],
+        bump
+    )]
+    pub journal: Account<'info, Journal>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct QueryAsOf<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        seeds = [JOURNAL_SEED, graph_store.key().as_ref()],
+        bump = journal.bump
+    )]
+    pub journal: Account<'info, Journal>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReadQuery<'info> {
+    /// CHECK: Decoded by `GraphStore::try_deserialize_structure`, which
+    /// checks the discriminator; the function checks the PDA seeds
+    #[account(owner = crate::ID)]
+    pub graph_store: UncheckedAccount<'info>,
+
+    /// Caller whose read access is checked; extra signers may be passed as
+    /// remaining accounts.
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetAuditLog<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        seeds = [AUDIT_LOG_SEED, graph_store.key().as_ref()],
+        bump = audit_log.bump
+    )]
+    pub audit_log: Account<'info, AuditLog>,
+
+    /// Required on private graphs; must hold the `Reader` role.
+    pub reader: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct OpenProposal<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Proposal::SPACE,
+        seeds = [
+            PROPOSAL_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StageProposal<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        mut,
+        seeds = [
+            PROPOSAL_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    /// Proposer; pays rent as the proposal grows.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitProposal<'info> {
+    #[account(
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
 
-        msg!(
-            "GraphStore initialized by: {:?}",
-            ctx.accounts.authority.key()
-        );
-        Ok(())
-    }
+    #[account(
+        mut,
+        seeds = [
+            PROPOSAL_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = proposal.bump,
+        close = authority
+    )]
+    pub proposal: Account<'info, Proposal>,
 
-    pub fn execute_query(ctx: Context<ExecuteQuery>, query: String) -> Result<VmResult> {
-        let graph = &ctx.accounts.graph_store;
-        let cypher_query = parse(&query).map_err(|_| ErrorCode::QueryExecutionFailed)?;
+    /// Proposer; must hold the Writer role when committing. Pays write fees.
+    #[account(mut)]
+    pub authority: Signer<'info>,
 
-        let has_create = matches!(cypher_query, CypherQuery::Create { .. });
+    /// CHECK: Receives write fees; must match the configured treasury
+    #[account(mut, address = graph_store.treasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
 
-        if has_create {
-            require!(
-                ctx.accounts.authority.key() == graph.authority,
-                ErrorCode::Unauthorized
-            );
-        }
+    /// Writer's holding of the gate mint on token-gated graphs.
+    pub gate_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
-        let graph = &mut ctx.accounts.graph_store;
-        let ops = compile_to_opcodes(cypher_query);
+    /// Sponsors rent when the commit grows the account; defaults to `authority`.
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
 
-        require!(query.len() <= 4096, ErrorCode::QueryExecutionFailed);
-        require!(ops.len() <= 100, ErrorCode::QueryExecutionFailed);
+    /// Caller's write counter; required on rate-limited graphs unless the
+    /// caller is an admin.
+    #[account(
+        mut,
+        seeds = [
+            WRITER_RATE_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = writer_rate.bump
+    )]
+    pub writer_rate: Option<Account<'info, WriterRate>>,
 
-        let mut vm = Vm::new(graph);
-        let result = vm.execute(&ops).map_err(|e| match e {
-            VmError::NodeNotFound => ErrorCode::NodeNotFound,
-            VmError::Overflow => ErrorCode::Overflow,
-            VmError::DataTooLarge | VmError::LabelTooLong | VmError::GraphLimitExceeded => {
-                ErrorCode::QueryExecutionFailed
-            }
-            _ => ErrorCode::QueryExecutionFailed,
-        })?;
-        Ok(result)
-    }
+    /// Graph's audit log; required once it is enabled.
+    #[account(
+        mut,
+        seeds = [AUDIT_LOG_SEED, graph_store.key().as_ref()],
+        bump = audit_log.bump
+    )]
+    pub audit_log: Option<Account<'info, AuditLog>>,
 
-    pub fn get_node_info(ctx: Context<GetNodeInfo>, node_id: u128) -> Result<()> {
-        let graph = &ctx.accounts.graph_store;
+    /// Graph's journal; required once the graph is journaled.
+    #[account(
+        mut,
+        seeds = [JOURNAL_SEED, graph_store.key().as_ref()],
+        bump = journal.bump
+    )]
+    pub journal: Option<Account<'info, Journal>>,
 
-        let node = graph
-            .nodes
-            .iter()
-            .find(|n| n.id == node_id)
-            .ok_or(ErrorCode::NodeNotFound)?;
+    pub system_program: Program<'info, System>,
+}
 
-        msg!(
-            "Node {}: label='{}', outgoing_edges={}",
-            node_id,
-            node.label,
-            node.outgoing_edge_indices.len()
-        );
+#[derive(Accounts)]
+pub struct CancelProposal<'info> {
+    #[account(
+        mut,
+        seeds = [
+            PROPOSAL_SEED,
+            proposal.graph.as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = proposal.bump,
+        close = authority
+    )]
+    pub proposal: Account<'info, Proposal>,
 
-        Ok(())
-    }
+    #[account(mut)]
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct InitializeGraph<'info> {
+pub struct OpenQueryCursor<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
     #[account(
         init,
         payer = authority,
-        space = 8 +
-                32 +
-                8 +
-                8 +
-                16 +
-                4 + (512) +
-                4 + (256),
-        seeds = [b"graph_store"],
+        space = QueryCursor::SPACE,
+        seeds = [
+            CURSOR_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
         bump
     )]
+    pub cursor: Account<'info, QueryCursor>,
+
+    /// Caller whose read access is checked; pays the cursor's rent.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResumeQuery<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
     pub graph_store: Account<'info, GraphStore>,
 
+    #[account(
+        mut,
+        seeds = [
+            CURSOR_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = cursor.bump
+    )]
+    pub cursor: Account<'info, QueryCursor>,
+
+    /// Cursor owner; pays rent as the cursor grows and gets it back when
+    /// the query finishes.
     #[account(mut)]
     pub authority: Signer<'info>,
 
@@ -106,38 +4067,402 @@ pub struct InitializeGraph<'info> {
 }
 
 #[derive(Accounts)]
-pub struct ExecuteQuery<'info> {
+pub struct CloseQueryCursor<'info> {
     #[account(
         mut,
-        seeds = [b"graph_store"],
+        seeds = [
+            CURSOR_SEED,
+            cursor.graph.as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = cursor.bump,
+        close = authority
+    )]
+    pub cursor: Account<'info, QueryCursor>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: Pubkey)]
+pub struct CreateSession<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
+    pub graph_store: Account<'info, GraphStore>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = SessionKey::SPACE,
+        seeds = [SESSION_SEED, graph_store.key().as_ref(), key.as_ref()],
         bump
     )]
+    pub session: Account<'info, SessionKey>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSession<'info> {
+    #[account(
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
+    )]
     pub graph_store: Account<'info, GraphStore>,
 
-    /// CHECK: Authority is only required for CREATE operations, checked in the function
-    pub authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [SESSION_SEED, graph_store.key().as_ref(), session.key.as_ref()],
+        bump = session.bump,
+        close = granter
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Receives the session's rent; must be the admin who created it
+    #[account(mut, address = session.granter)]
+    pub granter: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
-pub struct GetNodeInfo<'info> {
+pub struct WriteGraph<'info> {
     #[account(
-        seeds = [b"graph_store"],
-        bump
+        mut,
+        seeds = [
+            graph_store.seed_prefix(),
+            graph_store.name.as_bytes(),
+            graph_store.creator.as_ref()
+        ],
+        bump = graph_store.bump
     )]
     pub graph_store: Account<'info, GraphStore>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Receives write fees; must match the configured treasury
+    #[account(mut, address = graph_store.treasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+
+    /// Caller's write counter; required on rate-limited graphs unless the
+    /// caller is an admin.
+    #[account(
+        mut,
+        seeds = [
+            WRITER_RATE_SEED,
+            graph_store.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = writer_rate.bump
+    )]
+    pub writer_rate: Option<Account<'info, WriterRate>>,
+
+    /// Graph's audit log; required once it is enabled.
+    #[account(
+        mut,
+        seeds = [AUDIT_LOG_SEED, graph_store.key().as_ref()],
+        bump = audit_log.bump
+    )]
+    pub audit_log: Option<Account<'info, AuditLog>>,
+
+    /// Graph's journal; required once the graph is journaled.
+    #[account(
+        mut,
+        seeds = [JOURNAL_SEED, graph_store.key().as_ref()],
+        bump = journal.bump
+    )]
+    pub journal: Option<Account<'info, Journal>>,
+
+    /// Sponsors rent when a write grows the account; defaults to `authority`.
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[event]
 pub struct NodeAdded {
-    pub node_id: u128,
+    pub node_id: NodeId,
     pub node_count: u64,
 }
 
 #[event]
 pub struct EdgeAdded {
-    pub from: u128,
-    pub to: u128,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub edge_count: u64,
+}
+
+#[event]
+pub struct NodeMatched {
+    pub node_id: NodeId,
+    pub label: String,
+}
+
+#[event]
+pub struct NodeDeleted {
+    pub node_id: NodeId,
+}
+
+#[event]
+pub struct NodeRestored {
+    pub node_id: NodeId,
+}
+
+#[event]
+pub struct NodeProtectionChanged {
+    pub node_id: NodeId,
+    pub protected: bool,
+}
+
+#[event]
+pub struct LabelDeleted {
+    pub label: String,
+    pub removed_nodes: u64,
+    pub removed_edges: u64,
+    pub done: bool,
+}
+
+#[event]
+pub struct NodeRemoved {
+    pub node_id: NodeId,
+    pub removed_edges: u64,
+}
+
+#[event]
+pub struct CompressedNodeAppended {
+    pub index: u64,
+    pub label: String,
+    pub data: Vec<u8>,
+    pub root: [u8; 32],
+}
+
+#[event]
+pub struct CompressedNodeUpdated {
+    pub index: u64,
+    pub label: String,
+    pub data: Vec<u8>,
+    pub root: [u8; 32],
+}
+
+#[event]
+pub struct MintBound {
+    pub node_id: NodeId,
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct AccountAttributeSet {
+    pub node_id: NodeId,
+    pub key: String,
+    pub address: Pubkey,
+    pub owner: Option<Pubkey>,
+}
+
+#[event]
+pub struct AttributeUpdated {
+    pub node_id: NodeId,
+    pub key: String,
+    pub value: i64,
+}
+
+#[event]
+pub struct OracleChanged {
+    pub oracle: Pubkey,
+    pub keys: Vec<String>,
+}
+
+#[event]
+pub struct AcyclicLabelsChanged {
+    pub labels: Vec<String>,
+}
+
+#[event]
+pub struct NodeDataUpdated {
+    pub node_id: NodeId,
+    pub data_len: u32,
+}
+
+#[event]
+pub struct RoleGranted {
+    pub key: Pubkey,
+    pub role: Role,
+    pub partition: Option<u32>,
+}
+
+#[event]
+pub struct RoleRevoked {
+    pub key: Pubkey,
+}
+
+#[event]
+pub struct MultisigChanged {
+    pub signers: u8,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct GraphFrozenChanged {
+    pub frozen: bool,
+}
+
+#[event]
+pub struct GraphConfigChanged {
+    pub config: GraphConfig,
+}
+
+#[event]
+pub struct TokenGateChanged {
+    pub mint: Pubkey,
+    pub min_amount: u64,
+}
+
+#[event]
+pub struct WriteFeeChanged {
+    pub lamports: u64,
+    pub treasury: Pubkey,
+}
+
+#[event]
+pub struct GraphResized {
+    pub account_bytes: u64,
+}
+
+#[event]
+pub struct GraphMigrated {
+    pub from_layout: u8,
+    pub to_layout: u8,
+}
+
+#[event]
+pub struct NodeReclaimed {
+    pub node_id: NodeId,
+}
+
+#[event]
+pub struct EdgeReclaimed {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub label: String,
+}
+
+#[event]
+pub struct GraphCompacted {
+    pub removed_nodes: u64,
+    pub removed_edges: u64,
+    pub cursor: u32,
+    pub done: bool,
+}
+
+#[event]
+pub struct IndexRebuilt {
+    pub index: IndexKind,
+}
+
+#[event]
+pub struct GraphCloned {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub cursor: u32,
+    pub done: bool,
+}
+
+#[event]
+pub struct AuthorityTransferProposed {
+    pub current_authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct GraphMetadataChanged {
+    pub graph: Pubkey,
+}
+
+#[event]
+pub struct QueryReceiptIssued {
+    pub version: u64,
+    pub query_hash: [u8; 32],
+    pub receipt_hash: [u8; 32],
+}
+
+#[event]
+pub struct WriteCommitted {
+    pub committer: Pubkey,
+    pub hash: [u8; 32],
+    pub slot: u64,
+}
+
+#[event]
+pub struct WriteRevealed {
+    pub committer: Pubkey,
+    pub hash: [u8; 32],
+}
+
+#[event]
+pub struct ProposalStaged {
+    pub proposer: Pubkey,
+    pub staged_ops: u32,
+}
+
+#[event]
+pub struct ProposalCommitted {
+    pub proposer: Pubkey,
+    pub applied_ops: u32,
+    pub version: u64,
+}
+
+#[event]
+pub struct NodeAclChanged {
+    pub node_id: NodeId,
+    pub restricted: bool,
+}
+
+#[event]
+pub struct RateLimitChanged {
+    pub max_writes_per_window: u32,
+    pub rate_window_slots: u64,
+}
+
+#[event]
+pub struct SessionCreated {
+    pub key: Pubkey,
+    pub expires_slot: u64,
+    pub max_write_ops: u32,
+}
+
+#[event]
+pub struct SessionRevoked {
+    pub key: Pubkey,
+}
+
+#[event]
+pub struct GraphClosed {
+    pub recipient: Pubkey,
+    pub node_count: u64,
     pub edge_count: u64,
+    /// Child accounts closed along with the graph.
+    pub children: u32,
 }
 
 #[error_code]
@@ -158,4 +4483,190 @@ pub enum ErrorCode {
     LabelTooLong,
     #[msg("Graph limit exceeded")]
     GraphLimitExceeded,
+    #[msg("Graph version does not match the expected version")]
+    VersionMismatch,
+    #[msg("Duplicate edge")]
+    DuplicateEdge,
+    #[msg("Node is protected")]
+    NodeProtected,
+    #[msg("Graph name too long")]
+    NameTooLong,
+    #[msg("Query result exceeds the return data limit")]
+    ReturnDataTooLarge,
+    #[msg("Role table is full")]
+    RoleListFull,
+    #[msg("Account holds no role")]
+    RoleNotFound,
+    #[msg("Multisig threshold must not exceed the number of distinct signers")]
+    InvalidMultisig,
+    #[msg("Graph is frozen")]
+    GraphFrozen,
+    #[msg("Write fee is set but no treasury account was passed")]
+    TreasuryMissing,
+    #[msg("Account is not a GraphStore")]
+    NotAGraph,
+    #[msg("Graph already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("Unknown graph layout version")]
+    UnsupportedLayout,
+    #[msg("Edge not found")]
+    EdgeNotFound,
+    #[msg("Destination graph must be empty")]
+    GraphNotEmpty,
+    #[msg("Cannot clone a graph into itself")]
+    InvalidClone,
+    #[msg("Signer does not hold the token required to write")]
+    TokenGateFailed,
+    #[msg("Graph config limit is zero or above its hard cap")]
+    InvalidConfig,
+    #[msg("Traversal reached more nodes than the graph allows")]
+    TraversalBudgetExceeded,
+    #[msg("Session key expired, exhausted or out of scope")]
+    SessionInvalid,
+    #[msg("Too many session labels or a label is too long")]
+    InvalidSessionScope,
+    #[msg("Writes on this graph must be committed and revealed")]
+    CommitRevealRequired,
+    #[msg("Revealed write does not match its commitment")]
+    CommitmentMismatch,
+    #[msg("A write cannot be revealed in the slot it was committed")]
+    RevealTooEarly,
+    #[msg("Metadata field or tag list too large")]
+    MetadataTooLarge,
+    #[msg("Proposals may only stage write and write setup opcodes")]
+    InvalidProposal,
+    #[msg("Proposal exceeds the staged operation limit")]
+    ProposalTooLarge,
+    #[msg("Query cost exceeds the graph's configured limit")]
+    QueryTooExpensive,
+    #[msg("Node ACL table or reader list is full")]
+    AclListFull,
+    #[msg("Writer exceeded the graph's write rate limit")]
+    RateLimited,
+    #[msg("Graph has an audit log; pass it with every write")]
+    AuditLogRequired,
+    #[msg("Graph is journaled; pass its journal with every write")]
+    JournalRequired,
+    #[msg("Journal no longer covers the requested slot")]
+    HistoryUnavailable,
+    #[msg("Merkle proof does not match the compressed node root")]
+    InvalidProof,
+    #[msg("Node data is not an attribute list")]
+    NotAttributeData,
+    #[msg("Mint is not an NFT: it needs zero decimals and a supply of one")]
+    NotAnNft,
+    #[msg("Node is already bound to a mint")]
+    MintAlreadyBound,
+    #[msg("Account is not owned by the expected program")]
+    AccountOwnerMismatch,
+    #[msg("Graph changed since the query cursor was opened")]
+    CursorStale,
+    #[msg("Query cursor grew more than one transaction allows")]
+    CursorTooLarge,
+    #[msg("Edges with a label kept acyclic would form a cycle")]
+    CycleDetected,
+    #[msg("Account passed to close_graph is not one of the graph's")]
+    InvalidChildAccount,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{Discriminator, Event};
+    use sol_micro_sql_types::events::{self as mirror, ProgramEvent};
+
+    /// Encodes `event` and checks the mirror decodes it and re-encodes it
+    /// to the same bytes.
+    fn assert_mirrors<E: Event, M: ProgramEvent + AnchorSerialize>(event: E) {
+        let data = event.data();
+        let decoded = M::decode(&data).expect("mirror does not decode event");
+        let mut reencoded = M::discriminator().to_vec();
+        decoded.serialize(&mut reencoded).unwrap();
+        assert_eq!(reencoded, data);
+    }
+
+    macro_rules! assert_discriminators {
+        ($($event:ident),* $(,)?) => {
+            $(
+                assert_eq!(
+                    $event::DISCRIMINATOR,
+                    mirror::$event::discriminator(),
+                    stringify!($event)
+                );
+            )*
+        };
+    }
+
+    #[test]
+    fn test_event_mirrors_match() {
+        assert_discriminators!(
+            NodeAdded,
+            EdgeAdded,
+            NodeMatched,
+            NodeDeleted,
+            NodeRestored,
+            NodeProtectionChanged,
+            LabelDeleted,
+            NodeRemoved,
+            CompressedNodeAppended,
+            CompressedNodeUpdated,
+            MintBound,
+            AccountAttributeSet,
+            AttributeUpdated,
+            OracleChanged,
+            AcyclicLabelsChanged,
+            NodeDataUpdated,
+            RoleGranted,
+            RoleRevoked,
+            MultisigChanged,
+            GraphFrozenChanged,
+            GraphConfigChanged,
+            TokenGateChanged,
+            WriteFeeChanged,
+            GraphResized,
+            GraphMigrated,
+            NodeReclaimed,
+            EdgeReclaimed,
+            GraphCompacted,
+            IndexRebuilt,
+            GraphCloned,
+            AuthorityTransferProposed,
+            AuthorityTransferred,
+            GraphMetadataChanged,
+            QueryReceiptIssued,
+            WriteCommitted,
+            WriteRevealed,
+            ProposalStaged,
+            ProposalCommitted,
+            NodeAclChanged,
+            RateLimitChanged,
+            SessionCreated,
+            SessionRevoked,
+            GraphClosed,
+        );
+
+        assert_mirrors::<_, mirror::RoleGranted>(RoleGranted {
+            key: Pubkey::new_unique(),
+            role: Role::Admin,
+            partition: Some(2),
+        });
+        assert_mirrors::<_, mirror::GraphConfigChanged>(GraphConfigChanged {
+            config: GraphConfig::default(),
+        });
+        assert_mirrors::<_, mirror::IndexRebuilt>(IndexRebuilt {
+            index: IndexKind::EdgeKeyIndex,
+        });
+        assert_mirrors::<_, mirror::CompressedNodeAppended>(CompressedNodeAppended {
+            index: 9,
+            label: "Post".to_string(),
+            data: vec![1, 2],
+            root: [7; 32],
+        });
+        assert_mirrors::<_, mirror::AccountAttributeSet>(AccountAttributeSet {
+            node_id: 1,
+            key: "vault".to_string(),
+            address: Pubkey::new_unique(),
+            owner: Some(Pubkey::new_unique()),
+        });
+    }
 }
@@ -0,0 +1,214 @@
+//! Compute-unit estimates and lints for a query, checked off-chain before
+//! the transaction is sent.
+//!
+//! The CU model builds on `estimate_program`'s node and edge visits and
+//! adds what dominates in practice: Anchor deserializing (and, for writes,
+//! reserializing) the whole graph account, and parsing the query text. The
+//! per-unit constants are rough and deliberately on the high side; compare
+//! with the `consumed ... compute units` line of a real transaction before
+//! relying on them for tight budgets.
+
+use crate::graph::GraphStore as Graph;
+use crate::vm::{estimate_program, Opcode};
+use anchor_lang::prelude::borsh;
+use std::fmt;
+
+/// Instruction dispatch, account validation and the return data.
+const BASE_CU: u64 = 10_000;
+/// Borsh decoding of the graph account, per byte; writes pay it twice.
+const ACCOUNT_CU_PER_BYTE: u64 = 2;
+/// Tokenizing and parsing query text, per byte.
+const PARSE_CU_PER_BYTE: u64 = 40;
+/// Fixed cost of dispatching one opcode.
+const OP_CU: u64 = 200;
+/// One node or edge visit counted by `estimate_program`.
+const VISIT_CU: u64 = 60;
+/// Appending a node or edge, updating indexes and emitting its event.
+const WRITE_CU: u64 = 3_000;
+
+/// Compute units an instruction gets without a `SetComputeUnitLimit`.
+pub const DEFAULT_COMPUTE_UNITS: u64 = 200_000;
+/// Most compute units a transaction may request.
+pub const MAX_COMPUTE_UNITS: u64 = 1_400_000;
+
+/// Largest serialized transaction.
+const PACKET_DATA_SIZE: usize = 1232;
+/// Accounts `execute_query` and `execute_opcodes` list, plus the program.
+const EXECUTE_ACCOUNT_KEYS: usize = 12;
+/// Transaction bytes that are not instruction data: one signature, the
+/// message header, the account keys, the blockhash and the instruction's
+/// account indexes and length prefixes.
+const TRANSACTION_OVERHEAD: usize =
+    1 + 64 + 3 + 1 + 32 * EXECUTE_ACCOUNT_KEYS + 32 + 1 + 1 + 1 + EXECUTE_ACCOUNT_KEYS + 2;
+/// Instruction data that still fits next to `TRANSACTION_OVERHEAD`.
+pub const MAX_INSTRUCTION_DATA: usize = PACKET_DATA_SIZE - TRANSACTION_OVERHEAD;
+
+/// Estimated compute units for running `ops` on `graph`. Pass the query
+/// text when it is sent to `execute_query`, which parses it on-chain.
+pub fn compute_units(ops: &[Opcode], graph: &Graph, query: Option<&str>) -> u64 {
+    let estimate = estimate_program(ops, graph);
+    let writes = ops.iter().filter(|op| op.is_write()).count() as u64;
+    let account_passes = if writes > 0 { 2 } else { 1 };
+
+    BASE_CU
+        + graph.serialized_size() as u64 * ACCOUNT_CU_PER_BYTE * account_passes
+        + query.map_or(0, |q| q.len() as u64 * PARSE_CU_PER_BYTE)
+        + ops.len() as u64 * OP_CU
+        + estimate.cost * VISIT_CU
+        + writes * WRITE_CU
+}
+
+/// Something about a query worth fixing before sending it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    /// Starts from every node in the graph instead of known ids.
+    FullScan { nodes: u64 },
+    /// `SetLimit` comes after the last opcode that scans or traverses, so
+    /// none of them stop at the limit and they visit more nodes than it
+    /// needs.
+    LimitAfterScan,
+    /// Returns nodes without any limit.
+    NoLimit,
+    /// Node data over the graph's limit; the program will reject it.
+    DataTooLarge { len: usize, max: usize },
+    /// The instruction data does not fit in a transaction.
+    InstructionTooLarge { len: usize, max: usize },
+    /// Needs more than the default compute units, so the transaction must
+    /// request more, or more than any transaction can have.
+    ComputeUnits { estimated: u64 },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::FullScan { nodes } => write!(f, "scans all {} nodes", nodes),
+            Lint::LimitAfterScan => write!(f, "LIMIT is applied after scanning"),
+            Lint::NoLimit => write!(f, "returns nodes without a LIMIT"),
+            Lint::DataTooLarge { len, max } => {
+                write!(f, "node data is {} bytes, over the limit of {}", len, max)
+            }
+            Lint::InstructionTooLarge { len, max } => write!(
+                f,
+                "instruction data is {} bytes, more than the {} that fit in a transaction",
+                len, max
+            ),
+            Lint::ComputeUnits { estimated } if *estimated > MAX_COMPUTE_UNITS => write!(
+                f,
+                "needs about {} compute units, over the transaction maximum of {}",
+                estimated, MAX_COMPUTE_UNITS
+            ),
+            Lint::ComputeUnits { estimated } => write!(
+                f,
+                "needs about {} compute units; request them with SetComputeUnitLimit",
+                estimated
+            ),
+        }
+    }
+}
+
+/// Lints `ops` as they would run on `graph`, given the query text when it
+/// is sent as text.
+pub fn lint(ops: &[Opcode], graph: &Graph, query: Option<&str>) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    if ops.contains(&Opcode::SetCurrentFromAllNodes) {
+        lints.push(Lint::FullScan {
+            nodes: graph.nodes.len() as u64,
+        });
+    }
+
+    let scans = |op: &Opcode| matches!(op, Opcode::SetCurrentFromAllNodes | Opcode::TraverseOut(_));
+    match ops.iter().position(|op| matches!(op, Opcode::SetLimit(_))) {
+        Some(limit) if ops[..limit].iter().any(scans) && !ops[limit..].iter().any(scans) => {
+            lints.push(Lint::LimitAfterScan)
+        }
+        None if ops.contains(&Opcode::SaveResults) => lints.push(Lint::NoLimit),
+        _ => {}
+    }
+
+    let max_data = graph.config.max_node_data_len as usize;
+    for op in ops {
+        if let Opcode::CreateNode { data, .. } = op {
+            if data.len() > max_data {
+                lints.push(Lint::DataTooLarge {
+                    len: data.len(),
+                    max: max_data,
+                });
+            }
+        }
+    }
+
+    let len = 8 + match query {
+        // query, expected_version and partition
+        Some(query) => 4 + query.len() + 9 + 5,
+        // ops and expected_version
+        None => borsh::to_vec(ops).map_or(0, |data| data.len()) + 9,
+    };
+    if len > MAX_INSTRUCTION_DATA {
+        lints.push(Lint::InstructionTooLarge {
+            len,
+            max: MAX_INSTRUCTION_DATA,
+        });
+    }
+
+    let estimated = compute_units(ops, graph, query);
+    if estimated > DEFAULT_COMPUTE_UNITS {
+        lints.push(Lint::ComputeUnits { estimated });
+    }
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{large_graph, small_graph};
+
+    #[test]
+    fn test_compute_units() {
+        let graph = large_graph();
+        let scan = vec![Opcode::SetCurrentFromAllNodes, Opcode::SaveResults];
+        let lookup = vec![Opcode::SetCurrentFromIds(vec![1]), Opcode::SaveResults];
+        let create = vec![Opcode::CreateNode {
+            label: "User".to_string(),
+            data: Vec::new(),
+        }];
+        assert!(compute_units(&scan, &graph, None) > compute_units(&lookup, &graph, None));
+        assert!(compute_units(&create, &graph, None) > compute_units(&lookup, &graph, None));
+        assert!(
+            compute_units(&lookup, &graph, Some("MATCH (n) RETURN n LIMIT 1"))
+                > compute_units(&lookup, &graph, None)
+        );
+        assert!(compute_units(&scan, &small_graph(), None) < compute_units(&scan, &graph, None));
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut graph = small_graph();
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::SetLimit(5),
+            Opcode::SaveResults,
+        ];
+        assert_eq!(
+            lint(&ops, &graph, None),
+            vec![Lint::FullScan { nodes: 5 }, Lint::LimitAfterScan]
+        );
+
+        let ops = vec![Opcode::SetCurrentFromIds(vec![1]), Opcode::SaveResults];
+        assert_eq!(lint(&ops, &graph, None), vec![Lint::NoLimit]);
+
+        graph.config.max_node_data_len = 4;
+        let ops = vec![
+            Opcode::SetLimit(1),
+            Opcode::SetCurrentFromIds(vec![1]),
+            Opcode::SaveResults,
+            Opcode::CreateNode {
+                label: "Blob".to_string(),
+                data: vec![0; 900],
+            },
+        ];
+        let lints = lint(&ops, &graph, None);
+        assert_eq!(lints[0], Lint::DataTooLarge { len: 900, max: 4 });
+        assert!(matches!(lints[1], Lint::InstructionTooLarge { .. }));
+    }
+}
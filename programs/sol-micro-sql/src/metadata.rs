@@ -0,0 +1,85 @@
+use anchor_lang::prelude::*;
+
+pub const METADATA_SEED: &[u8] = b"metadata";
+pub const MAX_DISPLAY_NAME_LEN: usize = 64;
+pub const MAX_DESCRIPTION_LEN: usize = 256;
+pub const MAX_SCHEMA_URI_LEN: usize = 200;
+pub const MAX_TAGS: usize = 8;
+pub const MAX_TAG_LEN: usize = 32;
+
+/// Self-description of a graph, so explorers and SDKs can discover it
+/// without an off-chain registry. One per graph, next to the GraphStore.
+#[account]
+pub struct GraphMetadata {
+    pub graph: Pubkey,
+    pub display_name: String,
+    pub description: String,
+    /// Where the graph's labels and node data formats are documented.
+    pub schema_uri: String,
+    pub tags: Vec<String>,
+    /// Unix timestamp of `init_graph_metadata`.
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl GraphMetadata {
+    pub const SPACE: usize = 8
+        + 32
+        + (4 + MAX_DISPLAY_NAME_LEN)
+        + (4 + MAX_DESCRIPTION_LEN)
+        + (4 + MAX_SCHEMA_URI_LEN)
+        + (4 + MAX_TAGS * (4 + MAX_TAG_LEN))
+        + 8
+        + 1;
+
+    /// Whether every field fits the space reserved for it.
+    pub fn fits(display_name: &str, description: &str, schema_uri: &str, tags: &[String]) -> bool {
+        display_name.len() <= MAX_DISPLAY_NAME_LEN
+            && description.len() <= MAX_DESCRIPTION_LEN
+            && schema_uri.len() <= MAX_SCHEMA_URI_LEN
+            && tags.len() <= MAX_TAGS
+            && tags.iter().all(|t| t.len() <= MAX_TAG_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_fits() {
+        let tags = vec!["social".to_string(); MAX_TAGS];
+        assert!(GraphMetadata::fits("Friends", "", "ipfs://schema", &tags));
+
+        let too_many = vec!["social".to_string(); MAX_TAGS + 1];
+        assert!(!GraphMetadata::fits("Friends", "", "", &too_many));
+        assert!(!GraphMetadata::fits(
+            "Friends",
+            &"x".repeat(MAX_DESCRIPTION_LEN + 1),
+            "",
+            &[]
+        ));
+        assert!(!GraphMetadata::fits(
+            "Friends",
+            "",
+            "",
+            &["t".repeat(MAX_TAG_LEN + 1)]
+        ));
+    }
+
+    #[test]
+    fn test_space_fits_largest_metadata() {
+        let metadata = GraphMetadata {
+            graph: Pubkey::new_unique(),
+            display_name: "n".repeat(MAX_DISPLAY_NAME_LEN),
+            description: "d".repeat(MAX_DESCRIPTION_LEN),
+            schema_uri: "u".repeat(MAX_SCHEMA_URI_LEN),
+            tags: vec!["t".repeat(MAX_TAG_LEN); MAX_TAGS],
+            created_at: 0,
+            bump: 0,
+        };
+        let mut buf = Vec::new();
+        metadata.try_serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), GraphMetadata::SPACE);
+    }
+}
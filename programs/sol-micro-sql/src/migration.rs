@@ -0,0 +1,78 @@
+use crate::graph::{GraphStore, GRAPH_LAYOUT_VERSION};
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+/// `layout_version` sits right after the account discriminator.
+pub const LAYOUT_VERSION_OFFSET: usize = 8;
+const AUTHORITY_OFFSET: usize = LAYOUT_VERSION_OFFSET + 1;
+
+#[derive(Debug, PartialEq)]
+pub enum MigrationError {
+    NotAGraph,
+    AlreadyCurrent,
+    UnsupportedLayout(u8),
+}
+
+/// Reads the stable header (layout version and authority) of a GraphStore
+/// account written with any layout.
+pub fn read_header(data: &[u8]) -> std::result::Result<(u8, Pubkey), MigrationError> {
+    if data.len() < AUTHORITY_OFFSET + 32 || data[..8] != *GraphStore::DISCRIMINATOR {
+        return Err(MigrationError::NotAGraph);
+    }
+    let authority = Pubkey::try_from(&data[AUTHORITY_OFFSET..AUTHORITY_OFFSET + 32])
+        .map_err(|_| MigrationError::NotAGraph)?;
+    Ok((data[LAYOUT_VERSION_OFFSET], authority))
+}
+
+/// Decodes an account written with an older layout into the current one.
+pub fn upgrade(data: &[u8]) -> std::result::Result<GraphStore, MigrationError> {
+    let (layout, _) = read_header(data)?;
+    match layout {
+        GRAPH_LAYOUT_VERSION => Err(MigrationError::AlreadyCurrent),
+        // Each retired layout gets an arm here that decodes its struct,
+        // converts it and calls `rebuild_indexes()`, so changes that only
+        // touch derived indexes need no conversion code. Conversions carry
+        // the multisig over: `migrate_graph` checks the owner on the result
+        other => Err(MigrationError::UnsupportedLayout(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_graph;
+
+    fn encoded_graph() -> (Pubkey, Vec<u8>) {
+        let authority = Pubkey::new_unique();
+        let mut graph = empty_graph();
+        graph.authority = authority;
+        let mut buf = Vec::new();
+        graph.try_serialize(&mut buf).unwrap();
+        (authority, buf)
+    }
+
+    #[test]
+    fn test_read_header() {
+        let (authority, data) = encoded_graph();
+
+        assert_eq!(read_header(&data), Ok((GRAPH_LAYOUT_VERSION, authority)));
+        assert_eq!(read_header(&data[..20]), Err(MigrationError::NotAGraph));
+
+        let mut wrong = data.clone();
+        wrong[0] ^= 0xff;
+        assert_eq!(read_header(&wrong), Err(MigrationError::NotAGraph));
+    }
+
+    #[test]
+    fn test_upgrade_current_layout() {
+        let (_, mut data) = encoded_graph();
+
+        assert_eq!(upgrade(&data).err(), Some(MigrationError::AlreadyCurrent));
+
+        data[LAYOUT_VERSION_OFFSET] = GRAPH_LAYOUT_VERSION + 1;
+        assert_eq!(
+            upgrade(&data).err(),
+            Some(MigrationError::UnsupportedLayout(GRAPH_LAYOUT_VERSION + 1))
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::vm::Opcode;
+use anchor_lang::prelude::*;
+
+pub const PROPOSAL_SEED: &[u8] = b"proposal";
+/// Opcodes one proposal may hold; all of them run in the commit
+/// transaction, so this is bounded by its compute budget.
+pub const MAX_PROPOSAL_OPS: usize = 256;
+
+/// Writes staged over several transactions and applied together by
+/// `commit_proposal`, for imports too large for a single transaction.
+#[account]
+pub struct Proposal {
+    pub graph: Pubkey,
+    pub proposer: Pubkey,
+    pub ops: Vec<Opcode>,
+    pub bump: u8,
+}
+
+impl Proposal {
+    /// Space of an empty proposal; `stage_proposal` grows the account.
+    pub const SPACE: usize = 8 + 32 + 32 + 4 + 1;
+
+    /// Account size needed to hold the proposal with `ops` appended.
+    pub fn space_with(&self, ops: &[Opcode]) -> usize {
+        let mut encoded = Vec::new();
+        // Writing into a Vec cannot fail
+        self.ops.serialize(&mut encoded).unwrap();
+        ops.serialize(&mut encoded).unwrap();
+        // Both encodings carry a length prefix; the account stores one
+        Self::SPACE + encoded.len() - 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space_with_matches_borsh() {
+        let mut proposal = Proposal {
+            graph: Pubkey::new_unique(),
+            proposer: Pubkey::new_unique(),
+            ops: vec![Opcode::CreateNode {
+                label: "User".to_string(),
+                data: vec![1, 2, 3],
+            }],
+            bump: 255,
+        };
+        let staged = vec![Opcode::CreateEdge {
+            from: 1,
+            to: 2,
+            label: "FOLLOWS".to_string(),
+        }];
+
+        let space = proposal.space_with(&staged);
+        proposal.ops.extend(staged);
+        let mut buf = Vec::new();
+        proposal.try_serialize(&mut buf).unwrap();
+        assert_eq!(space, buf.len());
+    }
+}
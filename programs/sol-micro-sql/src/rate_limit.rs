@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+
+pub const WRITER_RATE_SEED: &[u8] = b"writer_rate";
+
+/// Recent write counts of one writer on one graph, for graphs with a write
+/// rate limit. Windows are `rate_window_slots` long and aligned to multiples
+/// of it; the count over the trailing window is estimated from the current
+/// window plus the overlapping share of the previous one.
+#[account]
+pub struct WriterRate {
+    pub graph: Pubkey,
+    pub writer: Pubkey,
+    /// First slot of the current window.
+    pub window_start: u64,
+    pub current_writes: u32,
+    pub previous_writes: u32,
+    pub bump: u8,
+}
+
+impl WriterRate {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 4 + 4 + 1;
+
+    /// Records `writes` at `slot` unless that would put the writer above
+    /// `max_writes` over the trailing `window` slots. Returns whether the
+    /// writes were allowed.
+    pub fn try_record(&mut self, slot: u64, writes: u32, max_writes: u32, window: u64) -> bool {
+        let window = window.max(1);
+        let window_start = slot - slot % window;
+        if window_start != self.window_start {
+            self.previous_writes = if window_start == self.window_start + window {
+                self.current_writes
+            } else {
+                0
+            };
+            self.current_writes = 0;
+            self.window_start = window_start;
+        }
+
+        let remaining = window - (slot - window_start);
+        let carried = (self.previous_writes as u64 * remaining).div_ceil(window);
+        let total = carried + self.current_writes as u64 + writes as u64;
+        if total > max_writes as u64 {
+            return false;
+        }
+        self.current_writes = self.current_writes.saturating_add(writes);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_rate() -> WriterRate {
+        WriterRate {
+            graph: Pubkey::new_unique(),
+            writer: Pubkey::new_unique(),
+            window_start: 0,
+            current_writes: 0,
+            previous_writes: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_within_window() {
+        let mut rate = empty_rate();
+        assert!(rate.try_record(5, 3, 4, 100));
+        assert!(rate.try_record(6, 1, 4, 100));
+        assert!(!rate.try_record(7, 1, 4, 100));
+        assert_eq!(rate.current_writes, 4);
+    }
+
+    #[test]
+    fn test_rate_limit_slides() {
+        let mut rate = empty_rate();
+        assert!(rate.try_record(90, 4, 4, 100));
+
+        // Early in the next window most of the previous one still counts
+        assert!(!rate.try_record(110, 1, 4, 100));
+        // Halfway through, half of it does
+        assert!(rate.try_record(150, 2, 4, 100));
+        assert!(!rate.try_record(150, 1, 4, 100));
+
+        // After a full idle window nothing carries over
+        assert!(rate.try_record(350, 4, 4, 100));
+        assert_eq!(rate.previous_writes, 0);
+    }
+}
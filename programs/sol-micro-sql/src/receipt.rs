@@ -0,0 +1,63 @@
+use crate::vm::VmResult;
+use anchor_lang::prelude::*;
+use solana_sha256_hasher::hashv;
+
+/// A query result bound to the graph state it was computed against.
+/// Verifiers recompute `receipt_hash` from the other fields.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct QueryReceipt {
+    pub result: VmResult,
+    /// Graph version the query ran against.
+    pub version: u64,
+    /// sha256 of the query text.
+    pub query_hash: [u8; 32],
+    /// sha256 of `version` (little-endian), `query_hash` and the Borsh-encoded result.
+    pub receipt_hash: [u8; 32],
+}
+
+impl QueryReceipt {
+    pub fn new(version: u64, query: &str, result: VmResult) -> Self {
+        let query_hash = hashv(&[query.as_bytes()]).to_bytes();
+        let mut encoded = Vec::with_capacity(result.serialized_size());
+        // Writing into a Vec cannot fail
+        result.serialize(&mut encoded).unwrap();
+        let receipt_hash = hashv(&[&version.to_le_bytes(), &query_hash, &encoded]).to_bytes();
+        Self {
+            result,
+            version,
+            query_hash,
+            receipt_hash,
+        }
+    }
+
+    /// Borsh-encoded size of this receipt.
+    pub fn serialized_size(&self) -> usize {
+        self.result.serialized_size() + 8 + 32 + 32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_binds_state_query_and_result() {
+        let receipt = QueryReceipt::new(3, "MATCH (n) RETURN n", VmResult::Nodes(vec![1, 2]));
+
+        let mut buf = Vec::new();
+        receipt.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), receipt.serialized_size());
+
+        assert_eq!(
+            receipt,
+            QueryReceipt::new(3, "MATCH (n) RETURN n", VmResult::Nodes(vec![1, 2]))
+        );
+        for other in [
+            QueryReceipt::new(4, "MATCH (n) RETURN n", VmResult::Nodes(vec![1, 2])),
+            QueryReceipt::new(3, "MATCH (m) RETURN m", VmResult::Nodes(vec![1, 2])),
+            QueryReceipt::new(3, "MATCH (n) RETURN n", VmResult::Nodes(vec![1])),
+        ] {
+            assert_ne!(other.receipt_hash, receipt.receipt_hash);
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use crate::graph::{GraphStore, NodeId, PartitionId, NODE_ID_LEN};
+use crate::vm::VmResult;
+use anchor_lang::prelude::*;
+
+/// Size of the role table.
+pub const MAX_ROLES: usize = 16;
+/// Size of the multisig signer set.
+pub const MAX_MULTISIG_SIGNERS: usize = 10;
+/// Nodes that may carry a read ACL.
+pub const MAX_NODE_ACLS: usize = 64;
+/// Readers one node ACL may list besides its owner.
+pub const MAX_ACL_READERS: usize = 8;
+
+/// Access levels, ordered so that each role includes the ones below it.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum Role {
+    /// May run read queries on a graph with restricted reads.
+    Reader,
+    /// May mutate nodes and edges.
+    Writer,
+    /// May manage roles, config and destructive operations.
+    Admin,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RoleGrant {
+    pub key: Pubkey,
+    pub role: Role,
+    /// Partition the key's queries and writes are confined to; `None`
+    /// reaches every partition.
+    pub partition: Option<PartitionId>,
+}
+
+impl RoleGrant {
+    /// Encoded size of a grant confined to a partition.
+    pub const MAX_SERIALIZED_SIZE: usize = 32 + 1 + 1 + 4;
+
+    pub fn serialized_size(&self) -> usize {
+        32 + 1 + 1 + self.partition.map_or(0, |_| 4)
+    }
+}
+
+/// Read restriction on a single node, honoured by `get_node_info`,
+/// `RETURN *` and `RETURN n.prop`. Nodes without one are readable by anyone
+/// who can read the graph; admins can always read.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct NodeAcl {
+    pub node_id: NodeId,
+    pub owner: Pubkey,
+    /// Only the owner may read; `readers` is ignored.
+    pub owner_only: bool,
+    pub readers: Vec<Pubkey>,
+}
+
+impl NodeAcl {
+    pub fn serialized_size(&self) -> usize {
+        NODE_ID_LEN + 32 + 1 + 4 + 32 * self.readers.len()
+    }
+
+    /// Whether one of `signers` is the owner or a listed reader.
+    pub fn allows(&self, signers: &[Pubkey]) -> bool {
+        signers.contains(&self.owner)
+            || (!self.owner_only && self.readers.iter().any(|r| signers.contains(r)))
+    }
+}
+
+impl GraphStore {
+    /// Role held by `key`. The authority is always an admin.
+    pub fn role_of(&self, key: &Pubkey) -> Option<Role> {
+        if *key == self.authority {
+            return Some(Role::Admin);
+        }
+        self.roles.iter().find(|g| g.key == *key).map(|g| g.role)
+    }
+
+    /// Whether `key` holds `role` or a higher one.
+    pub fn has_role(&self, key: &Pubkey, role: Role) -> bool {
+        self.role_of(key).is_some_and(|r| r >= role)
+    }
+
+    /// Whether any of `keys` holds `role` or a higher one.
+    pub fn any_has_role(&self, keys: &[Pubkey], role: Role) -> bool {
+        keys.iter().any(|k| self.has_role(k, role))
+    }
+
+    /// Whether `key` may run read queries.
+    pub fn can_read(&self, key: &Pubkey) -> bool {
+        !(self.restrict_reads || self.private) || self.has_role(key, Role::Reader)
+    }
+
+    pub fn node_acl(&self, id: NodeId) -> Option<&NodeAcl> {
+        self.node_acls.iter().find(|acl| acl.node_id == id)
+    }
+
+    /// Whether `signers` may see node `id` past its ACL, if it has one.
+    pub fn can_read_node(&self, id: NodeId, signers: &[Pubkey]) -> bool {
+        self.node_acl(id)
+            .is_none_or(|acl| acl.allows(signers) || self.is_admin(signers))
+    }
+
+    /// Whether `signers` pass the ACL of every restricted node.
+    pub fn can_read_all_nodes(&self, signers: &[Pubkey]) -> bool {
+        self.node_acls
+            .iter()
+            .all(|acl| self.can_read_node(acl.node_id, signers))
+    }
+
+    /// Drops the nodes `signers` may not see from a result that carries
+    /// node contents. Results of bare ids are left as they are.
+    pub fn hide_unreadable(&self, result: &mut VmResult, signers: &[Pubkey]) {
+        if !matches!(
+            result,
+            VmResult::Nodes(_) | VmResult::Pairs(_) | VmResult::Delegates(_) | VmResult::Cycle(_)
+        ) {
+            result.retain_nodes(|id| self.can_read_node(id, signers));
+        }
+    }
+
+    /// Sets or replaces the ACL of `acl.node_id`. Returns `None` if the
+    /// table is full.
+    pub fn set_node_acl(&mut self, acl: NodeAcl) -> Option<()> {
+        if let Some(existing) = self.node_acls.iter_mut().find(|a| a.node_id == acl.node_id) {
+            *existing = acl;
+            return Some(());
+        }
+        if self.node_acls.len() >= MAX_NODE_ACLS {
+            return None;
+        }
+        self.node_acls.push(acl);
+        Some(())
+    }
+
+    /// Drops the ACL of node `id`, making it public again. Returns `None`
+    /// if it had none.
+    pub fn remove_node_acl(&mut self, id: NodeId) -> Option<NodeAcl> {
+        let index = self.node_acls.iter().position(|a| a.node_id == id)?;
+        Some(self.node_acls.remove(index))
+    }
+
+    /// Partition a program run by `signers` is confined to, given the one
+    /// the caller `requested`. Admins, unconfined grants and keys without a
+    /// grant run where they ask; keys confined to partitions run in the
+    /// first of theirs unless they ask for another of theirs. Returns `None`
+    /// if `requested` is outside their partitions.
+    pub fn scoped_partition(
+        &self,
+        signers: &[Pubkey],
+        requested: Option<PartitionId>,
+    ) -> Option<Option<PartitionId>> {
+        let grants: Vec<&RoleGrant> = self
+            .roles
+            .iter()
+            .filter(|g| signers.contains(&g.key))
+            .collect();
+        if self.is_admin(signers) || grants.iter().all(|g| g.partition.is_none()) {
+            return Some(requested);
+        }
+        let mut confined = grants.iter().filter_map(|g| g.partition);
+        match requested {
+            None => confined.next().map(Some),
+            Some(partition) => confined.any(|p| p == partition).then_some(requested),
+        }
+    }
+
+    /// Whether `signers` may reach records stored in `partition`.
+    pub fn in_partition_scope(&self, signers: &[Pubkey], partition: PartitionId) -> bool {
+        self.scoped_partition(signers, Some(partition)).is_some()
+    }
+
+    /// Whether `signers` include enough distinct multisig members.
+    pub fn has_quorum(&self, signers: &[Pubkey]) -> bool {
+        let approvals = self
+            .multisig_signers
+            .iter()
+            .filter(|k| signers.contains(k))
+            .count();
+        approvals >= self.multisig_threshold as usize
+    }
+
+    /// Owner-level operations: the authority, or a quorum once a multisig is set.
+    pub fn is_owner(&self, signers: &[Pubkey]) -> bool {
+        if self.multisig_threshold > 0 {
+            self.has_quorum(signers)
+        } else {
+            signers.contains(&self.authority)
+        }
+    }
+
+    /// Admin-level operations: any admin, or a quorum once a multisig is set.
+    pub fn is_admin(&self, signers: &[Pubkey]) -> bool {
+        if self.multisig_threshold > 0 {
+            self.has_quorum(signers)
+        } else {
+            self.any_has_role(signers, Role::Admin)
+        }
+    }
+
+    /// Whether writes require holding `gate_mint`.
+    pub fn is_token_gated(&self) -> bool {
+        self.gate_mint != Pubkey::default()
+    }
+
+    /// Whether a token account owned by `owner` with `amount` of `mint`
+    /// lets `holder` past the write gate.
+    pub fn passes_token_gate(
+        &self,
+        holder: &Pubkey,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        amount: u64,
+    ) -> bool {
+        !self.is_token_gated()
+            || (owner == holder && *mint == self.gate_mint && amount >= self.gate_min_amount)
+    }
+
+    /// Grants or replaces the role of `key`, confined to `partition` if
+    /// given. Returns `None` if the table is full.
+    pub fn set_role(
+        &mut self,
+        key: Pubkey,
+        role: Role,
+        partition: Option<PartitionId>,
+    ) -> Option<()> {
+        if let Some(grant) = self.roles.iter_mut().find(|g| g.key == key) {
+            grant.role = role;
+            grant.partition = partition;
+            return Some(());
+        }
+        if self.roles.len() >= MAX_ROLES {
+            return None;
+        }
+        self.roles.push(RoleGrant {
+            key,
+            role,
+            partition,
+        });
+        Some(())
+    }
+
+    /// Drops the role of `key`. Returns `None` if it held none.
+    pub fn remove_role(&mut self, key: &Pubkey) -> Option<Role> {
+        let index = self.roles.iter().position(|g| g.key == *key)?;
+        Some(self.roles.remove(index).role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_graph;
+
+    #[test]
+    fn test_authority_is_admin() {
+        let graph = empty_graph();
+
+        assert_eq!(graph.role_of(&graph.authority), Some(Role::Admin));
+        assert!(graph.has_role(&graph.authority, Role::Writer));
+        assert_eq!(graph.role_of(&Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn test_roles_are_ordered() {
+        let mut graph = empty_graph();
+        let writer = Pubkey::new_unique();
+        graph.set_role(writer, Role::Writer, None).unwrap();
+
+        assert!(graph.has_role(&writer, Role::Reader));
+        assert!(graph.has_role(&writer, Role::Writer));
+        assert!(!graph.has_role(&writer, Role::Admin));
+
+        graph.set_role(writer, Role::Reader, None).unwrap();
+        assert!(!graph.has_role(&writer, Role::Writer));
+        assert_eq!(graph.roles.len(), 1);
+    }
+
+    #[test]
+    fn test_any_has_role() {
+        let mut graph = empty_graph();
+        let writer = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        graph.set_role(writer, Role::Writer, None).unwrap();
+
+        assert!(graph.any_has_role(&[stranger, writer], Role::Writer));
+        assert!(!graph.any_has_role(&[stranger], Role::Reader));
+        assert!(!graph.any_has_role(&[], Role::Reader));
+    }
+
+    #[test]
+    fn test_scoped_partition() {
+        let mut graph = empty_graph();
+        let tenant = Pubkey::new_unique();
+        let writer = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        graph.set_role(tenant, Role::Writer, Some(2)).unwrap();
+        graph.set_role(writer, Role::Writer, None).unwrap();
+
+        assert_eq!(graph.scoped_partition(&[tenant], None), Some(Some(2)));
+        assert_eq!(graph.scoped_partition(&[tenant], Some(2)), Some(Some(2)));
+        assert_eq!(graph.scoped_partition(&[tenant], Some(3)), None);
+        // Signing alongside a key without a grant does not widen the scope
+        assert_eq!(
+            graph.scoped_partition(&[tenant, stranger], None),
+            Some(Some(2))
+        );
+
+        assert_eq!(graph.scoped_partition(&[writer], None), Some(None));
+        assert_eq!(graph.scoped_partition(&[writer], Some(3)), Some(Some(3)));
+        assert_eq!(graph.scoped_partition(&[stranger], None), Some(None));
+        assert_eq!(
+            graph.scoped_partition(&[graph.authority], Some(3)),
+            Some(Some(3))
+        );
+
+        assert!(graph.in_partition_scope(&[tenant], 2));
+        assert!(!graph.in_partition_scope(&[tenant], 0));
+        assert!(graph.in_partition_scope(&[writer], 0));
+    }
+
+    #[test]
+    fn test_multisig_quorum() {
+        let mut graph = empty_graph();
+        let authority = graph.authority;
+        let members: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+
+        assert!(graph.is_owner(&[authority]));
+
+        graph.multisig_signers = members.clone();
+        graph.multisig_threshold = 2;
+
+        assert!(!graph.is_owner(&[authority]));
+        assert!(!graph.is_admin(&[authority, members[0]]));
+        assert!(graph.is_owner(&[members[0], members[2]]));
+        assert!(graph.is_admin(&[authority, members[1], members[2]]));
+    }
+
+    #[test]
+    fn test_role_table_limit() {
+        let mut graph = empty_graph();
+        for _ in 0..MAX_ROLES {
+            graph
+                .set_role(Pubkey::new_unique(), Role::Reader, None)
+                .unwrap();
+        }
+
+        assert!(graph
+            .set_role(Pubkey::new_unique(), Role::Reader, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_restricted_reads() {
+        let mut graph = empty_graph();
+        let reader = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        graph.set_role(reader, Role::Reader, None).unwrap();
+
+        assert!(graph.can_read(&stranger));
+
+        graph.restrict_reads = true;
+        assert!(graph.can_read(&reader));
+        assert!(!graph.can_read(&stranger));
+
+        assert_eq!(graph.remove_role(&reader), Some(Role::Reader));
+        assert!(!graph.can_read(&reader));
+    }
+
+    #[test]
+    fn test_token_gate() {
+        let mut graph = empty_graph();
+        let holder = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+
+        assert!(graph.passes_token_gate(&holder, &Pubkey::default(), &Pubkey::default(), 0));
+
+        graph.gate_mint = mint;
+        graph.gate_min_amount = 1;
+        assert!(graph.passes_token_gate(&holder, &holder, &mint, 1));
+        assert!(!graph.passes_token_gate(&holder, &holder, &mint, 0));
+        assert!(!graph.passes_token_gate(&holder, &Pubkey::new_unique(), &mint, 5));
+        assert!(!graph.passes_token_gate(&holder, &holder, &Pubkey::new_unique(), 5));
+    }
+
+    #[test]
+    fn test_private_graph_restricts_reads() {
+        let mut graph = empty_graph();
+        let reader = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        graph.set_role(reader, Role::Reader, None).unwrap();
+        assert!(graph.can_read(&stranger));
+
+        graph.private = true;
+        assert!(graph.can_read(&reader));
+        assert!(graph.can_read(&graph.authority));
+        assert!(!graph.can_read(&stranger));
+    }
+
+    #[test]
+    fn test_node_acl() {
+        let mut graph = empty_graph();
+        let owner = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        assert!(graph.can_read_node(1, &[stranger]));
+
+        graph
+            .set_node_acl(NodeAcl {
+                node_id: 1,
+                owner,
+                owner_only: false,
+                readers: vec![friend],
+            })
+            .unwrap();
+        assert!(graph.can_read_node(1, &[owner]));
+        assert!(graph.can_read_node(1, &[friend]));
+        assert!(graph.can_read_node(1, &[graph.authority]));
+        assert!(!graph.can_read_node(1, &[stranger]));
+        assert!(!graph.can_read_node(1, &[]));
+        assert!(graph.can_read_node(2, &[stranger]));
+
+        // Replacing keeps one entry per node
+        graph
+            .set_node_acl(NodeAcl {
+                node_id: 1,
+                owner,
+                owner_only: true,
+                readers: vec![friend],
+            })
+            .unwrap();
+        assert_eq!(graph.node_acls.len(), 1);
+        assert!(!graph.can_read_node(1, &[friend]));
+
+        assert!(graph.can_read_all_nodes(&[owner]));
+        assert!(!graph.can_read_all_nodes(&[friend]));
+        assert!(graph.can_read_all_nodes(&[graph.authority]));
+
+        assert!(graph.remove_node_acl(1).is_some());
+        assert!(graph.can_read_node(1, &[stranger]));
+        assert!(graph.remove_node_acl(1).is_none());
+    }
+
+    #[test]
+    fn test_hide_unreadable() {
+        let mut graph = empty_graph();
+        let owner = Pubkey::new_unique();
+        graph
+            .set_node_acl(NodeAcl {
+                node_id: 1,
+                owner,
+                owner_only: true,
+                readers: Vec::new(),
+            })
+            .unwrap();
+
+        let values = VmResult::Values(vec![(1, Some("a".to_string())), (2, None)]);
+        let mut result = values.clone();
+        graph.hide_unreadable(&mut result, &[owner]);
+        assert_eq!(result, values);
+        graph.hide_unreadable(&mut result, &[Pubkey::new_unique()]);
+        assert_eq!(result, VmResult::Values(vec![(2, None)]));
+
+        // Bare ids stay visible
+        let mut result = VmResult::Nodes(vec![1, 2]);
+        graph.hide_unreadable(&mut result, &[]);
+        assert_eq!(result, VmResult::Nodes(vec![1, 2]));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::graph::MAX_LABEL_LEN;
+use crate::vm::Opcode;
+use anchor_lang::prelude::*;
+
+pub const SESSION_SEED: &[u8] = b"session";
+/// Labels a session may be scoped to; an empty list allows any label.
+pub const MAX_SESSION_LABELS: usize = 8;
+
+/// Write permission delegated to an ephemeral key, e.g. a game or mobile
+/// client, so the main wallet does not have to sign every write.
+#[account]
+pub struct SessionKey {
+    /// Admin who created the session.
+    pub granter: Pubkey,
+    /// Key allowed to sign writes.
+    pub key: Pubkey,
+    /// First slot at which the session no longer works.
+    pub expires_slot: u64,
+    /// Labels the session may create nodes and edges with.
+    pub labels: Vec<String>,
+    /// Total write opcodes the session may run.
+    pub max_write_ops: u32,
+    pub write_ops_used: u32,
+    pub bump: u8,
+}
+
+impl SessionKey {
+    pub const SPACE: usize =
+        8 + 32 + 32 + 8 + 4 + MAX_SESSION_LABELS * (4 + MAX_LABEL_LEN) + 4 + 4 + 1;
+
+    /// Whether the session lets one of `signers` run `ops` at `slot`.
+    pub fn permits(&self, signers: &[Pubkey], slot: u64, ops: &[Opcode]) -> bool {
+        let writes = ops.iter().filter(|op| op.is_write()).count() as u64;
+        let in_scope = |label: &String| self.labels.is_empty() || self.labels.contains(label);
+        let labels_ok = ops.iter().all(|op| match op {
+            Opcode::CreateNode { label, .. }
+            | Opcode::CreateEdge { label, .. }
+            | Opcode::CreateEdgeBetween { label, .. } => in_scope(label),
+            _ => true,
+        });
+
+        signers.contains(&self.key)
+            && slot < self.expires_slot
+            && self.write_ops_used as u64 + writes <= self.max_write_ops as u64
+            && labels_ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(key: Pubkey) -> SessionKey {
+        SessionKey {
+            granter: Pubkey::new_unique(),
+            key,
+            expires_slot: 100,
+            labels: vec!["Move".to_string()],
+            max_write_ops: 2,
+            write_ops_used: 0,
+            bump: 0,
+        }
+    }
+
+    fn create(label: &str) -> Opcode {
+        Opcode::CreateNode {
+            label: label.to_string(),
+            data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_session_scope() {
+        let key = Pubkey::new_unique();
+        let mut session = session(key);
+
+        assert!(session.permits(&[key], 99, &[create("Move")]));
+        assert!(!session.permits(&[key], 100, &[create("Move")]));
+        assert!(!session.permits(&[Pubkey::new_unique()], 0, &[create("Move")]));
+        assert!(!session.permits(&[key], 0, &[create("Player")]));
+
+        session.labels.clear();
+        assert!(session.permits(&[key], 0, &[create("Player")]));
+    }
+
+    #[test]
+    fn test_session_write_budget() {
+        let key = Pubkey::new_unique();
+        let mut session = session(key);
+
+        assert!(session.permits(&[key], 0, &[create("Move"), create("Move")]));
+        assert!(!session.permits(&[key], 0, &[create("Move"), create("Move"), create("Move")]));
+
+        session.write_ops_used = 2;
+        assert!(!session.permits(&[key], 0, &[create("Move")]));
+        assert!(session.permits(&[key], 0, &[Opcode::SaveResults]));
+    }
+}
@@ -0,0 +1,267 @@
+//! Graph fixtures and assertions for tests, in this crate and in crates
+//! built on it (enable the `test-utils` feature).
+//!
+//! ```ignore
+//! let graph = GraphBuilder::new()
+//!     .node("City")
+//!     .node("City")
+//!     .edge(1, 2, "Railway")
+//!     .build();
+//! ```
+
+use crate::bloom::NodeBloom;
+use crate::graph::{to_label, Edge, GraphStore, Node, NodeId, PartitionId, GRAPH_LAYOUT_VERSION};
+use crate::hooks::IndexKind;
+use crate::vm::VmResult;
+use anchor_lang::prelude::Pubkey;
+
+/// A graph as `initialize_graph` leaves it, owned by a fresh authority.
+pub fn empty_graph() -> GraphStore {
+    GraphStore {
+        layout_version: GRAPH_LAYOUT_VERSION,
+        authority: Pubkey::new_unique(),
+        node_count: 0,
+        edge_count: 0,
+        nonce: 0,
+        version: 0,
+        nodes: Vec::new(),
+        edges: Vec::new(),
+        edge_offsets: vec![0],
+        adjacency: Vec::new(),
+        node_bloom: NodeBloom::default(),
+        edge_key_index: Vec::new(),
+        compaction_cursor: 0,
+        pending_authority: None,
+        name: String::new(),
+        creator: Pubkey::default(),
+        bump: 0,
+        roles: Vec::new(),
+        restrict_reads: false,
+        multisig_signers: Vec::new(),
+        multisig_threshold: 0,
+        frozen: false,
+        write_fee_lamports: 0,
+        treasury: Pubkey::default(),
+        emit_match_events: false,
+        gate_mint: Pubkey::default(),
+        gate_min_amount: 0,
+        config: Default::default(),
+        commit_reveal_writes: false,
+        user_graph: false,
+        private: false,
+        node_acls: Vec::new(),
+        max_writes_per_window: 0,
+        rate_window_slots: 0,
+        audit_log: false,
+        journaled: false,
+        oracle: Pubkey::default(),
+        oracle_keys: Vec::new(),
+        acyclic_labels: Vec::new(),
+        clone_source: Pubkey::default(),
+        clone_cursor: 0,
+    }
+}
+
+/// Builds a graph node by node. Ids are handed out from 1 in the order
+/// nodes are added, so edges can refer to them by position.
+pub struct GraphBuilder {
+    graph: GraphStore,
+}
+
+impl Default for GraphBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphBuilder {
+    pub fn new() -> Self {
+        let mut graph = empty_graph();
+        graph.nonce = 1;
+        GraphBuilder { graph }
+    }
+
+    /// Adds a node with `label` and no data.
+    pub fn node(self, label: &str) -> Self {
+        self.node_with(label, Vec::new(), 0)
+    }
+
+    /// Adds a node with `label` and `data` in `partition`.
+    pub fn node_with(mut self, label: &str, data: Vec<u8>, partition: PartitionId) -> Self {
+        let id = self.graph.next_node_id().expect("node id overflow");
+        self.graph.nodes.push(Node {
+            id,
+            label: to_label(label.to_string()),
+            data,
+            is_deleted: false,
+            is_protected: false,
+            partition,
+            created_at: 0,
+            updated_at: 0,
+            expires_slot: 0,
+            mint: None,
+        });
+        self
+    }
+
+    /// Adds an edge `from -[label]-> to` with no data.
+    pub fn edge(mut self, from: NodeId, to: NodeId, label: &str) -> Self {
+        self.graph.edges.push(Edge {
+            from,
+            to,
+            label: to_label(label.to_string()),
+            partition: 0,
+            data: Vec::new(),
+            created_at: 0,
+        });
+        self
+    }
+
+    /// Marks node `id` as deleted.
+    pub fn deleted(mut self, id: NodeId) -> Self {
+        let node = self.graph.nodes.iter_mut().find(|n| n.id == id);
+        node.expect("deleted node must be added first").is_deleted = true;
+        self
+    }
+
+    pub fn authority(mut self, authority: Pubkey) -> Self {
+        self.graph.authority = authority;
+        self
+    }
+
+    /// The graph with its counters and indexes computed, at version 0.
+    pub fn build(mut self) -> GraphStore {
+        self.graph.rebuild_index(IndexKind::Counters);
+        self.graph.rebuild_indexes();
+        self.graph
+    }
+}
+
+// Small fixture graph:
+//
+//     City(1) ──Railway──> City(2) ──Railway──> City(3)
+//       │                      │                    │
+//       │                      │                    │
+//       │                      └──Highway──> Town(4) │
+//       │                                           │
+//       └────────────Railway────────────────────────┘
+//                    (cycle)
+//
+//     Town(5) (isolated node)
+//
+pub fn small_graph() -> GraphStore {
+    GraphBuilder::new()
+        .node("City")
+        .node("City")
+        .node("City")
+        .node("Town")
+        .node("Town")
+        .edge(1, 2, "Railway")
+        .edge(1, 3, "Railway")
+        .edge(2, 3, "Railway")
+        .edge(2, 4, "Highway")
+        .edge(3, 1, "Railway")
+        .build()
+}
+
+// Large fixture graph:
+//
+//     City(1) ──Railway──> City(2) ──Railway──> City(3) ──Railway──> City(4)
+//       │                      │
+//       │                      └──Highway──> Town(5)
+//       │
+//       └──Highway──> Town(6)
+//
+//     City(7) ──Railway──> City(8) ──Highway──> Town(9) ──Highway──> Town(10)
+//       │
+//       └──Railway──> City(2)
+//
+//     Town(11) ──Highway──> Town(12) ──Highway──> Town(13)
+//       │
+//       └──Highway──> City(1)
+//
+pub fn large_graph() -> GraphStore {
+    GraphBuilder::new()
+        .node("City")
+        .node("City")
+        .node("City")
+        .node("City")
+        .node("Town")
+        .node("Town")
+        .node("City")
+        .node("City")
+        .node("Town")
+        .node("Town")
+        .node("Town")
+        .node("Town")
+        .node("Town")
+        .edge(1, 2, "Railway")
+        .edge(1, 6, "Highway")
+        .edge(2, 3, "Railway")
+        .edge(2, 5, "Highway")
+        .edge(3, 4, "Railway")
+        .edge(7, 2, "Railway")
+        .edge(7, 8, "Railway")
+        .edge(8, 9, "Highway")
+        .edge(9, 10, "Highway")
+        .edge(11, 1, "Highway")
+        .edge(11, 12, "Highway")
+        .edge(12, 13, "Highway")
+        .build()
+}
+
+/// Asserts that `result` holds exactly the nodes in `expected`, in any
+/// order.
+#[track_caller]
+pub fn assert_nodes(result: &VmResult, expected: &[NodeId]) {
+    let VmResult::Nodes(ids) = result else {
+        panic!("expected a Nodes result, got {:?}", result);
+    };
+    let mut ids = ids.clone();
+    let mut expected = expected.to_vec();
+    ids.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(ids, expected);
+}
+
+/// Asserts that the graph's counters and indexes match what rebuilding
+/// them from `nodes` and `edges` gives.
+#[track_caller]
+pub fn assert_indexes_consistent(graph: &GraphStore) {
+    let mut rebuilt = graph.clone();
+    rebuilt.rebuild_index(IndexKind::Counters);
+    rebuilt.rebuild_indexes();
+    assert_eq!(graph.node_count, rebuilt.node_count, "node_count");
+    assert_eq!(graph.edge_count, rebuilt.edge_count, "edge_count");
+    assert_eq!(graph.edge_offsets, rebuilt.edge_offsets, "edge_offsets");
+    assert_eq!(graph.adjacency, rebuilt.adjacency, "adjacency");
+    assert_eq!(
+        graph.edge_key_index, rebuilt.edge_key_index,
+        "edge_key_index"
+    );
+    for node in &graph.nodes {
+        assert!(graph.node_bloom.might_contain(node.id), "node_bloom");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_indexes_graph() {
+        let graph = GraphBuilder::new()
+            .node("City")
+            .node_with("Town", vec![1], 2)
+            .edge(1, 2, "Railway")
+            .deleted(2)
+            .build();
+        assert_eq!(graph.node_count, 2);
+        assert_eq!(graph.nonce, 3);
+        assert_eq!(graph.version, 0);
+        assert!(graph.has_edge(1, 2, "Railway"));
+        assert!(graph.nodes[1].is_deleted);
+        assert_indexes_consistent(&graph);
+        assert_indexes_consistent(&large_graph());
+    }
+}
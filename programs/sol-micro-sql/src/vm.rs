@@ -1,32 +1,14 @@
-use crate::graph::{Edge, GraphStore as Graph, Node, NodeId, TraverseFilter};
+use crate::attrs::{attribute_equals, attribute_int, attribute_text};
+use crate::graph::{
+    to_label, Edge, ExecutionCache, GraphConfig, GraphStore as Graph, Node, NodeId, PartitionId,
+    TraversalCheckpoint, TraverseFilter, MAX_NODES,
+};
 use anchor_lang::prelude::*;
+use sol_micro_sql_lang::ops::MAX_PATH_HOPS;
+pub use sol_micro_sql_lang::ops::{CmpOp, Opcode, Projection, TimeField, REGISTERS};
+pub use sol_micro_sql_types::result::{QueryEstimate, QueryPage, VmResult};
 use std::result::Result as StdResult;
 
-#[derive(Debug, Clone)]
-pub enum Opcode {
-    SetCurrentFromAllNodes,
-    SetCurrentFromIds(Vec<NodeId>),
-    TraverseOut(TraverseFilter),
-    SetLimit(usize),
-    SaveResults,
-    CreateNode {
-        label: String,
-        data: Vec<u8>,
-    },
-    CreateEdge {
-        from: NodeId,
-        to: NodeId,
-        label: String,
-    },
-}
-
-#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
-pub enum VmResult {
-    Nodes(Vec<NodeId>),
-    Scalar(i64),
-    None,
-}
-
 #[derive(Debug, Clone)]
 pub enum VmValue {
     Int(i64),
@@ -38,6 +20,51 @@ pub struct Vm<'g> {
     current_set: Vec<NodeId>,
     result_set: Vec<NodeId>,
     limit: Option<usize>,
+    partition: Option<PartitionId>,
+    /// Unix timestamp stamped onto nodes and edges this program creates.
+    now: i64,
+    /// Current slot; nodes expired by then are invisible. 0 disables expiry.
+    slot: u64,
+    ttl: Option<u64>,
+    /// Id for the next `CreateNode`, set by `SetNodeId`.
+    node_id: Option<NodeId>,
+    /// What the program returns of its nodes, set by `Project`.
+    projection: Option<Projection>,
+    /// Start node and neighbour pairs found by the last `MutualOut`.
+    pairs: Vec<(NodeId, NodeId)>,
+    /// Nodes and their delegates found by the last `ResolveDelegates`.
+    delegates: Vec<(NodeId, Option<NodeId>)>,
+    /// Chain ends and their weights summed by the last `PropagateWeight`.
+    weights: Vec<(NodeId, i64)>,
+    /// Total of the last `SumPaths`, returned in place of any nodes.
+    sum: Option<i64>,
+    /// Cycle found by the last `FindCycle`, returned in place of any nodes.
+    cycle: Option<Vec<NodeId>>,
+    /// Nodes stored by `Bind`.
+    registers: [Option<NodeId>; REGISTERS],
+    /// Spare node-id buffer that traversals write into before it is
+    /// swapped with `current_set`, so neither is reallocated per opcode.
+    next_set: Vec<NodeId>,
+    /// Node lookups and label filter results shared by all opcodes.
+    cache: ExecutionCache,
+}
+
+/// Where `Vm::resume` stopped: the next opcode to run, the node sets and
+/// settings so far, and the traversal in progress at that opcode, if any.
+/// It holds no TTL or clock, so only read programs are resumed from one.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct VmCheckpoint {
+    pub pc: u32,
+    pub current_set: Vec<NodeId>,
+    pub result_set: Vec<NodeId>,
+    pub limit: Option<u32>,
+    pub partition: Option<PartitionId>,
+    pub traversal: Option<TraversalCheckpoint>,
+    pub pairs: Vec<(NodeId, NodeId)>,
+    pub delegates: Vec<(NodeId, Option<NodeId>)>,
+    pub weights: Vec<(NodeId, i64)>,
+    pub sum: Option<i64>,
+    pub cycle: Option<Vec<NodeId>>,
 }
 
 #[derive(Debug)]
@@ -50,6 +77,254 @@ pub enum VmError {
     DataTooLarge,
     LabelTooLong,
     GraphLimitExceeded,
+    DuplicateEdge,
+    InvalidProgram,
+    TraversalBudgetExceeded,
+    DuplicateNodeId,
+    CycleDetected,
+}
+
+/// Property `name` of `node` as `RETURN n.name` shows it: one of the
+/// node's own fields, or else an attribute in its data.
+fn property(node: &Node, name: &str) -> Option<String> {
+    match name {
+        "label" => Some(node.label.to_string()),
+        "partition" => Some(node.partition.to_string()),
+        "created_at" => Some(node.created_at.to_string()),
+        "updated_at" => Some(node.updated_at.to_string()),
+        "mint" => node.mint.map(|mint| mint.to_string()),
+        _ => attribute_text(&node.data, name),
+    }
+}
+
+/// Static checks for a program supplied as raw opcodes rather than compiled
+/// from a query: bounded length, bounded id lists and record sizes, and
+/// a `SetPartition` only in first position.
+pub fn check_program(ops: &[Opcode], config: &GraphConfig) -> StdResult<(), VmError> {
+    let max_label_len = config.max_label_len as usize;
+    if ops.is_empty() || ops.len() > config.max_ops as usize {
+        return Err(VmError::InvalidProgram);
+    }
+
+    for (i, op) in ops.iter().enumerate() {
+        match op {
+            Opcode::SetCurrentFromIds(ids) if ids.len() > MAX_NODES => {
+                return Err(VmError::InvalidProgram);
+            }
+            Opcode::SumPaths {
+                min_hops, max_hops, ..
+            } if *min_hops == 0 || min_hops > max_hops || *max_hops > MAX_PATH_HOPS => {
+                return Err(VmError::InvalidProgram);
+            }
+            Opcode::SumPaths { key, .. } if key.len() > max_label_len => {
+                return Err(VmError::LabelTooLong);
+            }
+            Opcode::TraverseOut(filter)
+            | Opcode::MutualOut(filter)
+            | Opcode::SumPaths { filter, .. }
+            | Opcode::ProbeEdgeTo { filter, .. } => {
+                let labels = filter
+                    .where_node_labels
+                    .iter()
+                    .chain(&filter.where_edge_labels)
+                    .chain(&filter.where_not_node_labels)
+                    .chain(&filter.where_not_edge_labels);
+                if labels.clone().any(|l| l.len() > max_label_len) {
+                    return Err(VmError::LabelTooLong);
+                }
+            }
+            Opcode::SetPartition(_) if i != 0 => return Err(VmError::InvalidProgram),
+            Opcode::CreateNode { label, data } => {
+                if label.len() > max_label_len {
+                    return Err(VmError::LabelTooLong);
+                }
+                if data.len() > config.max_node_data_len as usize {
+                    return Err(VmError::DataTooLarge);
+                }
+            }
+            Opcode::CreateEdge { label, .. } | Opcode::CreateEdgeBetween { label, .. }
+                if label.len() > max_label_len =>
+            {
+                return Err(VmError::LabelTooLong);
+            }
+            Opcode::Bind(register) if *register as usize >= REGISTERS => {
+                return Err(VmError::InvalidProgram);
+            }
+            Opcode::CreateEdgeBetween { from, to, .. }
+                if (*from).max(*to) as usize >= REGISTERS =>
+            {
+                return Err(VmError::InvalidProgram);
+            }
+            Opcode::PropagateWeight { key, .. } if key.len() > max_label_len => {
+                return Err(VmError::LabelTooLong);
+            }
+            Opcode::ResolveDelegates { label, max_hops }
+            | Opcode::PropagateWeight {
+                label, max_hops, ..
+            } => {
+                if label.len() > max_label_len {
+                    return Err(VmError::LabelTooLong);
+                }
+                if *max_hops as usize > MAX_NODES {
+                    return Err(VmError::InvalidProgram);
+                }
+            }
+            Opcode::FindCycle(label)
+            | Opcode::RejectCycle(label)
+            | Opcode::FilterRange { key: label, .. }
+                if label.len() > max_label_len =>
+            {
+                return Err(VmError::LabelTooLong);
+            }
+            Opcode::Project(Projection::Property(name)) if name.len() > max_label_len => {
+                return Err(VmError::LabelTooLong);
+            }
+            Opcode::FilterAttr { key, value } => {
+                if key.len() > max_label_len {
+                    return Err(VmError::LabelTooLong);
+                }
+                if value.len() > config.max_node_data_len as usize {
+                    return Err(VmError::DataTooLarge);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Share of nodes a label or timestamp filter is assumed to keep, as a
+/// divisor; the cost model has no statistics to do better.
+const FILTER_SELECTIVITY: u64 = 4;
+
+/// A compiled query and its estimate, returned by `explain_query`.
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct QueryPlan {
+    pub ops: Vec<Opcode>,
+    pub estimate: QueryEstimate,
+}
+
+/// Estimated work of running `ops` on `graph`, in node and edge visits:
+/// every scanned node, every edge a traversal may follow from its frontier
+/// (using the graph's average out-degree), bounded by the traversal budget
+/// and any `LIMIT`. Filters shrink the frontier by `FILTER_SELECTIVITY`.
+pub fn program_cost(ops: &[Opcode], graph: &Graph) -> u64 {
+    estimate_program(ops, graph).cost
+}
+
+/// `program_cost` together with the opcode count and a worst-case bound on
+/// visited nodes, which tracks the frontier without assuming any filter
+/// removes anything.
+pub fn estimate_program(ops: &[Opcode], graph: &Graph) -> QueryEstimate {
+    let nodes = graph.nodes.len() as u64;
+    let edges = graph.edges.len() as u64;
+    let avg_degree = edges.div_ceil(nodes.max(1));
+    let budget = graph.config.max_traversal_nodes as u64;
+
+    let mut cost: u64 = 0;
+    let mut frontier: u64 = 0;
+    let mut visited: u64 = 0;
+    let mut worst_frontier: u64 = 0;
+    let mut limit = u64::MAX;
+    for op in ops {
+        match op {
+            Opcode::SetCurrentFromAllNodes => {
+                cost += nodes;
+                frontier = nodes.min(limit);
+                worst_frontier = frontier;
+                visited += nodes;
+            }
+            Opcode::SetCurrentFromIds(ids) => {
+                cost += ids.len() as u64;
+                frontier = (ids.len() as u64).min(nodes).min(limit);
+                worst_frontier = frontier;
+                visited += frontier;
+            }
+            Opcode::TraverseOut(filter) => {
+                let scanned = frontier.saturating_mul(avg_degree).min(edges);
+                cost += frontier + scanned;
+                frontier = (frontier + scanned).min(budget).min(limit);
+                if !filter.where_node_labels.is_empty() {
+                    frontier = frontier.div_ceil(FILTER_SELECTIVITY);
+                }
+                // Start nodes, then up to one past the budget before the
+                // query fails
+                visited += worst_frontier;
+                worst_frontier = nodes.min(budget + 1).min(limit);
+                visited += worst_frontier;
+            }
+            Opcode::MutualOut(filter) => {
+                // Each edge out costs a lookup of the edge back
+                let scanned = frontier.saturating_mul(avg_degree).min(edges);
+                cost += frontier + 2 * scanned;
+                frontier = scanned.min(budget).min(limit);
+                if !filter.where_node_labels.is_empty() {
+                    frontier = frontier.div_ceil(FILTER_SELECTIVITY);
+                }
+                visited += worst_frontier;
+                worst_frontier = nodes.min(budget + 1).min(limit);
+                visited += worst_frontier;
+            }
+            Opcode::ResolveDelegates { max_hops, .. }
+            | Opcode::PropagateWeight { max_hops, .. } => {
+                // Each hop scans one node's edges
+                let hops = (*max_hops as u64).min(nodes) + 1;
+                cost += frontier.saturating_mul(hops).saturating_mul(1 + avg_degree);
+                visited += worst_frontier.saturating_mul(hops);
+            }
+            Opcode::SumPaths { max_hops, .. } => {
+                // Every path walked scans the edges of its last node
+                let mut level = frontier;
+                let mut paths: u64 = 0;
+                for _ in 0..*max_hops {
+                    level = level.saturating_mul(avg_degree).min(budget + 1);
+                    paths = paths.saturating_add(level);
+                }
+                let paths = paths.min(budget + 1);
+                cost += (frontier + paths).saturating_mul(1 + avg_degree);
+                visited += worst_frontier + paths;
+            }
+            Opcode::ProbeEdgeTo { filter, .. } => {
+                // One index lookup per start node and edge label
+                let lookups = frontier.saturating_mul(filter.where_edge_labels.len() as u64);
+                cost += lookups.saturating_mul(1 + edges.max(1).ilog2() as u64);
+                frontier = frontier.min(1);
+                visited += worst_frontier + 1;
+                worst_frontier = 1;
+            }
+            Opcode::FindCycle(_) | Opcode::RejectCycle(_) => {
+                // A search of the whole graph, whatever the frontier
+                cost += nodes + edges;
+                visited += nodes;
+            }
+            Opcode::FilterTime { .. }
+            | Opcode::FilterMint(_)
+            | Opcode::FilterAttr { .. }
+            | Opcode::FilterRange { .. } => {
+                cost += frontier;
+                frontier = frontier.div_ceil(FILTER_SELECTIVITY);
+                visited += worst_frontier;
+            }
+            Opcode::SetLimit(l) => limit = *l as u64,
+            Opcode::SaveResults | Opcode::Project(_) => cost += frontier.min(limit),
+            Opcode::CreateNode { .. }
+            | Opcode::CreateEdge { .. }
+            | Opcode::CreateEdgeBetween { .. } => {
+                cost += 1;
+                visited += 1;
+            }
+            Opcode::SetPartition(_)
+            | Opcode::SetTtl(_)
+            | Opcode::SetNodeId(_)
+            | Opcode::Bind(_) => {}
+        }
+    }
+
+    QueryEstimate {
+        op_count: ops.len() as u32,
+        cost,
+        max_visited_nodes: visited,
+    }
 }
 
 impl<'g> Vm<'g> {
@@ -59,9 +334,44 @@ impl<'g> Vm<'g> {
             current_set: Vec::new(),
             result_set: Vec::new(),
             limit: None,
+            partition: None,
+            now: 0,
+            slot: 0,
+            ttl: None,
+            node_id: None,
+            projection: None,
+            pairs: Vec::new(),
+            delegates: Vec::new(),
+            weights: Vec::new(),
+            sum: None,
+            cycle: None,
+            registers: [None; REGISTERS],
+            next_set: Vec::new(),
+            cache: ExecutionCache::default(),
         }
     }
 
+    /// Sets the clock time recorded on writes.
+    pub fn at_time(mut self, now: i64) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Sets the slot that TTLs are measured from and checked against.
+    pub fn at_slot(mut self, slot: u64) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// Whether node `id` exists in scope and has not expired.
+    fn is_visible(&mut self, id: NodeId) -> bool {
+        self.cache.get_node(self.graph, id).is_some_and(|node| {
+            !node.is_deleted
+                && self.partition.is_none_or(|p| node.partition == p)
+                && !node.is_expired(self.slot)
+        })
+    }
+
     fn get_current_nodes(&self) -> StdResult<&[NodeId], VmError> {
         if self.current_set.is_empty() {
             return Err(VmError::InvalidNodeSet);
@@ -69,205 +379,656 @@ impl<'g> Vm<'g> {
         Ok(&self.current_set)
     }
 
+    /// Runs `ops`, borrowing their filters, labels and id lists rather than
+    /// cloning them; the node sets are reused in place from one opcode to
+    /// the next. The returned nodes are moved out, so a `Vm` runs one
+    /// program.
     pub fn execute(&mut self, ops: &[Opcode]) -> StdResult<VmResult, VmError> {
-        for op in ops {
+        // An unlimited budget never pauses
+        self.resume(ops, &mut VmCheckpoint::default(), u64::MAX)?
+            .ok_or(VmError::InvalidProgram)
+    }
+
+    /// Runs `ops` from `checkpoint` (the first opcode for a default one),
+    /// spending about `visits` node and edge visits: one per node for a
+    /// scan of all nodes, and traversals as `traverse_out_resumable`
+    /// charges them. Returns the result once the program ends, or `None`
+    /// after saving where it stopped, between two opcodes or two traversal
+    /// levels, into `checkpoint`. Resuming from it on the same graph and
+    /// slot gives the result an uninterrupted run would.
+    ///
+    /// A program that fails leaves the graph as it found it: nodes and
+    /// edges created before the failing opcode are removed again.
+    pub fn resume(
+        &mut self,
+        ops: &[Opcode],
+        checkpoint: &mut VmCheckpoint,
+        visits: u64,
+    ) -> StdResult<Option<VmResult>, VmError> {
+        let mark = self.graph.append_mark();
+        let result = self.run(ops, checkpoint, visits);
+        if result.is_err() {
+            self.graph.rollback_to(mark);
+        }
+        result
+    }
+
+    fn run(
+        &mut self,
+        ops: &[Opcode],
+        checkpoint: &mut VmCheckpoint,
+        mut visits: u64,
+    ) -> StdResult<Option<VmResult>, VmError> {
+        self.current_set = std::mem::take(&mut checkpoint.current_set);
+        self.result_set = std::mem::take(&mut checkpoint.result_set);
+        self.limit = checkpoint.limit.map(|limit| limit as usize);
+        self.partition = checkpoint.partition;
+        self.pairs = std::mem::take(&mut checkpoint.pairs);
+        self.delegates = std::mem::take(&mut checkpoint.delegates);
+        self.weights = std::mem::take(&mut checkpoint.weights);
+        self.sum = checkpoint.sum;
+        self.cycle = checkpoint.cycle.take();
+        let mut traversal = checkpoint.traversal.take();
+
+        for (pc, op) in ops.iter().enumerate().skip(checkpoint.pc as usize) {
+            if visits == 0 {
+                self.save(checkpoint, pc, traversal.take());
+                return Ok(None);
+            }
             match op {
                 Opcode::SetCurrentFromAllNodes => {
-                    self.current_set = self.graph.nodes.iter().map(|n| n.id).collect();
+                    visits = visits.saturating_sub(self.graph.nodes.len() as u64);
+                    let (partition, slot) = (self.partition, self.slot);
+                    self.current_set.clear();
+                    self.current_set.extend(
+                        self.graph
+                            .nodes
+                            .iter()
+                            .filter(|n| !n.is_deleted && !n.is_expired(slot))
+                            .filter(|n| partition.is_none_or(|p| n.partition == p))
+                            .map(|n| n.id)
+                            .take(self.limit.unwrap_or(usize::MAX)),
+                    );
                 }
                 Opcode::SetCurrentFromIds(node_ids) => {
-                    self.current_set = node_ids.clone();
+                    // Soft-deleted and expired nodes, and nodes outside the
+                    // partition, are invisible; missing ids are kept as given
+                    let mut current_set = std::mem::take(&mut self.current_set);
+                    current_set.clear();
+                    current_set.extend(node_ids.iter().copied().filter(|&id| {
+                        self.is_visible(id) || self.cache.node_index(self.graph, id).is_none()
+                    }));
+                    current_set.truncate(self.limit.unwrap_or(usize::MAX));
+                    self.current_set = current_set;
                 }
                 Opcode::TraverseOut(filter) => {
-                    let start_nodes = self.get_current_nodes()?;
-                    let result = self.graph.traverse_out(start_nodes, filter, self.limit);
-                    self.current_set = result;
+                    self.get_current_nodes()?;
+                    // Ask for one node past the budget to tell "exactly at
+                    // the budget" from "over it"
+                    let budget = self.graph.config.max_traversal_nodes as usize;
+                    let limit = self.limit.map_or(budget + 1, |l| l.min(budget + 1));
+                    let paused = self.graph.traverse_out_resumable(
+                        &self.current_set,
+                        filter,
+                        Some(limit),
+                        self.partition,
+                        self.slot,
+                        &mut self.cache,
+                        &mut self.next_set,
+                        traversal.take(),
+                        &mut visits,
+                    );
+                    if let Some(paused) = paused {
+                        self.save(checkpoint, pc, Some(paused));
+                        return Ok(None);
+                    }
+                    if self.next_set.len() > budget {
+                        return Err(VmError::TraversalBudgetExceeded);
+                    }
+                    std::mem::swap(&mut self.current_set, &mut self.next_set);
+                }
+                Opcode::ProbeEdgeTo { to, filter } => {
+                    visits = visits.saturating_sub(self.current_set.len() as u64);
+                    let passes = self.is_visible(*to)
+                        && self.cache.get_node(self.graph, *to).is_some_and(|node| {
+                            let label = node.label.as_str();
+                            (filter.where_node_labels.is_empty()
+                                || filter.where_node_labels.iter().any(|l| l == label))
+                                && !filter.where_not_node_labels.iter().any(|l| l == label)
+                        });
+                    let linked = passes
+                        && self
+                            .graph
+                            .has_edge_to(&self.current_set, *to, filter, self.partition);
+                    self.current_set.clear();
+                    if linked {
+                        self.current_set.push(*to);
+                    }
+                }
+                Opcode::MutualOut(filter) => {
+                    self.get_current_nodes()?;
+                    let budget = self.graph.config.max_traversal_nodes as usize;
+                    let limit = self.limit.map_or(budget + 1, |l| l.min(budget + 1));
+                    self.graph.mutual_out(
+                        &self.current_set,
+                        filter,
+                        Some(limit),
+                        self.partition,
+                        self.slot,
+                        &mut self.cache,
+                        &mut self.pairs,
+                        &mut visits,
+                    );
+                    if self.pairs.len() > budget {
+                        return Err(VmError::TraversalBudgetExceeded);
+                    }
+                    self.next_set.clear();
+                    for &(_, neighbour) in &self.pairs {
+                        if !self.next_set.contains(&neighbour) {
+                            self.next_set.push(neighbour);
+                        }
+                    }
+                    std::mem::swap(&mut self.current_set, &mut self.next_set);
+                }
+                Opcode::ResolveDelegates { label, max_hops } => {
+                    self.get_current_nodes()?;
+                    let mut delegates = std::mem::take(&mut self.delegates);
+                    delegates.clear();
+                    for i in 0..self.current_set.len() {
+                        let start = self.current_set[i];
+                        let delegate = self.resolve_delegate(start, label, *max_hops, &mut visits);
+                        delegates.push((start, delegate));
+                    }
+                    self.delegates = delegates;
+                }
+                Opcode::PropagateWeight {
+                    label,
+                    key,
+                    max_hops,
+                } => {
+                    self.get_current_nodes()?;
+                    let mut weights = std::mem::take(&mut self.weights);
+                    weights.clear();
+                    for i in 0..self.current_set.len() {
+                        let start = self.current_set[i];
+                        let weight = self
+                            .cache
+                            .get_node(self.graph, start)
+                            .and_then(|node| attribute_int(&node.data, key))
+                            .unwrap_or(0);
+                        let Some(end) = self.resolve_delegate(start, label, *max_hops, &mut visits)
+                        else {
+                            continue;
+                        };
+                        match weights.iter_mut().find(|(id, _)| *id == end) {
+                            Some((_, sum)) => {
+                                *sum = sum.checked_add(weight).ok_or(VmError::Overflow)?;
+                            }
+                            None => weights.push((end, weight)),
+                        }
+                    }
+                    self.current_set.clear();
+                    self.current_set.extend(weights.iter().map(|(id, _)| *id));
+                    self.weights = weights;
+                }
+                Opcode::SumPaths {
+                    filter,
+                    key,
+                    min_hops,
+                    max_hops,
+                } => {
+                    // A sum over no paths is 0, even with no start nodes
+                    let mut walk = PathWalk {
+                        filter,
+                        key,
+                        min_hops: *min_hops,
+                        max_hops: *max_hops,
+                        walked: 0,
+                        total: 0,
+                    };
+                    let mut path = Vec::with_capacity(*max_hops as usize + 1);
+                    for i in 0..self.current_set.len() {
+                        path.clear();
+                        path.push(self.current_set[i]);
+                        self.walk_paths(&mut walk, &mut path, 0, &mut visits)?;
+                    }
+                    self.sum = Some(walk.total);
+                }
+                Opcode::FindCycle(label) => {
+                    self.cycle = Some(self.find_cycle(label, &mut visits).unwrap_or_default());
+                }
+                Opcode::RejectCycle(label) => {
+                    if self.find_cycle(label, &mut visits).is_some() {
+                        return Err(VmError::CycleDetected);
+                    }
                 }
                 Opcode::SetLimit(limit) => {
-                    self.limit = Some(*limit);
+                    self.limit = Some(*limit as usize);
+                }
+                Opcode::SetPartition(partition) => {
+                    self.partition = Some(*partition);
+                }
+                Opcode::SetTtl(ttl) => {
+                    self.ttl = Some(*ttl);
+                }
+                Opcode::SetNodeId(id) => {
+                    self.node_id = Some(*id);
+                }
+                Opcode::Project(projection) => {
+                    self.projection = Some(projection.clone());
+                }
+                Opcode::FilterTime { field, op, value } => {
+                    let (graph, cache) = (&*self.graph, &mut self.cache);
+                    self.current_set.retain(|id| {
+                        cache.get_node(graph, *id).is_some_and(|node| {
+                            let at = match field {
+                                TimeField::CreatedAt => node.created_at,
+                                TimeField::UpdatedAt => node.updated_at,
+                            };
+                            op.holds(at, *value)
+                        })
+                    });
+                }
+                Opcode::FilterMint(mint) => {
+                    let (graph, cache) = (&*self.graph, &mut self.cache);
+                    self.current_set.retain(|id| {
+                        cache
+                            .get_node(graph, *id)
+                            .is_some_and(|node| node.mint == Some(*mint))
+                    });
+                }
+                Opcode::FilterAttr { key, value } => {
+                    let (graph, cache) = (&*self.graph, &mut self.cache);
+                    self.current_set.retain(|id| {
+                        cache
+                            .get_node(graph, *id)
+                            .is_some_and(|node| attribute_equals(&node.data, key, value))
+                    });
+                }
+                Opcode::FilterRange { key, min, max } => {
+                    let (graph, cache) = (&*self.graph, &mut self.cache);
+                    self.current_set.retain(|id| {
+                        cache
+                            .get_node(graph, *id)
+                            .and_then(|node| attribute_int(&node.data, key))
+                            .is_some_and(|value| (*min..=*max).contains(&value))
+                    });
                 }
                 Opcode::SaveResults => {
                     self.result_set.extend_from_slice(&self.current_set);
+                    self.result_set.truncate(self.limit.unwrap_or(usize::MAX));
                 }
                 Opcode::CreateNode { label, data } => {
                     // Security checks: limit data and label sizes
-                    if data.len() > 1024 {
+                    if data.len() > self.graph.config.max_node_data_len as usize {
                         return Err(VmError::DataTooLarge);
                     }
-                    if label.len() > 64 {
+                    if label.len() > self.graph.config.max_label_len as usize {
                         return Err(VmError::LabelTooLong);
                     }
 
                     // Limit total number of nodes to prevent DoS
-                    const MAX_NODES: usize = 1000;
-                    if self.graph.nodes.len() >= MAX_NODES {
+                    if self.graph.nodes.len() >= self.graph.config.max_nodes as usize {
                         return Err(VmError::GraphLimitExceeded);
                     }
 
-                    let id = self.graph.nonce;
-                    self.graph.nonce = self.graph.nonce.checked_add(1).ok_or(VmError::Overflow)?;
+                    // Soft-deleted nodes keep their ids, so they count too
+                    let id = match self.node_id.take() {
+                        Some(id) if self.graph.node_index(id).is_some() => {
+                            return Err(VmError::DuplicateNodeId);
+                        }
+                        Some(id) => id,
+                        None => self.graph.next_node_id().ok_or(VmError::Overflow)?,
+                    };
 
                     let node = Node {
                         id,
-                        label: label.clone(),
+                        label: to_label(label.as_str()),
                         data: data.clone(),
-                        outgoing_edge_indices: Vec::new(),
+                        is_deleted: false,
+                        is_protected: false,
+                        partition: self.partition.unwrap_or_default(),
+                        created_at: self.now,
+                        updated_at: self.now,
+                        expires_slot: self
+                            .ttl
+                            .map_or(0, |ttl| self.slot.saturating_add(ttl).max(1)),
+                        mint: None,
                     };
 
-                    self.graph.nodes.push(node);
-                    self.graph.node_count = self
-                        .graph
-                        .node_count
-                        .checked_add(1)
-                        .ok_or(VmError::Overflow)?;
+                    self.graph.add_node(node).ok_or(VmError::Overflow)?;
 
                     // Set the created node as the current set
-                    self.current_set = vec![id];
+                    self.current_set.clear();
+                    self.current_set.push(id);
                 }
-                Opcode::CreateEdge { from, to, label } => {
-                    // Security checks: limit label size
-                    if label.len() > 64 {
-                        return Err(VmError::LabelTooLong);
-                    }
-
-                    // Limit total number of edges to prevent DoS
-                    const MAX_EDGES: usize = 5000;
-                    if self.graph.edges.len() >= MAX_EDGES {
-                        return Err(VmError::GraphLimitExceeded);
-                    }
-
-                    let from_exists = self.graph.nodes.iter().any(|n| n.id == *from);
-                    let to_exists = self.graph.nodes.iter().any(|n| n.id == *to);
-
-                    if !from_exists || !to_exists {
-                        return Err(VmError::NodeNotFound);
-                    }
-
-                    let edge_index = self.graph.edges.len() as u32;
-                    let edge = Edge {
-                        from: *from,
-                        to: *to,
-                        label: label.clone(),
+                Opcode::CreateEdge { from, to, label } => self.create_edge(*from, *to, label)?,
+                Opcode::Bind(register) => {
+                    let node = match self.current_set[..] {
+                        [node] => node,
+                        [] => return Err(VmError::NodeNotFound),
+                        _ => return Err(VmError::InvalidNodeSet),
                     };
-
-                    self.graph.edges.push(edge);
-                    self.graph.edge_count = self
-                        .graph
-                        .edge_count
-                        .checked_add(1)
-                        .ok_or(VmError::Overflow)?;
-
-                    let from_node = self
-                        .graph
-                        .nodes
-                        .iter_mut()
-                        .find(|n| n.id == *from)
-                        .ok_or(VmError::NodeNotFound)?;
-
-                    from_node.outgoing_edge_indices.push(edge_index);
-
-                    // Set the current set to the "to" node
-                    self.current_set = vec![*to];
+                    *self
+                        .registers
+                        .get_mut(*register as usize)
+                        .ok_or(VmError::InvalidProgram)? = Some(node);
+                }
+                Opcode::CreateEdgeBetween { from, to, label } => {
+                    let bound = |register: u8| {
+                        self.registers
+                            .get(register as usize)
+                            .copied()
+                            .flatten()
+                            .ok_or(VmError::InvalidProgram)
+                    };
+                    let (from, to) = (bound(*from)?, bound(*to)?);
+                    self.create_edge(from, to, label)?;
                 }
             }
         }
 
-        if !self.current_set.is_empty() {
-            Ok(VmResult::Nodes(self.current_set.clone()))
+        if let Some(sum) = self.sum {
+            return Ok(Some(VmResult::Scalar(sum)));
+        }
+        if let Some(cycle) = self.cycle.take() {
+            return Ok(Some(VmResult::Cycle(cycle)));
+        }
+        // Sets built before `SetLimit` can still be over it
+        let limit = self.limit.unwrap_or(usize::MAX);
+        self.current_set.truncate(limit);
+        let ids = if !self.current_set.is_empty() {
+            std::mem::take(&mut self.current_set)
         } else if !self.result_set.is_empty() {
-            Ok(VmResult::Nodes(self.result_set.clone()))
+            std::mem::take(&mut self.result_set)
         } else {
-            Err(VmError::NoReturnValue)
+            return Err(VmError::NoReturnValue);
+        };
+        Ok(Some(self.project(ids)))
+    }
+
+    /// The node at the end of `start`'s chain of `label` edges, taking
+    /// the first edge to a visible node at each step. `None` if the chain
+    /// comes back to a node it passed, or has an edge left after
+    /// `max_hops`. Each step spends one of `visits` plus one per edge.
+    fn resolve_delegate(
+        &mut self,
+        start: NodeId,
+        label: &str,
+        max_hops: u32,
+        visits: &mut u64,
+    ) -> Option<NodeId> {
+        let mut chain = vec![start];
+        let mut current = start;
+        for hop in 0..=max_hops {
+            let index = self.cache.node_index(self.graph, current)?;
+            let outgoing = self.graph.outgoing_edge_indices(index);
+            *visits = visits.saturating_sub(1 + outgoing.len() as u64);
+            let targets: Vec<NodeId> = outgoing
+                .iter()
+                .filter_map(|&i| self.graph.edges.get(i as usize))
+                .filter(|edge| {
+                    edge.label.as_str() == label
+                        && self.partition.is_none_or(|p| edge.partition == p)
+                })
+                .map(|edge| edge.to)
+                .collect();
+            let Some(next) = targets.into_iter().find(|&to| self.is_visible(to)) else {
+                return Some(current);
+            };
+            if hop == max_hops || chain.contains(&next) {
+                return None;
+            }
+            chain.push(next);
+            current = next;
         }
+        None
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::graph::{Edge, GraphStore, Node};
-    use anchor_lang::prelude::Pubkey;
+    /// Extends `path`, whose edges sum to `sum`, by each `walk.filter`
+    /// edge out of its last node to a visible node not already on it,
+    /// adding the new path's sum to `walk.total` if it is long enough and
+    /// ends on a node passing the filter, and recursing until
+    /// `walk.max_hops`. Walking more paths than the traversal budget
+    /// fails. Each expansion spends one of `visits` plus one per edge.
+    fn walk_paths(
+        &mut self,
+        walk: &mut PathWalk,
+        path: &mut Vec<NodeId>,
+        sum: i64,
+        visits: &mut u64,
+    ) -> StdResult<(), VmError> {
+        let Some(&last) = path.last() else {
+            return Ok(());
+        };
+        let Some(index) = self.cache.node_index(self.graph, last) else {
+            return Ok(());
+        };
+        let filter = walk.filter;
+        let outgoing = self.graph.outgoing_edge_indices(index);
+        *visits = visits.saturating_sub(1 + outgoing.len() as u64);
+        let steps: Vec<(NodeId, i64)> = outgoing
+            .iter()
+            .filter_map(|&i| self.graph.edges.get(i as usize))
+            .filter(|edge| {
+                let label = edge.label.as_str();
+                (filter.where_edge_labels.is_empty()
+                    || filter.where_edge_labels.iter().any(|l| l == label))
+                    && !filter.where_not_edge_labels.iter().any(|l| l == label)
+                    && self.partition.is_none_or(|p| edge.partition == p)
+            })
+            .map(|edge| (edge.to, attribute_int(&edge.data, walk.key).unwrap_or(0)))
+            .collect();
+
+        let hops = path.len() as u32;
+        for (to, amount) in steps {
+            if path.contains(&to) || !self.is_visible(to) {
+                continue;
+            }
+            walk.walked += 1;
+            if walk.walked > self.graph.config.max_traversal_nodes as usize {
+                return Err(VmError::TraversalBudgetExceeded);
+            }
+            let sum = sum.checked_add(amount).ok_or(VmError::Overflow)?;
+            let ends = self.cache.get_node(self.graph, to).is_some_and(|node| {
+                let label = node.label.as_str();
+                (filter.where_node_labels.is_empty()
+                    || filter.where_node_labels.iter().any(|l| l == label))
+                    && !filter.where_not_node_labels.iter().any(|l| l == label)
+            });
+            if hops >= walk.min_hops && ends {
+                walk.total = walk.total.checked_add(sum).ok_or(VmError::Overflow)?;
+            }
+            if hops < walk.max_hops {
+                path.push(to);
+                self.walk_paths(walk, path, sum, visits)?;
+                path.pop();
+            }
+        }
+        Ok(())
+    }
 
-    fn create_small_test_graph() -> GraphStore {
-        let authority = Pubkey::new_unique();
+    /// The first cycle of `label` edges between visible nodes that a
+    /// depth-first search from each node in turn comes across, as its nodes
+    /// in edge order. Each node expanded spends one of `visits` plus one
+    /// per edge.
+    fn find_cycle(&mut self, label: &str, visits: &mut u64) -> Option<Vec<NodeId>> {
+        const UNSEEN: u8 = 0;
+        const ON_PATH: u8 = 1;
+        const DONE: u8 = 2;
+        let mut state = vec![UNSEEN; self.graph.nodes.len()];
+        // The nodes on the path searched, each with its next edge to try
+        let mut path: Vec<(usize, usize)> = Vec::new();
+        for root in 0..self.graph.nodes.len() {
+            if state[root] != UNSEEN || !self.is_visible(self.graph.nodes[root].id) {
+                continue;
+            }
+            state[root] = ON_PATH;
+            path.push((root, 0));
+            while let Some(&(index, next)) = path.last() {
+                let outgoing = self.graph.outgoing_edge_indices(index);
+                if next == 0 {
+                    *visits = visits.saturating_sub(1 + outgoing.len() as u64);
+                }
+                let Some(&edge_index) = outgoing.get(next) else {
+                    state[index] = DONE;
+                    path.pop();
+                    continue;
+                };
+                if let Some(top) = path.last_mut() {
+                    top.1 += 1;
+                }
+                let Some(edge) = self.graph.edges.get(edge_index as usize) else {
+                    continue;
+                };
+                if edge.label.as_str() != label
+                    || self.partition.is_some_and(|p| edge.partition != p)
+                {
+                    continue;
+                }
+                let to = edge.to;
+                if !self.is_visible(to) {
+                    continue;
+                }
+                let Some(to_index) = self.cache.node_index(self.graph, to) else {
+                    continue;
+                };
+                match state[to_index] {
+                    UNSEEN => {
+                        state[to_index] = ON_PATH;
+                        path.push((to_index, 0));
+                    }
+                    ON_PATH => {
+                        let start = path.iter().position(|&(i, _)| i == to_index)?;
+                        let nodes = &self.graph.nodes;
+                        return Some(path[start..].iter().map(|&(i, _)| nodes[i].id).collect());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
 
-        let mut nodes = Vec::new();
-        let mut edges = Vec::new();
+    /// What the program returns of the nodes `ids`, by its `Project`.
+    fn project(&mut self, ids: Vec<NodeId>) -> VmResult {
+        let (graph, cache) = (&*self.graph, &mut self.cache);
+        match &self.projection {
+            None => VmResult::Nodes(ids),
+            Some(Projection::Property(name)) => VmResult::Values(
+                ids.into_iter()
+                    .map(|id| {
+                        let value = cache.get_node(graph, id).and_then(|n| property(n, name));
+                        (id, value)
+                    })
+                    .collect(),
+            ),
+            Some(Projection::Records) => VmResult::Records(
+                ids.into_iter()
+                    .filter_map(|id| cache.get_node(graph, id).cloned())
+                    .collect(),
+            ),
+            Some(Projection::Delegates) => {
+                let mut delegates = std::mem::take(&mut self.delegates);
+                delegates.retain(|(id, _)| ids.contains(id));
+                VmResult::Delegates(delegates)
+            }
+            Some(Projection::Weights) => {
+                let mut weights = std::mem::take(&mut self.weights);
+                weights.retain(|(id, _)| ids.contains(id));
+                VmResult::Weights(weights)
+            }
+            Some(Projection::Pairs) => {
+                // Filters after `MutualOut` may have dropped neighbours
+                let mut pairs = std::mem::take(&mut self.pairs);
+                pairs.retain(|(_, neighbour)| ids.contains(neighbour));
+                VmResult::Pairs(pairs)
+            }
+        }
+    }
 
-        nodes.push(Node {
-            id: 1,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![0, 1],
-        });
+    fn create_edge(&mut self, from: NodeId, to: NodeId, label: &str) -> StdResult<(), VmError> {
+        // Security checks: limit label size
+        if label.len() > self.graph.config.max_label_len as usize {
+            return Err(VmError::LabelTooLong);
+        }
 
-        nodes.push(Node {
-            id: 2,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![2, 3],
-        });
+        // Limit total number of edges to prevent DoS
+        if self.graph.edges.len() >= self.graph.config.max_edges as usize {
+            return Err(VmError::GraphLimitExceeded);
+        }
 
-        nodes.push(Node {
-            id: 3,
-            label: "City".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![4],
-        });
+        if !self.is_visible(from) || !self.is_visible(to) {
+            return Err(VmError::NodeNotFound);
+        }
 
-        nodes.push(Node {
-            id: 4,
-            label: "Town".to_string(),
-            data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
+        if self.graph.has_edge(from, to, label) {
+            return Err(VmError::DuplicateEdge);
+        }
+        if self.graph.closes_cycle(from, to, label) {
+            return Err(VmError::CycleDetected);
+        }
 
-        nodes.push(Node {
-            id: 5,
-            label: "Town".to_string(),
+        let edge = Edge {
+            from,
+            to,
+            label: to_label(label),
+            partition: self.partition.unwrap_or_default(),
             data: Vec::new(),
-            outgoing_edge_indices: vec![],
-        });
-
-        edges.push(Edge {
-            from: 1,
-            to: 2,
-            label: "Railway".to_string(),
-        });
+            created_at: self.now,
+        };
 
-        edges.push(Edge {
-            from: 1,
-            to: 3,
-            label: "Railway".to_string(),
-        });
+        self.graph.add_edge(edge).ok_or(VmError::NodeNotFound)?;
 
-        edges.push(Edge {
-            from: 2,
-            to: 3,
-            label: "Railway".to_string(),
-        });
+        // Set the current set to the "to" node
+        self.current_set.clear();
+        self.current_set.push(to);
+        Ok(())
+    }
 
-        edges.push(Edge {
-            from: 2,
-            to: 4,
-            label: "Highway".to_string(),
-        });
+    /// Moves the VM's state into `checkpoint`, to resume at `ops[pc]`.
+    fn save(
+        &mut self,
+        checkpoint: &mut VmCheckpoint,
+        pc: usize,
+        traversal: Option<TraversalCheckpoint>,
+    ) {
+        *checkpoint = VmCheckpoint {
+            pc: pc as u32,
+            current_set: std::mem::take(&mut self.current_set),
+            result_set: std::mem::take(&mut self.result_set),
+            limit: self.limit.map(|limit| limit as u32),
+            partition: self.partition,
+            traversal,
+            pairs: std::mem::take(&mut self.pairs),
+            delegates: std::mem::take(&mut self.delegates),
+            weights: std::mem::take(&mut self.weights),
+            sum: self.sum,
+            cycle: self.cycle.take(),
+        };
+    }
+}
 
-        edges.push(Edge {
-            from: 3,
-            to: 1,
-            label: "Railway".to_string(),
-        });
+/// A `SumPaths` in progress: its settings, the paths walked so far and
+/// the total over those that matched.
+struct PathWalk<'a> {
+    filter: &'a TraverseFilter,
+    key: &'a str,
+    min_hops: u32,
+    max_hops: u32,
+    walked: usize,
+    total: i64,
+}
 
-        GraphStore {
-            authority,
-            node_count: 5,
-            edge_count: 5,
-            nonce: 6,
-            nodes,
-            edges,
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attrs::{set_attribute, AttrValue};
+    use crate::cypher::parse;
+    use crate::graph::{TraverseFilter, MAX_LABEL_LEN, NODE_ID_LEN};
+    use crate::lexer::compile_to_opcodes;
+    use crate::test_utils::{assert_nodes, large_graph, small_graph, GraphBuilder};
+    use anchor_lang::prelude::Pubkey;
 
     fn create_filter(node_label: &str, edge_label: &str) -> TraverseFilter {
         TraverseFilter {
@@ -280,28 +1041,18 @@ mod tests {
 
     #[test]
     fn test_set_current_from_all_nodes() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let ops = vec![Opcode::SetCurrentFromAllNodes];
         let result = vm.execute(&ops).unwrap();
 
-        match result {
-            VmResult::Nodes(nodes) => {
-                assert_eq!(nodes.len(), 5);
-                assert!(nodes.contains(&1));
-                assert!(nodes.contains(&2));
-                assert!(nodes.contains(&3));
-                assert!(nodes.contains(&4));
-                assert!(nodes.contains(&5));
-            }
-            _ => panic!("Expected Nodes result"),
-        }
+        assert_nodes(&result, &[1, 2, 3, 4, 5]);
     }
 
     #[test]
     fn test_set_current_from_ids() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let ops = vec![Opcode::SetCurrentFromIds(vec![1, 3, 5])];
@@ -322,7 +1073,7 @@ mod tests {
 
     #[test]
     fn test_filter_node_label_via_traverse() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter = TraverseFilter {
@@ -349,7 +1100,7 @@ mod tests {
 
     #[test]
     fn test_filter_node_label_not_via_traverse() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter = TraverseFilter {
@@ -376,7 +1127,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter = create_filter("City", "Railway");
@@ -399,7 +1150,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_with_limit() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter = create_filter("City", "Railway");
@@ -418,9 +1169,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_limit_bounds_every_pattern_shape() {
+        let mut graph = large_graph();
+        for query in [
+            "MATCH (n) RETURN n LIMIT 2",
+            "MATCH (n:City) RETURN n LIMIT 2",
+            "MATCH (n:Town) WHERE n.created_at >= 0 RETURN n LIMIT 2",
+            "MATCH (n:City)-[:Railway]->(m:City) RETURN m LIMIT 2",
+            "MATCH (n)-[:Highway]->(m) WHERE n.id = 11 RETURN m LIMIT 2",
+        ] {
+            let ops = compile_to_opcodes(parse(query).expect(query));
+            let result = Vm::new(&mut graph).execute(&ops).unwrap();
+            assert_eq!(result.node_ids().len(), 2, "{}", query);
+        }
+
+        // The limit applies after the label filter, not before it
+        let ops = compile_to_opcodes(parse("MATCH (n:Town) RETURN n LIMIT 2").unwrap());
+        assert_nodes(&Vm::new(&mut graph).execute(&ops).unwrap(), &[5, 6]);
+    }
+
+    #[test]
+    fn test_traversal_budget() {
+        let mut graph = small_graph();
+        graph.config.max_traversal_nodes = 2;
+        let mut vm = Vm::new(&mut graph);
+
+        let filter = create_filter("City", "Railway");
+        let ops = vec![
+            Opcode::SetCurrentFromIds(vec![1]),
+            Opcode::TraverseOut(filter.clone()),
+        ];
+        assert!(matches!(
+            vm.execute(&ops),
+            Err(VmError::TraversalBudgetExceeded)
+        ));
+
+        // A LIMIT within the budget keeps the query valid
+        let mut vm = Vm::new(&mut graph);
+        let ops = vec![
+            Opcode::SetCurrentFromIds(vec![1]),
+            Opcode::SetLimit(2),
+            Opcode::TraverseOut(filter),
+        ];
+        assert!(vm.execute(&ops).is_ok());
+    }
+
     #[test]
     fn test_save_results() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let ops = vec![
@@ -442,7 +1239,7 @@ mod tests {
 
     #[test]
     fn test_complex_query() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter1 = TraverseFilter {
@@ -472,7 +1269,7 @@ mod tests {
 
     #[test]
     fn test_traverse_out_empty_current_set() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter = create_filter("City", "Railway");
@@ -488,7 +1285,7 @@ mod tests {
 
     #[test]
     fn test_no_return_value() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter = TraverseFilter {
@@ -512,7 +1309,7 @@ mod tests {
 
     #[test]
     fn test_filter_after_traverse() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter1 = create_filter("City", "Railway");
@@ -546,7 +1343,7 @@ mod tests {
 
     #[test]
     fn test_multiple_traversals() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let filter1 = create_filter("City", "Railway");
@@ -568,9 +1365,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_repeated_filter_sees_created_nodes() {
+        let mut graph = small_graph();
+        let mut vm = Vm::new(&mut graph);
+
+        let filter = create_filter("City", "Railway");
+        let ops = vec![
+            Opcode::SetCurrentFromIds(vec![1]),
+            Opcode::TraverseOut(filter.clone()),
+            Opcode::CreateNode {
+                label: "City".to_string(),
+                data: Vec::new(),
+            },
+            Opcode::CreateEdge {
+                from: 3,
+                to: 6,
+                label: "Railway".to_string(),
+            },
+            Opcode::SetCurrentFromIds(vec![1]),
+            Opcode::TraverseOut(filter),
+        ];
+        let result = vm.execute(&ops).unwrap();
+
+        assert_nodes(&result, &[1, 2, 3, 6]);
+    }
+
     #[test]
     fn test_create_node() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let initial_node_count = graph.node_count;
         let initial_nonce = graph.nonce;
 
@@ -606,7 +1429,7 @@ mod tests {
 
     #[test]
     fn test_create_edge() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let initial_edge_count = graph.edge_count;
 
         let mut vm = Vm::new(&mut graph);
@@ -625,19 +1448,67 @@ mod tests {
         assert_eq!(graph.edge_count, initial_edge_count + 1);
 
         // Verify the edge exists and is linked from node 1
-        let node1 = graph.get_node_by_id(1).unwrap();
-        assert!(node1.outgoing_edge_indices.len() > 0);
+        let node1_edges = graph.outgoing_edge_indices(graph.node_index(1).unwrap());
+        assert!(!node1_edges.is_empty());
 
-        let last_edge_index = node1.outgoing_edge_indices.last().unwrap();
+        let last_edge_index = node1_edges.last().unwrap();
         let edge = &graph.edges[*last_edge_index as usize];
         assert_eq!(edge.from, 1);
         assert_eq!(edge.to, 5);
         assert_eq!(edge.label, "Road");
     }
 
+    #[test]
+    fn test_create_edge_between_new_nodes() {
+        let mut graph = small_graph();
+
+        let ops = compile_to_opcodes(parse("CREATE (a:Village)-[:Road]->(b:Town)").unwrap());
+        let result = Vm::new(&mut graph).execute(&ops).unwrap();
+
+        assert_nodes(&result, &[7]);
+        assert_eq!(graph.get_node_by_id(6).unwrap().label, "Village");
+        assert_eq!(graph.get_node_by_id(7).unwrap().label, "Town");
+        assert!(graph.has_edge(6, 7, "Road"));
+
+        // One new endpoint and one existing node
+        let ops = compile_to_opcodes(parse("CREATE (a:Village)-[:Road]->(4)").unwrap());
+        Vm::new(&mut graph).execute(&ops).unwrap();
+        assert!(graph.has_edge(8, 4, "Road"));
+
+        let ops = compile_to_opcodes(parse("CREATE (a:Village)-[:Road]->(99)").unwrap());
+        assert!(matches!(
+            Vm::new(&mut graph).execute(&ops),
+            Err(VmError::NodeNotFound)
+        ));
+        assert_eq!(graph.nodes.len(), 8);
+    }
+
+    #[test]
+    fn test_check_program_rejects_unknown_register() {
+        let config = small_graph().config;
+        let bind = |register| vec![Opcode::SetCurrentFromIds(vec![1]), Opcode::Bind(register)];
+        assert!(check_program(&bind(1), &config).is_ok());
+        assert!(matches!(
+            check_program(&bind(REGISTERS as u8), &config),
+            Err(VmError::InvalidProgram)
+        ));
+
+        // A register that was never bound fails when the edge is created
+        let mut graph = small_graph();
+        let ops = vec![Opcode::CreateEdgeBetween {
+            from: 0,
+            to: 1,
+            label: "Road".to_string(),
+        }];
+        assert!(matches!(
+            Vm::new(&mut graph).execute(&ops),
+            Err(VmError::InvalidProgram)
+        ));
+    }
+
     #[test]
     fn test_create_edge_invalid_from_node() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let ops = vec![Opcode::CreateEdge {
@@ -656,7 +1527,7 @@ mod tests {
 
     #[test]
     fn test_create_edge_invalid_to_node() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         let ops = vec![Opcode::CreateEdge {
@@ -675,7 +1546,7 @@ mod tests {
 
     #[test]
     fn test_create_node_and_edge_sequence() {
-        let mut graph = create_small_test_graph();
+        let mut graph = small_graph();
         let mut vm = Vm::new(&mut graph);
 
         // Create a new node
@@ -707,10 +1578,1098 @@ mod tests {
         assert!(node.is_some());
         assert_eq!(node.unwrap().label, "Village");
 
-        let node1 = graph.get_node_by_id(1).unwrap();
-        let last_edge_index = node1.outgoing_edge_indices.last().unwrap();
+        let node1_edges = graph.outgoing_edge_indices(graph.node_index(1).unwrap());
+        let last_edge_index = node1_edges.last().unwrap();
         let edge = &graph.edges[*last_edge_index as usize];
         assert_eq!(edge.to, new_node_id);
         assert_eq!(edge.label, "Path");
     }
+
+    #[test]
+    fn test_soft_deleted_node_hidden_from_match() {
+        let mut graph = small_graph();
+        assert!(graph.set_node_deleted(2, true).is_some());
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![Opcode::SetCurrentFromAllNodes];
+        let result = vm.execute(&ops).unwrap();
+
+        match result {
+            VmResult::Nodes(nodes) => {
+                assert_eq!(nodes.len(), 4);
+                assert!(!nodes.contains(&2));
+            }
+            _ => panic!("Expected Nodes result"),
+        }
+    }
+
+    #[test]
+    fn test_soft_deleted_node_hidden_from_ids() {
+        let mut graph = small_graph();
+        graph.set_node_deleted(3, true);
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![Opcode::SetCurrentFromIds(vec![1, 3])];
+        let result = vm.execute(&ops).unwrap();
+
+        match result {
+            VmResult::Nodes(nodes) => {
+                assert_eq!(nodes, vec![1]);
+            }
+            _ => panic!("Expected Nodes result"),
+        }
+    }
+
+    #[test]
+    fn test_create_edge_to_soft_deleted_node() {
+        let mut graph = small_graph();
+        graph.set_node_deleted(5, true);
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![Opcode::CreateEdge {
+            from: 1,
+            to: 5,
+            label: "Road".to_string(),
+        }];
+        let result = vm.execute(&ops);
+
+        match result.unwrap_err() {
+            VmError::NodeNotFound => {}
+            _ => panic!("Expected NodeNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_mutations_bump_version() {
+        let mut graph = small_graph();
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![
+            Opcode::CreateNode {
+                label: "Village".to_string(),
+                data: Vec::new(),
+            },
+            Opcode::CreateEdge {
+                from: 1,
+                to: 2,
+                label: "Road".to_string(),
+            },
+        ];
+        vm.execute(&ops).unwrap();
+        drop(vm);
+
+        assert_eq!(graph.version, 2);
+    }
+
+    #[test]
+    fn test_failed_program_leaves_graph_unchanged() {
+        let mut graph = small_graph();
+        let encode = |graph: &Graph| {
+            let mut buf = Vec::new();
+            graph.try_serialize(&mut buf).unwrap();
+            buf
+        };
+        let before = encode(&graph);
+
+        let ops = vec![
+            Opcode::CreateNode {
+                label: "Village".to_string(),
+                data: Vec::new(),
+            },
+            Opcode::CreateEdge {
+                from: 1,
+                to: 2,
+                label: "Road".to_string(),
+            },
+            Opcode::CreateEdge {
+                from: 1,
+                to: 99,
+                label: "Road".to_string(),
+            },
+        ];
+        assert!(matches!(
+            Vm::new(&mut graph).execute(&ops),
+            Err(VmError::NodeNotFound)
+        ));
+        assert_eq!(encode(&graph), before);
+
+        // The rolled-back node's id is handed out again
+        let result = Vm::new(&mut graph).execute(&ops[..1]).unwrap();
+        assert_nodes(&result, &[6]);
+    }
+
+    #[test]
+    fn test_create_node_with_explicit_id() {
+        let mut graph = small_graph();
+        let create = |id: Option<NodeId>| {
+            let mut ops: Vec<Opcode> = id.map(Opcode::SetNodeId).into_iter().collect();
+            ops.push(Opcode::CreateNode {
+                label: "Village".to_string(),
+                data: Vec::new(),
+            });
+            ops
+        };
+
+        let result = Vm::new(&mut graph).execute(&create(Some(7))).unwrap();
+        assert_nodes(&result, &[7]);
+        assert_eq!(graph.nonce, 6);
+        // Later ids step over the explicit one
+        let result = Vm::new(&mut graph).execute(&create(None)).unwrap();
+        assert_nodes(&result, &[6]);
+        let result = Vm::new(&mut graph).execute(&create(None)).unwrap();
+        assert_nodes(&result, &[8]);
+        assert_eq!(graph.nonce, 9);
+
+        graph.set_node_deleted(3, true).unwrap();
+        for id in [3, 7] {
+            assert!(matches!(
+                Vm::new(&mut graph).execute(&create(Some(id))),
+                Err(VmError::DuplicateNodeId)
+            ));
+        }
+        assert_eq!(graph.nodes.len(), 8);
+        assert_eq!(graph.nonce, 9);
+    }
+
+    #[test]
+    fn test_near_max_explicit_id_keeps_auto_ids() {
+        let mut graph = small_graph();
+        for id in [Some(NodeId::MAX - 1), Some(NodeId::MAX), None] {
+            let mut ops: Vec<Opcode> = id.map(Opcode::SetNodeId).into_iter().collect();
+            ops.push(Opcode::CreateNode {
+                label: "Village".to_string(),
+                data: Vec::new(),
+            });
+            let result = Vm::new(&mut graph).execute(&ops).unwrap();
+            assert_nodes(&result, &[id.unwrap_or(6)]);
+        }
+        assert_eq!(graph.nonce, 7);
+        assert_eq!(graph.next_node_id(), Some(7));
+    }
+
+    #[test]
+    fn test_read_query_keeps_version() {
+        let mut graph = small_graph();
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::TraverseOut(create_filter("City", "Railway")),
+        ];
+        vm.execute(&ops).unwrap();
+        drop(vm);
+
+        assert_eq!(graph.version, 0);
+    }
+
+    #[test]
+    fn test_partition_scopes_match() {
+        let mut graph = small_graph();
+        graph.nodes[3].partition = 2;
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![Opcode::SetPartition(2), Opcode::SetCurrentFromAllNodes];
+        let result = vm.execute(&ops).unwrap();
+
+        match result {
+            VmResult::Nodes(nodes) => {
+                assert_eq!(nodes, vec![4]);
+            }
+            _ => panic!("Expected Nodes result"),
+        }
+    }
+
+    #[test]
+    fn test_partition_stamped_on_create() {
+        let mut graph = small_graph();
+        graph.nodes[0].partition = 3;
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![
+            Opcode::SetPartition(3),
+            Opcode::CreateNode {
+                label: "Village".to_string(),
+                data: Vec::new(),
+            },
+        ];
+        let new_id = match vm.execute(&ops).unwrap() {
+            VmResult::Nodes(nodes) => nodes[0],
+            _ => panic!("Expected Nodes result"),
+        };
+
+        let ops = vec![
+            Opcode::SetPartition(3),
+            Opcode::CreateEdge {
+                from: 1,
+                to: new_id,
+                label: "Road".to_string(),
+            },
+        ];
+        vm.execute(&ops).unwrap();
+        drop(vm);
+
+        assert_eq!(graph.get_node_by_id(new_id).unwrap().partition, 3);
+        assert_eq!(graph.edges.last().unwrap().partition, 3);
+    }
+
+    #[test]
+    fn test_create_edge_across_partitions() {
+        let mut graph = small_graph();
+        graph.nodes[0].partition = 3;
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![
+            Opcode::SetPartition(3),
+            Opcode::CreateEdge {
+                from: 1,
+                to: 2,
+                label: "Road".to_string(),
+            },
+        ];
+        let result = vm.execute(&ops);
+
+        match result.unwrap_err() {
+            VmError::NodeNotFound => {}
+            _ => panic!("Expected NodeNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_set_current_from_ids_keeps_missing_ids() {
+        let mut graph = small_graph();
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![Opcode::SetCurrentFromIds(vec![1, 999])];
+        let result = vm.execute(&ops).unwrap();
+
+        match result {
+            VmResult::Nodes(nodes) => {
+                assert_eq!(nodes, vec![1, 999]);
+            }
+            _ => panic!("Expected Nodes result"),
+        }
+    }
+
+    #[test]
+    fn test_create_duplicate_edge() {
+        let mut graph = small_graph();
+        let initial_edge_count = graph.edge_count;
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![Opcode::CreateEdge {
+            from: 2,
+            to: 4,
+            label: "Highway".to_string(),
+        }];
+        let result = vm.execute(&ops);
+        drop(vm);
+
+        match result.unwrap_err() {
+            VmError::DuplicateEdge => {}
+            _ => panic!("Expected DuplicateEdge error"),
+        }
+        assert_eq!(graph.edge_count, initial_edge_count);
+    }
+
+    #[test]
+    fn test_probe_edge_to() {
+        let mut graph = small_graph();
+        let probe = |query: &str| {
+            let ops = compile_to_opcodes(parse(query).unwrap());
+            assert!(ops
+                .iter()
+                .any(|op| matches!(op, Opcode::ProbeEdgeTo { .. })));
+            ops
+        };
+
+        let mut vm = Vm::new(&mut graph);
+        let ops = probe("MATCH (a)-[:Railway]->(b) WHERE a.id = 2 AND b.id = 3 RETURN b LIMIT 1");
+        assert_nodes(&vm.execute(&ops).unwrap(), &[3]);
+
+        // An edge with another label, a reverse edge, or an end node with
+        // the wrong label does not count
+        for query in [
+            "MATCH (a)-[:Highway]->(b) WHERE a.id = 2 AND b.id = 3 RETURN b LIMIT 1",
+            "MATCH (a)-[:Railway]->(b) WHERE a.id = 3 AND b.id = 2 RETURN b LIMIT 1",
+            "MATCH (a)-[:Railway]->(b:Town) WHERE a.id = 2 AND b.id = 3 RETURN b LIMIT 1",
+        ] {
+            let mut vm = Vm::new(&mut graph);
+            assert!(
+                matches!(vm.execute(&probe(query)), Err(VmError::NoReturnValue)),
+                "{}",
+                query
+            );
+        }
+
+        // Within a partition the edge has to be in it too
+        graph.nodes[1].partition = 1;
+        graph.nodes[2].partition = 1;
+        let mut ops = vec![Opcode::SetPartition(1)];
+        ops.extend(probe(
+            "MATCH (a)-[:Railway]->(b) WHERE a.id = 2 AND b.id = 3 RETURN b LIMIT 1",
+        ));
+        let mut vm = Vm::new(&mut graph);
+        assert!(matches!(vm.execute(&ops), Err(VmError::NoReturnValue)));
+        graph.edges[2].partition = 1;
+        let mut vm = Vm::new(&mut graph);
+        assert_nodes(&vm.execute(&ops).unwrap(), &[3]);
+    }
+
+    #[test]
+    fn test_vm_result_serialized_size_matches_borsh() {
+        for result in [
+            VmResult::Nodes(vec![1, 2, 3]),
+            VmResult::Scalar(-7),
+            VmResult::None,
+        ] {
+            let mut buf = Vec::new();
+            result.serialize(&mut buf).unwrap();
+            assert_eq!(result.serialized_size(), buf.len());
+        }
+
+        let mut buf = Vec::new();
+        VmResult::Nodes(vec![1]).serialize(&mut buf).unwrap();
+        assert_eq!(&buf[..5], &[0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_vm_result_round_trips() {
+        for result in [
+            VmResult::Nodes(vec![NodeId::MAX, 0]),
+            VmResult::Scalar(i64::MIN),
+            VmResult::None,
+        ] {
+            let mut buf = Vec::new();
+            result.serialize(&mut buf).unwrap();
+            assert_eq!(VmResult::try_from_slice(&buf).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn test_check_program() {
+        let config = GraphConfig {
+            max_ops: 10,
+            ..GraphConfig::default()
+        };
+        let read = vec![Opcode::SetCurrentFromIds(vec![1]), Opcode::SaveResults];
+        assert!(check_program(&read, &config).is_ok());
+        assert!(!read.iter().any(Opcode::is_write));
+
+        assert!(matches!(
+            check_program(&[], &config),
+            Err(VmError::InvalidProgram)
+        ));
+        assert!(matches!(
+            check_program(
+                &read,
+                &GraphConfig {
+                    max_ops: 1,
+                    ..config
+                }
+            ),
+            Err(VmError::InvalidProgram)
+        ));
+
+        let late_partition = vec![Opcode::SetCurrentFromAllNodes, Opcode::SetPartition(1)];
+        assert!(matches!(
+            check_program(&late_partition, &config),
+            Err(VmError::InvalidProgram)
+        ));
+
+        let create = vec![Opcode::CreateNode {
+            label: "x".repeat(crate::graph::MAX_LABEL_LEN + 1),
+            data: Vec::new(),
+        }];
+        assert!(create[0].is_write());
+        assert!(matches!(
+            check_program(&create, &config),
+            Err(VmError::LabelTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_result_page() {
+        let result = VmResult::Nodes((0..10).collect());
+        // Room for exactly four ids
+        let max_bytes = 4 + 5 + 8 + 4 * NODE_ID_LEN;
+
+        let first = result.page(0, max_bytes, 7);
+        assert_eq!(first.ids, vec![0, 1, 2, 3]);
+        assert_eq!(first.next_cursor, Some(4));
+        assert_eq!(first.version, 7);
+
+        let mut buf = Vec::new();
+        first.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), max_bytes);
+
+        let last = result.page(8, max_bytes, 7);
+        assert_eq!(last.ids, vec![8, 9]);
+        assert_eq!(last.next_cursor, None);
+
+        assert!(result.page(42, max_bytes, 7).ids.is_empty());
+    }
+
+    #[test]
+    fn test_writes_record_time() {
+        let mut graph = small_graph();
+        let mut vm = Vm::new(&mut graph).at_time(1_000);
+        let ops = vec![Opcode::CreateNode {
+            label: "Post".to_string(),
+            data: Vec::new(),
+        }];
+        let new_id = vm.execute(&ops).unwrap().node_ids()[0];
+
+        let mut vm = Vm::new(&mut graph).at_time(2_000);
+        let ops = vec![Opcode::CreateEdge {
+            from: 1,
+            to: new_id,
+            label: "Posted".to_string(),
+        }];
+        vm.execute(&ops).unwrap();
+
+        let node = graph.get_node_by_id(new_id).unwrap();
+        assert_eq!((node.created_at, node.updated_at), (1_000, 1_000));
+        assert_eq!(graph.edges.last().unwrap().created_at, 2_000);
+    }
+
+    #[test]
+    fn test_filter_time() {
+        let mut graph = small_graph();
+        for (i, node) in graph.nodes.iter_mut().enumerate() {
+            node.created_at = i as i64 * 10;
+        }
+        let count = graph.nodes.len();
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::FilterTime {
+                field: TimeField::CreatedAt,
+                op: CmpOp::Ge,
+                value: 10,
+            },
+            Opcode::SaveResults,
+        ];
+        assert_eq!(vm.execute(&ops).unwrap().node_ids().len(), count - 1);
+
+        let mut vm = Vm::new(&mut graph);
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::FilterTime {
+                field: TimeField::UpdatedAt,
+                op: CmpOp::Eq,
+                value: 0,
+            },
+            Opcode::SaveResults,
+        ];
+        assert_eq!(vm.execute(&ops).unwrap().node_ids().len(), count);
+    }
+
+    #[test]
+    fn test_ttl_nodes_expire() {
+        let mut graph = small_graph();
+        let count = graph.nodes.len();
+        let mut vm = Vm::new(&mut graph).at_slot(100);
+        let ops = vec![
+            Opcode::SetTtl(50),
+            Opcode::CreateNode {
+                label: "Session".to_string(),
+                data: Vec::new(),
+            },
+        ];
+        let new_id = vm.execute(&ops).unwrap().node_ids()[0];
+        assert_eq!(graph.get_node_by_id(new_id).unwrap().expires_slot, 150);
+
+        let scan = vec![Opcode::SetCurrentFromAllNodes, Opcode::SaveResults];
+        let mut vm = Vm::new(&mut graph).at_slot(149);
+        assert_eq!(vm.execute(&scan).unwrap().node_ids().len(), count + 1);
+
+        // Once expired the node is skipped and cannot gain edges
+        let mut vm = Vm::new(&mut graph).at_slot(150);
+        assert_eq!(vm.execute(&scan).unwrap().node_ids().len(), count);
+        let mut vm = Vm::new(&mut graph).at_slot(150);
+        let ops = vec![Opcode::CreateEdge {
+            from: 1,
+            to: new_id,
+            label: "Owns".to_string(),
+        }];
+        assert!(vm.execute(&ops).is_err());
+    }
+
+    #[test]
+    fn test_filter_mint() {
+        let mut graph = small_graph();
+        let mint = Pubkey::new_unique();
+        graph.set_node_mint(2, mint).unwrap();
+        let mut vm = Vm::new(&mut graph);
+
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::FilterMint(mint),
+            Opcode::SaveResults,
+        ];
+        assert_eq!(vm.execute(&ops).unwrap().node_ids(), vec![2]);
+    }
+
+    #[test]
+    fn test_filter_attr() {
+        let mut graph = small_graph();
+        let data = set_attribute(&[], "name", AttrValue::Str("bob".to_string())).unwrap();
+        let data = set_attribute(&data, "age", AttrValue::Int(30)).unwrap();
+        graph.set_node_data(2, data, false).unwrap();
+        let data = set_attribute(&[], "name", AttrValue::Str("eve".to_string())).unwrap();
+        graph.set_node_data(3, data, false).unwrap();
+        // Raw bytes are not attributes
+        graph.set_node_data(4, vec![0xff], false).unwrap();
+
+        let filter = |key: &str, value: &str| {
+            vec![
+                Opcode::SetCurrentFromAllNodes,
+                Opcode::FilterAttr {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                },
+                Opcode::SaveResults,
+            ]
+        };
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&filter("name", "bob")).unwrap().node_ids(),
+            vec![2]
+        );
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&filter("age", "30")).unwrap().node_ids(),
+            vec![2]
+        );
+        let mut vm = Vm::new(&mut graph);
+        assert!(matches!(
+            vm.execute(&filter("name", "carol")),
+            Err(VmError::NoReturnValue)
+        ));
+
+        let long_key = filter(&"k".repeat(MAX_LABEL_LEN + 1), "x");
+        assert!(matches!(
+            check_program(&long_key, &GraphConfig::default()),
+            Err(VmError::LabelTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_filter_range() {
+        let mut graph = small_graph();
+        for (id, x, y) in [(1, 0, 0), (2, 10, 5), (3, 15, 25), (5, 20, -5)] {
+            let data = set_attribute(&[], "x", AttrValue::Int(x)).unwrap();
+            let data = set_attribute(&data, "y", AttrValue::Int(y)).unwrap();
+            graph.set_node_data(id, data, false).unwrap();
+        }
+        // A coordinate written as text is not in range
+        let data = set_attribute(&[], "x", AttrValue::Str("12".to_string())).unwrap();
+        graph.set_node_data(4, data, false).unwrap();
+
+        let range = |key: &str, min, max| Opcode::FilterRange {
+            key: key.to_string(),
+            min,
+            max,
+        };
+        let mut vm = Vm::new(&mut graph);
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            range("x", 10, 20),
+            Opcode::SaveResults,
+        ];
+        assert_eq!(vm.execute(&ops).unwrap().node_ids(), vec![2, 3, 5]);
+
+        // One range per axis bounds a box
+        let mut vm = Vm::new(&mut graph);
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            range("x", 10, 20),
+            range("y", -5, 10),
+            Opcode::SaveResults,
+        ];
+        assert_eq!(vm.execute(&ops).unwrap().node_ids(), vec![2, 5]);
+
+        let long_key = vec![range(&"k".repeat(MAX_LABEL_LEN + 1), 0, 1)];
+        assert!(matches!(
+            check_program(&long_key, &GraphConfig::default()),
+            Err(VmError::LabelTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_project() {
+        let mut graph = small_graph();
+        let data = set_attribute(&[], "name", AttrValue::Str("bob".to_string())).unwrap();
+        graph.set_node_data(4, data, false).unwrap();
+
+        let project = |projection: Projection| {
+            vec![
+                Opcode::SetCurrentFromIds(vec![4, 5]),
+                Opcode::SaveResults,
+                Opcode::Project(projection),
+            ]
+        };
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&project(Projection::Property("label".to_string())))
+                .unwrap(),
+            VmResult::Values(vec![
+                (4, Some("Town".to_string())),
+                (5, Some("Town".to_string())),
+            ])
+        );
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&project(Projection::Property("name".to_string())))
+                .unwrap(),
+            VmResult::Values(vec![(4, Some("bob".to_string())), (5, None)])
+        );
+        let mut vm = Vm::new(&mut graph);
+        let Ok(VmResult::Records(nodes)) = vm.execute(&project(Projection::Records)) else {
+            panic!("expected records");
+        };
+        assert_eq!(nodes, vec![graph.nodes[3].clone(), graph.nodes[4].clone()]);
+
+        let long_name = project(Projection::Property("k".repeat(MAX_LABEL_LEN + 1)));
+        assert!(matches!(
+            check_program(&long_name, &GraphConfig::default()),
+            Err(VmError::LabelTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_mutual_out() {
+        // The small graph with a second edge back from 3, which must not
+        // repeat the pair
+        let mut graph = GraphBuilder::new()
+            .node("City")
+            .node("City")
+            .node("City")
+            .node("Town")
+            .node("Town")
+            .edge(1, 2, "Railway")
+            .edge(1, 3, "Railway")
+            .edge(2, 3, "Railway")
+            .edge(2, 4, "Highway")
+            .edge(3, 1, "Railway")
+            .edge(3, 1, "Highway")
+            .build();
+        let railway = TraverseFilter {
+            where_node_labels: Vec::new(),
+            where_edge_labels: vec!["Railway".to_string()],
+            where_not_node_labels: Vec::new(),
+            where_not_edge_labels: Vec::new(),
+        };
+        let mutual = |filter: &TraverseFilter| {
+            vec![
+                Opcode::SetCurrentFromAllNodes,
+                Opcode::MutualOut(filter.clone()),
+                Opcode::SaveResults,
+                Opcode::Project(Projection::Pairs),
+            ]
+        };
+
+        // 1 and 3 follow each other by rail; 1 -> 2 -> 3 is one-way
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&mutual(&railway)).unwrap(),
+            VmResult::Pairs(vec![(1, 3), (3, 1)])
+        );
+
+        // Without an edge label any edge back counts
+        let any = TraverseFilter {
+            where_edge_labels: Vec::new(),
+            ..railway.clone()
+        };
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&mutual(&any)).unwrap(),
+            VmResult::Pairs(vec![(1, 3), (3, 1)])
+        );
+
+        let towns_only = TraverseFilter {
+            where_node_labels: vec!["Town".to_string()],
+            ..railway
+        };
+        let mut vm = Vm::new(&mut graph);
+        assert!(matches!(
+            vm.execute(&mutual(&towns_only)),
+            Err(VmError::NoReturnValue)
+        ));
+    }
+
+    #[test]
+    fn test_resolve_delegates() {
+        //     1 -> 2 -> 3, 4 on its own, 5 -> 6 -> 5, 7 -> deleted 8
+        let mut graph = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .edge(1, 2, "DELEGATES_TO")
+            .edge(2, 3, "DELEGATES_TO")
+            .edge(3, 4, "FOLLOWS")
+            .edge(5, 6, "DELEGATES_TO")
+            .edge(6, 5, "DELEGATES_TO")
+            .edge(7, 8, "DELEGATES_TO")
+            .build();
+        graph.set_node_deleted(8, true).unwrap();
+        let resolve = |ids: Vec<NodeId>, max_hops| {
+            vec![
+                Opcode::SetCurrentFromIds(ids),
+                Opcode::ResolveDelegates {
+                    label: "DELEGATES_TO".to_string(),
+                    max_hops,
+                },
+                Opcode::SaveResults,
+                Opcode::Project(Projection::Delegates),
+            ]
+        };
+
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&resolve(vec![1, 2, 4, 5, 7], 32)).unwrap(),
+            VmResult::Delegates(vec![
+                (1, Some(3)),
+                (2, Some(3)),
+                (4, Some(4)),
+                (5, None),
+                (7, Some(7)),
+            ])
+        );
+
+        // Two hops are enough for 1, one is not
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&resolve(vec![1], 2)).unwrap(),
+            VmResult::Delegates(vec![(1, Some(3))])
+        );
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&resolve(vec![1], 1)).unwrap(),
+            VmResult::Delegates(vec![(1, None)])
+        );
+
+        assert!(matches!(
+            check_program(
+                &resolve(vec![1], MAX_NODES as u32 + 1),
+                &GraphConfig::default()
+            ),
+            Err(VmError::InvalidProgram)
+        ));
+    }
+
+    #[test]
+    fn test_propagate_weight() {
+        //     1 -> 2 -> 3 <- 4, 5 -> 6 -> 5, 7 on its own
+        let mut graph = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .node("User")
+            .edge(1, 2, "DELEGATES_TO")
+            .edge(2, 3, "DELEGATES_TO")
+            .edge(4, 3, "DELEGATES_TO")
+            .edge(5, 6, "DELEGATES_TO")
+            .edge(6, 5, "DELEGATES_TO")
+            .build();
+        for (id, votes) in [(1, 10), (2, 20), (3, 5), (5, 7), (7, 2)] {
+            let data = set_attribute(&[], "votes", AttrValue::Int(votes)).unwrap();
+            graph.set_node_data(id, data, false).unwrap();
+        }
+        // Not a number, so it weighs nothing
+        let data = set_attribute(&[], "votes", AttrValue::Str("many".to_string())).unwrap();
+        graph.set_node_data(4, data, false).unwrap();
+
+        let propagate = |key: &str| {
+            vec![
+                Opcode::SetCurrentFromAllNodes,
+                Opcode::PropagateWeight {
+                    label: "DELEGATES_TO".to_string(),
+                    key: key.to_string(),
+                    max_hops: 32,
+                },
+                Opcode::SaveResults,
+                Opcode::Project(Projection::Weights),
+            ]
+        };
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&propagate("votes")).unwrap(),
+            VmResult::Weights(vec![(3, 35), (7, 2)])
+        );
+
+        let long_key = propagate(&"k".repeat(MAX_LABEL_LEN + 1));
+        assert!(matches!(
+            check_program(&long_key, &GraphConfig::default()),
+            Err(VmError::LabelTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        //     1 -> 2 -> 3 -> 1 -> 4 pledged, 4 <-> 5 backed, 5 -> 1 backed
+        let mut graph = GraphBuilder::new()
+            .node("Vault")
+            .node("Vault")
+            .node("Vault")
+            .node("Vault")
+            .node("Vault")
+            .edge(1, 4, "PLEDGES")
+            .edge(1, 2, "PLEDGES")
+            .edge(2, 3, "PLEDGES")
+            .edge(3, 1, "PLEDGES")
+            .edge(4, 5, "BACKS")
+            .edge(5, 1, "BACKS")
+            .build();
+        let find = |label: &str| vec![Opcode::FindCycle(label.to_string())];
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&find("PLEDGES")).unwrap(),
+            VmResult::Cycle(vec![1, 2, 3])
+        );
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(vm.execute(&find("BACKS")).unwrap(), VmResult::Cycle(vec![]));
+
+        assert!(graph.set_node_deleted(2, true).is_some());
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(
+            vm.execute(&find("PLEDGES")).unwrap(),
+            VmResult::Cycle(vec![])
+        );
+
+        // A write that closes a cycle is rolled back
+        let edges = graph.edges.len();
+        let close = vec![
+            Opcode::CreateEdge {
+                from: 1,
+                to: 4,
+                label: "BACKS".to_string(),
+            },
+            Opcode::RejectCycle("BACKS".to_string()),
+        ];
+        let mut vm = Vm::new(&mut graph);
+        assert!(matches!(vm.execute(&close), Err(VmError::CycleDetected)));
+        assert_eq!(graph.edges.len(), edges);
+
+        let open = vec![
+            Opcode::CreateEdge {
+                from: 5,
+                to: 3,
+                label: "BACKS".to_string(),
+            },
+            Opcode::RejectCycle("BACKS".to_string()),
+        ];
+        let mut vm = Vm::new(&mut graph);
+        assert_eq!(vm.execute(&open).unwrap(), VmResult::Nodes(vec![3]));
+        assert_eq!(graph.edges.len(), edges + 1);
+    }
+
+    #[test]
+    fn test_acyclic_label_rejects_closing_edge() {
+        let mut graph = GraphBuilder::new()
+            .node("Vault")
+            .node("Vault")
+            .node("Vault")
+            .edge(1, 2, "PLEDGES")
+            .edge(2, 3, "PLEDGES")
+            .build();
+        graph.acyclic_labels.push("PLEDGES".to_string());
+        let edges = graph.edges.len();
+        let create = |from, to| {
+            vec![Opcode::CreateEdge {
+                from,
+                to,
+                label: "PLEDGES".to_string(),
+            }]
+        };
+
+        assert!(matches!(
+            Vm::new(&mut graph).execute(&create(3, 1)),
+            Err(VmError::CycleDetected)
+        ));
+        assert_eq!(graph.edges.len(), edges);
+        Vm::new(&mut graph).execute(&create(1, 3)).unwrap();
+        assert_eq!(graph.edges.len(), edges + 1);
+    }
+
+    #[test]
+    fn test_sum_paths() {
+        //     1 -10-> 2 -20-> 3 -100-> 1, 1 -5-> 3, and 3 pays 4
+        let mut graph = GraphBuilder::new()
+            .node("User")
+            .node("User")
+            .node("Bank")
+            .node("User")
+            .edge(1, 2, "OWES")
+            .edge(2, 3, "OWES")
+            .edge(3, 1, "OWES")
+            .edge(1, 3, "OWES")
+            .edge(3, 4, "PAYS")
+            .build();
+        for (edge, amount) in graph.edges.iter_mut().zip([10, 20, 100, 5, 7]) {
+            edge.data = set_attribute(&[], "amount", AttrValue::Int(amount)).unwrap();
+        }
+
+        let sum = |start: NodeId, end_label: Option<&str>, min_hops, max_hops| {
+            vec![
+                Opcode::SetCurrentFromIds(vec![start]),
+                Opcode::SumPaths {
+                    filter: TraverseFilter {
+                        where_node_labels: end_label.into_iter().map(String::from).collect(),
+                        where_edge_labels: vec!["OWES".to_string()],
+                        where_not_node_labels: Vec::new(),
+                        where_not_edge_labels: Vec::new(),
+                    },
+                    key: "amount".to_string(),
+                    min_hops,
+                    max_hops,
+                },
+                Opcode::SaveResults,
+            ]
+        };
+        // 1-2, 1-2-3 and 1-3; 1-3-1 comes back to its start
+        for (ops, expected) in [
+            (sum(1, None, 1, 2), 45),
+            (sum(1, None, 1, 3), 45),
+            (sum(1, None, 2, 2), 30),
+            (sum(1, Some("Bank"), 1, 2), 35),
+            (sum(99, None, 1, 2), 0),
+        ] {
+            let mut vm = Vm::new(&mut graph);
+            assert_eq!(vm.execute(&ops).unwrap(), VmResult::Scalar(expected));
+        }
+
+        for bad in [
+            sum(1, None, 0, 2),
+            sum(1, None, 3, 2),
+            sum(1, None, 1, MAX_PATH_HOPS + 1),
+        ] {
+            assert!(matches!(
+                check_program(&bad, &GraphConfig::default()),
+                Err(VmError::InvalidProgram)
+            ));
+        }
+
+        graph.config.max_traversal_nodes = 2;
+        let mut vm = Vm::new(&mut graph);
+        assert!(matches!(
+            vm.execute(&sum(1, None, 1, 2)),
+            Err(VmError::TraversalBudgetExceeded)
+        ));
+
+        graph.config.max_traversal_nodes = GraphConfig::default().max_traversal_nodes;
+        graph.edges[3].data = set_attribute(&[], "amount", AttrValue::Int(i64::MAX)).unwrap();
+        let mut vm = Vm::new(&mut graph);
+        assert!(matches!(
+            vm.execute(&sum(1, None, 1, 2)),
+            Err(VmError::Overflow)
+        ));
+    }
+
+    #[test]
+    fn test_program_cost() {
+        let graph = small_graph();
+        let scan = vec![Opcode::SetCurrentFromAllNodes, Opcode::SaveResults];
+        let lookup = vec![Opcode::SetCurrentFromIds(vec![1]), Opcode::SaveResults];
+        assert!(program_cost(&scan, &graph) > program_cost(&lookup, &graph));
+
+        // A label filter narrows what later opcodes are charged for
+        let filter = create_filter("City", "Railway");
+        let traverse = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::TraverseOut(filter.clone()),
+            Opcode::TraverseOut(filter),
+        ];
+        let any = TraverseFilter {
+            where_node_labels: Vec::new(),
+            where_edge_labels: Vec::new(),
+            where_not_node_labels: Vec::new(),
+            where_not_edge_labels: Vec::new(),
+        };
+        let unfiltered = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::TraverseOut(any.clone()),
+            Opcode::TraverseOut(any),
+        ];
+        assert!(program_cost(&traverse, &graph) < program_cost(&unfiltered, &graph));
+
+        // The default threshold admits a full-length program of scans
+        let worst = vec![Opcode::SetCurrentFromAllNodes; crate::MAX_OPS];
+        assert!(program_cost(&worst, &graph) <= graph.config.max_query_cost as u64);
+    }
+
+    #[test]
+    fn test_estimate_program() {
+        let graph = small_graph();
+        let nodes = graph.nodes.len() as u64;
+        let ops = vec![
+            Opcode::SetCurrentFromAllNodes,
+            Opcode::TraverseOut(create_filter("City", "Railway")),
+            Opcode::SaveResults,
+        ];
+        let estimate = estimate_program(&ops, &graph);
+
+        assert_eq!(estimate.op_count, 3);
+        assert_eq!(estimate.cost, program_cost(&ops, &graph));
+        // The scan, the traversal's start nodes and everything it can reach
+        assert_eq!(estimate.max_visited_nodes, 3 * nodes);
+
+        let mut graph = graph;
+        let mut vm = Vm::new(&mut graph);
+        let found = vm.execute(&ops).unwrap().node_ids().len() as u64;
+        assert!(found <= estimate.max_visited_nodes);
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted_run() {
+        let roads = TraverseFilter {
+            where_node_labels: Vec::new(),
+            where_edge_labels: vec!["Railway".to_string(), "Highway".to_string()],
+            where_not_node_labels: Vec::new(),
+            where_not_edge_labels: Vec::new(),
+        };
+        let ops = vec![
+            Opcode::SetLimit(9),
+            Opcode::SetCurrentFromIds(vec![11, 7]),
+            Opcode::TraverseOut(roads),
+            Opcode::SaveResults,
+            Opcode::SetCurrentFromAllNodes,
+        ];
+        let mut graph = large_graph();
+        let expected = Vm::new(&mut graph).execute(&ops[..4]).unwrap();
+
+        for visits in [1, 3, 8] {
+            let mut checkpoint = VmCheckpoint::default();
+            let mut pauses = 0;
+            let result = loop {
+                // Each step starts a new VM, as a new transaction would, from
+                // a checkpoint that went through its account encoding
+                let mut step = Vm::new(&mut graph);
+                if let Some(result) = step.resume(&ops[..4], &mut checkpoint, visits).unwrap() {
+                    break result;
+                }
+                checkpoint =
+                    VmCheckpoint::try_from_slice(&checkpoint.try_to_vec().unwrap()).unwrap();
+                pauses += 1;
+            };
+            assert_eq!(result, expected);
+            assert!(pauses > 0);
+        }
+
+        let mut checkpoint = VmCheckpoint::default();
+        assert!(Vm::new(&mut graph)
+            .resume(&ops, &mut checkpoint, 1)
+            .unwrap()
+            .is_none());
+        assert!(checkpoint.pc < ops.len() as u32);
+    }
 }
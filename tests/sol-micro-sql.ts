@@ -2,6 +2,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { SolMicroSql } from "../target/types/sol_micro_sql";
 import { expect } from "chai";
+import { createHash } from "crypto";
 import { BN } from "@coral-xyz/anchor";
 
 describe("sol-micro-sql", () => {
@@ -10,11 +11,20 @@ describe("sol-micro-sql", () => {
 
   const program = anchor.workspace.solMicroSql as Program<SolMicroSql>;
   const authority = anchor.Wallet.local().payer;
+  const graphName = "default";
+  // The program's caps, as in `GraphSizing::default()`
+  const sizing = {
+    maxNodes: 1000,
+    maxEdges: 5000,
+    maxLabelLen: 64,
+    maxNodeDataLen: 1024,
+    maxEdgeDataLen: 256,
+  };
 
   // Helper function to get graph store PDA
   const getGraphStorePDA = async () => {
     const [graphStorePDA] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from("graph_store")],
+      [Buffer.from("graph_store"), Buffer.from(graphName), authority.publicKey.toBuffer()],
       program.programId
     );
     return graphStorePDA;
@@ -38,10 +48,11 @@ describe("sol-micro-sql", () => {
     if (!isInitialized) {
       try {
         const tx = await program.methods
-          .initializeGraph()
+          .initializeGraph(graphName, sizing)
           .accountsPartial({
             graphStore: graphStorePDA,
             authority: authority.publicKey,
+            payer: authority.publicKey,
             systemProgram: anchor.web3.SystemProgram.programId,
           })
           .signers([authority])
@@ -88,6 +99,32 @@ describe("sol-micro-sql", () => {
       expect(graphStore.authority.toString()).to.equal(
         authority.publicKey.toString()
       );
+      expect(graphStore.name).to.equal(graphName);
+    });
+
+    it("Initializes an independent graph under another name", async () => {
+      const [otherPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("graph_store"), Buffer.from("staging"), authority.publicKey.toBuffer()],
+        program.programId
+      );
+
+      try {
+        await program.account.graphStore.fetch(otherPDA);
+      } catch (err) {
+        await program.methods
+          .initializeGraph("staging", sizing)
+          .accountsPartial({
+            graphStore: otherPDA,
+            authority: authority.publicKey,
+            payer: authority.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .rpc();
+      }
+
+      const other = await program.account.graphStore.fetch(otherPDA);
+      expect(other.name).to.equal("staging");
+      expect(otherPDA.toString()).to.not.equal((await getGraphStorePDA()).toString());
     });
   });
 
@@ -97,7 +134,7 @@ describe("sol-micro-sql", () => {
 
       const query = "CREATE (n:Person)";
       const result = await program.methods
-        .executeQuery(query)
+        .executeQuery(query, null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -116,7 +153,7 @@ describe("sol-micro-sql", () => {
 
       const query = "CREATE (n:Person {0x1234})";
       const result = await program.methods
-        .executeQuery(query)
+        .executeQuery(query, null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -136,7 +173,7 @@ describe("sol-micro-sql", () => {
 
       // Create first node
       await program.methods
-        .executeQuery("CREATE (a:User)")
+        .executeQuery("CREATE (a:User)", null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -144,7 +181,7 @@ describe("sol-micro-sql", () => {
 
       // Create second node
       await program.methods
-        .executeQuery("CREATE (b:User)")
+        .executeQuery("CREATE (b:User)", null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -160,7 +197,7 @@ describe("sol-micro-sql", () => {
       // Create edge between them
       const query = `CREATE (${node1Id})-[:FOLLOWS]->(${node2Id})`;
       const result = await program.methods
-        .executeQuery(query)
+        .executeQuery(query, null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -181,7 +218,7 @@ describe("sol-micro-sql", () => {
 
       // First create a node
       await program.methods
-        .executeQuery("CREATE (n:City)")
+        .executeQuery("CREATE (n:City)", null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -190,7 +227,7 @@ describe("sol-micro-sql", () => {
       // Execute MATCH query
       const query = "MATCH (n:City) RETURN n.id LIMIT 10";
       const tx = await program.methods
-        .executeQuery(query)
+        .executeQuery(query, null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -205,6 +242,67 @@ describe("sol-micro-sql", () => {
       expect(graphStore.nodeCount.toNumber()).to.be.greaterThan(0);
     });
 
+    it("Rejects a query when the expected version is stale", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+      const staleVersion = before.version.subn(1);
+
+      try {
+        await program.methods
+          .executeQuery("CREATE (n:Person)", staleVersion, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        const errStr = err.toString();
+        expect(errStr.includes("VersionMismatch"), `Expected VersionMismatch error, got: ${errStr}`).to.be.true;
+      }
+
+      await program.methods
+        .executeQuery("CREATE (n:Person)", before.version, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      const after = await program.account.graphStore.fetch(graphStorePDA);
+      expect(after.version.toNumber()).to.equal(before.version.toNumber() + 1);
+    });
+
+    it("Runs a batch of queries atomically", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+
+      // The second query is invalid, so the first must not be applied either
+      try {
+        await program.methods
+          .batchExecute(["CREATE (a:Batch)", "CREATE (b:Batch"], null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.not.include("Should have thrown");
+      }
+
+      let graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.nodes.length).to.equal(before.nodes.length);
+
+      await program.methods
+        .batchExecute(["CREATE (a:Batch)", "CREATE (b:Batch)"], null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.nodes.length).to.equal(before.nodes.length + 2);
+    });
+
     it("Handles invalid query gracefully", async () => {
       const graphStorePDA = await getGraphStorePDA();
 
@@ -212,7 +310,7 @@ describe("sol-micro-sql", () => {
 
       try {
         await program.methods
-          .executeQuery(invalidQuery)
+          .executeQuery(invalidQuery, null, null)
           .accountsPartial({
             graphStore: graphStorePDA,
           })
@@ -237,7 +335,7 @@ describe("sol-micro-sql", () => {
 
       // Create a node first
       await program.methods
-        .executeQuery("CREATE (n:TestNode)")
+        .executeQuery("CREATE (n:TestNode)", null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -284,13 +382,2004 @@ describe("sol-micro-sql", () => {
     });
   });
 
+  describe("get_nodes_page", () => {
+    it("Lists nodes in bounded pages", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+
+      const page = await program.methods
+        .getNodesPage(0, 2)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .view();
+
+      expect(page.nodes.length).to.be.at.most(2);
+      if (graphStore.nodes.length > 2) {
+        expect(page.nextOffset).to.not.be.null;
+      }
+    });
+  });
+
+  describe("get_capacity", () => {
+    it("Reports remaining bytes and slots", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      const capacity = await program.methods
+        .getCapacity()
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .view();
+
+      expect(capacity.usedBytes.toNumber()).to.be.at.most(capacity.accountBytes.toNumber());
+      expect(capacity.freeNodeSlots.toNumber()).to.be.greaterThan(0);
+    });
+  });
+
+  describe("soft_delete_node", () => {
+    it("Hides a node and restores it with undelete_node", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      await program.methods
+        .executeQuery("CREATE (n:Ephemeral)", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const nodeId = graphStore.nonce.subn(1);
+
+      await program.methods
+        .softDeleteNode(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .getNodeInfo(nodeId)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        const errStr = err.toString();
+        expect(errStr.includes("NodeNotFound") || errStr.includes("Node not found")).to.be.true;
+      }
+
+      await program.methods
+        .undeleteNode(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      await program.methods
+        .getNodeInfo(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("transfer_authority", () => {
+    it("Moves authority only after the new owner accepts", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const newAuthority = anchor.web3.Keypair.generate();
+
+      await program.methods
+        .transferAuthority(newAuthority.publicKey)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      let graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.authority.toString()).to.equal(authority.publicKey.toString());
+      expect(graphStore.pendingAuthority.toString()).to.equal(newAuthority.publicKey.toString());
+
+      await program.methods
+        .acceptAuthority()
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          newAuthority: newAuthority.publicKey,
+        })
+        .signers([newAuthority])
+        .rpc();
+
+      graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.authority.toString()).to.equal(newAuthority.publicKey.toString());
+      expect(graphStore.pendingAuthority).to.be.null;
+
+      // Hand ownership back so the remaining tests keep working
+      await program.methods
+        .transferAuthority(authority.publicKey)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: newAuthority.publicKey,
+        })
+        .signers([newAuthority])
+        .rpc();
+      await program.methods
+        .acceptAuthority()
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          newAuthority: authority.publicKey,
+        })
+        .rpc();
+    });
+  });
+
+  describe("add_node", () => {
+    it("Inserts a node with raw data", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+
+      await program.methods
+        .addNode("Sensor", Buffer.from([1, 2, 3]), null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const node = graphStore.nodes.find((n) => n.id.eq(before.nonce));
+      expect(node.label).to.equal("Sensor");
+      expect(Array.from(node.data)).to.deep.equal([1, 2, 3]);
+    });
+
+    it("Inserts into the given partition", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+
+      await program.methods
+        .addNode("Zoned", Buffer.from([]), 4)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const node = graphStore.nodes.find((n) => n.id.eq(before.nonce));
+      expect(node.partition).to.equal(4);
+
+      // Edges within a partition need both endpoints in it
+      const outside = graphStore.nodes.find((n) => n.partition === 0 && !n.isDeleted);
+      try {
+        await program.methods
+          .addEdge(outside.id, node.id, "ZONED", Buffer.from([]), 4)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("NodeNotFound");
+      }
+    });
+  });
+
+  describe("add_edge", () => {
+    it("Links two existing nodes and rejects duplicates", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      for (const label of ["Left", "Right"]) {
+        await program.methods
+          .addNode(label, Buffer.from([]), null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+      }
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const to = graphStore.nonce.subn(1);
+      const from = graphStore.nonce.subn(2);
+
+      await program.methods
+        .addEdge(from, to, "LINKS", Buffer.from([7]), null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .addEdge(from, to, "LINKS", Buffer.from([]), null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("DuplicateEdge");
+      }
+    });
+  });
+
+  describe("delete_node", () => {
+    it("Removes a node for good", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      await program.methods
+        .addNode("Doomed", Buffer.from([]), null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      let graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const nodeId = graphStore.nonce.subn(1);
+
+      await program.methods
+        .deleteNode(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.nodes.some((n) => n.id.eq(nodeId))).to.be.false;
+    });
+  });
+
+  describe("update_node_data", () => {
+    it("Overwrites and appends node data", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      await program.methods
+        .addNode("Record", Buffer.from([1]), null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      let graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const nodeId = graphStore.nonce.subn(1);
+
+      await program.methods
+        .updateNodeData(nodeId, Buffer.from([2, 3]), false)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+      await program.methods
+        .updateNodeData(nodeId, Buffer.from([4]), true)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const node = graphStore.nodes.find((n) => n.id.eq(nodeId));
+      expect(Array.from(node.data)).to.deep.equal([2, 3, 4]);
+    });
+  });
+
+  describe("grant_role", () => {
+    it("Grants and revokes a role", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const writer = anchor.web3.Keypair.generate();
+
+      await program.methods
+        .grantRole(writer.publicKey, { writer: {} }, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      let graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const grant = graphStore.roles.find((g) => g.key.equals(writer.publicKey));
+      expect(grant.role).to.deep.equal({ writer: {} });
+
+      await program.methods
+        .revokeRole(writer.publicKey)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.roles).to.have.lengthOf(0);
+    });
+
+    it("Accepts a writer as co-signer and rejects unsigned writes", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const writer = anchor.web3.Keypair.generate();
+      const outsider = anchor.web3.Keypair.generate();
+
+      await program.methods
+        .grantRole(writer.publicKey, { writer: {} }, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      // The outsider signs as `authority`, the writer co-signs
+      await program.provider.connection.confirmTransaction(
+        await program.provider.connection.requestAirdrop(outsider.publicKey, 1_000_000_000)
+      );
+      await program.methods
+        .executeQuery("CREATE (n:CoSigned)", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: outsider.publicKey,
+        })
+        .remainingAccounts([{ pubkey: writer.publicKey, isSigner: true, isWritable: false }])
+        .signers([outsider, writer])
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQuery("CREATE (n:CoSigned)", null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: outsider.publicKey,
+          })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .revokeRole(writer.publicKey)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+    });
+
+    it("Confines a partitioned writer to its partition", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const tenant = anchor.web3.Keypair.generate();
+
+      await program.methods
+        .grantRole(tenant.publicKey, { writer: {} }, 7)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      // The tenant signs alone: the graph authority would be an admin
+      await program.provider.connection.confirmTransaction(
+        await program.provider.connection.requestAirdrop(tenant.publicKey, 1_000_000_000)
+      );
+      // Without a partition the tenant's writes land in its own
+      await program.methods
+        .executeQuery("CREATE (n:Tenant)", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: tenant.publicKey,
+        })
+        .signers([tenant])
+        .rpc();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const created = graphStore.nodes.filter((n) => n.label === "Tenant");
+      expect(created.map((n) => n.partition)).to.deep.equal([7]);
+
+      try {
+        await program.methods
+          .executeQuery("MATCH (n) RETURN n.id LIMIT 10", null, 8)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: tenant.publicKey,
+          })
+          .signers([tenant])
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .revokeRole(tenant.publicKey)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+    });
+  });
+
+  describe("set_multisig", () => {
+    it("Requires a quorum once a multisig is set", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const members = [anchor.web3.Keypair.generate(), anchor.web3.Keypair.generate()];
+
+      await program.methods
+        .setMultisig(
+          members.map((m) => m.publicKey),
+          2
+        )
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .setReadRestricted(false)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .setMultisig([], 0)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .remainingAccounts(
+          members.map((m) => ({ pubkey: m.publicKey, isSigner: true, isWritable: false }))
+        )
+        .signers(members)
+        .rpc();
+    });
+  });
+
+  describe("set_frozen", () => {
+    it("Blocks writes but not reads while frozen", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      await program.methods
+        .setFrozen(true)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQuery("CREATE (n:Frozen)", null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("GraphFrozen");
+      }
+
+      await program.methods
+        .executeQuery("MATCH (n) RETURN n.id LIMIT 10", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      await program.methods
+        .setFrozen(false)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+    });
+  });
+
+  describe("set_write_fee", () => {
+    it("Charges the writer and pays the treasury", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const treasury = anchor.web3.Keypair.generate().publicKey;
+      const fee = 1_000_000;
+
+      await program.methods
+        .setWriteFee(new BN(fee), treasury)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      await program.methods
+        .addNode("Paid", Buffer.from([]), null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+          treasury,
+        })
+        .rpc();
+
+      expect(await program.provider.connection.getBalance(treasury)).to.equal(fee);
+
+      await program.methods
+        .setWriteFee(new BN(0), treasury)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+    });
+  });
+
+  describe("execute_opcodes", () => {
+    it("Runs a precompiled program", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      await program.methods
+        .executeOpcodes(
+          [{ createNode: { label: "Compiled", data: Buffer.from([1]) } }, { saveResults: {} }],
+          null
+        )
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .executeOpcodes([{ setCurrentFromAllNodes: {} }, { setPartition: { 0: 1 } }], null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("QueryExecutionFailed");
+      }
+    });
+  });
+
+  describe("realloc_graph", () => {
+    it("Grows the account", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = await program.provider.connection.getAccountInfo(graphStorePDA);
+
+      await program.methods
+        .reallocGraph(1024)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+          payer: authority.publicKey,
+        })
+        .rpc();
+
+      const after = await program.provider.connection.getAccountInfo(graphStorePDA);
+      expect(after.data.length).to.equal(before.data.length + 1024);
+    });
+  });
+
+  describe("migrate_graph", () => {
+    it("Leaves a current-layout graph alone", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      try {
+        await program.methods
+          .migrateGraph()
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: authority.publicKey,
+            payer: authority.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("AlreadyMigrated");
+      }
+    });
+  });
+
+  describe("get_edge_info", () => {
+    it("Resolves an edge by index and by key", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      const byIndex = await program.methods
+        .getEdgeInfo({ index: { 0: 0 } })
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .view();
+
+      const byKey = await program.methods
+        .getEdgeInfo({ key: { from: byIndex.from, to: byIndex.to, label: byIndex.label } })
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .view();
+
+      expect(byKey.from.eq(byIndex.from)).to.be.true;
+      expect(byKey.label).to.equal(byIndex.label);
+    });
+  });
+
+  describe("execute_query_page", () => {
+    it("Serves a page of a read query and rejects writes", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      await program.methods
+        .executeQueryPage("MATCH (n) RETURN n.id LIMIT 1000", 0, null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQueryPage("CREATE (n:Paged)", 0, null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("QueryExecutionFailed");
+      }
+    });
+  });
+
+  describe("execute_query_with_params", () => {
+    it("Binds typed parameters to placeholders", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+
+      await program.methods
+        .executeQueryWithParams(
+          "CREATE (n:$1 { $2 })",
+          [{ str: { 0: "Param" } }, { bytes: { 0: Buffer.from([0xca, 0xfe]) } }],
+          null,
+          null
+        )
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const node = graphStore.nodes.find((n) => n.id.eq(before.nonce));
+      expect(node.label).to.equal("Param");
+      expect(Array.from(node.data)).to.deep.equal([0xca, 0xfe]);
+    });
+  });
+
+  describe("set_match_events", () => {
+    it("Emits a NodeMatched event per returned node once enabled", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      await program.methods
+        .setMatchEvents(true)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      const matched: any[] = [];
+      const listener = program.addEventListener("nodeMatched", (event) => {
+        matched.push(event);
+      });
+
+      await program.methods
+        .executeQuery("CREATE (n:Indexed)", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+      await program.removeEventListener(listener);
+
+      expect(matched.some((e) => e.label === "Indexed")).to.be.true;
+
+      await program.methods
+        .setMatchEvents(false)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("clone_graph", () => {
+    it("Copies a graph into a fresh one in chunks", async () => {
+      const [sourcePDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("graph_store"), Buffer.from("staging"), authority.publicKey.toBuffer()],
+        program.programId
+      );
+      const [forkPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("graph_store"), Buffer.from("fork"), authority.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeGraph("fork", sizing)
+        .accountsPartial({
+          graphStore: forkPDA,
+          authority: authority.publicKey,
+          payer: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      let cursor = 0;
+      for (;;) {
+        await program.methods
+          .cloneGraph(cursor, 8)
+          .accountsPartial({
+            source: sourcePDA,
+            destination: forkPDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        const fork = await program.account.graphStore.fetch(forkPDA);
+        const next = fork.nodes.length + fork.edges.length;
+        if (next === cursor) break;
+        cursor = next;
+      }
+
+      const source = await program.account.graphStore.fetch(sourcePDA);
+      const fork = await program.account.graphStore.fetch(forkPDA);
+      expect(fork.nodeCount.eq(source.nodeCount)).to.be.true;
+      expect(fork.edgeCount.eq(source.edgeCount)).to.be.true;
+
+      try {
+        await program.methods
+          .cloneGraph(0, 8)
+          .accountsPartial({
+            source: forkPDA,
+            destination: forkPDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("InvalidClone");
+      }
+    });
+  });
+
+  describe("set_token_gate", () => {
+    it("Rejects writes without a gate token account", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const mint = anchor.web3.Keypair.generate().publicKey;
+
+      await program.methods
+        .setTokenGate(mint, new anchor.BN(1))
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQuery("CREATE (n:Gated)", null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("TokenGateFailed");
+      }
+
+      await program.methods
+        .setTokenGate(null, new anchor.BN(0))
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("Separate rent payer", () => {
+    it("Lets a sponsor fund the account while the authority signs writes", async () => {
+      const sponsor = anchor.web3.Keypair.generate();
+      await program.provider.connection.confirmTransaction(
+        await program.provider.connection.requestAirdrop(sponsor.publicKey, 1_000_000_000)
+      );
+      const [sponsoredPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("graph_store"), Buffer.from("sponsored"), authority.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeGraph("sponsored", sizing)
+        .accountsPartial({
+          graphStore: sponsoredPDA,
+          authority: authority.publicKey,
+          payer: sponsor.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([sponsor])
+        .rpc();
+
+      const sponsorBalance = await program.provider.connection.getBalance(sponsor.publicKey);
+      expect(sponsorBalance).to.be.lessThan(1_000_000_000);
+
+      await program.methods
+        .executeQuery("CREATE (n:Sponsored)", null, null)
+        .accountsPartial({
+          graphStore: sponsoredPDA,
+          authority: authority.publicKey,
+          payer: sponsor.publicKey,
+        })
+        .signers([sponsor])
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(sponsoredPDA);
+      expect(graphStore.nodeCount.toNumber()).to.equal(1);
+    });
+  });
+
+  describe("rebuild_indices", () => {
+    it("Walks every derived index and keeps counters in sync", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+
+      let index: any = { counters: {} };
+      let steps = 0;
+      while (index) {
+        index = await program.methods
+          .rebuildIndices(index)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc()
+          .then(() => {
+            steps += 1;
+            return [{ adjacency: {} }, { nodeBloom: {} }, { edgeKeyIndex: {} }, null][steps - 1];
+          });
+      }
+      expect(steps).to.equal(4);
+
+      const after = await program.account.graphStore.fetch(graphStorePDA);
+      expect(after.nodeCount.toNumber()).to.equal(before.nodes.length);
+      expect(after.edgeCount.toNumber()).to.equal(before.edges.length);
+    });
+  });
+
+  describe("set_graph_config", () => {
+    it("Enforces adjustable limits and rejects values above the caps", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const { config } = await program.account.graphStore.fetch(graphStorePDA);
+
+      await program.methods
+        .setGraphConfig({ ...config, maxLabelLen: 4 })
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .addNode("TooLong", Buffer.from([]), null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("LabelTooLong");
+      }
+
+      try {
+        await program.methods
+          .setGraphConfig({ ...config, maxOps: 101 })
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("InvalidConfig");
+      }
+
+      await program.methods
+        .setGraphConfig(config)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+
+    it("Rejects queries above the cost threshold before running them", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const { config } = await program.account.graphStore.fetch(graphStorePDA);
+
+      await program.methods
+        .setGraphConfig({ ...config, maxQueryCost: 1 })
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQuery("MATCH (n) RETURN n.id LIMIT 10", null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("QueryTooExpensive");
+      }
+
+      await program.methods
+        .setGraphConfig(config)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("Typed query results", () => {
+    it("Decodes execute_query return data with the IDL VmResult type", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      const sim = await program.methods
+        .executeQuery("MATCH (n) RETURN n.id LIMIT 1000", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .simulate();
+
+      const prefix = `Program return: ${program.programId} `;
+      const line = sim.raw.find((l: string) => l.startsWith(prefix));
+      const result = program.coder.types.decode(
+        "VmResult",
+        Buffer.from(line.slice(prefix.length), "base64")
+      );
+      expect(result.nodes).to.not.be.undefined;
+      expect(result.nodes[0].length).to.be.greaterThan(0);
+    });
+  });
+
+  describe("Session keys", () => {
+    it("Lets a scoped session key write until it is revoked", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const sessionKey = anchor.web3.Keypair.generate();
+      await program.provider.connection.confirmTransaction(
+        await program.provider.connection.requestAirdrop(sessionKey.publicKey, 1_000_000_000)
+      );
+      const [sessionPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("session"), graphStorePDA.toBuffer(), sessionKey.publicKey.toBuffer()],
+        program.programId
+      );
+      const slot = await program.provider.connection.getSlot();
+
+      await program.methods
+        .createSession(sessionKey.publicKey, new anchor.BN(slot + 1000), ["Move"], 1)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          session: sessionPDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      await program.methods
+        .executeQuery("CREATE (n:Move)", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: sessionKey.publicKey,
+          session: sessionPDA,
+        })
+        .signers([sessionKey])
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQuery("CREATE (n:Move)", null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: sessionKey.publicKey,
+            session: sessionPDA,
+          })
+          .signers([sessionKey])
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("SessionInvalid");
+      }
+
+      await program.methods
+        .revokeSession()
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          session: sessionPDA,
+          authority: authority.publicKey,
+          granter: authority.publicKey,
+        })
+        .rpc();
+      expect(await program.provider.connection.getAccountInfo(sessionPDA)).to.be.null;
+    });
+  });
+
+  describe("Commit-reveal writes", () => {
+    it("Applies a write only when revealed after its commit", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const query = "CREATE (n:Claim)";
+      const salt = Buffer.alloc(32, 7);
+      const hash = createHash("sha256")
+        .update(query)
+        .update(salt)
+        .update(authority.publicKey.toBuffer())
+        .digest();
+      const [commitmentPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("commit"),
+          graphStorePDA.toBuffer(),
+          authority.publicKey.toBuffer(),
+          hash,
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .setCommitReveal(true)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQuery(query, null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("CommitRevealRequired");
+      }
+
+      await program.methods
+        .commitWrite([...hash])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          commitment: commitmentPDA,
+        })
+        .rpc();
+
+      // Wait for a later slot before revealing
+      const commitSlot = await program.provider.connection.getSlot();
+      while ((await program.provider.connection.getSlot()) <= commitSlot) {
+        await new Promise((resolve) => setTimeout(resolve, 200));
+      }
+
+      await program.methods
+        .revealWrite(query, [...salt], null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          commitment: commitmentPDA,
+        })
+        .rpc();
+      expect(await program.provider.connection.getAccountInfo(commitmentPDA)).to.be.null;
+
+      await program.methods
+        .setCommitReveal(false)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("execute_query_with_receipt", () => {
+    it("Returns a receipt bound to the graph version and query", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const query = "MATCH (n) RETURN n.id LIMIT 5";
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+
+      const sim = await program.methods
+        .executeQueryWithReceipt(query, null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .simulate();
+
+      const prefix = `Program return: ${program.programId} `;
+      const line = sim.raw.find((l: string) => l.startsWith(prefix));
+      const receipt = program.coder.types.decode(
+        "QueryReceipt",
+        Buffer.from(line.slice(prefix.length), "base64")
+      );
+      expect(receipt.version.eq(graphStore.version)).to.be.true;
+      expect(Buffer.from(receipt.queryHash)).to.deep.equal(
+        createHash("sha256").update(query).digest()
+      );
+      expect(sim.events.some((e) => e.name === "queryReceiptIssued")).to.be.true;
+    });
+  });
+
+  describe("garbage_collect", () => {
+    it("Reclaims soft-deleted nodes and reports each one", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+
+      await program.methods
+        .executeQuery("CREATE (n:Garbage)", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      const nodeId = before.nonce;
+      await program.methods
+        .softDeleteNode(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      const reclaimed: any[] = [];
+      const listener = program.addEventListener("nodeReclaimed", (event) => {
+        reclaimed.push(event);
+      });
+      for (let done = false; !done; ) {
+        await program.methods
+          .garbageCollect(200)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+        done = graphStore.compactionCursor === 0;
+      }
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+      await program.removeEventListener(listener);
+
+      expect(reclaimed.some((e) => e.nodeId.eq(nodeId))).to.be.true;
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.nodes.some((n) => n.id.eq(nodeId))).to.be.false;
+    });
+  });
+
+  describe("Graph metadata", () => {
+    it("Creates and updates the metadata account", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const [metadataPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("metadata"), graphStorePDA.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initGraphMetadata("Test graph", "Graph used by the test suite", "", ["test"])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          metadata: metadataPDA,
+        })
+        .rpc();
+
+      await program.methods
+        .updateGraphMetadata("Test graph", "Updated", "https://example.com/schema.json", [
+          "test",
+          "social",
+        ])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          metadata: metadataPDA,
+        })
+        .rpc();
+
+      const metadata = await program.account.graphMetadata.fetch(metadataPDA);
+      expect(metadata.graph.equals(graphStorePDA)).to.be.true;
+      expect(metadata.description).to.equal("Updated");
+      expect(metadata.tags).to.deep.equal(["test", "social"]);
+      expect(metadata.createdAt.toNumber()).to.be.greaterThan(0);
+    });
+  });
+
+  describe("User graphs", () => {
+    it("Creates a per-wallet graph that runs queries like a shared one", async () => {
+      const user = anchor.web3.Keypair.generate();
+      const [userGraphPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("user_graph"), user.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeUserGraph(sizing)
+        .accountsPartial({
+          graphStore: userGraphPDA,
+          user: user.publicKey,
+          payer: authority.publicKey,
+        })
+        .signers([user])
+        .rpc();
+
+      await program.methods
+        .executeQuery("CREATE (n:Private)", null, null)
+        .accountsPartial({
+          graphStore: userGraphPDA,
+          authority: user.publicKey,
+          payer: authority.publicKey,
+        })
+        .signers([user])
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(userGraphPDA);
+      expect(graphStore.authority.equals(user.publicKey)).to.be.true;
+      expect(graphStore.userGraph).to.be.true;
+      expect(graphStore.nodeCount.toNumber()).to.equal(1);
+    });
+  });
+
+  describe("delete_by_label", () => {
+    it("Removes every node with a label in batches", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      for (let i = 0; i < 2; i++) {
+        await program.methods
+          .executeQuery("CREATE (n:Retired)", null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+      }
+
+      let cursor: number | null = 0;
+      while (cursor !== null) {
+        const sim = await program.methods
+          .deleteByLabel("Retired", cursor, 100)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .simulate();
+        await program.methods
+          .deleteByLabel("Retired", cursor, 100)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+          })
+          .rpc();
+        const event = sim.events.find((e) => e.name === "labelDeleted");
+        cursor = event.data.done ? null : cursor + 100 - event.data.removedNodes.toNumber();
+      }
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.nodes.some((n) => n.label === "Retired")).to.be.false;
+    });
+  });
+
+  describe("Timestamps", () => {
+    it("Records write time and filters on it", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const before = Math.floor(Date.now() / 1000) - 60;
+      await program.methods
+        .executeQuery("CREATE (n:Post)", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const post = graphStore.nodes[graphStore.nodes.length - 1];
+      expect(post.createdAt.toNumber()).to.be.greaterThan(before);
+      expect(post.updatedAt.eq(post.createdAt)).to.be.true;
+
+      const sim = await program.methods
+        .executeQuery(
+          `MATCH (n:Post) WHERE n.created_at >= ${post.createdAt.toString()} RETURN n LIMIT 10`,
+          null,
+          null
+        )
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .simulate();
+      const prefix = `Program return: ${program.programId} `;
+      const line = sim.raw.find((l: string) => l.startsWith(prefix));
+      const result = program.coder.types.decode(
+        "VmResult",
+        Buffer.from(line.slice(prefix.length), "base64")
+      );
+      expect(result.nodes[0].some((id) => id.eq(post.id))).to.be.true;
+    });
+  });
+
+  describe("Proposals", () => {
+    it("Stages writes across transactions and commits them together", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const [proposalPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("proposal"), graphStorePDA.toBuffer(), authority.publicKey.toBuffer()],
+        program.programId
+      );
+      const before = await program.account.graphStore.fetch(graphStorePDA);
+
+      await program.methods
+        .openProposal()
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          proposal: proposalPDA,
+        })
+        .rpc();
+      for (let chunk = 0; chunk < 2; chunk++) {
+        await program.methods
+          .stageProposal([
+            { createNode: { label: "Imported", data: Buffer.from([chunk]) } },
+            { createNode: { label: "Imported", data: Buffer.from([chunk]) } },
+            { bind: { 0: chunk } },
+          ])
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            proposal: proposalPDA,
+          })
+          .rpc();
+      }
+      // Registers bound in earlier chunks link the nodes they created
+      await program.methods
+        .stageProposal([{ createEdgeBetween: { from: 0, to: 1, label: "Imported" } }])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          proposal: proposalPDA,
+        })
+        .rpc();
+
+      // Staged writes are invisible until the commit
+      let graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.nodeCount.eq(before.nodeCount)).to.be.true;
+
+      await program.methods
+        .commitProposal(null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          proposal: proposalPDA,
+        })
+        .rpc();
+
+      graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      expect(graphStore.nodeCount.toNumber()).to.equal(before.nodeCount.toNumber() + 4);
+      expect(graphStore.edgeCount.toNumber()).to.equal(before.edgeCount.toNumber() + 1);
+      expect(await program.provider.connection.getAccountInfo(proposalPDA)).to.be.null;
+    });
+  });
+
+  describe("estimate_query", () => {
+    it("Returns an estimate without running the query", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+
+      const sim = await program.methods
+        .estimateQuery("MATCH (n) RETURN n.id LIMIT 10", null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .simulate();
+
+      const prefix = `Program return: ${program.programId} `;
+      const line = sim.raw.find((l: string) => l.startsWith(prefix));
+      const estimate = program.coder.types.decode(
+        "QueryEstimate",
+        Buffer.from(line.slice(prefix.length), "base64")
+      );
+      expect(estimate.opCount).to.be.greaterThan(0);
+      expect(estimate.maxVisitedNodes.toNumber()).to.be.at.least(graphStore.nodes.length);
+    });
+  });
+
+  describe("explain_query", () => {
+    it("Returns the compiled opcodes", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+
+      const plan = await program.methods
+        .explainQuery("MATCH (n:User) RETURN n.id LIMIT 10", 2)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .view();
+
+      expect(plan.ops[0]).to.deep.equal({ setPartition: { 0: 2 } });
+      expect(plan.ops[1]).to.have.property("setCurrentFromAllNodes");
+      expect(plan.ops[2].traverseOut[0].whereNodeLabels).to.deep.equal(["User"]);
+      expect(plan.estimate.opCount).to.equal(plan.ops.length);
+    });
+  });
+
+  describe("Private graphs", () => {
+    it("Gates node lookups on a Reader signer", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const nodeId = graphStore.nodes[0].id;
+      const stranger = anchor.web3.Keypair.generate();
+
+      await program.methods
+        .setPrivate(true)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .getNodeInfo(nodeId)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            reader: stranger.publicKey,
+          })
+          .signers([stranger])
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .getNodeInfo(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          reader: authority.publicKey,
+        })
+        .rpc();
+
+      await program.methods
+        .setPrivate(false)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("Node ACLs", () => {
+    it("Hides restricted nodes from get_node_info for other readers", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const nodeId = graphStore.nodes[0].id;
+      const owner = anchor.web3.Keypair.generate();
+      const stranger = anchor.web3.Keypair.generate();
+
+      await program.methods
+        .setNodeAcl(nodeId, owner.publicKey, true, [])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .getNodeInfo(nodeId)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            reader: stranger.publicKey,
+          })
+          .signers([stranger])
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .getNodeInfo(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          reader: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .clearNodeAcl(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+
+    it("Leaves restricted nodes out of get_nodes_page", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const nodeId = graphStore.nodes[0].id;
+      const owner = anchor.web3.Keypair.generate();
+
+      await program.methods
+        .setNodeAcl(nodeId, owner.publicKey, true, [])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      const page = await program.methods
+        .getNodesPage(0, 1)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .view();
+      expect(page.nodes.map((n: any) => n.id.toString())).to.not.include(
+        nodeId.toString()
+      );
+
+      await program.methods
+        .clearNodeAcl(nodeId)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("Rate limiting", () => {
+    it("Rejects a writer past the configured rate", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const writer = anchor.web3.Keypair.generate();
+      await program.provider.connection.confirmTransaction(
+        await program.provider.connection.requestAirdrop(writer.publicKey, 1_000_000_000)
+      );
+      const [writerRatePDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("writer_rate"), graphStorePDA.toBuffer(), writer.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .grantRole(writer.publicKey, { writer: {} }, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      await program.methods
+        .setRateLimit(1, new anchor.BN(10_000))
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      await program.methods
+        .initWriterRate()
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          writerRate: writerRatePDA,
+          authority: writer.publicKey,
+        })
+        .signers([writer])
+        .rpc();
+
+      const write = () =>
+        program.methods
+          .executeQuery("CREATE (n:Spam)", null, null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: writer.publicKey,
+            writerRate: writerRatePDA,
+          })
+          .signers([writer])
+          .rpc();
+      await write();
+      try {
+        await write();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("RateLimited");
+      }
+
+      await program.methods
+        .setRateLimit(0, new anchor.BN(0))
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      await program.methods
+        .revokeRole(writer.publicKey)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("TTL", () => {
+    it("Sets an expiry slot on nodes created with TTL", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const slot = await program.provider.connection.getSlot();
+      await program.methods
+        .executeQuery("CREATE (n:Session) TTL 1000", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const session = graphStore.nodes[graphStore.nodes.length - 1];
+      expect(session.label).to.equal("Session");
+      expect(session.expiresSlot.toNumber()).to.be.greaterThanOrEqual(slot + 1000);
+
+      const sim = await program.methods
+        .executeQuery("MATCH (n:Session) RETURN n LIMIT 10", null, null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .simulate();
+      const prefix = `Program return: ${program.programId} `;
+      const line = sim.raw.find((l: string) => l.startsWith(prefix));
+      const result = program.coder.types.decode(
+        "VmResult",
+        Buffer.from(line.slice(prefix.length), "base64")
+      );
+      expect(result.nodes[0].some((id) => id.eq(session.id))).to.be.true;
+    });
+  });
+
+  describe("Audit log", () => {
+    it("Records writes and requires the log once enabled", async () => {
+      const [auditedPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("graph_store"), Buffer.from("audited"), authority.publicKey.toBuffer()],
+        program.programId
+      );
+      const [auditLogPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("audit_log"), auditedPDA.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeGraph("audited", sizing)
+        .accountsPartial({
+          graphStore: auditedPDA,
+          authority: authority.publicKey,
+          payer: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .initAuditLog()
+        .accountsPartial({
+          graphStore: auditedPDA,
+          auditLog: auditLogPDA,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .executeQuery("CREATE (n:Unlogged)", null, null)
+          .accountsPartial({
+            graphStore: auditedPDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("AuditLogRequired");
+      }
+
+      await program.methods
+        .executeQuery("CREATE (n:Logged)", null, null)
+        .accountsPartial({
+          graphStore: auditedPDA,
+          auditLog: auditLogPDA,
+        })
+        .rpc();
+      await program.methods
+        .addNode("Logged", Buffer.from([]), null)
+        .accountsPartial({
+          graphStore: auditedPDA,
+          auditLog: auditLogPDA,
+        })
+        .rpc();
+
+      const entries = await program.methods
+        .getAuditLog(0, 10)
+        .accountsPartial({
+          graphStore: auditedPDA,
+          auditLog: auditLogPDA,
+        })
+        .view();
+      expect(entries.length).to.equal(2);
+      expect(entries[0].op).to.deep.equal({ addNode: {} });
+      expect(entries[1].op).to.deep.equal({ query: {} });
+      expect(entries[0].signer.equals(authority.publicKey)).to.be.true;
+      expect(entries[0].version.gt(entries[1].version)).to.be.true;
+    });
+  });
+
+  describe("Time travel", () => {
+    it("Queries the graph as of an earlier slot", async () => {
+      const connection = program.provider.connection;
+      const [journaledPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("graph_store"), Buffer.from("journaled"), authority.publicKey.toBuffer()],
+        program.programId
+      );
+      const [journalPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("journal"), journaledPDA.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeGraph("journaled", sizing)
+        .accountsPartial({
+          graphStore: journaledPDA,
+          authority: authority.publicKey,
+          payer: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .initJournal()
+        .accountsPartial({
+          graphStore: journaledPDA,
+          journal: journalPDA,
+        })
+        .rpc();
+
+      const create = (label: string) =>
+        program.methods
+          .executeQuery(`CREATE (n:${label})`, null, null)
+          .accountsPartial({
+            graphStore: journaledPDA,
+            journal: journalPDA,
+          })
+          .rpc({ commitment: "confirmed" });
+      const first = await connection.getTransaction(await create("Early"), {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      while ((await connection.getSlot("confirmed")) <= first.slot) {
+        await new Promise((resolve) => setTimeout(resolve, 100));
+      }
+      await create("Late");
+
+      const asOf = (slot: number) =>
+        program.methods
+          .executeQueryAsOf("MATCH (n) RETURN n LIMIT 10", new anchor.BN(slot))
+          .accountsPartial({
+            graphStore: journaledPDA,
+            journal: journalPDA,
+          })
+          .view();
+      const graphStore = await program.account.graphStore.fetch(journaledPDA);
+      const [early, late] = graphStore.nodes.map((n) => n.id);
+
+      const then = await asOf(first.slot);
+      expect(then.nodes[0].some((id) => id.eq(early))).to.be.true;
+      expect(then.nodes[0].some((id) => id.eq(late))).to.be.false;
+
+      try {
+        await asOf(0);
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("HistoryUnavailable");
+      }
+    });
+  });
+
+  describe("Compressed nodes", () => {
+    const sha256 = (...parts: Buffer[]) =>
+      createHash("sha256").update(Buffer.concat(parts)).digest();
+    const leafHash = (index: number, label: string, data: Buffer) =>
+      sha256(
+        Buffer.from([0]),
+        new BN(index).toArrayLike(Buffer, "le", 8),
+        new BN(Buffer.byteLength(label)).toArrayLike(Buffer, "le", 4),
+        Buffer.from(label),
+        data
+      );
+
+    it("Appends payloads under a Merkle root and verifies them", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const [compressedPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("compressed_nodes"), graphStorePDA.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initCompressedNodes()
+        .accountsPartial({
+          graphStore: graphStorePDA,
+          compressedNodes: compressedPDA,
+        })
+        .rpc();
+      for (const data of [Buffer.from([1]), Buffer.from([2])]) {
+        await program.methods
+          .appendCompressedNode("Like", data)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            compressedNodes: compressedPDA,
+          })
+          .rpc();
+      }
+
+      // Proof for leaf 0: leaf 1, then the roots of empty subtrees
+      const proof = [leafHash(1, "Like", Buffer.from([2]))];
+      let empty = Buffer.alloc(32);
+      for (let level = 1; level < 20; level++) {
+        empty = sha256(Buffer.from([1]), empty, empty);
+        proof.push(empty);
+      }
+      const verify = (data: Buffer) =>
+        program.methods
+          .verifyCompressedNode(
+            new BN(0),
+            "Like",
+            data,
+            proof.map((p) => Array.from(p))
+          )
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            compressedNodes: compressedPDA,
+          })
+          .rpc();
+
+      await verify(Buffer.from([1]));
+      try {
+        await verify(Buffer.from([9]));
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("InvalidProof");
+      }
+
+      const store = await program.account.compressedNodes.fetch(compressedPDA);
+      expect(store.leafCount.toNumber()).to.equal(2);
+    });
+  });
+
+  describe("Acyclic labels", () => {
+    it("Rejects add_edge closing a cycle of an acyclic label", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      for (const label of ["Task", "Task"]) {
+        await program.methods
+          .addNode(label, Buffer.from([]), null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+      }
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const to = graphStore.nonce.subn(1);
+      const from = graphStore.nonce.subn(2);
+      const addEdge = (a: BN, b: BN) =>
+        program.methods
+          .addEdge(a, b, "DEPENDS_ON", Buffer.from([]), null)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: authority.publicKey,
+          })
+          .rpc();
+
+      await program.methods
+        .setAcyclicLabels(["DEPENDS_ON"])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      await addEdge(from, to);
+      try {
+        await addEdge(to, from);
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("CycleDetected");
+      }
+
+      await program.methods
+        .setAcyclicLabels([])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("Oracle attributes", () => {
+    it("Lets the configured oracle push allowed attributes", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const oracle = anchor.web3.Keypair.generate();
+      await program.provider.connection.confirmTransaction(
+        await program.provider.connection.requestAirdrop(oracle.publicKey, 1_000_000_000)
+      );
+
+      await program.methods
+        .setOracle(oracle.publicKey, ["price"])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      await program.methods
+        .addNode("Asset", Buffer.from([]), null)
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+      let graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const asset = graphStore.nodes[graphStore.nodes.length - 1].id;
+
+      const push = (key: string) =>
+        program.methods
+          .pushAttributes([{ nodeId: asset, key, value: new BN(4200) }])
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            authority: oracle.publicKey,
+          })
+          .signers([oracle])
+          .rpc();
+      await push("price");
+      try {
+        await push("owner");
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const node = graphStore.nodes.find((n) => n.id.eq(asset));
+      // One attribute: count, key "price", Int tag, 4200
+      expect(node.data.length).to.equal(4 + 4 + 5 + 1 + 8);
+      expect(Buffer.from(node.data).readBigInt64LE(14)).to.equal(BigInt(4200));
+
+      await program.methods
+        .setOracle(anchor.web3.PublicKey.default, [])
+        .accountsPartial({
+          graphStore: graphStorePDA,
+        })
+        .rpc();
+    });
+  });
+
+  describe("NFT provenance", () => {
+    it("Only binds nodes to real mint accounts", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const node = graphStore.nodes.find((n) => !n.isDeleted);
+
+      try {
+        await program.methods
+          .bindMint(node.id)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            mint: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("AccountOwnedByWrongProgram");
+      }
+    });
+  });
+
+  describe("Account attributes", () => {
+    it("Checks the referenced account's owner", async () => {
+      const graphStorePDA = await getGraphStorePDA();
+      const graphStore = await program.account.graphStore.fetch(graphStorePDA);
+      const node = graphStore.nodes.find((n) => !n.isDeleted);
+
+      try {
+        await program.methods
+          .setAccountAttribute(node.id, "vault", anchor.web3.SystemProgram.programId)
+          .accountsPartial({
+            graphStore: graphStorePDA,
+            account: graphStorePDA,
+          })
+          .rpc();
+        expect.fail("Should have thrown an error");
+      } catch (err: any) {
+        expect(err.toString()).to.include("AccountOwnerMismatch");
+      }
+    });
+  });
+
   describe("Complex queries", () => {
     it("Creates multiple nodes and edges in sequence", async () => {
       const graphStorePDA = await getGraphStorePDA();
 
       // Create first node
       await program.methods
-        .executeQuery("CREATE (a:User {0x0102})")
+        .executeQuery("CREATE (a:User {0x0102})", null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -298,7 +2387,7 @@ describe("sol-micro-sql", () => {
 
       // Create second node
       await program.methods
-        .executeQuery("CREATE (b:User {0x0304})")
+        .executeQuery("CREATE (b:User {0x0304})", null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -311,7 +2400,7 @@ describe("sol-micro-sql", () => {
 
       // Create edge
       await program.methods
-        .executeQuery(`CREATE (${node1Id})-[:KNOWS]->(${node2Id})`)
+        .executeQuery(`CREATE (${node1Id})-[:KNOWS]->(${node2Id})`, null, null)
         .accountsPartial({
           graphStore: graphStorePDA,
         })
@@ -0,0 +1,443 @@
+//! Mirrors of the events the program emits. Anchor only lists events in
+//! the IDL when they are declared in the program crate, so they are
+//! repeated here; the program's tests check both copies agree.
+
+use crate::graph::NodeId;
+use crate::prelude::{borsh, AnchorDeserialize, AnchorSerialize, Pubkey};
+use sha2::{Digest, Sha256};
+
+/// Implemented by every event the program emits.
+pub trait ProgramEvent: AnchorDeserialize {
+    /// Struct name, from which Anchor derives the discriminator.
+    const NAME: &'static str;
+
+    /// First 8 bytes of every `Program data:` log entry for this event.
+    fn discriminator() -> [u8; 8] {
+        let hash = Sha256::digest(format!("event:{}", Self::NAME));
+        let mut discriminator = [0u8; 8];
+        discriminator.copy_from_slice(&hash[..8]);
+        discriminator
+    }
+
+    /// Decodes a `Program data:` entry, or `None` if it is another event.
+    fn decode(data: &[u8]) -> Option<Self> {
+        let payload = data.strip_prefix(&Self::discriminator())?;
+        Self::try_from_slice(payload).ok()
+    }
+}
+
+macro_rules! program_events {
+    ($($event:ident),* $(,)?) => {
+        $(
+            impl ProgramEvent for $event {
+                const NAME: &'static str = stringify!($event);
+            }
+        )*
+    };
+}
+
+program_events!(
+    NodeAdded,
+    EdgeAdded,
+    NodeMatched,
+    NodeDeleted,
+    NodeRestored,
+    NodeProtectionChanged,
+    LabelDeleted,
+    NodeRemoved,
+    CompressedNodeAppended,
+    CompressedNodeUpdated,
+    MintBound,
+    AccountAttributeSet,
+    AttributeUpdated,
+    OracleChanged,
+    AcyclicLabelsChanged,
+    NodeDataUpdated,
+    RoleGranted,
+    RoleRevoked,
+    MultisigChanged,
+    GraphFrozenChanged,
+    GraphConfigChanged,
+    TokenGateChanged,
+    WriteFeeChanged,
+    GraphResized,
+    GraphMigrated,
+    NodeReclaimed,
+    EdgeReclaimed,
+    GraphCompacted,
+    IndexRebuilt,
+    GraphCloned,
+    AuthorityTransferProposed,
+    AuthorityTransferred,
+    GraphMetadataChanged,
+    QueryReceiptIssued,
+    WriteCommitted,
+    WriteRevealed,
+    ProposalStaged,
+    ProposalCommitted,
+    NodeAclChanged,
+    RateLimitChanged,
+    SessionCreated,
+    SessionRevoked,
+    GraphClosed,
+);
+
+/// Access levels, ordered so that each role includes the ones below it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Role {
+    /// May run read queries on a graph with restricted reads.
+    Reader,
+    /// May mutate nodes and edges.
+    Writer,
+    /// May manage roles, config and destructive operations.
+    Admin,
+}
+
+/// Derived state that `rebuild_index` recomputes, in rebuild order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IndexKind {
+    /// `node_count` and `edge_count`.
+    Counters,
+    /// `edge_offsets` and `adjacency`.
+    Adjacency,
+    NodeBloom,
+    EdgeKeyIndex,
+}
+
+/// Per-graph limits. Admins may tune them up to the compile-time caps.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphConfig {
+    pub max_query_len: u32,
+    pub max_ops: u32,
+    pub max_label_len: u32,
+    pub max_node_data_len: u32,
+    pub max_edge_data_len: u32,
+    /// Most nodes one `TraverseOut` may return before the query fails.
+    pub max_traversal_nodes: u32,
+    /// Highest `program_cost` a query may have; costlier ones are rejected
+    /// before they run.
+    pub max_query_cost: u32,
+    /// Most nodes one level of a `TraverseOut` may hold. A wider level
+    /// makes the traversal continue by iterative deepening, which keeps
+    /// only the current path instead of the whole level.
+    pub max_frontier: u32,
+    /// Most nodes and edges the graph may hold, deleted ones included.
+    pub max_nodes: u32,
+    pub max_edges: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeAdded {
+    pub node_id: NodeId,
+    pub node_count: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdgeAdded {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub edge_count: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeMatched {
+    pub node_id: NodeId,
+    pub label: String,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeDeleted {
+    pub node_id: NodeId,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeRestored {
+    pub node_id: NodeId,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeProtectionChanged {
+    pub node_id: NodeId,
+    pub protected: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LabelDeleted {
+    pub label: String,
+    pub removed_nodes: u64,
+    pub removed_edges: u64,
+    pub done: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeRemoved {
+    pub node_id: NodeId,
+    pub removed_edges: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedNodeAppended {
+    pub index: u64,
+    pub label: String,
+    pub data: Vec<u8>,
+    pub root: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedNodeUpdated {
+    pub index: u64,
+    pub label: String,
+    pub data: Vec<u8>,
+    pub root: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MintBound {
+    pub node_id: NodeId,
+    pub mint: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountAttributeSet {
+    pub node_id: NodeId,
+    pub key: String,
+    pub address: Pubkey,
+    pub owner: Option<Pubkey>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttributeUpdated {
+    pub node_id: NodeId,
+    pub key: String,
+    pub value: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OracleChanged {
+    pub oracle: Pubkey,
+    pub keys: Vec<String>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AcyclicLabelsChanged {
+    pub labels: Vec<String>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeDataUpdated {
+    pub node_id: NodeId,
+    pub data_len: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoleGranted {
+    pub key: Pubkey,
+    pub role: Role,
+    pub partition: Option<u32>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoleRevoked {
+    pub key: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultisigChanged {
+    pub signers: u8,
+    pub threshold: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphFrozenChanged {
+    pub frozen: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphConfigChanged {
+    pub config: GraphConfig,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenGateChanged {
+    pub mint: Pubkey,
+    pub min_amount: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteFeeChanged {
+    pub lamports: u64,
+    pub treasury: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphResized {
+    pub account_bytes: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphMigrated {
+    pub from_layout: u8,
+    pub to_layout: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeReclaimed {
+    pub node_id: NodeId,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdgeReclaimed {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub label: String,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphCompacted {
+    pub removed_nodes: u64,
+    pub removed_edges: u64,
+    pub cursor: u32,
+    pub done: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexRebuilt {
+    pub index: IndexKind,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphCloned {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub cursor: u32,
+    pub done: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthorityTransferProposed {
+    pub current_authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthorityTransferred {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphMetadataChanged {
+    pub graph: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryReceiptIssued {
+    pub version: u64,
+    pub query_hash: [u8; 32],
+    pub receipt_hash: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteCommitted {
+    pub committer: Pubkey,
+    pub hash: [u8; 32],
+    pub slot: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteRevealed {
+    pub committer: Pubkey,
+    pub hash: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProposalStaged {
+    pub proposer: Pubkey,
+    pub staged_ops: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProposalCommitted {
+    pub proposer: Pubkey,
+    pub applied_ops: u32,
+    pub version: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeAclChanged {
+    pub node_id: NodeId,
+    pub restricted: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RateLimitChanged {
+    pub max_writes_per_window: u32,
+    pub rate_window_slots: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionCreated {
+    pub key: Pubkey,
+    pub expires_slot: u64,
+    pub max_write_ops: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionRevoked {
+    pub key: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphClosed {
+    pub recipient: Pubkey,
+    pub node_count: u64,
+    pub edge_count: u64,
+    /// Child accounts closed along with the graph.
+    pub children: u32,
+}
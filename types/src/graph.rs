@@ -0,0 +1,225 @@
+use crate::prelude::{borsh, AnchorDeserialize, AnchorSerialize, Pubkey};
+pub use sol_micro_sql_lang::ops::NODE_ID_LEN;
+use std::fmt;
+use std::ops::Deref;
+
+// Repeats `sol_micro_sql_lang::ops::NodeId` so the IDL build can resolve it
+#[cfg(not(feature = "wide-ids"))]
+pub type NodeId = u64;
+#[cfg(feature = "wide-ids")]
+pub type NodeId = u128;
+pub type PartitionId = u32;
+
+/// How node and edge labels are stored: a Borsh string, or with the
+/// `fixed-labels` feature a `FixedLabel`. The choice fixes the account
+/// layout, so a program is built with one or the other for its lifetime.
+#[cfg(not(feature = "fixed-labels"))]
+pub type Label = String;
+#[cfg(feature = "fixed-labels")]
+pub type Label = FixedLabel;
+
+/// Bytes of a `FixedLabel`, and the longest label it holds.
+pub const FIXED_LABEL_LEN: usize = 16;
+
+/// A label of up to `FIXED_LABEL_LEN` bytes stored inline, zero-padded.
+/// It takes 16 bytes instead of a string's 4-byte length prefix plus
+/// contents, and gives nodes and edges a fixed-size label field.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct FixedLabel([u8; FIXED_LABEL_LEN]);
+
+impl FixedLabel {
+    /// `label` as a fixed label, or `None` if it is longer than
+    /// `FIXED_LABEL_LEN` bytes or contains a NUL byte.
+    pub fn new(label: &str) -> Option<Self> {
+        if label.len() > FIXED_LABEL_LEN || label.contains('\0') {
+            return None;
+        }
+        let mut bytes = [0; FIXED_LABEL_LEN];
+        bytes[..label.len()].copy_from_slice(label.as_bytes());
+        Some(FixedLabel(bytes))
+    }
+
+    pub fn as_str(&self) -> &str {
+        let len = self
+            .0
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(FIXED_LABEL_LEN);
+        std::str::from_utf8(&self.0[..len]).unwrap_or_default()
+    }
+}
+
+/// Labels reaching storage have been checked against `max_label_len`,
+/// which cannot exceed `FIXED_LABEL_LEN` with this layout.
+impl From<&str> for FixedLabel {
+    fn from(label: &str) -> Self {
+        FixedLabel::new(label).expect("label longer than FIXED_LABEL_LEN")
+    }
+}
+
+impl From<String> for FixedLabel {
+    fn from(label: String) -> Self {
+        label.as_str().into()
+    }
+}
+
+impl From<FixedLabel> for String {
+    fn from(label: FixedLabel) -> Self {
+        label.as_str().to_string()
+    }
+}
+
+impl Deref for FixedLabel {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for FixedLabel {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for FixedLabel {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for FixedLabel {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl fmt::Display for FixedLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for FixedLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for FixedLabel {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FixedLabel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label = <String as serde::Deserialize>::deserialize(deserializer)?;
+        FixedLabel::new(&label).ok_or_else(|| serde::de::Error::custom("label too long"))
+    }
+}
+
+/// `label` in its stored form. Callers check it against the graph's
+/// `max_label_len` first. Given a `&str`, a fixed label is built without
+/// allocating.
+pub fn to_label(label: impl Into<Label>) -> Label {
+    label.into()
+}
+
+/// Borsh-encoded size of `label`.
+pub fn label_size(label: &Label) -> usize {
+    if cfg!(feature = "fixed-labels") {
+        FIXED_LABEL_LEN
+    } else {
+        4 + label.len()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    pub id: NodeId,
+    pub label: Label,
+    pub data: Vec<u8>,
+    pub is_deleted: bool,
+    /// Protected nodes cannot be deleted or garbage collected.
+    pub is_protected: bool,
+    pub partition: PartitionId,
+    /// Unix timestamp of the write that created this node.
+    pub created_at: i64,
+    /// Unix timestamp of the most recent write to this node.
+    pub updated_at: i64,
+    /// First slot at which the node counts as gone; 0 never expires.
+    pub expires_slot: u64,
+    /// NFT mint the node is bound to, for provenance graphs.
+    pub mint: Option<Pubkey>,
+}
+
+impl Node {
+    /// Borsh-encoded size of this node.
+    pub fn serialized_size(&self) -> usize {
+        NODE_ID_LEN
+            + label_size(&self.label)
+            + (4 + self.data.len())
+            + 1
+            + 1
+            + 4
+            + 8
+            + 8
+            + 8
+            + (1 + self.mint.map_or(0, |_| 32))
+    }
+
+    /// Whether the node's TTL has run out by `slot`.
+    pub fn is_expired(&self, slot: u64) -> bool {
+        self.expires_slot != 0 && slot >= self.expires_slot
+    }
+
+    /// Decodes a Borsh-encoded node from the front of `buf` like
+    /// `deserialize`, but steps over its data instead of copying it, so
+    /// structure-only reads don't pay for large payloads. `data` is empty.
+    pub fn deserialize_without_data(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let id = NodeId::deserialize(buf)?;
+        let label = Label::deserialize(buf)?;
+        let data_len = u32::deserialize(buf)? as usize;
+        *buf = buf
+            .get(data_len..)
+            .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+        Ok(Node {
+            id,
+            label,
+            data: Vec::new(),
+            is_deleted: bool::deserialize(buf)?,
+            is_protected: bool::deserialize(buf)?,
+            partition: PartitionId::deserialize(buf)?,
+            created_at: i64::deserialize(buf)?,
+            updated_at: i64::deserialize(buf)?,
+            expires_slot: u64::deserialize(buf)?,
+            mint: Option::<Pubkey>::deserialize(buf)?,
+        })
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub label: Label,
+    pub partition: PartitionId,
+    pub data: Vec<u8>,
+    /// Unix timestamp of the write that created this edge.
+    pub created_at: i64,
+}
+
+impl Edge {
+    /// Borsh-encoded size of this edge.
+    pub fn serialized_size(&self) -> usize {
+        NODE_ID_LEN + NODE_ID_LEN + label_size(&self.label) + 4 + (4 + self.data.len()) + 8
+    }
+}
@@ -0,0 +1,130 @@
+//! Borsh types shared by the sol-micro-sql program and its clients: query
+//! results, stored nodes and edges, opcodes and events. Indexers and
+//! clients decode return data, account contents and logs with these
+//! instead of copying structs.
+//!
+//! With the `serde` feature every type also derives `Serialize` and
+//! `Deserialize`. The Borsh layouts are the program's wire format and only
+//! change together with it.
+
+pub mod events;
+pub mod graph;
+pub mod result;
+
+pub use graph::{Edge, FixedLabel, Label, Node, NodeId, PartitionId};
+pub use result::{QueryEstimate, QueryPage, VmResult};
+pub use sol_micro_sql_lang::ops;
+
+/// Serialization derives: Anchor's inside the program, whose IDL lists
+/// these types, and plain Borsh everywhere else.
+mod prelude {
+    #[cfg(feature = "anchor")]
+    pub use anchor_lang::prelude::{borsh, AnchorDeserialize, AnchorSerialize, Pubkey};
+    #[cfg(not(feature = "anchor"))]
+    pub use borsh::{
+        self, BorshDeserialize as AnchorDeserialize, BorshSerialize as AnchorSerialize,
+    };
+    #[cfg(not(feature = "anchor"))]
+    pub use solana_pubkey::Pubkey;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{NodeAdded, ProgramEvent};
+    use crate::prelude::{AnchorDeserialize, AnchorSerialize};
+
+    #[test]
+    fn test_vm_result_layout() {
+        let mut data = Vec::new();
+        VmResult::Nodes(vec![7]).serialize(&mut data).unwrap();
+        let mut expected = vec![0, 1, 0, 0, 0];
+        expected.extend_from_slice(&NodeId::to_le_bytes(7));
+        assert_eq!(data, expected);
+        assert_eq!(data.len(), VmResult::Nodes(vec![7]).serialized_size());
+
+        let node = graph::Node {
+            id: 3,
+            label: graph::to_label("User"),
+            data: vec![1, 2],
+            is_deleted: false,
+            is_protected: false,
+            partition: 0,
+            created_at: 1,
+            updated_at: 2,
+            expires_slot: 0,
+            mint: None,
+        };
+        for result in [
+            VmResult::Values(vec![(1, Some("bob".to_string())), (2, None)]),
+            VmResult::Records(vec![node]),
+            VmResult::Pairs(vec![(1, 2), (2, 1)]),
+            VmResult::Delegates(vec![(1, Some(3)), (2, None)]),
+            VmResult::Weights(vec![(3, 150), (4, -2)]),
+            VmResult::Cycle(vec![2, 5, 3]),
+        ] {
+            let mut data = Vec::new();
+            result.serialize(&mut data).unwrap();
+            assert_eq!(data.len(), result.serialized_size());
+            assert_eq!(VmResult::try_from_slice(&data).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn test_node_decodes_without_data() {
+        let node = graph::Node {
+            id: 9,
+            label: graph::to_label("User"),
+            data: vec![0xab; 300],
+            is_deleted: false,
+            is_protected: true,
+            partition: 2,
+            created_at: 10,
+            updated_at: 11,
+            expires_slot: 12,
+            mint: Some(prelude::Pubkey::new_from_array([3; 32])),
+        };
+        let mut data = Vec::new();
+        node.serialize(&mut data).unwrap();
+        data.push(0xee);
+
+        let mut buf = &data[..];
+        let decoded = graph::Node::deserialize_without_data(&mut buf).unwrap();
+        assert_eq!(buf, [0xee]);
+        assert!(decoded.data.is_empty());
+        assert_eq!(decoded.serialized_size(), node.serialized_size() - 300);
+        assert_eq!((decoded.id, decoded.is_protected), (9, true));
+        assert_eq!((decoded.expires_slot, decoded.mint), (12, node.mint));
+
+        let mut short = &data[..100];
+        assert!(graph::Node::deserialize_without_data(&mut short).is_err());
+    }
+
+    #[test]
+    fn test_fixed_label_layout() {
+        let label = FixedLabel::new("User").unwrap();
+        let mut data = Vec::new();
+        label.serialize(&mut data).unwrap();
+        let mut expected = b"User".to_vec();
+        expected.resize(graph::FIXED_LABEL_LEN, 0);
+        assert_eq!(data, expected);
+        assert_eq!(label, "User");
+        assert_eq!(label.len(), 4);
+
+        assert_eq!(FixedLabel::new(&"x".repeat(16)).unwrap().as_str().len(), 16);
+        assert_eq!(FixedLabel::new(&"x".repeat(17)), None);
+        assert_eq!(FixedLabel::new("a\0b"), None);
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let event = NodeAdded {
+            node_id: 3,
+            node_count: 4,
+        };
+        let mut data = NodeAdded::discriminator().to_vec();
+        event.serialize(&mut data).unwrap();
+        assert_eq!(NodeAdded::decode(&data), Some(event));
+        assert_eq!(NodeAdded::decode(&data[1..]), None);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::graph::{Node, NodeId, NODE_ID_LEN};
+use crate::prelude::{borsh, AnchorDeserialize, AnchorSerialize};
+
+/// Query result, also written to the transaction return data.
+///
+/// Borsh layout: a one-byte variant tag followed by its payload.
+/// - `0` `Nodes`: `u32` little-endian count, then each id as a little-endian
+///   `NodeId` (8 bytes, or 16 with the `wide-ids` feature)
+/// - `1` `Scalar`: 8-byte little-endian `i64`
+/// - `2` `None`: no payload
+/// - `3` `Values`: `u32` count, then per node its `NodeId` and the
+///   property `RETURN n.<property>` asked for as an `Option<String>`
+/// - `4` `Records`: `u32` count, then each `Node` as stored, for `RETURN *`
+/// - `5` `Pairs`: `u32` count, then each pair as two `NodeId`s, for
+///   `RETURN a, b`
+/// - `6` `Delegates`: `u32` count, then per input node its `NodeId` and
+///   its effective delegate as an `Option<NodeId>`
+/// - `7` `Weights`: `u32` count, then per node its `NodeId` and the `i64`
+///   weight summed into it
+/// - `8` `Cycle`: `u32` count, then each `NodeId` on the cycle found in
+///   edge order; empty if there is none
+#[derive(Debug, Clone, PartialEq, AnchorSerialize, AnchorDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VmResult {
+    Nodes(Vec<NodeId>),
+    Scalar(i64),
+    None,
+    Values(Vec<(NodeId, Option<String>)>),
+    Records(Vec<Node>),
+    Pairs(Vec<(NodeId, NodeId)>),
+    Delegates(Vec<(NodeId, Option<NodeId>)>),
+    Weights(Vec<(NodeId, i64)>),
+    Cycle(Vec<NodeId>),
+}
+
+impl VmResult {
+    /// Borsh-encoded size of this result.
+    pub fn serialized_size(&self) -> usize {
+        1 + match self {
+            VmResult::Nodes(ids) | VmResult::Cycle(ids) => 4 + ids.len() * NODE_ID_LEN,
+            VmResult::Scalar(_) => 8,
+            VmResult::None => 0,
+            VmResult::Values(values) => {
+                4 + values
+                    .iter()
+                    .map(|(_, value)| NODE_ID_LEN + 1 + value.as_ref().map_or(0, |v| 4 + v.len()))
+                    .sum::<usize>()
+            }
+            VmResult::Records(nodes) => 4 + nodes.iter().map(Node::serialized_size).sum::<usize>(),
+            VmResult::Pairs(pairs) => 4 + pairs.len() * 2 * NODE_ID_LEN,
+            VmResult::Delegates(delegates) => {
+                4 + delegates
+                    .iter()
+                    .map(|(_, delegate)| NODE_ID_LEN + 1 + delegate.map_or(0, |_| NODE_ID_LEN))
+                    .sum::<usize>()
+            }
+            VmResult::Weights(weights) => 4 + weights.len() * (NODE_ID_LEN + 8),
+        }
+    }
+}
+
+/// One slice of a read query's node ids, for results too large to return
+/// at once. Pass `next_cursor` back to fetch the following slice.
+#[derive(Debug, Clone, PartialEq, AnchorSerialize, AnchorDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryPage {
+    pub ids: Vec<NodeId>,
+    pub next_cursor: Option<u32>,
+    /// Graph version the page was read at; pass it as `expected_version` for
+    /// the next page to detect writes in between.
+    pub version: u64,
+}
+
+impl VmResult {
+    /// The ids of the nodes in this result, whatever it returns of them.
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        match self {
+            VmResult::Nodes(ids) | VmResult::Cycle(ids) => ids.clone(),
+            VmResult::Values(values) => values.iter().map(|(id, _)| *id).collect(),
+            VmResult::Records(nodes) => nodes.iter().map(|node| node.id).collect(),
+            VmResult::Pairs(pairs) => pairs.iter().flat_map(|&(a, b)| [a, b]).collect(),
+            VmResult::Delegates(delegates) => delegates.iter().map(|(id, _)| *id).collect(),
+            VmResult::Weights(weights) => weights.iter().map(|(id, _)| *id).collect(),
+            VmResult::Scalar(_) | VmResult::None => Vec::new(),
+        }
+    }
+
+    /// Keeps the nodes whose id satisfies `keep`, whatever the result
+    /// returns of them.
+    pub fn retain_nodes(&mut self, mut keep: impl FnMut(NodeId) -> bool) {
+        match self {
+            VmResult::Nodes(ids) | VmResult::Cycle(ids) => ids.retain(|id| keep(*id)),
+            VmResult::Values(values) => values.retain(|(id, _)| keep(*id)),
+            VmResult::Records(nodes) => nodes.retain(|node| keep(node.id)),
+            VmResult::Pairs(pairs) => pairs.retain(|&(a, b)| keep(a) && keep(b)),
+            VmResult::Delegates(delegates) => delegates.retain(|(id, _)| keep(*id)),
+            VmResult::Weights(weights) => weights.retain(|(id, _)| keep(*id)),
+            VmResult::Scalar(_) | VmResult::None => {}
+        }
+    }
+
+    /// Cuts the ids starting at `cursor` down to what fits in `max_bytes`
+    /// of encoded `QueryPage`.
+    pub fn page(&self, cursor: usize, max_bytes: usize, version: u64) -> QueryPage {
+        let ids = self.node_ids();
+        // Vec length prefix, Some(u32) cursor and the u64 version
+        let per_page = max_bytes.saturating_sub(4 + 5 + 8) / NODE_ID_LEN;
+        let start = cursor.min(ids.len());
+        let end = start.saturating_add(per_page).min(ids.len());
+
+        QueryPage {
+            ids: ids[start..end].to_vec(),
+            next_cursor: (end < ids.len()).then_some(end as u32),
+            version,
+        }
+    }
+}
+
+/// Pre-execution figures for a program, returned by `estimate_query` so
+/// clients can size their compute budget.
+#[derive(Debug, Clone, PartialEq, AnchorSerialize, AnchorDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryEstimate {
+    pub op_count: u32,
+    /// `program_cost` of the program.
+    pub cost: u64,
+    /// Most node visits the program can make, ignoring filter selectivity.
+    pub max_visited_nodes: u64,
+}